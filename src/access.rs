@@ -0,0 +1,88 @@
+use http::HeaderMap;
+use http::header::{AUTHORIZATION, HeaderValue};
+
+/// Header the API key is sent in
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Realm sent in the `WWW-Authenticate` header if none is configured
+pub const DEFAULT_REALM: &str = "pact-stub-server";
+
+/// Credentials the clients must send to use any of the endpoints of the stub server, so it can be exposed on shared
+/// infrastructure. A request is allowed if it has any of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessCredentials {
+    /// User name and password for basic authentication
+    pub basic: Option<(String, String)>,
+    /// Key for the `X-API-Key` header
+    pub api_key: Option<String>,
+    /// Realm for the `WWW-Authenticate` header of the 401 responses
+    pub realm: String
+}
+
+/// Compares the values in a time that does not depend on where they differ, so the credentials can not be guessed
+/// from how long the checks take
+fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+impl AccessCredentials {
+    /// If the request headers have one of the credentials
+    pub fn allows(&self, headers: &HeaderMap<HeaderValue>) -> bool {
+        let basic = self.basic.as_ref().map(|(user, password)| {
+            headers.get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| {
+                    let mut parts = value.trim().splitn(2, ' ');
+                    match (parts.next(), parts.next()) {
+                        (Some(scheme), Some(encoded)) if scheme.eq_ignore_ascii_case("basic") => base64::decode(encoded.trim()).ok(),
+                        _ => None
+                    }
+                })
+                .map(|decoded| same_secret(&decoded, format!("{}:{}", user, password).as_bytes()))
+                .unwrap_or(false)
+        }).unwrap_or(false);
+        let api_key = self.api_key.as_ref().map(|key| {
+            headers.get(API_KEY_HEADER)
+                .map(|value| same_secret(value.as_bytes(), key.as_bytes()))
+                .unwrap_or(false)
+        }).unwrap_or(false);
+        basic || api_key
+    }
+
+    /// Value of the `WWW-Authenticate` header for the 401 responses
+    pub fn challenge(&self) -> String {
+        let scheme = if self.basic.is_some() { "Basic" } else { "ApiKey" };
+        format!("{} realm=\"{}\"", scheme, self.realm.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    fn headers(values: Vec<(&'static str, &str)>) -> HeaderMap<HeaderValue> {
+        values.into_iter().map(|(name, value)| (http::header::HeaderName::from_static(name), HeaderValue::from_str(value).unwrap())).collect()
+    }
+
+    #[test]
+    fn allows_requests_with_one_of_the_credentials() {
+        let credentials = AccessCredentials {
+            basic: Some((s!("user"), s!("secret"))),
+            api_key: Some(s!("key")),
+            realm: s!(DEFAULT_REALM)
+        };
+        expect!(credentials.allows(&headers(vec![ ("authorization", "Basic dXNlcjpzZWNyZXQ=") ]))).to(be_true());
+        expect!(credentials.allows(&headers(vec![ ("authorization", "basic  dXNlcjpzZWNyZXQ=") ]))).to(be_true());
+        expect!(credentials.allows(&headers(vec![ ("x-api-key", "key") ]))).to(be_true());
+        expect!(credentials.allows(&headers(vec![ ("authorization", "Basic dXNlcjpvdGhlcg==") ]))).to(be_false());
+        expect!(credentials.allows(&headers(vec![ ("authorization", "Bearer dXNlcjpzZWNyZXQ=") ]))).to(be_false());
+        expect!(credentials.allows(&headers(vec![ ("x-api-key", "other") ]))).to(be_false());
+        expect!(credentials.allows(&headers(vec![]))).to(be_false());
+        expect!(credentials.challenge()).to(be_equal_to(s!("Basic realm=\"pact-stub-server\"")));
+
+        let api_key_only = AccessCredentials { basic: None, api_key: Some(s!("key")), realm: s!("dev \"cluster\"") };
+        expect!(api_key_only.allows(&headers(vec![ ("authorization", "Basic dXNlcjpzZWNyZXQ=") ]))).to(be_false());
+        expect!(api_key_only.challenge()).to(be_equal_to(s!("ApiKey realm=\"dev \\\"cluster\\\"\"")));
+    }
+}
@@ -0,0 +1,300 @@
+use pact_matching::models::{Interaction, OptionalBody, Pact, parse_query_string};
+use serde_json::Value;
+use crate::pact_support;
+use sha2::{Digest, Sha256};
+
+/// Path of the endpoint that lists the loaded pacts and interactions
+pub const INTERACTIONS_PATH: &str = "/_pact-stub/interactions";
+/// Path of the endpoint that searches the loaded interactions
+pub const SEARCH_PATH: &str = "/_pact-stub/interactions/search";
+/// Path of the endpoint for pact broker webhooks, which reloads the pacts
+pub const WEBHOOK_PATH: &str = "/_pact-stub/webhook";
+
+/// Returns the id from the path of an added interaction, like `/_pact-stub/interactions/1`
+pub fn interaction_id(path: &str) -> Option<&str> {
+    path.strip_prefix(INTERACTIONS_PATH)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .filter(|id| !id.is_empty() && !id.contains('/'))
+}
+
+/// Identifies the interaction by its consumer, provider, description and provider states, so it is the same each time
+/// the pact is loaded (even if the interactions are in another order)
+pub fn interaction_key(pact: &Pact, interaction: &Interaction) -> String {
+    let identity = json!([
+        pact.consumer.name,
+        pact.provider.name,
+        interaction.description,
+        interaction.provider_states.iter().map(|state| state.name.clone()).collect::<Vec<String>>()
+    ]);
+    let digest = Sha256::digest(identity.to_string().as_bytes());
+    digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Returns a JSON summary of an interaction, with the pact it was loaded from
+pub fn interaction_summary(pact: &Pact, interaction: &Interaction) -> Value {
+    json!({
+        "consumer": pact.consumer.name,
+        "provider": pact.provider.name,
+        "description": interaction.description,
+        "providerStates": interaction.provider_states.iter().map(|state| state.name.clone()).collect::<Vec<String>>(),
+        "method": interaction.request.method,
+        "path": interaction.request.path,
+        "status": interaction.response.status
+    })
+}
+
+/// Filters for searching the loaded interactions, given as query parameters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InteractionQuery {
+    /// Request method (case-insensitive)
+    pub method: Option<String>,
+    /// Exact request path
+    pub path: Option<String>,
+    /// Text the request path must contain
+    pub path_contains: Option<String>,
+    /// Text the request or response body must contain
+    pub body_contains: Option<String>,
+    /// Text the description must contain (case-insensitive)
+    pub description: Option<String>,
+    /// Text one of the provider states must contain (case-insensitive)
+    pub state: Option<String>,
+    /// Consumer name
+    pub consumer: Option<String>,
+    /// Provider name
+    pub provider: Option<String>,
+    /// Response status code
+    pub status: Option<u16>,
+    /// Maximum number of interactions to return
+    pub limit: Option<usize>
+}
+
+fn contains(text: &str, value: &Option<String>) -> bool {
+    value.as_ref().map(|value| text.to_lowercase().contains(&value.to_lowercase())).unwrap_or(true)
+}
+
+fn body_contains(body: &OptionalBody, value: &str) -> bool {
+    match body {
+        OptionalBody::Present(bytes) => String::from_utf8_lossy(bytes).contains(value),
+        _ => false
+    }
+}
+
+impl InteractionQuery {
+    /// Parses the query from a query string, like `method=POST&path=/orders&body-contains=sku`
+    pub fn parse(query: Option<&str>) -> Result<InteractionQuery, String> {
+        let mut result = InteractionQuery::default();
+        let params = query.and_then(|query| parse_query_string(&query.to_string())).unwrap_or_default();
+        for (key, values) in params {
+            let value = values.last().cloned().unwrap_or_default();
+            match key.as_str() {
+                "method" => result.method = Some(value),
+                "path" => result.path = Some(value),
+                "path-contains" => result.path_contains = Some(value),
+                "body-contains" => result.body_contains = Some(value),
+                "description" => result.description = Some(value),
+                "state" => result.state = Some(value),
+                "consumer" => result.consumer = Some(value),
+                "provider" => result.provider = Some(value),
+                "status" => result.status = Some(value.parse()
+                    .map_err(|err| format!("'{}' is not a valid status - {}", value, err))?),
+                "limit" => result.limit = Some(value.parse()
+                    .map_err(|err| format!("'{}' is not a valid limit - {}", value, err))?),
+                _ => return Err(format!("'{}' is not a search parameter", key))
+            }
+        }
+        Ok(result)
+    }
+
+    /// If the interaction from the pact matches all the filters
+    pub fn matches(&self, pact: &Pact, interaction: &Interaction) -> bool {
+        let request = &interaction.request;
+        self.method.as_ref().map(|method| method.eq_ignore_ascii_case(&request.method)).unwrap_or(true) &&
+            self.path.as_ref().map(|path| path == &request.path).unwrap_or(true) &&
+            self.path_contains.as_ref().map(|path| request.path.contains(path.as_str())).unwrap_or(true) &&
+            self.body_contains.as_ref().map(|value| body_contains(&request.body, value) ||
+                body_contains(&interaction.response.body, value)).unwrap_or(true) &&
+            contains(&interaction.description, &self.description) &&
+            (self.state.is_none() || interaction.provider_states.iter().any(|state| contains(&state.name, &self.state))) &&
+            self.consumer.as_ref().map(|consumer| consumer == &pact.consumer.name).unwrap_or(true) &&
+            self.provider.as_ref().map(|provider| provider == &pact.provider.name).unwrap_or(true) &&
+            self.status.map(|status| status == interaction.response.status).unwrap_or(true)
+    }
+}
+
+/// Searches the interactions of the pacts, returning the summaries of the ones that match the query and the total
+/// number that matched
+pub fn search<'a, I: IntoIterator<Item = &'a Pact>>(pacts: I, query: &InteractionQuery) -> Value {
+    let found = pacts.into_iter()
+        .flat_map(|pact| pact.interactions.iter().map(move |interaction| (pact, interaction)))
+        .filter(|(pact, interaction)| query.matches(pact, interaction))
+        .collect::<Vec<(&Pact, &Interaction)>>();
+    let interactions = found.iter()
+        .take(query.limit.unwrap_or(found.len()))
+        .map(|(pact, interaction)| interaction_summary(pact, interaction))
+        .collect::<Vec<Value>>();
+    json!({
+        "total": found.len(),
+        "interactions": interactions
+    })
+}
+
+/// Lists the pacts (with the number of interactions in each) and the summaries of all their interactions
+pub fn list<'a, I: IntoIterator<Item = &'a Pact>>(pacts: I) -> Value {
+    let pacts = pacts.into_iter().collect::<Vec<&Pact>>();
+    json!({
+        "pacts": pacts.iter().map(|pact| json!({
+            "consumer": pact.consumer.name,
+            "provider": pact.provider.name,
+            "interactions": pact.interactions.len()
+        })).collect::<Vec<Value>>(),
+        "interactions": pacts.iter()
+            .flat_map(|pact| pact.interactions.iter().map(move |interaction| interaction_summary(pact, interaction)))
+            .collect::<Vec<Value>>()
+    })
+}
+
+/// Describes the event a pact broker webhook was called for, from the `eventName`, `consumerName` and `providerName`
+/// values in the body (which the webhook needs to be configured to send)
+pub fn webhook_event(body: &OptionalBody) -> String {
+    let json = serde_json::from_slice::<Value>(pact_support::body_bytes(body)).unwrap_or_default();
+    let value = |key: &str| json.get(key).and_then(Value::as_str).map(|value| value.to_string());
+    let event = value("eventName").unwrap_or_else(|| s!("webhook"));
+    match (value("consumerName"), value("providerName")) {
+        (Some(consumer), Some(provider)) => format!("{} for '{}' -> '{}'", event, consumer, provider),
+        (None, Some(provider)) => format!("{} for '{}'", event, provider),
+        (Some(consumer), None) => format!("{} from '{}'", event, consumer),
+        (None, None) => event
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Consumer, Interaction, OptionalBody, Pact, Provider, Request, Response};
+    use super::*;
+
+    fn pacts() -> Vec<Pact> {
+        vec![
+            Pact {
+                consumer: Consumer { name: s!("web") },
+                provider: Provider { name: s!("orders") },
+                interactions: vec![
+                    Interaction {
+                        description: s!("create an order"),
+                        request: Request {
+                            method: s!("POST"),
+                            path: s!("/orders"),
+                            body: OptionalBody::Present("{\"sku\": \"1234\"}".into()),
+                            .. Request::default_request()
+                        },
+                        response: Response { status: 201, .. Response::default_response() },
+                        .. Interaction::default()
+                    },
+                    Interaction {
+                        description: s!("get the orders"),
+                        request: Request { path: s!("/orders"), .. Request::default_request() },
+                        response: Response { body: OptionalBody::Present("[{\"sku\": \"1234\"}]".into()), .. Response::default_response() },
+                        .. Interaction::default()
+                    }
+                ],
+                .. Pact::default()
+            },
+            Pact {
+                consumer: Consumer { name: s!("web") },
+                provider: Provider { name: s!("users") },
+                interactions: vec![
+                    Interaction {
+                        description: s!("get a user"),
+                        request: Request { path: s!("/users/1"), .. Request::default_request() },
+                        .. Interaction::default()
+                    }
+                ],
+                .. Pact::default()
+            }
+        ]
+    }
+
+    fn descriptions(result: &Value) -> Vec<String> {
+        result["interactions"].as_array().unwrap().iter()
+            .map(|interaction| interaction["description"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn gets_the_id_from_the_path_of_an_added_interaction() {
+        expect!(interaction_id("/_pact-stub/interactions/12")).to(be_some().value("12"));
+        expect!(interaction_id("/_pact-stub/interactions")).to(be_none());
+        expect!(interaction_id("/_pact-stub/interactions/")).to(be_none());
+        expect!(interaction_id("/_pact-stub/interactions/1/2")).to(be_none());
+    }
+
+    #[test]
+    fn keys_the_interactions_by_their_identity() {
+        let (loaded, reloaded) = (pacts(), pacts());
+        let key = interaction_key(&loaded[0], &loaded[0].interactions[0]);
+        expect!(key.len()).to(be_equal_to(16));
+        expect!(interaction_key(&reloaded[0], &reloaded[0].interactions[0])).to(be_equal_to(key.clone()));
+        expect!(interaction_key(&loaded[0], &loaded[0].interactions[1])).to_not(be_equal_to(key.clone()));
+        expect!(interaction_key(&loaded[1], &loaded[0].interactions[0])).to_not(be_equal_to(key));
+    }
+
+    #[test]
+    fn parses_the_search_query() {
+        let query = InteractionQuery::parse(Some("method=POST&path=/orders&body-contains=sku&status=201")).unwrap();
+        expect!(query).to(be_equal_to(InteractionQuery {
+            method: Some(s!("POST")),
+            path: Some(s!("/orders")),
+            body_contains: Some(s!("sku")),
+            status: Some(201),
+            .. InteractionQuery::default()
+        }));
+        expect!(InteractionQuery::parse(None)).to(be_ok().value(InteractionQuery::default()));
+        expect!(InteractionQuery::parse(Some("status=ok"))).to(be_err());
+        expect!(InteractionQuery::parse(Some("colour=red"))).to(be_err());
+    }
+
+    #[test]
+    fn searches_the_interactions() {
+        let pacts = pacts();
+        let search_for = |query: &str| search(&pacts, &InteractionQuery::parse(Some(query)).unwrap());
+
+        expect!(descriptions(&search_for("method=post&path=/orders&body-contains=sku"))).to(be_equal_to(vec![ s!("create an order") ]));
+        expect!(descriptions(&search_for("body-contains=sku"))).to(be_equal_to(vec![ s!("create an order"), s!("get the orders") ]));
+        expect!(descriptions(&search_for("provider=users"))).to(be_equal_to(vec![ s!("get a user") ]));
+        expect!(descriptions(&search_for("description=GET&path-contains=/orders"))).to(be_equal_to(vec![ s!("get the orders") ]));
+        let limited = search_for("consumer=web&limit=1");
+        expect!(limited["total"].clone()).to(be_equal_to(json!(3)));
+        expect!(descriptions(&limited).len()).to(be_equal_to(1));
+    }
+
+    #[test]
+    fn lists_the_pacts_and_interactions() {
+        let pacts = pacts();
+        let result = list(&pacts);
+
+        expect!(result["pacts"].clone()).to(be_equal_to(json!([
+            { "consumer": "web", "provider": "orders", "interactions": 2 },
+            { "consumer": "web", "provider": "users", "interactions": 1 }
+        ])));
+        expect!(descriptions(&result)).to(be_equal_to(vec![ s!("create an order"), s!("get the orders"), s!("get a user") ]));
+        expect!(result["interactions"][0].clone()).to(be_equal_to(json!({
+            "consumer": "web",
+            "provider": "orders",
+            "description": "create an order",
+            "providerStates": [],
+            "method": "POST",
+            "path": "/orders",
+            "status": 201
+        })));
+    }
+
+    #[test]
+    fn describes_webhook_events() {
+        let body = |json: &str| OptionalBody::Present(json.as_bytes().to_vec());
+        expect!(webhook_event(&body(r#"{"eventName": "contract_published", "consumerName": "Web", "providerName": "Orders"}"#)))
+            .to(be_equal_to(s!("contract_published for 'Web' -> 'Orders'")));
+        expect!(webhook_event(&body(r#"{"providerName": "Orders"}"#))).to(be_equal_to(s!("webhook for 'Orders'")));
+        expect!(webhook_event(&body("not json"))).to(be_equal_to(s!("webhook")));
+        expect!(webhook_event(&OptionalBody::Missing)).to(be_equal_to(s!("webhook")));
+    }
+}
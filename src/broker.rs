@@ -0,0 +1,115 @@
+use serde_json::{Map, Value};
+
+/// Selector properties with true/false values
+static BOOLEAN_PROPERTIES: [&str; 5] = [ "mainBranch", "latest", "deployed", "released", "deployedOrReleased" ];
+
+/// Selector properties with text values
+static TEXT_PROPERTIES: [&str; 6] = [ "branch", "tag", "fallbackTag", "fallbackBranch", "environment", "consumer" ];
+
+/// A consumer version selector for the pacts for verification API of a pact broker, like `mainBranch=true` (the latest
+/// pacts from the main branch) or `deployed=true,environment=production` (the pacts that are deployed to production)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumerVersionSelector(Map<String, Value>);
+
+impl ConsumerVersionSelector {
+    /// Parses a selector from comma separated `property=value` pairs, or from a JSON object
+    pub fn parse(value: &str) -> Result<ConsumerVersionSelector, String> {
+        if value.trim_start().starts_with('{') {
+            return match serde_json::from_str(value) {
+                Ok(Value::Object(selector)) => Ok(ConsumerVersionSelector(selector)),
+                Ok(_) => Err(s!("must be a JSON object")),
+                Err(err) => Err(format!("is not valid JSON - {}", err))
+            };
+        }
+        let mut selector = Map::new();
+        for pair in value.split(',') {
+            let (property, value) = pair.split_once('=')
+                .map(|(property, value)| (property.trim(), value.trim()))
+                .ok_or_else(|| s!("must be in the form property=value[,property=value]"))?;
+            let value = if BOOLEAN_PROPERTIES.contains(&property) {
+                value.parse::<bool>().map(Value::Bool)
+                    .map_err(|_| format!("'{}' is not a valid value for {}, it must be true or false", value, property))?
+            } else if TEXT_PROPERTIES.contains(&property) {
+                Value::String(value.to_string())
+            } else {
+                return Err(format!("'{}' is not a known selector property (the properties are {}, {})", property,
+                    BOOLEAN_PROPERTIES.join(", "), TEXT_PROPERTIES.join(", ")));
+            };
+            selector.insert(property.to_string(), value);
+        }
+        Ok(ConsumerVersionSelector(selector))
+    }
+}
+
+fn path_encode(value: &str) -> String {
+    value.bytes().map(|byte| if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+        (byte as char).to_string()
+    } else {
+        format!("%{:02X}", byte)
+    }).collect()
+}
+
+/// URL of the pacts for verification API of the broker for the provider
+pub fn pacts_for_verification_url(broker_url: &str, provider: &str) -> String {
+    format!("{}/pacts/provider/{}/for-verification", broker_url.trim_end_matches('/'), path_encode(provider))
+}
+
+/// Body of the request to the pacts for verification API. Without any selectors, the broker uses its default ones.
+pub fn pacts_for_verification_body(selectors: &[ConsumerVersionSelector]) -> Value {
+    if selectors.is_empty() {
+        json!({})
+    } else {
+        json!({ "consumerVersionSelectors": selectors.iter().map(|selector| Value::Object(selector.0.clone())).collect::<Vec<Value>>() })
+    }
+}
+
+/// URLs of the pacts in the response of the pacts for verification API
+pub fn pact_urls(response: &Value) -> Result<Vec<String>, String> {
+    match response.pointer("/_embedded/pacts") {
+        Some(Value::Array(pacts)) => pacts.iter().map(|pact| {
+            pact.pointer("/_links/self/href").and_then(Value::as_str).map(|href| href.to_string())
+                .ok_or_else(|| s!("a pact in the response has no self link"))
+        }).collect(),
+        _ => Err(s!("the response has no list of pacts"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn parses_consumer_version_selectors() {
+        let selector = ConsumerVersionSelector::parse("deployed=true, environment=production").unwrap();
+        expect!(pacts_for_verification_body(&[selector])).to(be_equal_to(json!({
+            "consumerVersionSelectors": [ { "deployed": true, "environment": "production" } ]
+        })));
+        let selector = ConsumerVersionSelector::parse(r#"{"tag": "prod", "latest": true}"#).unwrap();
+        expect!(pacts_for_verification_body(&[selector])).to(be_equal_to(json!({
+            "consumerVersionSelectors": [ { "tag": "prod", "latest": true } ]
+        })));
+        expect!(pacts_for_verification_body(&[])).to(be_equal_to(json!({})));
+        expect!(ConsumerVersionSelector::parse("mainBranch=yes")).to(be_err());
+        expect!(ConsumerVersionSelector::parse("version=1.0")).to(be_err());
+        expect!(ConsumerVersionSelector::parse("mainBranch")).to(be_err());
+        expect!(ConsumerVersionSelector::parse("[]")).to(be_err());
+    }
+
+    #[test]
+    fn reads_the_pact_urls_from_the_response() {
+        expect!(pacts_for_verification_url("https://broker/", "Order Service"))
+            .to(be_equal_to(s!("https://broker/pacts/provider/Order%20Service/for-verification")));
+        let response = json!({
+            "_embedded": {
+                "pacts": [
+                    { "shortDescription": "latest from main", "_links": { "self": { "href": "https://broker/pacts/1" } } },
+                    { "shortDescription": "deployed to production", "_links": { "self": { "href": "https://broker/pacts/2" } } }
+                ]
+            }
+        });
+        expect!(pact_urls(&response)).to(be_ok().value(vec![ s!("https://broker/pacts/1"), s!("https://broker/pacts/2") ]));
+        expect!(pact_urls(&json!({ "_embedded": { "pacts": [ {} ] } }))).to(be_err());
+        expect!(pact_urls(&json!({}))).to(be_err());
+    }
+}
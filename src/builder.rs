@@ -0,0 +1,225 @@
+use pact_matching::Mismatch;
+use pact_matching::models::{Interaction, Pact, Request};
+use crate::callbacks::Callbacks;
+use crate::generators::{PactStateGenerators, StateGenerators};
+use crate::pact_support;
+use crate::server::{self, ListenOptions, ServerHandler, ServerOptions};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use tokio::runtime;
+use tokio::sync::oneshot;
+
+/// A stub server that runs on a background thread of the current process, so Rust tests can use it without running
+/// the binary
+///
+/// ```rust,ignore
+/// let server = StubServer::builder().add_pact(pact).port(0).start()?;
+/// // send requests to server.url()
+/// server.shutdown();
+/// ```
+pub struct StubServer;
+
+impl StubServer {
+    /// Returns a builder for a stub server
+    pub fn builder() -> StubServerBuilder {
+        StubServerBuilder::default()
+    }
+}
+
+/// Sets up the pacts and options of a stub server, and starts it
+#[derive(Debug, Clone, Default)]
+pub struct StubServerBuilder {
+    pacts: Vec<Pact>,
+    /// Provider state generators of the responses of each pact
+    state_generators: Vec<PactStateGenerators>,
+    port: u16,
+    options: ServerOptions,
+    callbacks: Callbacks
+}
+
+impl StubServerBuilder {
+    /// Adds a pact to respond to requests with
+    pub fn add_pact(mut self, pact: Pact) -> StubServerBuilder {
+        self.pacts.push(pact);
+        self.state_generators.push(PactStateGenerators::new());
+        self
+    }
+
+    /// Loads a pact file to respond to requests with
+    pub fn add_pact_file<P: AsRef<Path>>(mut self, path: P) -> Result<StubServerBuilder, String> {
+        let path = path.as_ref();
+        let (pact, state_generators) = pact_support::read_pact(path)
+            .map_err(|err| format!("Could not load pact file '{}' - {}", path.display(), err))?;
+        self.pacts.push(pact);
+        self.state_generators.push(state_generators);
+        Ok(self)
+    }
+
+    /// Port to listen on, or 0 (the default) for a random port
+    pub fn port(mut self, port: u16) -> StubServerBuilder {
+        self.port = port;
+        self
+    }
+
+    /// Calls the function with each request that matches an interaction, and the interaction
+    pub fn on_match<F>(mut self, callback: F) -> StubServerBuilder where F: Fn(&Request, &Interaction) + Send + Sync + 'static {
+        self.callbacks.on_match(callback);
+        self
+    }
+
+    /// Calls the function with each request that does not match any interaction, and the mismatches with the
+    /// interactions it was compared with
+    pub fn on_mismatch<F>(mut self, callback: F) -> StubServerBuilder
+        where F: Fn(&Request, &[(&Interaction, Vec<Mismatch>)]) + Send + Sync + 'static {
+        self.callbacks.on_mismatch(callback);
+        self
+    }
+
+    /// Binds the port and starts the server on a background thread. The server runs until the returned handle is shut
+    /// down or dropped.
+    pub fn start(self) -> Result<StubServerHandle, String> {
+        let listen = ListenOptions { port: self.port, .. ListenOptions::default() };
+        let pacts = self.pacts.into_iter().map(Arc::new).collect();
+        let state_generators = StateGenerators::new(self.state_generators);
+        let mut options = self.options;
+        options.callbacks.append(self.callbacks);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let thread = thread::Builder::new()
+            .name(s!("pact-stub-server"))
+            .spawn(move || {
+                let runtime = match runtime::Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => runtime,
+                    Err(err) => {
+                        let _ = started_tx.send(Err(format!("Could not create the runtime - {}", err)));
+                        return;
+                    }
+                };
+                runtime.block_on(async move {
+                    let bound = server::bind(&listen).await.and_then(|listener| listener.local_addr()
+                        .map(|address| (listener, address))
+                        .map_err(|err| format!("Could not get the address the server is listening on - {}", err)));
+                    match bound {
+                        Ok((listener, address)) => {
+                            let _ = started_tx.send(Ok(address));
+                            let signal = async { let _ = shutdown_rx.await; };
+                            server::serve(listener, ServerHandler::new(pacts, state_generators, options), signal).await;
+                        },
+                        Err(err) => { let _ = started_tx.send(Err(err)); }
+                    }
+                });
+            })
+            .map_err(|err| format!("Could not start the server thread - {}", err))?;
+        match started_rx.recv() {
+            Ok(Ok(address)) => {
+                debug!("Stub server started on {}", address);
+                Ok(StubServerHandle { address, shutdown: Some(shutdown_tx), thread: Some(thread) })
+            },
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err)
+            },
+            Err(_) => Err(s!("The server thread stopped before the server started"))
+        }
+    }
+}
+
+/// A running stub server. It is shut down when the handle is dropped.
+#[derive(Debug)]
+pub struct StubServerHandle {
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>
+}
+
+impl StubServerHandle {
+    /// Address the server is bound to
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Port the server is listening on
+    pub fn port(&self) -> u16 {
+        self.address.port()
+    }
+
+    /// Base URL to send requests to the server with, like `http://127.0.0.1:34567`
+    pub fn url(&self) -> String {
+        format!("http://{}", SocketAddr::from((Ipv4Addr::LOCALHOST, self.port())))
+    }
+
+    /// Shuts the server down, waiting for the requests it is handling to finish
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("The stub server thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for StubServerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, Pact, Request, Response};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    fn get(address: SocketAddr, path: &str) -> std::io::Result<String> {
+        let mut stream = TcpStream::connect(address)?;
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    }
+
+    #[test]
+    fn runs_the_server_until_it_is_shut_down() {
+        let pact = Pact {
+            interactions: vec![ Interaction {
+                request: Request { path: s!("/orders"), .. Request::default_request() },
+                response: Response { status: 204, .. Response::default_response() },
+                .. Interaction::default()
+            } ],
+            .. Pact::default()
+        };
+        let requests = Arc::new(Mutex::new(vec![]));
+        let (matched, mismatched) = (requests.clone(), requests.clone());
+        let server = StubServer::builder()
+            .add_pact(pact)
+            .port(0)
+            .on_match(move |request, _| matched.lock().unwrap().push(format!("matched {}", request.path)))
+            .on_mismatch(move |request, _| mismatched.lock().unwrap().push(format!("mismatched {}", request.path)))
+            .start()
+            .unwrap();
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, server.port()));
+        expect!(server.port()).to_not(be_equal_to(0));
+        expect!(server.url()).to(be_equal_to(format!("http://127.0.0.1:{}", server.port())));
+
+        let response = get(address, "/orders").unwrap();
+        expect!(response.starts_with("HTTP/1.1 204")).to(be_true());
+        let response = get(address, "/customers").unwrap();
+        expect!(response.starts_with("HTTP/1.1 404")).to(be_true());
+
+        server.shutdown();
+        expect!(get(address, "/orders")).to(be_err());
+        expect!(requests.lock().unwrap().clone()).to(be_equal_to(vec![ s!("matched /orders"), s!("mismatched /customers") ]));
+    }
+}
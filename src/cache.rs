@@ -0,0 +1,71 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Keeps a copy of each download (like a pact from a URL, or the list of pacts from a broker), so the last copy can be
+/// used if the download fails
+#[derive(Debug, Clone, PartialEq)]
+pub struct PactCache {
+    dir: PathBuf
+}
+
+impl PactCache {
+    /// Cache in the directory, which is created when the first download is stored
+    pub fn new(dir: &Path) -> PactCache {
+        PactCache { dir: dir.to_path_buf() }
+    }
+
+    /// Directory the copies are kept in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// File with the copy of the download. The key is the URL (and anything else the download depends on, like the
+    /// body of the request).
+    fn path(&self, key: &str) -> PathBuf {
+        let digest = Sha256::digest(key.as_bytes());
+        let name: String = digest.iter().take(16).map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Stores a copy of the download, replacing the previous one
+    pub fn store(&self, key: &str, contents: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, contents)?;
+        fs::rename(&partial, &path)
+    }
+
+    /// The last copy of the download, and when it was stored
+    pub fn load(&self, key: &str) -> Option<(Vec<u8>, Option<SystemTime>)> {
+        let path = self.path(key);
+        let contents = fs::read(&path).ok()?;
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        Some((contents, modified))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_copy_of_each_download() {
+        let dir = env::temp_dir().join(format!("pact-stub-server-cache-{}", ::std::process::id()));
+        let cache = PactCache::new(&dir);
+        expect!(cache.load("https://broker/pacts/1")).to(be_none());
+        expect!(cache.store("https://broker/pacts/1", b"first")).to(be_ok());
+        expect!(cache.store("https://broker/pacts/1", b"second")).to(be_ok());
+        expect!(cache.store("https://broker/pacts/2", b"other")).to(be_ok());
+        let first = cache.load("https://broker/pacts/1").map(|(contents, _)| contents);
+        let other = cache.load("https://broker/pacts/2").map(|(contents, _)| contents);
+        fs::remove_dir_all(&dir).unwrap();
+        expect!(first).to(be_some().value(b"second".to_vec()));
+        expect!(other).to(be_some().value(b"other".to_vec()));
+    }
+}
@@ -0,0 +1,89 @@
+use pact_matching::Mismatch;
+use pact_matching::models::{Interaction, Request};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+/// Called with a request and the interaction that was selected to respond to it
+pub type MatchCallback = dyn Fn(&Request, &Interaction) + Send + Sync;
+/// Called with a request that did not match any interaction, and the mismatches with each interaction it was compared
+/// with
+pub type MismatchCallback = dyn Fn(&Request, &[(&Interaction, Vec<Mismatch>)]) + Send + Sync;
+
+/// Functions that are called for each request that matches an interaction or does not match any, so tests that embed
+/// the stub server can make their own assertions or collect their own metrics
+#[derive(Clone, Default)]
+pub struct Callbacks {
+    on_match: Vec<Arc<MatchCallback>>,
+    on_mismatch: Vec<Arc<MismatchCallback>>
+}
+
+impl Callbacks {
+    /// Adds a function to call when a request matches an interaction
+    pub fn on_match<F>(&mut self, callback: F) where F: Fn(&Request, &Interaction) + Send + Sync + 'static {
+        self.on_match.push(Arc::new(callback));
+    }
+
+    /// Adds a function to call when a request does not match any interaction
+    pub fn on_mismatch<F>(&mut self, callback: F) where F: Fn(&Request, &[(&Interaction, Vec<Mismatch>)]) + Send + Sync + 'static {
+        self.on_mismatch.push(Arc::new(callback));
+    }
+
+    /// Adds the functions from the other callbacks
+    pub fn append(&mut self, other: Callbacks) {
+        self.on_match.extend(other.on_match);
+        self.on_mismatch.extend(other.on_mismatch);
+    }
+
+    /// The functions to call when a request does not match any interaction, without the ones for matched requests
+    pub fn mismatches_only(&self) -> Callbacks {
+        Callbacks { on_match: vec![], on_mismatch: self.on_mismatch.clone() }
+    }
+
+    /// Calls the functions for a request that matched the interaction
+    pub fn matched(&self, request: &Request, interaction: &Interaction) {
+        for callback in &self.on_match {
+            callback(request, interaction);
+        }
+    }
+
+    /// Calls the functions for a request that did not match any interaction
+    pub fn mismatched(&self, request: &Request, mismatches: &[(&Interaction, Vec<Mismatch>)]) {
+        for callback in &self.on_mismatch {
+            callback(request, mismatches);
+        }
+    }
+}
+
+impl Debug for Callbacks {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Callbacks {{ on_match: {}, on_mismatch: {} }}", self.on_match.len(), self.on_mismatch.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, Request};
+    use std::sync::Mutex;
+    use super::*;
+
+    #[test]
+    fn calls_each_of_the_functions() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let mut callbacks = Callbacks::default();
+        let (first, second, third) = (calls.clone(), calls.clone(), calls.clone());
+        callbacks.on_match(move |_, interaction| first.lock().unwrap().push(format!("first {}", interaction.description)));
+        let mut others = Callbacks::default();
+        others.on_match(move |request, _| second.lock().unwrap().push(format!("second {}", request.path)));
+        others.on_mismatch(move |_, mismatches| third.lock().unwrap().push(format!("mismatch {}", mismatches.len())));
+        callbacks.append(others);
+
+        let request = Request { path: s!("/orders"), .. Request::default_request() };
+        let interaction = Interaction { description: s!("get orders"), .. Interaction::default() };
+        callbacks.matched(&request, &interaction);
+        callbacks.mismatched(&request, &[ (&interaction, vec![]) ]);
+        expect!(calls.lock().unwrap().clone()).to(be_equal_to(vec![
+            s!("first get orders"), s!("second /orders"), s!("mismatch 1")
+        ]));
+    }
+}
@@ -0,0 +1,110 @@
+use http::{HeaderMap, Method, StatusCode, Uri, Version};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Suffix of the files with the requests
+const REQUEST_SUFFIX: &str = "-request.http";
+/// Suffix of the files with the responses
+const RESPONSE_SUFFIX: &str = "-response.http";
+
+/// Directory that each request and the response served for it are written to, as numbered files with the HTTP messages
+#[derive(Debug)]
+pub struct CaptureDir {
+    dir: PathBuf,
+    next: AtomicU64
+}
+
+impl CaptureDir {
+    /// Captures to the directory, creating it if it does not exist. The numbering continues after the requests that are
+    /// already in it, so they are not replaced when the server is started again.
+    pub fn new(dir: &Path) -> Result<CaptureDir, String> {
+        fs::create_dir_all(dir).map_err(|err| format!("Could not create the capture directory '{}' - {}", dir.display(), err))?;
+        let last = fs::read_dir(dir)
+            .map_err(|err| format!("Could not read the capture directory '{}' - {}", dir.display(), err))?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()
+                .and_then(|name| name.strip_suffix(REQUEST_SUFFIX).and_then(|number| number.parse::<u64>().ok())))
+            .max()
+            .unwrap_or(0);
+        Ok(CaptureDir { dir: dir.to_path_buf(), next: AtomicU64::new(last + 1) })
+    }
+
+    /// The directory the requests are written to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes the request and its response with the next number, returning the number
+    pub fn write(&self, request: &[u8], response: &[u8]) -> io::Result<u64> {
+        let number = self.next.fetch_add(1, Ordering::SeqCst);
+        fs::write(self.dir.join(format!("{:06}{}", number, REQUEST_SUFFIX)), request)?;
+        fs::write(self.dir.join(format!("{:06}{}", number, RESPONSE_SUFFIX)), response)?;
+        Ok(number)
+    }
+}
+
+fn message(start_line: String, headers: &HeaderMap, body: &[u8]) -> Vec<u8> {
+    let mut message = start_line.into_bytes();
+    message.extend_from_slice(b"\r\n");
+    for (name, value) in headers {
+        message.extend_from_slice(name.as_str().as_bytes());
+        message.extend_from_slice(b": ");
+        message.extend_from_slice(value.as_bytes());
+        message.extend_from_slice(b"\r\n");
+    }
+    message.extend_from_slice(b"\r\n");
+    message.extend_from_slice(body);
+    message
+}
+
+/// The request as an HTTP message, with the headers and body it was received with
+pub fn request_message(method: &Method, uri: &Uri, version: Version, headers: &HeaderMap, body: &[u8]) -> Vec<u8> {
+    let target = uri.path_and_query().map(|target| target.as_str()).unwrap_or("/");
+    message(format!("{} {} {:?}", method, target, version), headers, body)
+}
+
+/// The response as an HTTP message, with the headers and body it is sent with
+pub fn response_message(status: StatusCode, version: Version, headers: &HeaderMap, body: &[u8]) -> Vec<u8> {
+    let mut headers = headers.clone();
+    // the server adds the length of the body when it sends the response
+    if !headers.contains_key(http::header::CONTENT_LENGTH) && !headers.contains_key(http::header::TRANSFER_ENCODING) {
+        headers.insert(http::header::CONTENT_LENGTH, body.len().into());
+    }
+    message(format!("{:?} {}", version, status), &headers, body)
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use http::HeaderValue;
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn writes_the_http_messages() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        let uri: Uri = "/orders?status=open".parse().unwrap();
+        let request = request_message(&Method::POST, &uri, Version::HTTP_11, &headers, b"{\"id\":1}");
+        expect!(String::from_utf8(request).unwrap())
+            .to(be_equal_to(s!("POST /orders?status=open HTTP/1.1\r\ncontent-type: application/json\r\n\r\n{\"id\":1}")));
+        let response = response_message(StatusCode::NOT_FOUND, Version::HTTP_11, &HeaderMap::new(), b"");
+        expect!(String::from_utf8(response).unwrap()).to(be_equal_to(s!("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")));
+    }
+
+    #[test]
+    fn numbers_the_captures_after_the_ones_in_the_directory() {
+        let dir = env::temp_dir().join(format!("pact-stub-server-capture-{}", ::std::process::id()));
+        let capture = CaptureDir::new(&dir).unwrap();
+        expect!(capture.write(b"first request", b"first response")).to(be_ok().value(1));
+        expect!(capture.write(b"second request", b"second response")).to(be_ok().value(2));
+        let restarted = CaptureDir::new(&dir).unwrap();
+        let number = restarted.write(b"third request", b"third response");
+        let response = fs::read(dir.join("000002-response.http"));
+        fs::remove_dir_all(&dir).unwrap();
+        expect!(number).to(be_ok().value(3));
+        expect!(response.ok()).to(be_some().value(b"second response".to_vec()));
+    }
+}
@@ -0,0 +1,2131 @@
+//! Command line interface of the pact stub server
+
+use clap::{App, AppSettings, Arg, ArgMatches, ArgSettings, ErrorKind, SubCommand};
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::Request as HyperRequest;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use log::{Log, LogLevel, LogLevelFilter};
+use pact_matching::models::{Interaction, Pact, PactSpecification};
+use simplelog::{Config, SimpleLogger, TermLogger};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::{self, Runtime};
+use tokio::sync::{Notify, watch};
+use base64::encode;
+use regex::Regex;
+use itertools::Itertools;
+
+use crate::{Callbacks, access, fixtures, journal, openapi, pact_support, priorities, scripts, server, validate, wiremock};
+use crate::matching::{DuplicateQueryKeys, MatchingOptions, UnorderedArrays};
+use crate::access::AccessCredentials;
+use crate::cache::PactCache;
+use crate::capture::CaptureDir;
+use crate::broker::{self, ConsumerVersionSelector};
+use crate::clients::{ClientFilter, IpRange};
+use crate::coverage::Coverage;
+use crate::git::{self, GitSource};
+use crate::journal::Journal;
+use crate::metrics::Metrics;
+use crate::overrides::InteractionOverrides;
+use crate::fetch_proxy::FetchProxy;
+use crate::generators::{self, PactStateGenerators, StateGenerators};
+use crate::pact_support::{FetchOptions, LoadedPact};
+use crate::plugins::Plugins;
+use crate::priorities::{SourcePriorities, SourcedPact};
+use crate::privileges::PrivilegeDrop;
+use crate::proxy::ProxyClient;
+use crate::quiet::QuietLogger;
+use crate::ratelimit::RateLimit;
+use crate::recorder::Recorder;
+use crate::report::MismatchReport;
+use crate::retry::{self, Retry};
+use crate::rewrite::PathRewrite;
+use crate::s3::{S3Client, S3Config, S3Location};
+use crate::scripts::Scripts;
+use crate::telemetry::{self, Tracer};
+use crate::templates::ResponseTemplates;
+use crate::tenants::TenantTokens;
+use crate::validate::AmbiguousInteractions;
+use crate::server::{ConnectionOptions, ExpectContinue, ListenOptions, ProviderStateFilter, ProviderStateMode, RequestCheck, ServeLimits, ServerOptions};
+
+/// Runs the command with the arguments the process was started with, exiting the process if it fails
+pub fn main() {
+    std::env::set_var("RUST_LOG", "pact_matching=debug");
+
+    let result = handle_command_args();
+    git::remove_process_checkouts();
+    match result {
+        Ok(_) => (),
+        Err(err) => std::process::exit(err)
+    }
+}
+
+fn print_version() {
+    println!("\npact stub server version  : v{}", crate_version!());
+    println!("pact specification version: v{}", PactSpecification::V3.version_str());
+}
+
+/// Prefix of the environment variables that can be used instead of the command line options
+const ENV_PREFIX: &str = "PACT_STUB_";
+/// Options that take a path, which can also be separated like in `PATH` when they are set with an environment variable
+const PATH_OPTIONS: [&str; 4] = [ "file", "dir", "openapi", "wiremock-dir" ];
+
+fn integer_value(v: String) -> Result<(), String> {
+    v.parse::<u16>().map(|_| ()).map_err(|e| format!("'{}' is not a valid port value: {}", v, e) )
+}
+
+fn count_value(v: String) -> Result<(), String> {
+    v.parse::<usize>().map_err(|e| e.to_string())
+        .and_then(|count| if count > 0 { Ok(()) } else { Err(s!("must be at least 1")) })
+        .map_err(|e| format!("'{}' is not a valid count: {}", v, e))
+}
+
+fn retries_value(v: String) -> Result<(), String> {
+    v.parse::<u32>().map(|_| ()).map_err(|e| format!("'{}' is not a valid number of retries: {}", v, e))
+}
+
+fn privileges_value(v: String) -> Result<(), String> {
+    PrivilegeDrop::parse(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid user and group: {}", v, e) )
+}
+
+fn regex_value(v: String) -> Result<(), String> {
+    Regex::new(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid regular expression: {}", v, e) )
+}
+
+fn unordered_array_path_value(v: String) -> Result<(), String> {
+    UnorderedArrays::at_paths(&[ &v ]).map(|_| ()).map_err(|e| format!("'{}' is not a valid body path: {}", v, e))
+}
+
+fn seed_value(v: String) -> Result<(), String> {
+    v.parse::<u64>().map(|_| ()).map_err(|e| format!("'{}' is not a valid seed: {}", v, e))
+}
+
+fn rate_limit_value(v: String) -> Result<(), String> {
+    RateLimit::parse(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid rate limit: {}", v, e))
+}
+
+fn ip_range_value(v: String) -> Result<(), String> {
+    v.parse::<IpRange>().map(|_| ()).map_err(|e| format!("'{}' is not a valid IP address range: {}", v, e))
+}
+
+/// Parses a `<provider>:<port>` mapping
+fn parse_provider_port(v: &str) -> Result<(String, u16), String> {
+    match v.rsplit_once(':') {
+        Some((provider, port)) if !provider.is_empty() => port.parse::<u16>().ok().filter(|port| *port > 0)
+            .map(|port| (provider.to_string(), port))
+            .ok_or_else(|| format!("'{}' is not a valid port", port)),
+        _ => Err(s!("must be in the form <provider>:<port>"))
+    }
+}
+
+fn provider_port_value(v: String) -> Result<(), String> {
+    parse_provider_port(&v).map(|_| ()).map_err(|e| format!("'{}' is not a valid provider port mapping: {}", v, e))
+}
+
+fn base_path_value(v: String) -> Result<(), String> {
+    if v.starts_with('/') {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid base path: it must start with a /", v))
+    }
+}
+
+fn rewrite_value(v: String) -> Result<(), String> {
+    PathRewrite::parse(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid rewrite rule: {}", v, e))
+}
+
+fn script_value(v: String) -> Result<(), String> {
+    scripts::parse_path_script(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid path script: {}", v, e))
+}
+
+fn status_value(v: String) -> Result<(), String> {
+    v.parse::<u16>().map_err(|e| e.to_string())
+        .and_then(|status| if (100..600).contains(&status) { Ok(()) } else { Err(s!("must be between 100 and 599")) })
+        .map_err(|e| format!("'{}' is not a valid HTTP status code: {}", v, e))
+}
+
+/// Parses a `<name>: <value>` header
+fn parse_header(v: &str) -> Result<(String, String), String> {
+    match v.find(':') {
+        Some(index) if !v[..index].trim().is_empty() => Ok((v[..index].trim().to_string(), v[index + 1..].trim().to_string())),
+        _ => Err(format!("'{}' is not a valid header: must be in the form <name>: <value>", v))
+    }
+}
+
+fn header_value(v: String) -> Result<(), String> {
+    parse_header(&v).map(|_| ())
+}
+
+/// Parses a `<name>: <value>` header that is sent with the responses
+fn parse_response_header(v: &str) -> Result<(http::HeaderName, http::HeaderValue), String> {
+    let (name, value) = parse_header(v)?;
+    let name = http::HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("'{}' is not a valid header name", name))?;
+    let value = http::HeaderValue::from_str(&value).map_err(|_| format!("'{}' is not a valid header value", value))?;
+    Ok((name, value))
+}
+
+fn response_header_value(v: String) -> Result<(), String> {
+    parse_response_header(&v).map(|_| ())
+}
+
+fn url_value(v: String) -> Result<(), String> {
+    ProxyClient::new(v.as_str(), false).map(|_| ())
+}
+
+fn header_name_value(v: String) -> Result<(), String> {
+    http::HeaderName::from_bytes(v.as_bytes()).map(|_| ()).map_err(|_| format!("'{}' is not a valid header name", v))
+}
+
+fn otlp_endpoint_value(v: String) -> Result<(), String> {
+    telemetry::traces_endpoint(v.as_str()).map(|_| ())
+}
+
+fn parse_size(v: &str) -> Result<usize, String> {
+    let value = v.trim().to_uppercase();
+    let (digits, multiplier) = match value.find(|ch: char| !ch.is_ascii_digit()) {
+        Some(index) => match &value[index..] {
+            "B" => (&value[..index], 1),
+            "K" | "KB" => (&value[..index], 1024),
+            "M" | "MB" => (&value[..index], 1024 * 1024),
+            "G" | "GB" => (&value[..index], 1024 * 1024 * 1024),
+            unit => return Err(format!("'{}' is not a valid size unit", unit))
+        },
+        None => (value.as_str(), 1)
+    };
+    digits.parse::<usize>()
+        .map_err(|e| e.to_string())
+        .and_then(|size| size.checked_mul(multiplier).ok_or_else(|| s!("size is too large")))
+}
+
+fn s3_url_value(v: String) -> Result<(), String> {
+    S3Location::parse(&v).map(|_| ()).map_err(|e| format!("'{}' is not a valid S3 URL: {}", v, e))
+}
+
+fn selector_value(v: String) -> Result<(), String> {
+    ConsumerVersionSelector::parse(&v).map(|_| ()).map_err(|e| format!("'{}' is not a valid consumer version selector: {}", v, e))
+}
+
+fn fetch_proxy_value(v: String) -> Result<(), String> {
+    FetchProxy::new(&v, "").map(|_| ())
+}
+
+fn ca_cert_value(v: String) -> Result<(), String> {
+    pact_support::read_ca_cert(Path::new(&v)).map(|_| ())
+}
+
+fn priority_value(v: String) -> Result<(), String> {
+    priorities::parse_priority(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid source priority: {}", v, e) )
+}
+
+fn size_value(v: String) -> Result<(), String> {
+    parse_size(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid size value: {}", v, e) )
+}
+
+fn parse_duration(v: &str) -> Result<Duration, String> {
+    let value = v.trim().to_lowercase();
+    let (digits, unit) = match value.find(|ch: char| !ch.is_ascii_digit()) {
+        Some(index) => (&value[..index], &value[index..]),
+        None => (value.as_str(), "s")
+    };
+    let amount = digits.parse::<u64>().map_err(|e| e.to_string())?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 60 * 60)),
+        unit => Err(format!("'{}' is not a valid duration unit", unit))
+    }
+}
+
+fn duration_value(v: String) -> Result<(), String> {
+    parse_duration(v.as_str()).map(|_| ()).map_err(|e| format!("'{}' is not a valid duration value: {}", v, e) )
+}
+
+/// Type of authentication to use
+#[derive(Debug, Clone)]
+pub enum UrlAuth {
+  /// Username and Password
+  User(String),
+  /// Bearer token
+  Token(String)
+}
+
+/// Source for loading pacts
+#[derive(Debug, Clone)]
+pub enum PactSource {
+    /// Load the pact from a pact file
+    File(String),
+    /// Load all the pacts from a Directory
+    Dir(String),
+    /// Load the pact from a URL
+    Url(String, Option<UrlAuth>),
+    /// Create a pact from the examples in an OpenAPI specification file
+    OpenApi(String),
+    /// Create a pact from a directory of WireMock stub mappings
+    WireMockDir(String),
+    /// Load all the pacts from a bucket (with a key prefix) in S3
+    S3(String),
+    /// Load all the pacts from a directory in a git repository
+    Git(GitSource),
+    /// Load the pacts for a provider from a pact broker, with the consumer version selectors
+    Broker {
+        /// Base URL of the broker
+        url: String,
+        /// Name of the provider to load the pacts for
+        provider: String,
+        /// Which consumer versions to load the pacts of
+        selectors: Vec<ConsumerVersionSelector>,
+        /// Credentials for the broker
+        auth: Option<UrlAuth>
+    }
+}
+
+impl PactSource {
+    /// The kind of source, used to look up its priority
+    fn kind(&self) -> &'static str {
+        match self {
+            PactSource::File(_) => "file",
+            PactSource::Dir(_) => "dir",
+            PactSource::Url(_, _) => "url",
+            PactSource::OpenApi(_) => "openapi",
+            PactSource::WireMockDir(_) => "wiremock-dir",
+            PactSource::S3(_) => "s3",
+            PactSource::Git(_) => "git",
+            PactSource::Broker { .. } => "broker"
+        }
+    }
+
+    /// The file, directory, URL, bucket, repository or broker the pacts are loaded from
+    fn location(&self) -> String {
+        match self {
+            PactSource::File(location) | PactSource::Dir(location) | PactSource::Url(location, _) |
+            PactSource::OpenApi(location) | PactSource::WireMockDir(location) | PactSource::S3(location) => location.clone(),
+            PactSource::Git(source) => source.url.clone(),
+            PactSource::Broker { url, .. } => url.clone()
+        }
+    }
+}
+
+fn url_auth(matches: &ArgMatches) -> Option<UrlAuth> {
+    matches.value_of("user").map(|u| UrlAuth::User(u.to_string()))
+        .or(matches.value_of("token").map(|v| UrlAuth::Token(v.to_string())))
+}
+
+fn pact_source(matches: &ArgMatches) -> Vec<PactSource> {
+    let mut sources = vec![];
+    if let Some(values) = matches.values_of("file") {
+        sources.extend(values.map(|v| PactSource::File(s!(v))).collect::<Vec<PactSource>>());
+    }
+    if let Some(values) = matches.values_of("dir") {
+        sources.extend(values.map(|v| PactSource::Dir(s!(v))).collect::<Vec<PactSource>>());
+    }
+    if let Some(values) = matches.values_of("url") {
+        sources.extend(values.map(|v| PactSource::Url(s!(v), url_auth(matches))).collect::<Vec<PactSource>>());
+    }
+    if let Some(values) = matches.values_of("openapi") {
+        sources.extend(values.map(|v| PactSource::OpenApi(s!(v))).collect::<Vec<PactSource>>());
+    }
+    if let Some(values) = matches.values_of("wiremock-dir") {
+        sources.extend(values.map(|v| PactSource::WireMockDir(s!(v))).collect::<Vec<PactSource>>());
+    }
+    if let Some(values) = matches.values_of("s3-url") {
+        sources.extend(values.map(|v| PactSource::S3(s!(v))).collect::<Vec<PactSource>>());
+    }
+    if let Some(values) = matches.values_of("git-url") {
+        sources.extend(values.map(|v| PactSource::Git(GitSource {
+            url: s!(v),
+            reference: matches.value_of("git-ref").map(|r| r.to_string()),
+            subdir: matches.value_of("git-subdir").map(|d| d.to_string())
+        })).collect::<Vec<PactSource>>());
+    }
+    if let Some(values) = matches.values_of("broker-url") {
+        let selectors = matches.values_of("consumer-version-selector").unwrap_or_default()
+            .map(|v| ConsumerVersionSelector::parse(v).unwrap())
+            .collect::<Vec<ConsumerVersionSelector>>();
+        for url in values {
+            sources.extend(matches.values_of("provider-name").unwrap_or_default().map(|provider| PactSource::Broker {
+                url: s!(url),
+                provider: s!(provider),
+                selectors: selectors.clone(),
+                auth: url_auth(matches)
+            }).collect::<Vec<PactSource>>());
+        }
+    }
+    sources
+}
+
+fn walkdir(dir: &Path) -> io::Result<Vec<(PathBuf, io::Result<LoadedPact>)>> {
+    let mut pacts = vec![];
+    debug!("Scanning {:?}", dir);
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walkdir(&path)?;
+        } else {
+            let pact = pact_support::read_pact(&path);
+            pacts.push((path, pact))
+        }
+    }
+    Ok(pacts)
+}
+
+fn load_dir(dir: &Path) -> Vec<Result<LoadedPact, String>> {
+    match walkdir(dir) {
+        Ok(pacts) => pacts.into_iter().map(|(path, pact)| {
+            pact.map_err(|err| format!("Failed to load pact '{}' - {}", path.display(), err))
+        }).collect(),
+        Err(err) => vec![Err(format!("Could not load pacts from directory '{}' - {}", dir.display(), err))]
+    }
+}
+
+fn with_auth(req: http::request::Builder, auth: &Option<UrlAuth>) -> http::request::Builder {
+    match auth {
+        Some(UrlAuth::User(user)) => req.header("Authorization", format!("Basic {}", encode(&user))),
+        Some(UrlAuth::Token(token)) => req.header("Authorization", format!("Bearer {}", token)),
+        None => req
+    }
+}
+
+/// Fetches the URLs of the pacts for the provider that match the consumer version selectors from the broker
+fn pact_urls_from_broker(url: &str, provider: &str, selectors: &[ConsumerVersionSelector], auth: &Option<UrlAuth>,
+                         runtime: &mut Runtime, fetch: &FetchOptions) -> Result<Vec<String>, String> {
+    let uri = broker::pacts_for_verification_url(url, provider).parse::<hyper::Uri>()
+        .map_err(|err| format!("Request failed - {}", err))?;
+    let body = bytes::Bytes::from(broker::pacts_for_verification_body(selectors).to_string());
+    let request = || {
+        let req = with_auth(HyperRequest::builder().uri(uri.clone()).method("POST"), auth)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/hal+json")
+            .body(Full::new(body.clone()))
+            .map_err(|err| format!("Request failed - {}", err))?;
+        debug!("Executing Request to fetch the pacts for verification from the broker: {:?}", req);
+        Ok(req)
+    };
+    let body = fetch_body(request, &format!("POST {} {}", uri, String::from_utf8_lossy(&body)), runtime, fetch)?;
+    let json = serde_json::from_slice(&body)
+        .map_err(|err| format!("Failed to parse the response - {}", err))?;
+    broker::pact_urls(&json)
+}
+
+/// Sends the request (which is built again for each attempt), and returns the body of the response. Requests that fail
+/// in a way that may be transient (like not being able to connect, or a 503 response) are retried, and if they still
+/// fail, the cached copy of the response (with the cache key) is used if there is one.
+fn fetch_body<B, F>(request: F, cache_key: &str, runtime: &mut Runtime, fetch: &FetchOptions) -> Result<bytes::Bytes, String>
+    where F: Fn() -> Result<HyperRequest<B>, String>,
+          B: hyper::body::Body + Send + Unpin + 'static,
+          B::Data: Send,
+          B::Error: Into<Box<dyn std::error::Error + Send + Sync>> {
+    let client = Client::builder(TokioExecutor::new()).build::<_, B>(fetch.connector()?);
+    runtime.block_on(async {
+        let mut retry = 0;
+        loop {
+            let req = request()?;
+            let uri = req.uri().clone();
+            let error = match client.request(req).await {
+                Ok(res) if res.status().is_success() => {
+                    let body = res.into_body().collect().await
+                        .map(|body| body.to_bytes())
+                        .map_err(|err| format!("Failed to read the response body - {}", err))?;
+                    if let Some(ref cache) = fetch.cache {
+                        if let Err(err) = cache.store(cache_key, &body) {
+                            warn!("Could not cache the response for {} - {}", uri, err);
+                        }
+                    }
+                    return Ok(body);
+                },
+                Ok(res) if retry::is_transient_status(res.status().as_u16()) => format!("Request failed - {}", res.status()),
+                Ok(res) => return Err(format!("Request failed - {}", res.status())),
+                Err(err) => format!("Request failed - {}", err)
+            };
+            retry += 1;
+            match fetch.retry.delay(retry) {
+                Some(delay) => {
+                    warn!("{} for {}, retrying in {:?} ({} of {})", error, uri, delay, retry, fetch.retry.retries);
+                    tokio::time::sleep(delay).await;
+                },
+                None => return match fetch.cache.as_ref().and_then(|cache| cache.load(cache_key)) {
+                    Some((body, stored)) => {
+                        let stored = stored.map(|time| chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string())
+                            .unwrap_or_else(|| s!("an unknown time"));
+                        warn!("{} for {}, using the copy cached at {}", error, uri, stored);
+                        Ok(bytes::Bytes::from(body))
+                    },
+                    None => Err(error)
+                }
+            }
+        }
+    })
+}
+
+fn pact_from_url(url: String, auth: &Option<UrlAuth>, runtime: &mut Runtime, fetch: &FetchOptions) -> Result<LoadedPact, String> {
+    match url.parse::<hyper::Uri>() {
+        Ok(uri) => {
+            let request = || {
+                let req = with_auth(HyperRequest::builder().uri(uri.clone()).method("GET"), auth);
+                debug!("Executing Request to fetch pact from URL: {:?}", req);
+                req.body(Empty::<bytes::Bytes>::new()).map_err(|err| format!("Request failed - {}", err))
+            };
+            let body = fetch_body(request, &url, runtime, fetch)?;
+            let pact_json = pact_support::parse_pact_json(&body)?;
+            let (pact, state_generators) = pact_support::pact_from_json(&url, &pact_json);
+            debug!("Fetched Pact: {:?}", pact);
+            Ok((pact, state_generators))
+        },
+        Err(err) => Err(format!("Request failed - {}", err))
+    }
+}
+
+fn load_pacts(sources: Vec<PactSource>, runtime: &mut Runtime, fetch: &FetchOptions) -> Vec<Result<LoadedPact, String>> {
+    sources.iter().flat_map(|s| {
+        match s {
+            PactSource::File(file) => vec![pact_support::read_pact(Path::new(&file))
+                .map_err(|err| format!("Failed to load pact '{}' - {}", file, err))],
+            PactSource::Dir(dir) => load_dir(Path::new(dir)),
+            PactSource::Url(url, auth) => vec![
+                pact_from_url(url.clone(), auth, runtime, fetch)
+                    .map_err(|err| format!("Failed to load pact '{}' - {}", url, err))
+            ],
+            PactSource::OpenApi(file) => vec![openapi::load_openapi(Path::new(&file))
+                .map(|pact| (pact, PactStateGenerators::new()))
+                .map_err(|err| format!("Failed to load OpenAPI specification '{}' - {}", file, err))],
+            PactSource::WireMockDir(dir) => vec![wiremock::load_wiremock_dir(Path::new(&dir))
+                .map(|pact| (pact, PactStateGenerators::new()))
+                .map_err(|err| format!("Failed to load WireMock mappings from '{}' - {}", dir, err))],
+            PactSource::S3(url) => {
+                let pacts = S3Location::parse(url)
+                    .and_then(|location| runtime.block_on(async {
+                        S3Client::new(S3Config::load().await, fetch)?.load_pacts(&location).await
+                    }));
+                match pacts {
+                    Ok(pacts) => pacts,
+                    Err(err) => vec![Err(format!("Could not load pacts from '{}' - {}", url, err))]
+                }
+            },
+            PactSource::Git(source) => match source.checkout(fetch, load_dir) {
+                Ok(pacts) => pacts,
+                Err(err) => vec![Err(format!("Could not load pacts from '{}' - {}", source.url, err))]
+            },
+            PactSource::Broker { url, provider, selectors, auth } =>
+                match pact_urls_from_broker(url, provider, selectors, auth, runtime, fetch) {
+                    Ok(urls) => {
+                        debug!("Broker {} returned {} pact(s) for '{}'", url, urls.len(), provider);
+                        urls.into_iter().map(|pact_url| pact_from_url(pact_url.clone(), auth, runtime, fetch)
+                            .map_err(|err| format!("Failed to load pact '{}' - {}", pact_url, err))).collect()
+                    },
+                    Err(err) => vec![Err(format!("Could not load the pacts for '{}' from broker '{}' - {}", provider, url, err))]
+                }
+        }
+    })
+        .collect()
+}
+
+fn check_body_sizes(pact: &Pact, max_body_size: usize) -> Result<(), String> {
+    for interaction in &pact.interactions {
+        let bodies = [ ("request", &interaction.request.body), ("response", &interaction.response.body) ];
+        for &(part, body) in bodies.iter() {
+            let size = body.value().len();
+            if size > max_body_size {
+                return Err(format!("Interaction '{}' in the pact between '{}' and '{}' has a {} body of {} bytes, \
+                    which exceeds the maximum of {} bytes. Use a smaller example body (with matchers) and keep large \
+                    payloads in separate files, or increase the limit with --max-interaction-body-size",
+                    interaction.description, pact.consumer.name, pact.provider.name, part, size, max_body_size));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Filters the loaded pacts by consumer and provider names, and their interactions by description
+#[derive(Debug, Clone, Default)]
+pub struct PactFilter {
+    /// Consumer names to include (all consumers if empty)
+    pub consumers: Vec<String>,
+    /// Provider names to include (all providers if empty)
+    pub providers: Vec<String>,
+    /// Regular expression for the descriptions of the interactions to include (all interactions if none)
+    pub descriptions: Option<Regex>
+}
+
+impl PactFilter {
+    fn is_empty(&self) -> bool {
+        self.consumers.is_empty() && self.providers.is_empty()
+    }
+
+    fn matches(&self, pact: &Pact) -> bool {
+        (self.consumers.is_empty() || self.consumers.contains(&pact.consumer.name)) &&
+            (self.providers.is_empty() || self.providers.contains(&pact.provider.name))
+    }
+
+    fn includes(&self, interaction: &Interaction) -> bool {
+        self.descriptions.as_ref().map(|regex| regex.is_match(&interaction.description)).unwrap_or(true)
+    }
+}
+
+fn filter_pacts(pacts: Vec<LoadedPact>, filter: &PactFilter) -> Vec<LoadedPact> {
+    let total = pacts.len();
+    let pacts = pacts.into_iter()
+        .filter(|(pact, _)| {
+            let included = filter.matches(pact);
+            if !included {
+                debug!("Skipping pact between '{}' and '{}' as it does not match the consumer/provider filters",
+                       pact.consumer.name, pact.provider.name);
+            }
+            included
+        })
+        .collect_vec();
+    if !filter.is_empty() {
+        info!("Filtered pacts by consumer {:?} and provider {:?}: using {} of {} pacts", filter.consumers,
+              filter.providers, pacts.len(), total);
+    }
+    match filter.descriptions {
+        Some(ref regex) => {
+            let total = pacts.iter().map(|(pact, _)| pact.interactions.len()).sum::<usize>();
+            let pacts = pacts.into_iter()
+                .map(|(mut pact, mut state_generators)| {
+                    generators::retain_interactions(&mut pact, &mut state_generators, |interaction| {
+                        let included = filter.includes(interaction);
+                        if !included {
+                            debug!("Skipping interaction '{}' as its description does not match the interaction filter",
+                                   interaction.description);
+                        }
+                        included
+                    });
+                    (pact, state_generators)
+                })
+                .collect_vec();
+            info!("Filtered interactions by description '{}': using {} of {} interactions", regex,
+                  pacts.iter().map(|(pact, _)| pact.interactions.len()).sum::<usize>(), total);
+            pacts
+        },
+        None => pacts
+    }
+}
+
+fn log_loaded_pacts(pacts: &[Pact]) {
+    info!("Loaded {} pact(s) with {} interaction(s)", pacts.len(),
+          pacts.iter().map(|pact| pact.interactions.len()).sum::<usize>());
+    for pact in pacts {
+        info!("  - '{}' -> '{}' ({} interaction(s))", pact.consumer.name, pact.provider.name, pact.interactions.len());
+    }
+}
+
+fn log_draft_interactions(pacts: &[Pact], overrides: &InteractionOverrides) {
+    for interaction in pacts.iter().flat_map(|pact| &pact.interactions) {
+        if overrides.is_draft(&interaction.description) {
+            info!("Interaction '{}' is a draft, and will respond with a 501 (Not Implemented)", interaction.description);
+        }
+    }
+}
+
+/// Arguments for loading and filtering the pacts, shared by the server and the sub-commands
+fn pact_source_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("file")
+            .short("f")
+            .long("file")
+            .required_unless_one(&["dir", "url", "openapi", "wiremock-dir", "s3-url", "git-url", "broker-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("Pact file to verify (can be repeated)"),
+        Arg::with_name("dir")
+            .short("d")
+            .long("dir")
+            .required_unless_one(&["file", "url", "openapi", "wiremock-dir", "s3-url", "git-url", "broker-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("Directory of pact files to verify (can be repeated)"),
+        Arg::with_name("url")
+            .short("u")
+            .long("url")
+            .required_unless_one(&["file", "dir", "openapi", "wiremock-dir", "s3-url", "git-url", "broker-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("URL of pact file to verify (can be repeated)"),
+        Arg::with_name("openapi")
+            .long("openapi")
+            .required_unless_one(&["file", "dir", "url", "wiremock-dir", "s3-url", "git-url", "broker-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("OpenAPI or Swagger specification (JSON or YAML) to serve the operation examples from (can be repeated)"),
+        Arg::with_name("wiremock-dir")
+            .long("wiremock-dir")
+            .required_unless_one(&["file", "dir", "url", "openapi", "s3-url", "git-url", "broker-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("Directory of WireMock stub mappings to serve (can be repeated)"),
+        Arg::with_name("s3-url")
+            .long("s3-url")
+            .required_unless_one(&["file", "dir", "url", "openapi", "wiremock-dir", "git-url", "broker-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .validator(s3_url_value)
+            .value_name("s3://bucket/prefix/")
+            .help("Load all the pact files in an S3 bucket with the key prefix, using the standard AWS credentials and environment variables (can be repeated)"),
+        Arg::with_name("git-url")
+            .long("git-url")
+            .required_unless_one(&["file", "dir", "url", "openapi", "wiremock-dir", "s3-url", "broker-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("Git repository to clone and load all the pact files from (can be repeated)"),
+        Arg::with_name("git-ref")
+            .long("git-ref")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .requires("git-url")
+            .help("Branch, tag or commit of the git repositories to load the pact files from (defaults to the default branch)"),
+        Arg::with_name("git-subdir")
+            .long("git-subdir")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .requires("git-url")
+            .help("Directory in the git repositories to load the pact files from (defaults to the root of the repository)"),
+        Arg::with_name("broker-url")
+            .long("broker-url")
+            .required_unless_one(&["file", "dir", "url", "openapi", "wiremock-dir", "s3-url", "git-url"])
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .requires("provider-name")
+            .help("Pact broker to load the pacts for the providers given with --provider-name from (can be repeated)"),
+        Arg::with_name("consumer-version-selector")
+            .long("consumer-version-selector")
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .requires("broker-url")
+            .validator(selector_value)
+            .value_name("property=value,...")
+            .help("Consumer version selector for the pacts to load from the broker, like mainBranch=true or deployed=true,environment=production (can be repeated, defaults to the selectors of the broker)"),
+        Arg::with_name("user")
+            .long("user")
+            .takes_value(true)
+            .use_delimiter(false)
+            .number_of_values(1)
+            .empty_values(false)
+            .conflicts_with("token")
+            .help("User and password to use when fetching pacts from URLS or a pact broker in user:password form"),
+        Arg::with_name("token")
+            .short("t")
+            .long("token")
+            .takes_value(true)
+            .use_delimiter(false)
+            .number_of_values(1)
+            .empty_values(false)
+            .conflicts_with("user")
+            .help("Bearer token to use when fetching pacts from URLS or a pact broker"),
+        Arg::with_name("insecure-tls")
+            .long("insecure-tls")
+            .visible_alias("fetch-insecure")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Disables TLS certificate validation"),
+        Arg::with_name("fetch-retries")
+            .long("fetch-retries")
+            .takes_value(true)
+            .use_delimiter(false)
+            .default_value("0")
+            .validator(retries_value)
+            .help("Number of times to retry downloading pacts from URLs and pact brokers that fail in a way that may be transient (like connection errors or 503 responses), waiting twice as long before each retry"),
+        Arg::with_name("cache-dir")
+            .long("cache-dir")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .help("Keep a copy of the pacts downloaded from URLs and pact brokers in this directory, and use it if they can not be downloaded (like when working offline)"),
+        Arg::with_name("fetch-proxy")
+            .long("fetch-proxy")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .validator(fetch_proxy_value)
+            .help("HTTP proxy to fetch the pacts from URLs, pact brokers, S3 and git repositories through (defaults to the HTTPS_PROXY and HTTP_PROXY environment variables, and hosts in NO_PROXY are fetched directly)"),
+        Arg::with_name("fetch-ca-cert")
+            .long("fetch-ca-cert")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .validator(ca_cert_value)
+            .help("CA certificate (PEM file) to trust when fetching pacts from URLs, pact brokers, S3 and git repositories with internal or self-signed certificates"),
+        Arg::with_name("consumer-name")
+            .long("consumer-name")
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("Only load pacts for the given consumer (can be repeated)"),
+        Arg::with_name("provider-name")
+            .long("provider-name")
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("Only load pacts for the given provider (can be repeated)"),
+        Arg::with_name("interaction-filter")
+            .long("interaction-filter")
+            .takes_value(true)
+            .use_delimiter(false)
+            .value_name("regex")
+            .validator(regex_value)
+            .help("Only load the interactions with descriptions matching the regular expression (like 'expired token')"),
+        Arg::with_name("max-interaction-body-size")
+            .long("max-interaction-body-size")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(size_value)
+            .help("Refuse to load pacts with an interaction request or response body larger than this (in bytes, or with a KB, MB or GB suffix)"),
+        Arg::with_name("skip-invalid")
+            .long("skip-invalid")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Log the pact files that can not be loaded (like ones that are not valid JSON) and carry on with the others, instead of failing"),
+        Arg::with_name("source-priority")
+            .long("source-priority")
+            .takes_value(true)
+            .use_delimiter(false)
+            .multiple(true)
+            .number_of_values(1)
+            .empty_values(false)
+            .validator(priority_value)
+            .value_name("kind=priority")
+            .help("Priority of a kind of source (file, dir, url, openapi, wiremock-dir, s3, git or broker). Interactions from higher priority sources shadow the ones with the same consumer, provider and description from lower priority sources (can be repeated)")
+    ]
+}
+
+/// Builds the multi-threaded runtime, with the given number of worker threads (or one per CPU)
+fn build_runtime(threads: Option<usize>) -> Runtime {
+    let mut builder = runtime::Builder::new_multi_thread();
+    if let Some(threads) = threads {
+        builder.worker_threads(threads);
+    }
+    builder.enable_all().build().unwrap()
+}
+
+fn log_parallelism(runtime: &Runtime) {
+    match thread::available_parallelism() {
+        Ok(cpus) => info!("Using {} worker thread(s), with {} CPU(s) available", runtime.metrics().num_workers(), cpus),
+        Err(_) => info!("Using {} worker thread(s)", runtime.metrics().num_workers())
+    }
+}
+
+/// Loads the pacts from the sources given on the command line, and filters them by consumer and provider
+fn load_sources(matches: &ArgMatches, runtime: &mut Runtime) -> Result<(Vec<Pact>, StateGenerators), i32> {
+    let max_body_size = matches.value_of("max-interaction-body-size").map(|size| parse_size(size).unwrap());
+    let priorities = SourcePriorities::parse(matches.values_of("source-priority").unwrap_or_default()).unwrap();
+    let proxy = match matches.value_of("fetch-proxy") {
+        Some(url) => FetchProxy::new(url, &env::var("NO_PROXY").or_else(|_| env::var("no_proxy")).unwrap_or_default()).map(Some),
+        None => FetchProxy::from_env()
+    };
+    let fetch = FetchOptions {
+        insecure: matches.is_present("insecure-tls"),
+        ca_cert: matches.value_of("fetch-ca-cert").map(PathBuf::from),
+        retry: Retry::new(matches.value_of("fetch-retries").unwrap().parse().unwrap()),
+        cache: matches.value_of("cache-dir").map(|dir| PactCache::new(Path::new(dir))),
+        proxy: match proxy {
+            Ok(proxy) => proxy,
+            Err(err) => {
+                error!("Invalid proxy for fetching the pacts: {}", err);
+                return Err(3);
+            }
+        }
+    };
+    let pacts: Vec<Result<SourcedPact, String>> = pact_source(matches).into_iter()
+        .flat_map(|source| {
+            let (location, priority) = (source.location(), priorities.get(source.kind()));
+            load_pacts(vec![source], runtime, &fetch).into_iter()
+                .map(move |p| p.map(|(pact, state_generators)| SourcedPact { pact, state_generators, source: location.clone(), priority }))
+        })
+        .map(|p| p.and_then(|sourced| match max_body_size {
+            Some(max_body_size) => check_body_sizes(&sourced.pact, max_body_size).map(|_| sourced),
+            None => Ok(sourced)
+        }))
+        .collect();
+    let errors = pacts.iter().filter_map(|p| p.as_ref().err()).collect::<Vec<&String>>();
+    if !errors.is_empty() && !matches.is_present("skip-invalid") {
+        error!("There were errors loading the pact files.");
+        for error in errors {
+            error!("  - {}", error);
+        }
+        Err(3)
+    } else {
+        if !errors.is_empty() {
+            warn!("Skipping {} pact file(s) that could not be loaded:", errors.len());
+            for error in errors {
+                warn!("  - {}", error);
+            }
+        }
+        let filter = PactFilter {
+            consumers: matches.values_of("consumer-name").map(|v| v.map(String::from).collect()).unwrap_or_default(),
+            providers: matches.values_of("provider-name").map(|v| v.map(String::from).collect()).unwrap_or_default(),
+            descriptions: matches.value_of("interaction-filter").map(|regex| Regex::new(regex).unwrap())
+        };
+        let (pacts, shadowed) = priorities::resolve_priorities(pacts.into_iter().filter_map(Result::ok).collect());
+        priorities::log_shadowed_interactions(&shadowed);
+        let (pacts, state_generators): (Vec<Pact>, Vec<PactStateGenerators>) = filter_pacts(pacts, &filter).into_iter().unzip();
+        log_loaded_pacts(&pacts);
+        Ok((pacts, StateGenerators::new(state_generators)))
+    }
+}
+
+fn same_pacts(pacts: &(Vec<Arc<Pact>>, StateGenerators), other: &(Vec<Arc<Pact>>, StateGenerators)) -> bool {
+    pacts.0.len() == other.0.len() && pacts.1 == other.1 && pacts.0.iter().zip(&other.0)
+        .all(|(pact, other)| pact.to_json(PactSpecification::V3) == other.to_json(PactSpecification::V3))
+}
+
+/// Loads the pacts from the sources again after each interval (or when the webhook is called), and sends them to the
+/// server if they have changed. If they can not be loaded, the server keeps the ones it has.
+fn refresh_sources(matches: &ArgMatches, interval: Option<Duration>, webhook: Arc<Notify>, sender: watch::Sender<(Vec<Arc<Pact>>, StateGenerators)>) {
+    let mut runtime = build_runtime(Some(1));
+    loop {
+        runtime.block_on(async {
+            match interval {
+                Some(interval) => { let _ = tokio::time::timeout(interval, webhook.notified()).await; },
+                None => webhook.notified().await
+            }
+        });
+        if sender.is_closed() {
+            break;
+        }
+        debug!("Refreshing the pacts from the sources");
+        match load_sources(matches, &mut runtime) {
+            Ok((pacts, state_generators)) => {
+                let pacts = (pacts.into_iter().map(Arc::new).collect_vec(), state_generators);
+                if !same_pacts(&sender.borrow(), &pacts) {
+                    info!("The pacts have changed, serving the refreshed ones");
+                    sender.send_replace(pacts);
+                } else {
+                    debug!("The pacts have not changed");
+                }
+            },
+            Err(_) => warn!("Could not refresh the pacts, the ones that were loaded before are still being served")
+        }
+    }
+}
+
+fn export_openapi(matches: &ArgMatches, runtime: &mut Runtime) -> Result<(), i32> {
+    let (pacts, _) = load_sources(matches, runtime)?;
+    let json = serde_json::to_string_pretty(&openapi::openapi_from_pacts(&pacts)).unwrap();
+    match matches.value_of("output") {
+        Some(output) => fs::write(output, json + "\n")
+            .map(|_| info!("Wrote the OpenAPI document to {}", output))
+            .map_err(|err| {
+                error!("Could not write the OpenAPI document to '{}' - {}", output, err);
+                4
+            }),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+fn export_fixtures(matches: &ArgMatches, runtime: &mut Runtime) -> Result<(), i32> {
+    let (pacts, _) = load_sources(matches, runtime)?;
+    let dir = matches.value_of("output-dir").unwrap();
+    fixtures::export_fixtures(&pacts, Path::new(dir))
+        .map(|count| info!("Wrote the fixtures for {} interaction(s) to {}", count, dir))
+        .map_err(|err| {
+            error!("Could not write the fixtures - {}", err);
+            4
+        })
+}
+
+/// The pact files in the paths, and the JSON files in the directories (and the directories in them)
+fn pact_files(paths: Vec<&str>) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths.into_iter().map(PathBuf::from) {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(&path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<PathBuf>>>()?;
+            entries.sort();
+            let dirs = entries.iter().filter(|entry| entry.is_dir()).map(|dir| dir.to_str().unwrap_or_default()).collect();
+            files.extend(entries.iter().filter(|entry| entry.is_file() && entry.extension().map(|ext| ext == "json").unwrap_or(false)).cloned());
+            files.extend(pact_files(dirs)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn validate_pacts(matches: &ArgMatches) -> Result<(), i32> {
+    let files = pact_files(matches.values_of("path").unwrap().collect()).map_err(|err| {
+        error!("Could not read the pact files - {}", err);
+        3
+    })?;
+    let mut problems = 0;
+    for file in &files {
+        let pact_json = fs::read(file).map_err(|err| format!("Could not read the file - {}", err))
+            .and_then(|contents| pact_support::parse_pact_json(&contents));
+        match pact_json {
+            Ok(pact_json) => {
+                let validation = validate::validate_pact(&file.to_string_lossy(), &pact_json);
+                println!("{}: {}", file.display(), validation);
+                for problem in &validation.problems {
+                    println!("  - {}", problem);
+                }
+                problems += validation.problems.len();
+            },
+            Err(err) => {
+                println!("{}: {}", file.display(), err);
+                problems += 1;
+            }
+        }
+    }
+    println!("Validated {} pact file(s), found {} problem(s)", files.len(), problems);
+    if problems > 0 { Err(3) } else { Ok(()) }
+}
+
+/// The lines of a table of the interactions in the pacts, with the consumer, description, method, path and status
+fn interactions_table(pacts: &[Pact]) -> Vec<String> {
+    let header = vec![ s!("CONSUMER"), s!("DESCRIPTION"), s!("METHOD"), s!("PATH"), s!("STATUS") ];
+    let rows = pacts.iter()
+        .flat_map(|pact| pact.interactions.iter().map(move |interaction| vec![
+            pact.consumer.name.clone(),
+            interaction.description.clone(),
+            interaction.request.method.to_uppercase(),
+            interaction.request.path.clone(),
+            interaction.response.status.to_string()
+        ]))
+        .collect::<Vec<Vec<String>>>();
+    format_table(header, rows)
+}
+
+/// The interactions that have the same request as others, as a table with a row for each interaction
+fn ambiguous_interactions_table(ambiguous: &[AmbiguousInteractions]) -> Vec<String> {
+    let header = vec![ s!("METHOD"), s!("PATH"), s!("PROVIDER"), s!("CONSUMER"), s!("DESCRIPTION") ];
+    let rows = ambiguous.iter()
+        .flat_map(|group| group.interactions.iter().map(move |(provider, consumer, description)| vec![
+            group.method.clone(),
+            group.path.clone(),
+            provider.clone(),
+            consumer.clone(),
+            description.clone()
+        ]))
+        .collect::<Vec<Vec<String>>>();
+    format_table(header, rows)
+}
+
+/// Lines of a table with the columns padded to the widest value
+fn format_table(header: Vec<String>, rows: Vec<Vec<String>>) -> Vec<String> {
+    let widths = (0..header.len())
+        .map(|column| rows.iter().chain(std::iter::once(&header)).map(|row| row[column].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<usize>>();
+    std::iter::once(&header).chain(rows.iter())
+        .map(|row| row.iter().zip(widths.iter())
+            .map(|(value, width)| format!("{:width$}", value, width = width))
+            .join("  ")
+            .trim_end()
+            .to_string())
+        .collect()
+}
+
+/// The request given with the options of the match sub-command, converted the same way as a received request
+fn request_from_args(matches: &ArgMatches) -> Result<pact_matching::models::Request, String> {
+    let mut builder = HyperRequest::builder()
+        .method(matches.value_of("method").unwrap_or("GET").to_uppercase().as_str())
+        .uri(matches.value_of("path").unwrap());
+    for header in matches.values_of("header").unwrap_or_default() {
+        let (name, value) = parse_header(header)?;
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    let (parts, _) = builder.body(()).map_err(|err| format!("Invalid request - {}", err))?.into_parts();
+    let body = match matches.value_of("body") {
+        Some(body) => match body.strip_prefix('@') {
+            Some(file) => fs::read(file).map_err(|err| format!("Could not read the body from '{}' - {}", file, err))?,
+            None => body.as_bytes().to_vec()
+        },
+        None => vec![]
+    };
+    Ok(pact_support::hyper_request_to_pact_request(&parts, pact_support::optional_body(body)))
+}
+
+fn match_request(matches: &ArgMatches, runtime: &mut Runtime) -> Result<(), i32> {
+    let request = request_from_args(matches).map_err(|err| {
+        error!("{}", err);
+        3
+    })?;
+    let (pacts, state_generators) = load_sources(matches, runtime)?;
+    let options = matching_options(matches, &pacts)?;
+    let pacts = pacts.into_iter().map(Arc::new).collect_vec();
+    match server::check_request(&request, &pacts, &state_generators, &options) {
+        RequestCheck::Matched { consumer, provider, description, response } => {
+            println!("{} {} matched interaction '{}' from the pact between '{}' and '{}'", request.method, request.path, description,
+                     consumer, provider);
+            println!();
+            println!("Response status: {}", response.status);
+            for (name, values) in response.headers.iter().flat_map(|headers| headers.iter()).sorted_by(|(a, _), (b, _)| Ord::cmp(a, b)) {
+                println!("{}: {}", name, values.join(", "));
+            }
+            let body = pact_support::body_for_log(&response.body);
+            if !body.is_empty() {
+                println!();
+                println!("{}", body);
+            }
+            Ok(())
+        },
+        RequestCheck::Mismatched(mismatches) => {
+            println!("{} {} did not match any of the {} interaction(s)", request.method, request.path, mismatches.len());
+            for (description, mismatches) in mismatches {
+                println!();
+                println!("Interaction '{}':", description);
+                for mismatch in mismatches {
+                    println!("  - {}", mismatch);
+                }
+            }
+            Err(2)
+        }
+    }
+}
+
+fn list_interactions(matches: &ArgMatches, runtime: &mut Runtime) -> Result<(), i32> {
+    let (pacts, _) = load_sources(matches, runtime)?;
+    for line in interactions_table(&pacts) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Arguments for running the server, other than the ones for loading the pacts
+fn server_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("loglevel")
+            .short("l")
+            .long("loglevel")
+            .global(true)
+            .takes_value(true)
+            .use_delimiter(false)
+            .possible_values(&["error", "warn", "info", "debug", "trace", "none"])
+            .help("Log level (defaults to info)"),
+        Arg::with_name("port")
+            .short("p")
+            .long("port")
+            .takes_value(true)
+            .use_delimiter(false)
+            .help("Port to run on (defaults to random port assigned by the OS)")
+            .validator(integer_value),
+        Arg::with_name("port-range")
+            .long("port-range")
+            .takes_value(true)
+            .use_delimiter(false)
+            .requires("port")
+            .validator(integer_value)
+            .help("If the port is already in use, try this many of the following ports and use the first free one"),
+        Arg::with_name("port-file")
+            .long("port-file")
+            .takes_value(true)
+            .use_delimiter(false)
+            .help("Write the port the server is listening on to this file once it has started"),
+        Arg::with_name("map")
+            .long("map")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(false)
+            .validator(provider_port_value)
+            .conflicts_with("tenant-tokens")
+            .help("Also listen on this port, and only serve the interactions of the provider on it, in the form \
+            <provider>:<port> (can be repeated)"),
+        Arg::with_name("base-path")
+            .long("base-path")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(base_path_value)
+            .help("Remove this prefix from the request paths before matching them (like /mock/users, for a gateway that routes \
+            the requests to the server with it)"),
+        Arg::with_name("rewrite")
+            .long("rewrite")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(false)
+            .validator(rewrite_value)
+            .help("Rewrite the request paths matching the regular expression before matching them, in the form \
+            '<regex> -> <replacement>' (like '^/v2/(.*)$ -> /v1/$1'). The rules are applied in turn (can be repeated)"),
+        Arg::with_name("quiet-path")
+            .long("quiet-path")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(false)
+            .value_name("regex")
+            .validator(regex_value)
+            .help("Only log the errors of requests with paths matching the regular expression, like health checks or polling (can be repeated)"),
+        Arg::with_name("virtual-hosts")
+            .long("virtual-hosts")
+            .help("Only use the interactions of the provider named by the first part of the Host header of a request \
+            (like users for users.localhost), if there is one"),
+        Arg::with_name("unix-socket")
+            .long("unix-socket")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .conflicts_with_all(&["port", "port-range", "port-file"])
+            .help("Listen on this Unix domain socket instead of a port"),
+        Arg::with_name("refresh-interval")
+            .long("refresh-interval")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(duration_value)
+            .help("Load the pacts from the sources again this often (e.g. 5m), and replace the ones being served if they have changed"),
+        Arg::with_name("webhook")
+            .long("webhook")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Reload the pacts from the sources when a pact broker webhook (like for contract published events) POSTs to /_pact-stub/webhook"),
+        Arg::with_name("serve-for")
+            .long("serve-for")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(duration_value)
+            .help("Shut down the server after running for this long (e.g. 90s, 5m or 1h)"),
+        Arg::with_name("serve-until-idle")
+            .long("serve-until-idle")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(duration_value)
+            .help("Shut down the server once no requests have been received for this long (e.g. 30s)"),
+        Arg::with_name("threads")
+            .long("threads")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(count_value)
+            .help("Number of worker threads that handle the requests (defaults to the number of CPUs)"),
+        Arg::with_name("keep-alive")
+            .long("keep-alive")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Keep connections open between requests (HTTP keep-alive), instead of closing them after each response"),
+        Arg::with_name("idle-connection-timeout")
+            .long("idle-connection-timeout")
+            .takes_value(true)
+            .use_delimiter(false)
+            .requires("keep-alive")
+            .validator(duration_value)
+            .help("Close kept-alive connections that have not sent a request for this long (e.g. 30s)"),
+        Arg::with_name("max-body-size")
+            .long("max-body-size")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(size_value)
+            .help("Respond with a 413 to requests with a body larger than this (in bytes, or with a KB, MB or GB suffix), without reading all of it"),
+        Arg::with_name("rate-limit")
+            .long("rate-limit")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(rate_limit_value)
+            .help("Respond with a 429 and a Retry-After header to the requests over this limit, in the form <count>/<period> with \
+            a period of s, min or h, followed by per-ip to limit each client separately (e.g. '10/min per-ip')"),
+        Arg::with_name("read-timeout")
+            .long("read-timeout")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(duration_value)
+            .help("Respond with a 408 if the request body has not been received within this time (e.g. 10s)"),
+        Arg::with_name("write-timeout")
+            .long("write-timeout")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(duration_value)
+            .help("Close the connection if the client does not accept any of the response for this long (e.g. 10s)"),
+        Arg::with_name("max-connections")
+            .long("max-connections")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(count_value)
+            .help("Maximum number of connections served at once, further connections wait until one is closed"),
+        Arg::with_name("drop-privileges")
+            .long("drop-privileges")
+            .takes_value(true)
+            .use_delimiter(false)
+            .value_name("user:group")
+            .validator(privileges_value)
+            .help("Switch to this user and group (or the user's primary group) once the port is bound"),
+        Arg::with_name("chroot")
+            .long("chroot")
+            .takes_value(true)
+            .use_delimiter(false)
+            .requires("drop-privileges")
+            .help("Change the root directory of the process to this directory before dropping privileges"),
+        Arg::with_name("working-dir")
+            .long("working-dir")
+            .takes_value(true)
+            .use_delimiter(false)
+            .requires("drop-privileges")
+            .help("Change the working directory to this directory (inside the chroot directory) before dropping privileges"),
+        Arg::with_name("cors")
+            .short("o")
+            .long("cors")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Automatically respond to OPTIONS requests and return default CORS headers"),
+        Arg::with_name("provider-state")
+            .short("s")
+            .long("provider-state")
+            .takes_value(true)
+            .use_delimiter(false)
+            .number_of_values(1)
+            .empty_values(false)
+            .multiple(true)
+            .validator(regex_value)
+            .help("Provider state regular expression to filter the responses by (can be repeated)"),
+        Arg::with_name("provider-state-mode")
+            .long("provider-state-mode")
+            .takes_value(true)
+            .use_delimiter(false)
+            .possible_values(&["any", "all"])
+            .requires("provider-state")
+            .help("How multiple provider state filters are combined: interactions matching any of them, or all of \
+            them (defaults to any)"),
+        Arg::with_name("no-provider-state")
+            .long("no-provider-state")
+            .takes_value(false)
+            .use_delimiter(false)
+            .conflicts_with("provider-state")
+            .help("Only use the interactions that do not have any provider states"),
+        Arg::with_name("log-missmatching-bodies")
+            .short("b")
+            .long("missmatching-bodies")
+            .visible_alias("print-mismatching-bodies")
+            .alias("log-missmatching-bodies")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Logs the expected and actual bodies of requests that do not match an interaction, and a diff of them"),
+        Arg::with_name("compress-responses")
+            .long("compress-responses")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Compress the response bodies with gzip or brotli if the request has an Accept-Encoding header that allows it"),
+        Arg::with_name("enforce-auth")
+            .long("enforce-auth")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Respond with a 401 to requests for interactions that expect an Authorization header, if the request does not have one of the same scheme"),
+        Arg::with_name("no-browser-probes")
+            .long("no-browser-probes")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Do not respond with a 204 to unmatched browser requests for /favicon.ico, /robots.txt and the Chrome devtools probe"),
+        Arg::with_name("no-interaction-headers")
+            .long("no-interaction-headers")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Do not send the X-Pact-Stub-Interaction-* headers identifying the interaction with the responses"),
+        Arg::with_name("no-body-matching")
+            .long("no-body-matching")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Ignore request bodies, and only match requests on the method, path, query parameters and headers"),
+        Arg::with_name("duplicate-query-keys")
+            .long("duplicate-query-keys")
+            .takes_value(true)
+            .use_delimiter(false)
+            .possible_values(&["ordered", "unordered", "last"])
+            .help("How repeated query parameters are matched: as an ordered list, an unordered set, or only the last value (defaults to ordered)"),
+        Arg::with_name("ignore-trailing-slash")
+            .long("ignore-trailing-slash")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Match request paths that only differ from the interactions by a trailing slash (like /api/users and /api/users/)"),
+        Arg::with_name("ignore-header-case")
+            .long("ignore-header-case")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Match header names (including the ones in matching rules) in lowercase, so headers that only differ in case match"),
+        Arg::with_name("ignore-query-key-case")
+            .long("ignore-query-key-case")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Match query parameter names (including the ones in matching rules) in lowercase, so parameters that only differ in case match"),
+        Arg::with_name("json-ignore-whitespace")
+            .long("json-ignore-whitespace")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Compare request bodies that are both JSON without their whitespace and key order, even if they are not sent with a JSON content type"),
+        Arg::with_name("json-lenient-numbers")
+            .long("json-lenient-numbers")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Compare numbers in JSON request bodies by their value, so 1 and 1.0 are equal"),
+        Arg::with_name("json-allow-extra-keys")
+            .long("json-allow-extra-keys")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Allow keys in JSON request bodies that are not in the interactions"),
+        Arg::with_name("unordered-arrays")
+            .long("unordered-arrays")
+            .takes_value(true)
+            .min_values(0)
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(false)
+            .value_name("path")
+            .validator(unordered_array_path_value)
+            .help("Match the arrays in JSON request bodies in any order. Without a path, all arrays are unordered, otherwise only the ones at the body paths (like $.items or $.orders[*].lines, can be repeated)"),
+        Arg::with_name("expect-continue")
+            .long("expect-continue")
+            .takes_value(true)
+            .use_delimiter(false)
+            .possible_values(&["always", "check"])
+            .help("How requests with an 'Expect: 100-continue' header are handled: always read the body, or check that an interaction matches the request without its body first and respond with a 404 without reading the body if none do (defaults to always)"),
+        Arg::with_name("metrics")
+            .long("metrics")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Collect the request and response body sizes of each interaction, and serve them in the Prometheus format from /_pact-stub/metrics"),
+        Arg::with_name("correlation-header")
+            .long("correlation-header")
+            .takes_value(true)
+            .use_delimiter(false)
+            .value_name("name")
+            .validator(header_name_value)
+            .help("Send the value of this request header (like X-Request-Id) back in the same header of every response, generating a UUID for requests that do not have one"),
+        Arg::with_name("add-response-header")
+            .long("add-response-header")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(false)
+            .value_name("header")
+            .validator(response_header_value)
+            .help("Add this header, in the form '<name>: <value>' (like 'Cache-Control: no-store'), to every response that does not already have it (can be repeated)"),
+        Arg::with_name("override-response-header")
+            .long("override-response-header")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(false)
+            .value_name("header")
+            .validator(response_header_value)
+            .help("Set this header, in the form '<name>: <value>', on every response, replacing the value from the interaction (can be repeated)"),
+        Arg::with_name("otlp-endpoint")
+            .long("otlp-endpoint")
+            .takes_value(true)
+            .use_delimiter(false)
+            .value_name("url")
+            .validator(otlp_endpoint_value)
+            .help("Export a span for each request to the OpenTelemetry collector at this URL with OTLP/HTTP (like http://localhost:4318), continuing the traces of the traceparent headers of the requests. The service name is taken from OTEL_SERVICE_NAME (defaults to pact-stub-server)"),
+        Arg::with_name("journal-size")
+            .long("journal-size")
+            .takes_value(true)
+            .use_delimiter(false)
+            .value_name("count")
+            .validator(count_value)
+            .help("Number of recent requests kept for the /_pact-stub/requests endpoint (defaults to 100)"),
+        Arg::with_name("fail-on-unused")
+            .long("fail-on-unused")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("When the server shuts down, list the interactions that were never used and exit with a non-zero status if there are any"),
+        Arg::with_name("warmup")
+            .long("warmup")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Match the request of each interaction a few times before starting the server, and log how long each one takes to match"),
+        Arg::with_name("response-cache")
+            .long("response-cache")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Cache the responses of GET requests that have no generated values, and answer identical requests (like polling ones) from the cache without matching them again"),
+        Arg::with_name("proxy-base-url")
+            .long("proxy-base-url")
+            .takes_value(true)
+            .use_delimiter(false)
+            .number_of_values(1)
+            .empty_values(false)
+            .validator(url_value)
+            .help("Forward any request that does not match an interaction to this base URL, and return its response"),
+        Arg::with_name("capture-dir")
+            .long("capture-dir")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .value_name("dir")
+            .help("Write each request and the response served for it to this directory, as numbered files with the HTTP messages (the headers and body)"),
+        Arg::with_name("mismatch-report")
+            .long("mismatch-report")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .value_name("file")
+            .help("Write the requests that did not match any interaction, with the mismatches of the closest interactions, to this file while the server is running. It is in the JUnit XML format if the file has an .xml extension, otherwise JSON"),
+        Arg::with_name("record-dir")
+            .long("record-dir")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .requires("proxy-base-url")
+            .help("Record the proxied requests and responses as interactions in a pact file in this directory"),
+        Arg::with_name("compare-with")
+            .long("compare-with")
+            .takes_value(true)
+            .use_delimiter(false)
+            .number_of_values(1)
+            .empty_values(false)
+            .validator(url_value)
+            .help("Also send the requests that match an interaction to the real provider at this base URL, and log the differences between its responses and the stubbed ones"),
+        Arg::with_name("malformed-json-status")
+            .long("malformed-json-status")
+            .takes_value(true)
+            .use_delimiter(false)
+            .validator(status_value)
+            .help("Respond with this status code (e.g. 400) and a parse error body when a request body is not valid \
+            JSON, but would otherwise match an interaction"),
+        Arg::with_name("tenant-tokens")
+            .long("tenant-tokens")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .help("JSON file mapping bearer tokens to the providers whose interactions the clients using them can access"),
+        Arg::with_name("allow-ip")
+            .long("allow-ip")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(ip_range_value)
+            .help("Only allow clients with an IP address in this range (like 10.0.0.0/8 or 127.0.0.1) to use the server, others get a 403 (can be repeated)"),
+        Arg::with_name("deny-ip")
+            .long("deny-ip")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(ip_range_value)
+            .help("Respond with a 403 to clients with an IP address in this range (can be repeated)"),
+        Arg::with_name("auth-user")
+            .long("auth-user")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .requires("auth-pass")
+            .conflicts_with("tenant-tokens")
+            .help("User name clients must send with basic authentication to use the server"),
+        Arg::with_name("auth-pass")
+            .long("auth-pass")
+            .takes_value(true)
+            .use_delimiter(false)
+            .requires("auth-user")
+            .help("Password for the --auth-user user"),
+        Arg::with_name("api-key")
+            .long("api-key")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .help("Key clients must send in an X-API-Key header to use the server (if they do not use basic authentication)"),
+        Arg::with_name("auth-realm")
+            .long("auth-realm")
+            .takes_value(true)
+            .use_delimiter(false)
+            .help("Realm for the WWW-Authenticate header of the responses to requests without the credentials (defaults to pact-stub-server)"),
+        Arg::with_name("fail-on-ambiguous")
+            .long("fail-on-ambiguous")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Exit with an error if interactions have the same request and provider states, instead of logging a warning"),
+        Arg::with_name("overrides")
+            .long("overrides")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .help("JSON file with settings for interactions by description, like marking them as drafts that respond with a 501 or giving them a priority"),
+        Arg::with_name("no-generators")
+            .long("no-generators")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Send the response values that have generators (like RandomInt, Uuid or DateTime) as they are in the pacts, instead of generating them"),
+        Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .use_delimiter(false)
+            .conflicts_with("no-generators")
+            .validator(seed_value)
+            .help("Seed for the random generators, so each interaction gets the same generated values (like RandomInt, RandomString or Uuid) on every run"),
+        Arg::with_name("response-templates")
+            .long("response-templates")
+            .takes_value(false)
+            .use_delimiter(false)
+            .help("Render the response bodies and headers as Handlebars templates with values from the request, like {{request.path}} or {{request.body.name}}"),
+        Arg::with_name("script")
+            .long("script")
+            .takes_value(true)
+            .use_delimiter(false)
+            .number_of_values(1)
+            .empty_values(false)
+            .multiple(true)
+            .value_name("path-regex=file")
+            .validator(script_value)
+            .help("Rhai script that changes the responses to requests with paths matching the regular expression (can be repeated)"),
+        Arg::with_name("plugin-dir")
+            .long("plugin-dir")
+            .takes_value(true)
+            .use_delimiter(false)
+            .empty_values(false)
+            .help("Directory of plugins (dynamic libraries) that match and generate bodies of other content types"),
+        Arg::with_name("provider-state-header-name")
+            .long("provider-state-header-name")
+            .takes_value(true)
+            .use_delimiter(false)
+            .number_of_values(1)
+            .empty_values(false)
+            .help("Name of the header parameter containing the provider state to be used in case \
+            multiple matching interactions are found"),
+        Arg::with_name("interaction-header-name")
+            .long("interaction-header-name")
+            .takes_value(true)
+            .use_delimiter(false)
+            .value_name("name")
+            .validator(header_name_value)
+            .help("Name of the header parameter containing a regular expression for the descriptions of the interactions \
+            to match the request against (like X-Pact-Interaction)")
+    ]
+}
+
+/// Options of the server that change which interaction a request matches, or the response generated for it, so the
+/// `match` sub-command takes them as well
+const MATCHING_OPTIONS: [&str; 19] = [ "provider-state", "provider-state-mode", "no-provider-state", "enforce-auth",
+    "no-body-matching", "duplicate-query-keys", "ignore-trailing-slash", "ignore-header-case", "ignore-query-key-case",
+    "json-ignore-whitespace", "json-lenient-numbers", "json-allow-extra-keys", "unordered-arrays", "overrides",
+    "no-generators", "seed", "response-templates", "script", "plugin-dir" ];
+
+/// Arguments of the server for the matching options
+fn matching_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    server_args().into_iter().filter(|arg| MATCHING_OPTIONS.contains(&arg.b.name)).collect()
+}
+
+/// Name of the environment variable for an option, like `PACT_STUB_PROVIDER_STATE` for `--provider-state`
+fn env_var_name(long: &str) -> String {
+    format!("{}{}", ENV_PREFIX, long.to_uppercase().replace('-', "_"))
+}
+
+/// Returns the arguments for the options that are set with environment variables, but not on the command line (which
+/// takes precedence). Flags are set with a value of `true`, `1`, `yes` or `on`, and options that can be repeated take
+/// values on separate lines (or separated like in `PATH`, for the files and directories).
+fn env_args(args: &[Arg], given: &[String], vars: &HashMap<String, String>) -> Vec<String> {
+    let mut env_args = vec![];
+    for arg in args {
+        let long = match arg.s.long {
+            Some(long) => long,
+            None => continue
+        };
+        let value = match vars.get(&env_var_name(long)) {
+            Some(value) => value.trim(),
+            None => continue
+        };
+        let on_command_line = given.iter().any(|given| given == &format!("--{}", long) ||
+            given.starts_with(&format!("--{}=", long)) ||
+            arg.s.short.map(|short| !given.starts_with("--") && given.starts_with(&format!("-{}", short))).unwrap_or(false));
+        if on_command_line {
+            continue;
+        }
+        if !arg.is_set(ArgSettings::TakesValue) {
+            if ["true", "1", "yes", "on"].contains(&value.to_lowercase().as_str()) {
+                env_args.push(format!("--{}", long));
+            }
+        } else if arg.is_set(ArgSettings::Multiple) {
+            let values: Vec<String> = if PATH_OPTIONS.contains(&long) {
+                value.lines().flat_map(env::split_paths).map(|path| path.to_string_lossy().to_string()).collect()
+            } else {
+                value.lines().map(str::to_string).collect()
+            };
+            env_args.extend(values.iter().map(|value| value.trim()).filter(|value| !value.is_empty())
+                .map(|value| format!("--{}={}", long, value)));
+        } else if !value.is_empty() {
+            env_args.push(format!("--{}={}", long, value));
+        }
+    }
+    env_args
+}
+
+/// Adds the options set with `PACT_STUB_` environment variables to the command line arguments, so the server can be
+/// configured without any arguments (like in a container). For the sub-commands, only the options they have are added.
+/// The arguments are parsed first to find the sub-command, so an option value that is the name of one (like
+/// `--consumer-name list`) is not taken for it. If they can not be parsed, they are left as they are for the error to
+/// be reported.
+fn with_env_args(mut args: Vec<String>, vars: &HashMap<String, String>) -> Vec<String> {
+    let program = args.first().cloned().unwrap_or_default();
+    let sub_command = match app(program, "", false).get_matches_from_safe(&args) {
+        Ok(matches) => matches.subcommand_name().map(str::to_string),
+        Err(_) => return args
+    };
+    let global_args = || server_args().into_iter().filter(|arg| arg.is_set(ArgSettings::Global));
+    let options = match sub_command.as_deref() {
+        Some("validate") => global_args().collect::<Vec<Arg>>(),
+        Some("match") => pact_source_args().into_iter().chain(matching_args()).chain(global_args()).collect(),
+        Some(_) => pact_source_args().into_iter().chain(global_args()).collect(),
+        None => pact_source_args().into_iter().chain(server_args()).collect()
+    };
+    // the sub-command takes all the arguments after its name, so they can be added at the end
+    let env_args = env_args(&options, &args[1..], vars);
+    args.extend(env_args);
+    args
+}
+
+/// The options and sub-commands. The pact sources are only required if `require_sources` is true, so the arguments
+/// can be parsed before the sources from the environment variables are added to them.
+fn app<'a, 'b>(program: String, version: &'b str, require_sources: bool) -> App<'a, 'b> {
+    let sources = || pact_source_args().into_iter()
+        .map(|arg| if require_sources { arg } else { arg.unset(ArgSettings::Required) })
+        .collect::<Vec<Arg>>();
+    let app = App::new(program)
+        .version(version)
+        .about("Pact Stub Server")
+        .version_short("v")
+        .setting(AppSettings::ColoredHelp)
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .args(&sources())
+        .args(&server_args())
+        .subcommand(SubCommand::with_name("export-openapi")
+            .about("Writes an OpenAPI 3 document describing the paths, methods and example bodies of the loaded pacts")
+            .setting(AppSettings::ColoredHelp)
+            .args(&sources())
+            .arg(Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .use_delimiter(false)
+                .empty_values(false)
+                .help("File to write the OpenAPI document to (defaults to standard output)")))
+        .subcommand(SubCommand::with_name("export-fixtures")
+            .about("Writes the request (request.http) and response (response.json) of each loaded interaction as fixture files in a directory tree")
+            .setting(AppSettings::ColoredHelp)
+            .args(&sources())
+            .arg(Arg::with_name("output-dir")
+                .short("o")
+                .long("output-dir")
+                .required(true)
+                .takes_value(true)
+                .use_delimiter(false)
+                .empty_values(false)
+                .help("Directory to write the fixture files to")))
+        .subcommand(SubCommand::with_name("validate")
+            .about("Checks pact files for problems (like duplicate interactions, bodies that can not be parsed and matching rules that are not supported) without starting the server")
+            .setting(AppSettings::ColoredHelp)
+            .arg(Arg::with_name("path")
+                .required(true)
+                .multiple(true)
+                .empty_values(false)
+                .help("Pact file, or directory of pact files, to check (can be repeated)")))
+        .subcommand(SubCommand::with_name("list")
+            .about("Prints a table of the interactions that would be served (consumer, description, method, path and status) without starting the server")
+            .setting(AppSettings::ColoredHelp)
+            .args(&sources()))
+        .subcommand(SubCommand::with_name("match")
+            .about("Checks which interaction of the loaded pacts a request matches, and prints its response or why the request does not match any, without starting the server")
+            .setting(AppSettings::ColoredHelp)
+            .args(&sources())
+            .arg(Arg::with_name("method")
+                .short("X")
+                .long("method")
+                .takes_value(true)
+                .use_delimiter(false)
+                .empty_values(false)
+                .default_value("GET")
+                .help("HTTP method of the request"))
+            .arg(Arg::with_name("path")
+                .long("path")
+                .required(true)
+                .takes_value(true)
+                .use_delimiter(false)
+                .empty_values(false)
+                .help("Path of the request, with the query string (like /orders?status=open)"))
+            .arg(Arg::with_name("header")
+                .short("H")
+                .long("header")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(false)
+                .validator(header_value)
+                .help("Header of the request, in the form '<name>: <value>' (can be repeated)"))
+            .arg(Arg::with_name("body")
+                .long("body")
+                .takes_value(true)
+                .use_delimiter(false)
+                .help("Body of the request, or @<file> to read it from a file"))
+            .args(&matching_args()));
+    if require_sources {
+        app.setting(AppSettings::ArgRequiredElseHelp)
+    } else {
+        app
+    }
+}
+
+/// The options that change which interaction a request matches, and the response generated for it, which the server
+/// and the `match` sub-command both use. The other options are the defaults.
+fn matching_options(matches: &ArgMatches, pacts: &[Pact]) -> Result<ServerOptions, i32> {
+    let overrides = match matches.value_of("overrides").map(|file| InteractionOverrides::load(Path::new(file))) {
+        Some(Ok(overrides)) => Some(Arc::new(overrides)),
+        Some(Err(err)) => {
+            error!("{}", err);
+            return Err(3);
+        },
+        None => None
+    };
+    let plugins = match matches.value_of("plugin-dir").map(|dir| Plugins::load_dir(Path::new(dir))) {
+        Some(Ok(plugins)) => {
+            for plugin in plugins.plugins() {
+                info!("Loaded plugin {:?}", plugin);
+            }
+            Some(Arc::new(plugins))
+        },
+        Some(Err(err)) => {
+            error!("{}", err);
+            return Err(3);
+        },
+        None => None
+    };
+    let path_scripts = matches.values_of("script")
+        .map(|values| values.map(|value| scripts::parse_path_script(value).unwrap()).collect())
+        .unwrap_or_default();
+    let scripts = match Scripts::load(path_scripts, pacts) {
+        Ok(scripts) => if scripts.is_empty() { None } else { Some(Arc::new(scripts)) },
+        Err(err) => {
+            error!("{}", err);
+            return Err(3);
+        }
+    };
+    let provider_state = matches.values_of("provider-state")
+        .map(|filters| {
+            let mode = match matches.value_of("provider-state-mode") {
+                Some("all") => ProviderStateMode::All,
+                _ => ProviderStateMode::Any
+            };
+            ProviderStateFilter::new(filters.map(|filter| Regex::new(filter).unwrap()).collect(), mode)
+        })
+        .or_else(|| if matches.is_present("no-provider-state") { Some(ProviderStateFilter::stateless()) } else { None });
+    Ok(ServerOptions {
+        provider_state,
+        matching: MatchingOptions {
+            ignore_body: matches.is_present("no-body-matching"),
+            duplicate_query_keys: match matches.value_of("duplicate-query-keys") {
+                Some("unordered") => DuplicateQueryKeys::Unordered,
+                Some("last") => DuplicateQueryKeys::LastValue,
+                _ => DuplicateQueryKeys::Ordered
+            },
+            plugins,
+            lowercase_headers: matches.is_present("ignore-header-case"),
+            lowercase_query_keys: matches.is_present("ignore-query-key-case"),
+            ignore_trailing_slash: matches.is_present("ignore-trailing-slash"),
+            json_ignore_whitespace: matches.is_present("json-ignore-whitespace"),
+            json_lenient_numbers: matches.is_present("json-lenient-numbers"),
+            json_allow_extra_keys: matches.is_present("json-allow-extra-keys"),
+            unordered_arrays: match matches.values_of("unordered-arrays") {
+                Some(paths) => UnorderedArrays::at_paths(&paths.collect_vec()).unwrap(),
+                None if matches.is_present("unordered-arrays") => UnorderedArrays::all(),
+                None => UnorderedArrays::default()
+            }
+        },
+        skip_generators: matches.is_present("no-generators"),
+        generator_seed: matches.value_of("seed").map(|seed| seed.parse().unwrap()),
+        templates: if matches.is_present("response-templates") { Some(Arc::new(ResponseTemplates::default())) } else { None },
+        scripts,
+        overrides,
+        enforce_auth: matches.is_present("enforce-auth"),
+        .. ServerOptions::default()
+    })
+}
+
+/// Client for the URL of the option (like `--proxy-base-url`), if it is given
+fn proxy_client(matches: &ArgMatches, name: &str) -> Result<Option<ProxyClient>, String> {
+    matches.value_of(name).map(|url| ProxyClient::new(url, matches.is_present("insecure-tls"))).transpose()
+}
+
+/// Recorder for the proxied requests, if the `--record-dir` option is given. The pact file is named after the host of
+/// the provider.
+fn recorder(matches: &ArgMatches) -> Result<Option<Arc<Recorder>>, String> {
+    matches.value_of("record-dir").map(|dir| {
+        let provider = matches.value_of("proxy-base-url").and_then(|url| url.parse::<hyper::Uri>().ok())
+            .and_then(|uri| uri.host().map(String::from))
+            .unwrap_or_else(|| s!("provider"));
+        Recorder::new(Path::new(dir), "recorded-consumer", &provider).map(Arc::new)
+    }).transpose()
+}
+
+fn handle_command_args() -> Result<(), i32> {
+    let vars = env::vars().filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+    let args = with_env_args(env::args().collect(), &vars);
+    let program = args[0].clone();
+
+    let version = format!("v{}", crate_version!());
+    let matches = app(program, &version, true).get_matches_from_safe(args);
+    match matches {
+        Ok(ref matches) => {
+            let mut tokio_runtime = build_runtime(matches.value_of("threads").map(|threads| threads.parse().unwrap()));
+            if let ("export-openapi", Some(sub_matches)) = matches.subcommand() {
+                // the document is written to standard output, so only log warnings and errors by default
+                setup_logger(sub_matches.value_of("loglevel").unwrap_or("warn"));
+                return export_openapi(sub_matches, &mut tokio_runtime);
+            }
+            if let ("validate", Some(sub_matches)) = matches.subcommand() {
+                setup_logger(sub_matches.value_of("loglevel").unwrap_or("warn"));
+                return validate_pacts(sub_matches);
+            }
+            if let ("list", Some(sub_matches)) = matches.subcommand() {
+                setup_logger(sub_matches.value_of("loglevel").unwrap_or("warn"));
+                return list_interactions(sub_matches, &mut tokio_runtime);
+            }
+            if let ("match", Some(sub_matches)) = matches.subcommand() {
+                // the outcome is printed to standard output, so the mismatches are not also logged
+                setup_logger(sub_matches.value_of("loglevel").unwrap_or("error"));
+                return match_request(sub_matches, &mut tokio_runtime);
+            }
+            if let ("export-fixtures", Some(sub_matches)) = matches.subcommand() {
+                setup_logger(sub_matches.value_of("loglevel").unwrap_or("info"));
+                return export_fixtures(sub_matches, &mut tokio_runtime);
+            }
+            let level = matches.value_of("loglevel").unwrap_or("info");
+            setup_logger(level);
+            log_parallelism(&tokio_runtime);
+            let tenants = match matches.value_of("tenant-tokens").map(|file| TenantTokens::load(Path::new(file))) {
+                Some(Ok(tenants)) => Some(tenants),
+                Some(Err(err)) => {
+                    error!("{}", err);
+                    return Err(3);
+                },
+                None => None
+            };
+            let capture = match matches.value_of("capture-dir").map(|dir| CaptureDir::new(Path::new(dir))) {
+                Some(Ok(capture)) => {
+                    info!("Capturing the requests and responses to {}", capture.dir().display());
+                    Some(Arc::new(capture))
+                },
+                Some(Err(err)) => {
+                    error!("{}", err);
+                    return Err(3);
+                },
+                None => None
+            };
+            let mismatch_report = match matches.value_of("mismatch-report").map(|file| MismatchReport::create(Path::new(file))) {
+                Some(Ok(report)) => Some(Arc::new(report)),
+                Some(Err(err)) => {
+                    error!("{}", err);
+                    return Err(3);
+                },
+                None => None
+            };
+            let (pacts, state_generators) = match load_sources(matches, &mut tokio_runtime) {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    tokio_runtime.shutdown_background();
+                    return Err(err);
+                }
+            };
+            let ambiguous = validate::ambiguous_interactions(&pacts);
+            if !ambiguous.is_empty() {
+                let level = if matches.is_present("fail-on-ambiguous") { LogLevel::Error } else { LogLevel::Warn };
+                log!(level, "{} request(s) match more than one interaction, and only the first one loaded (or the one with the highest priority) is used:",
+                     ambiguous.len());
+                for line in ambiguous_interactions_table(&ambiguous) {
+                    log!(level, "  {}", line);
+                }
+                if matches.is_present("fail-on-ambiguous") {
+                    tokio_runtime.shutdown_background();
+                    return Err(3);
+                }
+            }
+            let matching = match matching_options(matches, &pacts) {
+                Ok(options) => options,
+                Err(err) => {
+                    tokio_runtime.shutdown_background();
+                    return Err(err);
+                }
+            };
+            if let Some(ref overrides) = matching.overrides {
+                log_draft_interactions(&pacts, overrides);
+            }
+            let listen = ListenOptions {
+                port: matches.value_of("port").unwrap_or("0").parse::<u16>().unwrap(),
+                port_range: matches.value_of("port-range").map(|range| range.parse().unwrap()).unwrap_or(0),
+                port_file: matches.value_of("port-file").map(PathBuf::from),
+                unix_socket: matches.value_of("unix-socket").map(PathBuf::from),
+                provider_ports: matches.values_of("map").unwrap_or_default()
+                    .map(|value| parse_provider_port(value).unwrap())
+                    .fold(BTreeMap::new(), |mut ports, (provider, port)| {
+                        ports.entry(port).or_insert_with(Vec::new).push(provider);
+                        ports
+                    })
+            };
+            let (proxy, compare_with, recorder) = match (proxy_client(matches, "proxy-base-url"),
+                                                         proxy_client(matches, "compare-with"), recorder(matches)) {
+                (Ok(proxy), Ok(compare_with), Ok(recorder)) => (proxy, compare_with, recorder),
+                (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+                    error!("{}", err);
+                    tokio_runtime.shutdown_background();
+                    return Err(3);
+                }
+            };
+            let provider_state_header_name = matches.value_of("provider-state-header-name")
+                .map(String::from);
+            let webhook = Arc::new(Notify::new());
+            let mut options = ServerOptions {
+                auto_cors: matches.is_present("cors"),
+                print_missmatching_bodies: matches.is_present("log-missmatching-bodies"),
+                provider_state_header_name,
+                interaction_header_name: matches.value_of("interaction-header-name").map(String::from),
+                providers: None,
+                clients: if matches.is_present("allow-ip") || matches.is_present("deny-ip") {
+                    let ranges = |name| matches.values_of(name).map(|values| values.map(|value| value.parse().unwrap()).collect())
+                        .unwrap_or_default();
+                    Some(ClientFilter { allow: ranges("allow-ip"), deny: ranges("deny-ip") })
+                } else {
+                    None
+                },
+                base_path: matches.value_of("base-path").map(String::from),
+                rewrites: matches.values_of("rewrite")
+                    .map(|values| values.map(|value| PathRewrite::parse(value).unwrap()).collect())
+                    .unwrap_or_default(),
+                virtual_hosts: matches.is_present("virtual-hosts"),
+                access: if matches.is_present("auth-user") || matches.is_present("api-key") {
+                    Some(AccessCredentials {
+                        basic: matches.value_of("auth-user")
+                            .map(|user| (user.to_string(), matches.value_of("auth-pass").unwrap_or_default().to_string())),
+                        api_key: matches.value_of("api-key").map(String::from),
+                        realm: matches.value_of("auth-realm").unwrap_or(access::DEFAULT_REALM).to_string()
+                    })
+                } else {
+                    None
+                },
+                tenants,
+                proxy,
+                compare_with,
+                recorder,
+                malformed_json_status: matches.value_of("malformed-json-status").map(|status| status.parse().unwrap()),
+                metrics: if matches.is_present("metrics") { Some(Arc::new(Metrics::default())) } else { None },
+                coverage: Some(Arc::new(Coverage::default())),
+                journal: Some(Arc::new(Journal::new(matches.value_of("journal-size")
+                    .map(|size| size.parse().unwrap()).unwrap_or(journal::DEFAULT_SIZE)))),
+                callbacks: Callbacks::default(),
+                fail_on_unused: matches.is_present("fail-on-unused"),
+                answer_browser_probes: !matches.is_present("no-browser-probes"),
+                compress_responses: matches.is_present("compress-responses"),
+                expect_continue: match matches.value_of("expect-continue") {
+                    Some("check") => ExpectContinue::Check,
+                    _ => ExpectContinue::Always
+                },
+                connections: ConnectionOptions {
+                    keep_alive: matches.is_present("keep-alive"),
+                    idle_timeout: matches.value_of("idle-connection-timeout").map(|duration| parse_duration(duration).unwrap()),
+                    max_connections: matches.value_of("max-connections").map(|max| max.parse().unwrap()),
+                    read_timeout: matches.value_of("read-timeout").map(|duration| parse_duration(duration).unwrap()),
+                    write_timeout: matches.value_of("write-timeout").map(|duration| parse_duration(duration).unwrap())
+                },
+                max_body_size: matches.value_of("max-body-size").map(|size| parse_size(size).unwrap()),
+                rate_limit: matches.value_of("rate-limit").map(|limit| RateLimit::parse(limit).unwrap()),
+                drop_privileges: matches.value_of("drop-privileges").map(|value| PrivilegeDrop {
+                    chroot: matches.value_of("chroot").map(PathBuf::from),
+                    working_dir: matches.value_of("working-dir").map(PathBuf::from),
+                    .. PrivilegeDrop::parse(value).unwrap()
+                }),
+                refreshed_pacts: None,
+                webhook_reload: if matches.is_present("webhook") { Some(webhook.clone()) } else { None },
+                tracer: matches.value_of("otlp-endpoint").map(|endpoint| {
+                    let service_name = env::var("OTEL_SERVICE_NAME").ok().filter(|name| !name.trim().is_empty())
+                        .unwrap_or_else(|| telemetry::DEFAULT_SERVICE_NAME.to_string());
+                    Arc::new(Tracer::new(endpoint, &service_name).unwrap())
+                }),
+                correlation_header: matches.value_of("correlation-header").map(String::from),
+                quiet_paths: matches.values_of("quiet-path")
+                    .map(|values| values.map(|value| Regex::new(value).unwrap()).collect())
+                    .unwrap_or_default(),
+                capture,
+                added_response_headers: matches.values_of("add-response-header")
+                    .map(|values| values.map(|value| parse_response_header(value).unwrap()).collect())
+                    .unwrap_or_default(),
+                overridden_response_headers: matches.values_of("override-response-header")
+                    .map(|values| values.map(|value| parse_response_header(value).unwrap()).collect())
+                    .unwrap_or_default(),
+                description_filter: None,
+                interaction_headers: !matches.is_present("no-interaction-headers"),
+                response_cache: matches.is_present("response-cache"),
+                .. matching
+            };
+            let pacts = pacts.into_iter().map(Arc::new).collect_vec();
+            if let Some(report) = mismatch_report {
+                info!("Writing the unmatched requests to {}", report.path().display());
+                options.callbacks.on_mismatch(move |request, mismatches| report.record(request, mismatches));
+            }
+            if matches.is_present("refresh-interval") || matches.is_present("webhook") {
+                let interval = matches.value_of("refresh-interval").map(|interval| parse_duration(interval).unwrap());
+                let (sender, receiver) = watch::channel((pacts.clone(), state_generators.clone()));
+                let matches = matches.clone();
+                let webhook = webhook.clone();
+                thread::spawn(move || refresh_sources(&matches, interval, webhook, sender));
+                options.refreshed_pacts = Some(receiver);
+            }
+            if matches.is_present("warmup") {
+                server::warm_up(&pacts, &state_generators, &options, server::WARMUP_ITERATIONS);
+            }
+            let limits = ServeLimits {
+                serve_for: matches.value_of("serve-for").map(|duration| parse_duration(duration).unwrap()),
+                until_idle: matches.value_of("serve-until-idle").map(|duration| parse_duration(duration).unwrap())
+            };
+            server::start_server(listen, pacts, state_generators, options, limits, &mut tokio_runtime)
+        },
+        Err(ref err) => {
+            match err.kind {
+                ErrorKind::HelpDisplayed => {
+                    println!("{}", err.message);
+                    Ok(())
+                },
+                ErrorKind::VersionDisplayed => {
+                    print_version();
+                    println!();
+                    Ok(())
+                },
+                _ => err.exit()
+            }
+        }
+    }
+}
+
+fn setup_logger(level: &str) {
+    let log_level = match level {
+        "none" => LogLevelFilter::Off,
+        _ => LogLevelFilter::from_str(level).unwrap()
+    };
+    let logger: Box<dyn Log> = match TermLogger::new(log_level, Config::default()) {
+        Some(logger) => logger,
+        None => SimpleLogger::new(log_level, Config::default())
+    };
+    log::set_logger(|max_log_level| {
+        max_log_level.set(log_level);
+        Box::new(QuietLogger::new(logger))
+    }).unwrap_or(())
+}
+
+#[cfg(test)]
+mod test;
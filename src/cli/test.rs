@@ -0,0 +1,215 @@
+use quickcheck::{TestResult, quickcheck};
+use rand::Rng;
+use super::{integer_value, regex_value, status_value, parse_header, parse_provider_port, parse_size, parse_duration, check_body_sizes, filter_pacts, interactions_table, with_env_args, app, matching_options, PactFilter};
+use expectest::prelude::*;
+use pact_matching::models::{Consumer, Interaction, OptionalBody, Pact, Provider, Request, Response};
+use crate::generators::PactStateGenerators;
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+#[test]
+fn validates_integer_value() {
+    fn prop(s: String) -> TestResult {
+        let mut rng = ::rand::thread_rng();
+        if rng.gen() && s.chars().any(|ch| !ch.is_numeric()) {
+            TestResult::discard()
+        } else {
+            let validation = integer_value(s.clone());
+            match validation {
+                Ok(_) => TestResult::from_bool(!s.is_empty() && s.chars().all(|ch| ch.is_numeric() )),
+                Err(_) => TestResult::from_bool(s.is_empty() || s.chars().find(|ch| !ch.is_numeric() ).is_some())
+            }
+        }
+    }
+    quickcheck(prop as fn(_) -> _);
+
+    expect!(integer_value(s!("1234"))).to(be_ok());
+    expect!(integer_value(s!("1234x"))).to(be_err());
+}
+
+#[test]
+fn validates_regex_value() {
+    expect!(regex_value(s!("1234"))).to(be_ok());
+    expect!(regex_value(s!("["))).to(be_err());
+}
+
+#[test]
+fn validates_status_value() {
+    expect!(status_value(s!("400"))).to(be_ok());
+    expect!(status_value(s!("99"))).to(be_err());
+    expect!(status_value(s!("600"))).to(be_err());
+    expect!(status_value(s!("bad"))).to(be_err());
+}
+
+#[test]
+fn parses_header_values() {
+    expect!(parse_header("Content-Type: application/json")).to(be_ok().value((s!("Content-Type"), s!("application/json"))));
+    expect!(parse_header("X-Empty:")).to(be_ok().value((s!("X-Empty"), s!(""))));
+    expect!(parse_header("Accept")).to(be_err());
+    expect!(parse_header(": value")).to(be_err());
+}
+
+#[test]
+fn parses_provider_port_mappings() {
+    expect!(parse_provider_port("provider-a:8081")).to(be_ok().value((s!("provider-a"), 8081)));
+    expect!(parse_provider_port("urn:provider:8082")).to(be_ok().value((s!("urn:provider"), 8082)));
+    expect!(parse_provider_port("provider-a")).to(be_err());
+    expect!(parse_provider_port(":8081")).to(be_err());
+    expect!(parse_provider_port("provider-a:0")).to(be_err());
+    expect!(parse_provider_port("provider-a:http")).to(be_err());
+}
+
+#[test]
+fn parses_size_values() {
+    expect!(parse_size("1234")).to(be_ok().value(1234));
+    expect!(parse_size("10b")).to(be_ok().value(10));
+    expect!(parse_size("2KB")).to(be_ok().value(2048));
+    expect!(parse_size("1m")).to(be_ok().value(1024 * 1024));
+    expect!(parse_size("1GB")).to(be_ok().value(1024 * 1024 * 1024));
+    expect!(parse_size("")).to(be_err());
+    expect!(parse_size("12TB")).to(be_err());
+    expect!(parse_size("MB")).to(be_err());
+}
+
+#[test]
+fn parses_duration_values() {
+    expect!(parse_duration("90")).to(be_ok().value(Duration::from_secs(90)));
+    expect!(parse_duration("90s")).to(be_ok().value(Duration::from_secs(90)));
+    expect!(parse_duration("500ms")).to(be_ok().value(Duration::from_millis(500)));
+    expect!(parse_duration("5m")).to(be_ok().value(Duration::from_secs(300)));
+    expect!(parse_duration("1H")).to(be_ok().value(Duration::from_secs(3600)));
+    expect!(parse_duration("")).to(be_err());
+    expect!(parse_duration("5d")).to(be_err());
+    expect!(parse_duration("s")).to(be_err());
+}
+
+#[test]
+fn rejects_pacts_with_bodies_larger_than_the_maximum_size() {
+    let interaction = Interaction {
+        response: Response { body: OptionalBody::Present(vec![b'a'; 100]), .. Response::default_response() },
+        .. Interaction::default()
+    };
+    let pact = Pact { interactions: vec![ interaction ], .. Pact::default() };
+
+    expect!(check_body_sizes(&pact, 100)).to(be_ok());
+    expect!(check_body_sizes(&pact, 99)).to(be_err());
+}
+
+#[test]
+fn filters_pacts_by_consumer_and_provider_name() {
+    let pact = |consumer: &str, provider: &str| Pact {
+        consumer: Consumer { name: s!(consumer) },
+        provider: Provider { name: s!(provider) },
+        .. Pact::default()
+    };
+    let pacts = vec![ pact("web", "users"), pact("web", "orders"), pact("mobile", "users") ].into_iter()
+        .map(|pact| (pact, PactStateGenerators::new())).collect::<Vec<_>>();
+    let names = |pacts: Vec<(Pact, PactStateGenerators)>| pacts.iter()
+        .map(|(p, _)| format!("{}->{}", p.consumer.name, p.provider.name)).collect::<Vec<String>>();
+
+    expect!(names(filter_pacts(pacts.clone(), &PactFilter::default())).len()).to(be_equal_to(3));
+    expect!(names(filter_pacts(pacts.clone(), &PactFilter { consumers: vec![ s!("web") ], providers: vec![], .. PactFilter::default() })))
+        .to(be_equal_to(vec![ s!("web->users"), s!("web->orders") ]));
+    expect!(names(filter_pacts(pacts.clone(), &PactFilter { consumers: vec![], providers: vec![ s!("users") ], .. PactFilter::default() })))
+        .to(be_equal_to(vec![ s!("web->users"), s!("mobile->users") ]));
+    expect!(names(filter_pacts(pacts.clone(), &PactFilter { consumers: vec![ s!("mobile") ], providers: vec![ s!("orders") ], .. PactFilter::default() })))
+        .to(be_equal_to(Vec::<String>::new()));
+}
+
+#[test]
+fn filters_interactions_by_description() {
+    let interaction = |description: &str| Interaction { description: s!(description), .. Interaction::default() };
+    let pacts = vec![
+        (Pact { interactions: vec![ interaction("get a user"), interaction("get a user with expired token") ], .. Pact::default() },
+         PactStateGenerators::new()),
+        (Pact { interactions: vec![ interaction("delete a user with expired token") ], .. Pact::default() }, PactStateGenerators::new())
+    ];
+    let filter = PactFilter { descriptions: Some(Regex::new("expired token$").unwrap()), .. PactFilter::default() };
+    let descriptions = filter_pacts(pacts, &filter).iter()
+        .map(|(pact, _)| pact.interactions.iter().map(|i| i.description.clone()).collect::<Vec<String>>())
+        .collect::<Vec<Vec<String>>>();
+    expect!(descriptions).to(be_equal_to(vec![
+        vec![ s!("get a user with expired token") ], vec![ s!("delete a user with expired token") ]
+    ]));
+}
+
+#[test]
+fn prints_a_table_of_the_interactions() {
+    let interaction = |description: &str, method: &str, path: &str, status: u16| Interaction {
+        description: s!(description),
+        request: Request { method: s!(method), path: s!(path), .. Request::default_request() },
+        response: Response { status, .. Response::default_response() },
+        .. Interaction::default()
+    };
+    let pact = Pact {
+        consumer: Consumer { name: s!("web") },
+        interactions: vec![ interaction("get the users", "get", "/users", 200), interaction("create a user", "POST", "/users", 201) ],
+        .. Pact::default()
+    };
+    expect!(interactions_table(&[ pact ])).to(be_equal_to(vec![
+        s!("CONSUMER  DESCRIPTION    METHOD  PATH    STATUS"),
+        s!("web       get the users  GET     /users  200"),
+        s!("web       create a user  POST    /users  201")
+    ]));
+}
+
+#[test]
+fn adds_the_options_from_environment_variables() {
+    let vars = vec![
+        (s!("PACT_STUB_PORT"), s!("8080")),
+        (s!("PACT_STUB_DIR"), s!("/pacts/a\n /pacts/b\n")),
+        (s!("PACT_STUB_ADD_RESPONSE_HEADER"), s!("Cache-Control: no-store, no-cache")),
+        (s!("PACT_STUB_CORS"), s!("true")),
+        (s!("PACT_STUB_KEEP_ALIVE"), s!("false")),
+        (s!("PACT_STUB_LOGLEVEL"), s!("debug")),
+        (s!("PACT_STUB_PROVIDER_STATE"), s!(""))
+    ].into_iter().collect::<HashMap<String, String>>();
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+    let mut server = with_env_args(args(&[ "pact-stub-server", "-p", "1234" ]), &vars);
+    server[1..].sort();
+    expect!(server).to(be_equal_to(args(&[
+        "pact-stub-server", "--add-response-header=Cache-Control: no-store, no-cache", "--cors", "--dir=/pacts/a",
+        "--dir=/pacts/b", "--loglevel=debug", "-p", "1234"
+    ])));
+    expect!(with_env_args(args(&[ "pact-stub-server", "export-openapi", "-o", "api.json" ]), &vars)).to(be_equal_to(args(&[
+        "pact-stub-server", "export-openapi", "-o", "api.json", "--dir=/pacts/a", "--dir=/pacts/b", "--loglevel=debug"
+    ])));
+    expect!(with_env_args(args(&[ "pact-stub-server", "validate", "a.json" ]), &vars)).to(be_equal_to(args(&[
+        "pact-stub-server", "validate", "a.json", "--loglevel=debug"
+    ])));
+
+    // an option value with the name of a sub-command is not taken for it
+    let mut server = with_env_args(args(&[ "pact-stub-server", "--consumer-name", "list" ]), &vars);
+    server[1..].sort();
+    expect!(server).to(be_equal_to(args(&[
+        "pact-stub-server", "--add-response-header=Cache-Control: no-store, no-cache", "--consumer-name", "--cors",
+        "--dir=/pacts/a", "--dir=/pacts/b", "--loglevel=debug", "--port=8080", "list"
+    ])));
+
+    let files = env::join_paths([ "/pacts/a.json", "/pacts/b.json" ]).unwrap().into_string().unwrap();
+    let vars = hashmap!{ s!("PACT_STUB_FILE") => files };
+    expect!(with_env_args(args(&[ "pact-stub-server" ]), &vars)).to(be_equal_to(args(&[
+        "pact-stub-server", "--file=/pacts/a.json", "--file=/pacts/b.json"
+    ])));
+}
+
+#[test]
+fn the_match_sub_command_takes_the_matching_options_of_the_server() {
+    let matches = app(s!("pact-stub-server"), "", true).get_matches_from_safe(vec![
+        "pact-stub-server", "match", "--dir", "pacts", "--path", "/orders", "-s", "an order exists", "--no-body-matching",
+        "--ignore-header-case", "--seed", "42"
+    ]).unwrap();
+    let options = matching_options(matches.subcommand_matches("match").unwrap(), &[]).unwrap();
+    expect!(options.provider_state.is_some()).to(be_true());
+    expect!(options.matching.ignore_body).to(be_true());
+    expect!(options.matching.lowercase_headers).to(be_true());
+    expect!(options.matching.lowercase_query_keys).to(be_false());
+    expect!(options.generator_seed).to(be_some().value(42));
+    expect!(options.scripts.is_none()).to(be_true());
+
+    expect!(app(s!("pact-stub-server"), "", true).get_matches_from_safe(vec![
+        "pact-stub-server", "match", "--dir", "pacts", "--path", "/orders", "--cors"
+    ])).to(be_err());
+}
@@ -0,0 +1,123 @@
+use std::fmt::{self, Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A range of IP addresses in CIDR notation (like `10.0.0.0/8`), or a single address
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8
+}
+
+fn bits(address: IpAddr) -> (u128, u8) {
+    match address {
+        IpAddr::V4(address) => (u128::from(u32::from(address)), 32),
+        IpAddr::V6(address) => (u128::from(address), 128)
+    }
+}
+
+impl IpRange {
+    /// If the address is in the range. IPv4 addresses mapped to IPv6 ones (`::ffff:10.0.0.1`) are treated as the IPv4
+    /// address.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        let (network, width) = bits(self.network);
+        let (address, address_width) = bits(address.to_canonical());
+        if width != address_width {
+            return false;
+        }
+        let shift = u32::from(width - self.prefix);
+        network.checked_shr(shift).unwrap_or(0) == address.checked_shr(shift).unwrap_or(0)
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<IpRange, String> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None)
+        };
+        let network = address.trim().parse::<IpAddr>().map_err(|_| format!("'{}' is not an IP address", address))?.to_canonical();
+        let width = bits(network).1;
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse::<u8>().ok().filter(|prefix| *prefix <= width)
+                .ok_or_else(|| format!("'{}' is not a valid prefix length for {}", prefix, network))?,
+            None => width
+        };
+        Ok(IpRange { network, prefix })
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// Which client IP addresses can use the server. If there are allowed ranges, only the addresses in them can, and the
+/// addresses in the denied ranges never can.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientFilter {
+    pub allow: Vec<IpRange>,
+    pub deny: Vec<IpRange>
+}
+
+impl ClientFilter {
+    /// If the client with the address can use the server
+    pub fn allows(&self, address: IpAddr) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|range| range.contains(address))) &&
+            !self.deny.iter().any(|range| range.contains(address))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ip_ranges() {
+        expect!("10.0.0.0/8".parse::<IpRange>().map(|range| range.to_string())).to(be_ok().value(s!("10.0.0.0/8")));
+        expect!("192.168.1.7".parse::<IpRange>().map(|range| range.to_string())).to(be_ok().value(s!("192.168.1.7/32")));
+        expect!("fd00::/8".parse::<IpRange>().map(|range| range.to_string())).to(be_ok().value(s!("fd00::/8")));
+        expect!("::ffff:10.0.0.1".parse::<IpRange>().map(|range| range.to_string())).to(be_ok().value(s!("10.0.0.1/32")));
+        expect!("10.0.0.0/33".parse::<IpRange>()).to(be_err());
+        expect!("10.0.0/8".parse::<IpRange>()).to(be_err());
+        expect!("localhost".parse::<IpRange>()).to(be_err());
+    }
+
+    #[test]
+    fn checks_if_addresses_are_in_the_range() {
+        let range: IpRange = "10.1.0.0/16".parse().unwrap();
+        expect!(range.contains(ip("10.1.200.3"))).to(be_true());
+        expect!(range.contains(ip("::ffff:10.1.0.1"))).to(be_true());
+        expect!(range.contains(ip("10.2.0.1"))).to(be_false());
+        expect!(range.contains(ip("::1"))).to(be_false());
+        let all: IpRange = "0.0.0.0/0".parse().unwrap();
+        expect!(all.contains(ip("203.0.113.9"))).to(be_true());
+        let v6: IpRange = "2001:db8::/32".parse().unwrap();
+        expect!(v6.contains(ip("2001:db8:1::5"))).to(be_true());
+        expect!(v6.contains(ip("2001:db9::5"))).to(be_false());
+    }
+
+    #[test]
+    fn allows_the_clients_in_the_allowed_ranges_that_are_not_denied() {
+        let filter = ClientFilter {
+            allow: vec![ "10.0.0.0/8".parse().unwrap(), "127.0.0.1".parse().unwrap() ],
+            deny: vec![ "10.0.66.0/24".parse().unwrap() ]
+        };
+        expect!(filter.allows(ip("10.3.0.1"))).to(be_true());
+        expect!(filter.allows(ip("127.0.0.1"))).to(be_true());
+        expect!(filter.allows(ip("10.0.66.12"))).to(be_false());
+        expect!(filter.allows(ip("192.168.0.1"))).to(be_false());
+
+        let deny_only = ClientFilter { allow: vec![], deny: vec![ "192.168.0.0/16".parse().unwrap() ] };
+        expect!(deny_only.allows(ip("192.168.0.1"))).to(be_false());
+        expect!(deny_only.allows(ip("10.0.0.1"))).to(be_true());
+    }
+}
@@ -0,0 +1,80 @@
+use itertools::Itertools;
+use pact_matching::{self, Mismatch};
+use pact_matching::models::{Request, Response};
+use crate::proxy::ProxyClient;
+use crate::server;
+
+/// Request headers that are not sent to the real provider, as the stubbed response does not take them into account
+static IGNORED_REQUEST_HEADERS: [&str; 2] = [ "accept-encoding", "range" ];
+
+fn describe_difference(mismatch: &Mismatch) -> String {
+    match mismatch {
+        Mismatch::StatusMismatch { expected, actual } =>
+            format!("Status does not match, stubbed: {}, real: {}", expected, actual),
+        _ => server::describe_mismatch(mismatch)
+    }
+}
+
+/// Returns the differences between the stubbed response and the one from the real provider. The matching rules of the
+/// stubbed response are applied, so values that are expected to change (like ids and timestamps) are not reported.
+pub fn differences(stubbed: &Response, real: &Response) -> Vec<String> {
+    pact_matching::match_response(stubbed.clone(), real.clone()).iter()
+        .map(describe_difference)
+        .collect()
+}
+
+/// Sends a request that matched an interaction to the real provider as well, and logs any differences between its
+/// response and the stubbed one
+pub async fn compare_with_provider(client: &ProxyClient, mut request: Request, stubbed: Response) {
+    if let Some(ref mut headers) = request.headers {
+        headers.retain(|name, _| !IGNORED_REQUEST_HEADERS.contains(&name.to_lowercase().as_str()));
+    }
+    match client.forward(&request).await {
+        Ok(real) => {
+            let differences = differences(&stubbed, &real);
+            if differences.is_empty() {
+                info!("Response from {} for {} {} matches the stubbed response", client.base_url(), request.method,
+                      request.path);
+            } else {
+                warn!("Response from {} for {} {} differs from the stubbed response:\n{}", client.base_url(),
+                      request.method, request.path, differences.iter().map(|difference| format!("  - {}", difference)).join("\n"));
+            }
+        },
+        Err(err) => warn!("Could not compare the response for {} {} with {} - {}", request.method, request.path,
+                          client.base_url(), err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Response};
+    use pact_matching::models::matchingrules::*;
+    use super::*;
+
+    #[test]
+    fn reports_the_differences_from_the_real_response() {
+        let stubbed = Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present("{\"id\": 1, \"name\": \"Fred\"}".into()),
+            matching_rules: matchingrules!{ "body" => { "$.id" => [ MatchingRule::Type ] } },
+            .. Response::default_response()
+        };
+        let real = Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ], s!("Date") => vec![ s!("today") ] }),
+            body: OptionalBody::Present("{\"id\": 2, \"name\": \"Fred\"}".into()),
+            .. Response::default_response()
+        };
+        expect!(differences(&stubbed, &real)).to(be_equal_to(Vec::<String>::new()));
+
+        let changed = Response {
+            status: 201,
+            body: OptionalBody::Present("{\"id\": 2, \"name\": \"Frederick\"}".into()),
+            .. real
+        };
+        let found = differences(&stubbed, &changed);
+        expect!(found.len()).to(be_equal_to(2));
+        expect!(found.contains(&s!("Status does not match, stubbed: 200, real: 201"))).to(be_true());
+        expect!(found.iter().any(|difference| difference.starts_with("Body does not match at path '$.name'"))).to(be_true());
+    }
+}
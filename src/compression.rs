@@ -0,0 +1,149 @@
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use http::StatusCode;
+use pact_matching::models::{HttpPart, OptionalBody, Request, Response};
+use crate::pact_support;
+use std::io::Write;
+
+/// Content encodings that response bodies can be compressed with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentEncoding {
+    /// Brotli compression
+    Brotli,
+    /// Gzip compression
+    Gzip
+}
+
+impl ContentEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip"
+        }
+    }
+}
+
+/// Chooses the encoding to compress a response with from an `Accept-Encoding` header value, using the one with the
+/// highest quality value (preferring brotli if they are the same). The `*` wildcard only applies to the encodings the
+/// header does not list, so `br;q=0, *` does not accept brotli. Returns `None` if neither is acceptable.
+pub fn preferred_encoding(accept_encoding: &str) -> Option<ContentEncoding> {
+    let codings = accept_encoding.split(',')
+        .map(|value| {
+            let mut params = value.split(';');
+            let coding = params.next().unwrap_or_default().trim().to_lowercase();
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q=").and_then(|q| q.trim().parse::<f32>().ok()))
+                .next()
+                .unwrap_or(1.0);
+            (coding, quality)
+        })
+        .collect::<Vec<(String, f32)>>();
+    let quality_of = |names: &[&str]| codings.iter().find(|(coding, _)| names.contains(&coding.as_str())).map(|(_, quality)| *quality);
+    let mut preferred: Option<(ContentEncoding, f32)> = None;
+    for (encoding, names) in [ (ContentEncoding::Brotli, &["br"][..]), (ContentEncoding::Gzip, &["gzip", "x-gzip"][..]) ] {
+        let quality = quality_of(names).or_else(|| quality_of(&["*"])).unwrap_or(0.0);
+        // brotli comes first, so it is kept if gzip has the same quality
+        if quality > 0.0 && preferred.map(|(_, current_quality)| quality > current_quality).unwrap_or(true) {
+            preferred = Some((encoding, quality));
+        }
+    }
+    preferred.map(|(encoding, _)| encoding)
+}
+
+/// Compresses the bytes with the content encoding
+pub fn compress(encoding: ContentEncoding, body: &[u8]) -> Result<Vec<u8>, String> {
+    match encoding {
+        ContentEncoding::Brotli => {
+            let mut writer = brotli::CompressorWriter::new(vec![], 4096, 5, 22);
+            writer.write_all(body).map_err(|err| err.to_string())?;
+            Ok(writer.into_inner())
+        },
+        ContentEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(body).and_then(|_| encoder.finish()).map_err(|err| err.to_string())
+        }
+    }
+}
+
+/// Compresses the body of the response with the encoding preferred by the `Accept-Encoding` header of the request,
+/// setting the `Content-Encoding` and `Vary` headers. Responses without a body, partial responses and responses that
+/// already have a content encoding are not changed.
+pub fn compress_response(request: &Request, mut response: Response) -> Response {
+    if !matches!(response.body, OptionalBody::Present(ref body) if !body.is_empty()) {
+        return response;
+    }
+    if response.status == StatusCode::PARTIAL_CONTENT.as_u16() ||
+        response.lookup_header_value(&s!("content-encoding")).is_some() {
+        return response;
+    }
+    pact_support::add_header(&mut response, "Vary", "Accept-Encoding");
+    let encoding = request.lookup_header_value(&s!("accept-encoding"))
+        .and_then(|accept_encoding| preferred_encoding(&accept_encoding));
+    if let Some(encoding) = encoding {
+        let body = pact_support::body_bytes(&response.body);
+        let (length, compressed) = (body.len(), compress(encoding, body));
+        match compressed {
+            Ok(compressed) => {
+                debug!("Compressed the {} byte response body to {} bytes with {}", length, compressed.len(),
+                       encoding.name());
+                pact_support::add_header(&mut response, "Content-Encoding", encoding.name());
+                response.body = OptionalBody::Present(compressed);
+            },
+            Err(err) => warn!("Could not compress the response body with {} - {}", encoding.name(), err)
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use flate2::read::GzDecoder;
+    use pact_matching::models::{OptionalBody, Request, Response};
+    use std::io::Read;
+    use super::*;
+
+    #[test]
+    fn chooses_the_preferred_encoding() {
+        expect!(preferred_encoding("gzip, deflate, br")).to(be_some().value(ContentEncoding::Brotli));
+        expect!(preferred_encoding("gzip, deflate")).to(be_some().value(ContentEncoding::Gzip));
+        expect!(preferred_encoding("br;q=0.5, gzip;q=0.8")).to(be_some().value(ContentEncoding::Gzip));
+        expect!(preferred_encoding("*")).to(be_some().value(ContentEncoding::Brotli));
+        expect!(preferred_encoding("br;q=0, gzip;q=0")).to(be_none());
+        expect!(preferred_encoding("br;q=0, *")).to(be_some().value(ContentEncoding::Gzip));
+        expect!(preferred_encoding("gzip;q=0, br;q=0, *")).to(be_none());
+        expect!(preferred_encoding("br;q=0.2, *;q=0.5")).to(be_some().value(ContentEncoding::Gzip));
+        expect!(preferred_encoding("identity")).to(be_none());
+    }
+
+    #[test]
+    fn compresses_response_bodies_the_client_accepts() {
+        let json = b"{\"items\": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]}".to_vec();
+        let response = Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![s!("application/json")] }),
+            body: OptionalBody::Present(json.clone()),
+            .. Response::default_response()
+        };
+        let request = |accept_encoding: &str| Request {
+            headers: Some(hashmap!{ s!("Accept-Encoding") => vec![accept_encoding.to_string()] }),
+            .. Request::default_request()
+        };
+
+        let gzipped = compress_response(&request("gzip"), response.clone());
+        expect!(gzipped.lookup_header_value(&s!("Content-Encoding"))).to(be_some().value("gzip"));
+        expect!(gzipped.lookup_header_value(&s!("Vary"))).to(be_some().value("Accept-Encoding"));
+        let mut body = vec![];
+        GzDecoder::new(gzipped.body.value().as_slice()).read_to_end(&mut body).unwrap();
+        expect!(body).to(be_equal_to(json.clone()));
+
+        let compressed = compress_response(&request("br"), response.clone());
+        expect!(compressed.lookup_header_value(&s!("Content-Encoding"))).to(be_some().value("br"));
+        let mut body = vec![];
+        brotli::Decompressor::new(compressed.body.value().as_slice(), 4096).read_to_end(&mut body).unwrap();
+        expect!(body).to(be_equal_to(json.clone()));
+
+        let plain = compress_response(&Request::default_request(), response.clone());
+        expect!(plain.body.value()).to(be_equal_to(json));
+        expect!(plain.lookup_header_value(&s!("Content-Encoding"))).to(be_none());
+    }
+}
@@ -0,0 +1,132 @@
+use http::HeaderMap;
+use http::header::HeaderName;
+use itertools::Itertools;
+use regex::Regex;
+use std::time::Duration;
+
+/// Prefix of the request headers that control how the stub server handles that one request. They are removed from the
+/// request before it is matched against the interactions.
+pub const CONTROL_HEADER_PREFIX: &str = "x-pact-stub-";
+/// Delays the response by a number of milliseconds (or a duration like `2s`)
+pub const DELAY_HEADER: &str = "x-pact-stub-delay";
+/// Sends the response with this status code instead of the one of the interaction
+pub const STATUS_HEADER: &str = "x-pact-stub-status";
+/// Only matches the request against the interaction with this description
+pub const INTERACTION_HEADER: &str = "x-pact-stub-interaction";
+/// Longest delay that can be asked for, so a request can not hold its connection indefinitely
+pub const MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// How the request is handled, from its control headers
+#[derive(Debug, Clone, Default)]
+pub struct RequestControls {
+    /// Wait this long before sending the response
+    pub delay: Option<Duration>,
+    /// Status code of the response
+    pub status: Option<u16>,
+    /// Description of the interaction to match the request against
+    pub interaction: Option<String>
+}
+
+impl RequestControls {
+    /// Removes the control headers from the request headers, and returns what they ask for, or an error if any of
+    /// them is not valid
+    pub fn take(headers: &mut HeaderMap) -> Result<RequestControls, String> {
+        let names = headers.keys()
+            .filter(|name| is_control_header(name))
+            .cloned()
+            .collect_vec();
+        let mut controls = RequestControls::default();
+        for name in names {
+            let value = headers.remove(&name);
+            let value = value.as_ref()
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim())
+                .ok_or_else(|| format!("The {} header is not valid text", name))?;
+            match name.as_str() {
+                DELAY_HEADER => controls.delay = Some(parse_delay(value)
+                    .map_err(|err| format!("'{}' is not a valid {} header: {}", value, name, err))?),
+                STATUS_HEADER => controls.status = Some(value.parse::<u16>().ok()
+                    .filter(|status| (100..600).contains(status))
+                    .ok_or_else(|| format!("'{}' is not a valid {} header: must be a status code between 100 and 599", value, name))?),
+                INTERACTION_HEADER => controls.interaction = Some(value.to_string()),
+                _ => warn!("Ignoring the unknown control header {}", name)
+            }
+        }
+        Ok(controls)
+    }
+
+    /// If the request has any control headers
+    pub fn is_empty(&self) -> bool {
+        self.delay.is_none() && self.status.is_none() && self.interaction.is_none()
+    }
+
+    /// Regular expression that only matches the description of the interaction, if there is one
+    pub fn interaction_filter(&self) -> Option<Regex> {
+        self.interaction.as_ref().map(|description| Regex::new(&format!("^{}$", regex::escape(description))).unwrap())
+    }
+}
+
+/// Parses a delay in milliseconds, or with a unit of `ms` or `s`, of at most the maximum delay
+fn parse_delay(value: &str) -> Result<Duration, String> {
+    let (digits, unit) = match value.find(|ch: char| !ch.is_ascii_digit()) {
+        Some(index) => (&value[..index], value[index..].trim()),
+        None => (value, "ms")
+    };
+    let amount = digits.parse::<u64>().map_err(|e| e.to_string())?;
+    let delay = match unit.to_lowercase().as_str() {
+        "ms" => Duration::from_millis(amount),
+        "s" => Duration::from_secs(amount),
+        unit => return Err(format!("'{}' is not a valid unit, it must be ms or s", unit))
+    };
+    if delay > MAX_DELAY {
+        Err(format!("must be at most {} seconds", MAX_DELAY.as_secs()))
+    } else {
+        Ok(delay)
+    }
+}
+
+/// If the header is one of the control headers
+fn is_control_header(name: &HeaderName) -> bool {
+    name.as_str().starts_with(CONTROL_HEADER_PREFIX)
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use http::HeaderValue;
+    use super::*;
+
+    #[test]
+    fn takes_the_control_headers_out_of_the_request() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Pact-Stub-Delay", HeaderValue::from_static("250"));
+        headers.insert("x-pact-stub-status", HeaderValue::from_static("503"));
+        headers.insert("X-Pact-Stub-Interaction", HeaderValue::from_static("a request for user 42"));
+        headers.insert("x-pact-stub-unknown", HeaderValue::from_static("ignored"));
+        headers.insert("accept", HeaderValue::from_static("application/json"));
+        let controls = RequestControls::take(&mut headers).unwrap();
+        expect!(controls.delay).to(be_some().value(Duration::from_millis(250)));
+        expect!(controls.status).to(be_some().value(503));
+        expect!(controls.interaction_filter().map(|regex| regex.is_match("a request for user 42"))).to(be_some().value(true));
+        expect!(controls.interaction_filter().map(|regex| regex.is_match("a request for user 420"))).to(be_some().value(false));
+        expect!(headers.keys().map(|name| name.as_str()).collect_vec()).to(be_equal_to(vec![ "accept" ]));
+    }
+
+    #[test]
+    fn rejects_invalid_control_headers() {
+        let take = |name: &'static str, value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            RequestControls::take(&mut headers)
+        };
+        expect!(take(DELAY_HEADER, "2s").map(|controls| controls.delay)).to(be_ok().value(Some(Duration::from_secs(2))));
+        expect!(take(DELAY_HEADER, "soon")).to(be_err());
+        expect!(take(DELAY_HEADER, "5m")).to(be_err());
+        expect!(take(DELAY_HEADER, "300s").map(|controls| controls.delay)).to(be_ok().value(Some(MAX_DELAY)));
+        expect!(take(DELAY_HEADER, "301s")).to(be_err());
+        expect!(take(DELAY_HEADER, "99999999999s")).to(be_err());
+        expect!(take(STATUS_HEADER, "99")).to(be_err());
+        expect!(take(STATUS_HEADER, "OK")).to(be_err());
+        expect!(take("accept", "*/*").map(|controls| controls.is_empty())).to(be_ok().value(true));
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::{DateTime, Local};
+use crate::admin;
+use pact_matching::models::{Interaction, Pact};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Path of the endpoint that returns which interactions have been used
+pub const COVERAGE_PATH: &str = "/_pact-stub/coverage";
+
+/// Path of the endpoint that returns how often and when each interaction was last used
+pub const STATS_PATH: &str = "/_pact-stub/stats";
+
+/// How often an interaction has been used, and when it was last used
+#[derive(Debug, Clone, Copy)]
+struct Hits {
+    count: u64,
+    last: SystemTime
+}
+
+/// Counts the number of requests each interaction has been used to respond to, so the interactions that the
+/// consumer tests never use can be reported, and the number of requests that did not match any
+#[derive(Debug, Default)]
+pub struct Coverage {
+    hits: Mutex<HashMap<(String, String, String), Hits>>,
+    unmatched: AtomicU64
+}
+
+fn key(pact: &Pact, interaction: &Interaction) -> (String, String, String) {
+    (pact.consumer.name.clone(), pact.provider.name.clone(), interaction.description.clone())
+}
+
+impl Coverage {
+    /// Records that the interaction from the pact was used to respond to a request
+    pub fn record(&self, pact: &Pact, interaction: &Interaction) {
+        let now = SystemTime::now();
+        let mut hits = self.hits.lock().unwrap();
+        let hits = hits.entry(key(pact, interaction)).or_insert(Hits { count: 0, last: now });
+        hits.count += 1;
+        hits.last = now;
+    }
+
+    /// Records that a request did not match any interaction
+    pub fn record_unmatched(&self) {
+        self.unmatched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of requests the interaction from the pact has been used for
+    pub fn hits(&self, pact: &Pact, interaction: &Interaction) -> u64 {
+        self.hits.lock().unwrap().get(&key(pact, interaction)).map(|hits| hits.count).unwrap_or(0)
+    }
+
+    /// Returns the interactions of the pacts that have not been used
+    pub fn unused<'a, I: IntoIterator<Item = &'a Pact>>(&self, pacts: I) -> Vec<(&'a Pact, &'a Interaction)> {
+        pacts.into_iter()
+            .flat_map(|pact| pact.interactions.iter().map(move |interaction| (pact, interaction)))
+            .filter(|(pact, interaction)| self.hits(pact, interaction) == 0)
+            .collect()
+    }
+
+    /// Returns the total and used number of interactions in the pacts, with a summary of each interaction (with the
+    /// number of times it has been used) and of the unused ones
+    pub fn report<'a, I: IntoIterator<Item = &'a Pact>>(&self, pacts: I) -> Value {
+        let interactions = pacts.into_iter()
+            .flat_map(|pact| pact.interactions.iter().map(move |interaction| (pact, interaction)))
+            .map(|(pact, interaction)| {
+                let mut summary = admin::interaction_summary(pact, interaction);
+                summary["hits"] = json!(self.hits(pact, interaction));
+                summary
+            })
+            .collect::<Vec<Value>>();
+        let unused = interactions.iter()
+            .filter(|summary| summary["hits"] == json!(0))
+            .cloned()
+            .collect::<Vec<Value>>();
+        json!({
+            "total": interactions.len(),
+            "used": interactions.len() - unused.len(),
+            "unused": unused,
+            "interactions": interactions
+        })
+    }
+
+    /// Returns the number of requests that matched an interaction and that did not, with the number of requests
+    /// each interaction of the pacts has been used for and when it was last used
+    pub fn stats<'a, I: IntoIterator<Item = &'a Pact>>(&self, pacts: I) -> Value {
+        let hits = self.hits.lock().unwrap();
+        let interactions = pacts.into_iter()
+            .flat_map(|pact| pact.interactions.iter().map(move |interaction| (pact, interaction)))
+            .map(|(pact, interaction)| {
+                let hits = hits.get(&key(pact, interaction));
+                let mut summary = admin::interaction_summary(pact, interaction);
+                summary["hits"] = json!(hits.map(|hits| hits.count).unwrap_or(0));
+                summary["lastHit"] = json!(hits.map(|hits| DateTime::<Local>::from(hits.last).to_rfc3339()));
+                summary
+            })
+            .collect::<Vec<Value>>();
+        json!({
+            "matched": hits.values().map(|hits| hits.count).sum::<u64>(),
+            "unmatched": self.unmatched.load(Ordering::Relaxed),
+            "interactions": interactions
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, Pact, Provider};
+    use super::*;
+
+    #[test]
+    fn reports_the_unused_interactions() {
+        let pact = Pact {
+            provider: Provider { name: s!("orders") },
+            interactions: vec![
+                Interaction { description: s!("create an order"), .. Interaction::default() },
+                Interaction { description: s!("delete an order"), .. Interaction::default() }
+            ],
+            .. Pact::default()
+        };
+        let pacts = vec![ pact ];
+        let coverage = Coverage::default();
+        coverage.record(&pacts[0], &pacts[0].interactions[0]);
+        coverage.record(&pacts[0], &pacts[0].interactions[0]);
+
+        let unused = coverage.unused(&pacts).iter().map(|(_, interaction)| interaction.description.clone()).collect::<Vec<String>>();
+        expect!(unused).to(be_equal_to(vec![ s!("delete an order") ]));
+        let report = coverage.report(&pacts);
+        expect!(report["total"].clone()).to(be_equal_to(json!(2)));
+        expect!(report["used"].clone()).to(be_equal_to(json!(1)));
+        expect!(report["interactions"][0]["hits"].clone()).to(be_equal_to(json!(2)));
+        expect!(report["unused"][0]["description"].clone()).to(be_equal_to(json!("delete an order")));
+    }
+
+    #[test]
+    fn counts_the_matched_and_unmatched_requests() {
+        let pact = Pact {
+            provider: Provider { name: s!("orders") },
+            interactions: vec![
+                Interaction { description: s!("create an order"), .. Interaction::default() },
+                Interaction { description: s!("delete an order"), .. Interaction::default() }
+            ],
+            .. Pact::default()
+        };
+        let pacts = vec![ pact ];
+        let coverage = Coverage::default();
+        coverage.record(&pacts[0], &pacts[0].interactions[1]);
+        coverage.record(&pacts[0], &pacts[0].interactions[1]);
+        coverage.record_unmatched();
+
+        let stats = coverage.stats(&pacts);
+        expect!(stats["matched"].clone()).to(be_equal_to(json!(2)));
+        expect!(stats["unmatched"].clone()).to(be_equal_to(json!(1)));
+        expect!(stats["interactions"][0]["hits"].clone()).to(be_equal_to(json!(0)));
+        expect!(stats["interactions"][0]["lastHit"].clone()).to(be_equal_to(Value::Null));
+        expect!(stats["interactions"][1]["hits"].clone()).to(be_equal_to(json!(2)));
+        expect!(stats["interactions"][1]["lastHit"].is_string()).to(be_true());
+    }
+}
@@ -0,0 +1,83 @@
+use pact_matching::models::OptionalBody;
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str;
+
+/// Unchanged lines shown before and after each change
+const CONTEXT_LINES: usize = 3;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// If the diffs are colored: when the output is a terminal, and `NO_COLOR` is not set
+pub fn use_colors() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/// The lines of the body, with JSON bodies pretty printed (with the keys sorted) so the diff is per value
+fn body_lines(body: &OptionalBody) -> String {
+    match body {
+        OptionalBody::Present(bytes) => match serde_json::from_slice::<Value>(bytes) {
+            Ok(json) => serde_json::to_string_pretty(&json).unwrap_or_default(),
+            Err(_) => match str::from_utf8(bytes) {
+                Ok(text) => text.to_string(),
+                Err(_) => format!("<{} bytes of binary data>", bytes.len())
+            }
+        },
+        OptionalBody::Null => s!("null"),
+        _ => String::new()
+    }
+}
+
+/// Unified diff of the expected and actual bodies, showing the changed lines with a few lines around them
+pub fn body_diff(expected: &OptionalBody, actual: &OptionalBody, colors: bool) -> String {
+    let (expected, actual) = (body_lines(expected), body_lines(actual));
+    let lines = TextDiff::from_lines(&expected, &actual);
+    let paint = |color: &str, text: String| if colors { format!("{}{}{}", color, text, RESET) } else { text };
+    let mut diff = vec![ paint(RED, s!("--- expected")), paint(GREEN, s!("+++ actual")) ];
+    // each group is the changes close to each other, with the unchanged lines around them
+    for (i, group) in lines.grouped_ops(CONTEXT_LINES).iter().enumerate() {
+        let from_start = group.first().map(|op| op.old_range().start == 0 && op.new_range().start == 0).unwrap_or(false);
+        if i > 0 || !from_start {
+            diff.push(paint(CYAN, s!("@@ ... @@")));
+        }
+        for change in group.iter().flat_map(|op| lines.iter_changes(op)) {
+            let line = change.value().trim_end_matches('\n');
+            diff.push(match change.tag() {
+                ChangeTag::Delete => paint(RED, format!("-{}", line)),
+                ChangeTag::Insert => paint(GREEN, format!("+{}", line)),
+                ChangeTag::Equal => format!(" {}", line)
+            });
+        }
+    }
+    diff.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    fn body(text: &str) -> OptionalBody {
+        OptionalBody::Present(text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn shows_the_changed_lines_of_the_pretty_printed_json() {
+        let expected = body(r#"{"id": 1, "items": [1, 2, 3, 4, 5, 6, 7, 8, 9], "name": "Widget"}"#);
+        let actual = body(r#"{"id": 1, "items": [1, 2, 3, 4, 5, 6, 7, 8, 9], "name": "Gadget"}"#);
+        expect!(body_diff(&expected, &actual, false)).to(be_equal_to([
+            "--- expected", "+++ actual", "@@ ... @@", "     8,", "     9", "   ],", "-  \"name\": \"Widget\"", "+  \"name\": \"Gadget\"", " }"
+        ].join("\n")));
+        expect!(body_diff(&body("a"), &body("b"), true))
+            .to(be_equal_to(s!("\x1b[31m--- expected\x1b[0m\n\x1b[32m+++ actual\x1b[0m\n\x1b[31m-a\x1b[0m\n\x1b[32m+b\x1b[0m")));
+        expect!(body_diff(&body("a\nb\nc\nd\ne\nf\ng\nh\ni\nj"), &body("A\nb\nc\nd\ne\nf\ng\nh\ni\nJ"), false)).to(be_equal_to([
+            "--- expected", "+++ actual", "-a", "+A", " b", " c", " d", "@@ ... @@", " g", " h", " i", "-j", "+J"
+        ].join("\n")));
+        expect!(body_diff(&body("same"), &body("same"), false)).to(be_equal_to(s!("--- expected\n+++ actual")));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::generators::StateGenerators;
+use crate::index::InteractionIndex;
+use crate::response_cache::ResponseCache;
+use itertools::Itertools;
+use pact_matching::models::{Consumer, Interaction, Pact, PactSpecification, Provider};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Consumer and provider name for interactions that are added without one
+pub const DEFAULT_NAME: &str = "dynamic";
+
+/// An interaction that was added through the admin API while the server is running
+#[derive(Debug, Clone)]
+pub struct DynamicInteraction {
+    pub id: String,
+    pub consumer: String,
+    pub provider: String,
+    pub interaction: Interaction
+}
+
+/// The pacts that requests are matched against, with the index of their interactions
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub sources: Vec<Arc<Pact>>,
+    /// Provider state generators of the responses, by the position of their interactions in the sources
+    pub state_generators: StateGenerators,
+    pub index: InteractionIndex,
+    /// Responses of the requests matched against the snapshot
+    pub responses: ResponseCache
+}
+
+/// The loaded pacts and the interactions added while the server is running. Requests are matched against a snapshot
+/// of them, which is replaced when interactions are added or removed.
+#[derive(Debug)]
+pub struct Interactions {
+    loaded: Vec<Arc<Pact>>,
+    state_generators: StateGenerators,
+    dynamic: Vec<DynamicInteraction>,
+    next_id: u64,
+    snapshot: Arc<Snapshot>
+}
+
+/// Parses an interaction from the JSON body of a request, in the same format as in a pact file
+pub fn parse_interaction(body: &[u8]) -> Result<Interaction, String> {
+    let json: Value = serde_json::from_slice(body).map_err(|err| format!("Interaction is not valid JSON - {}", err))?;
+    if !json.is_object() {
+        return Err(s!("Interaction must be a JSON object"));
+    }
+    for key in &[ "description", "request", "response" ] {
+        if json.get(key).is_none() {
+            return Err(format!("Interaction does not have a '{}'", key));
+        }
+    }
+    Ok(Interaction::from_json(0, &json, &PactSpecification::V3))
+}
+
+impl Snapshot {
+    pub fn new(sources: Vec<Arc<Pact>>, state_generators: StateGenerators) -> Snapshot {
+        Snapshot { index: InteractionIndex::new(&sources), responses: ResponseCache::new(&sources), sources, state_generators }
+    }
+}
+
+impl Interactions {
+    pub fn new(loaded: Vec<Arc<Pact>>, state_generators: StateGenerators) -> Interactions {
+        let snapshot = Arc::new(Snapshot::new(loaded.clone(), state_generators.clone()));
+        Interactions { loaded, state_generators, dynamic: vec![], next_id: 1, snapshot }
+    }
+
+    /// The pacts to match requests against
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot.clone()
+    }
+
+    /// Adds an interaction for the consumer and provider, returning its id
+    pub fn add(&mut self, consumer: &str, provider: &str, interaction: Interaction) -> String {
+        let id = self.next_id.to_string();
+        self.next_id += 1;
+        info!("Adding interaction '{}' with id {} for '{}' -> '{}'", interaction.description, id, consumer, provider);
+        self.dynamic.push(DynamicInteraction {
+            id: id.clone(),
+            consumer: consumer.to_string(),
+            provider: provider.to_string(),
+            interaction
+        });
+        self.rebuild();
+        id
+    }
+
+    /// The added interaction with the id
+    pub fn get(&self, id: &str) -> Option<&DynamicInteraction> {
+        self.dynamic.iter().find(|dynamic| dynamic.id == id)
+    }
+
+    /// Removes the added interaction with the id. Returns false if there is not one.
+    pub fn remove(&mut self, id: &str) -> bool {
+        match self.dynamic.iter().position(|dynamic| dynamic.id == id) {
+            Some(position) => {
+                let removed = self.dynamic.remove(position);
+                info!("Removed interaction '{}' with id {}", removed.interaction.description, id);
+                self.rebuild();
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Replaces the loaded pacts (like when they are loaded from the sources again), keeping the added interactions
+    pub fn replace_loaded(&mut self, loaded: Vec<Arc<Pact>>, state_generators: StateGenerators) {
+        self.loaded = loaded;
+        self.state_generators = state_generators;
+        self.rebuild();
+    }
+
+    /// The added interactions, grouped into a pact for each consumer and provider. These come before the loaded pacts,
+    /// so they are used when they match a request as well as a loaded interaction does.
+    fn rebuild(&mut self) {
+        let mut sources = self.dynamic.iter()
+            .sorted_by(|a, b| (&a.consumer, &a.provider).cmp(&(&b.consumer, &b.provider)))
+            .into_iter()
+            .group_by(|dynamic| (dynamic.consumer.clone(), dynamic.provider.clone()))
+            .into_iter()
+            .map(|((consumer, provider), interactions)| Arc::new(Pact {
+                consumer: Consumer { name: consumer },
+                provider: Provider { name: provider },
+                interactions: interactions.map(|dynamic| dynamic.interaction.clone()).collect(),
+                .. Pact::default()
+            }))
+            .collect::<Vec<Arc<Pact>>>();
+        let state_generators = self.state_generators.after(sources.len());
+        sources.extend(self.loaded.iter().cloned());
+        self.snapshot = Arc::new(Snapshot::new(sources, state_generators));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, Pact};
+    use pact_matching::models::generators::GeneratorCategory;
+    use crate::generators::StateGenerator;
+    use super::*;
+
+    #[test]
+    fn parses_interactions_in_the_pact_file_format() {
+        let interaction = parse_interaction(br#"{
+            "description": "get an order",
+            "request": { "method": "GET", "path": "/orders/1" },
+            "response": { "status": 200, "body": { "id": 1 } }
+        }"#).unwrap();
+        expect!(interaction.description).to(be_equal_to("get an order"));
+        expect!(interaction.request.path).to(be_equal_to("/orders/1"));
+        expect!(interaction.response.status).to(be_equal_to(200));
+
+        expect!(parse_interaction(b"not json")).to(be_err());
+        expect!(parse_interaction(br#"{ "description": "no request" }"#)).to(be_err());
+    }
+
+    #[test]
+    fn adds_and_removes_interactions() {
+        let loaded = Pact {
+            interactions: vec![ Interaction { description: s!("loaded"), .. Interaction::default() } ],
+            .. Pact::default()
+        };
+        let mut interactions = Interactions::new(vec![ Arc::new(loaded) ], StateGenerators::default());
+        let first = interactions.add("web", "orders", Interaction { description: s!("first"), .. Interaction::default() });
+        let second = interactions.add("web", "orders", Interaction { description: s!("second"), .. Interaction::default() });
+
+        let snapshot = interactions.snapshot();
+        expect!(snapshot.sources.len()).to(be_equal_to(2));
+        expect!(snapshot.sources[0].provider.name.clone()).to(be_equal_to("orders"));
+        expect!(snapshot.sources[0].interactions.len()).to(be_equal_to(2));
+        expect!(snapshot.index.candidates("GET", "/")).to(be_equal_to(vec![ (0, 0), (0, 1), (1, 0) ]));
+
+        expect!(interactions.remove(&first)).to(be_true());
+        expect!(interactions.remove(&first)).to(be_false());
+        let snapshot = interactions.snapshot();
+        expect!(snapshot.sources[0].interactions[0].description.clone()).to(be_equal_to("second"));
+        expect!(interactions.remove(&second)).to(be_true());
+        expect!(interactions.snapshot().sources.len()).to(be_equal_to(1));
+    }
+
+    #[test]
+    fn groups_the_added_interactions_by_consumer_and_provider() {
+        let mut interactions = Interactions::new(vec![], StateGenerators::default());
+        for (provider, description) in &[ ("orders", "first"), ("users", "second"), ("orders", "third") ] {
+            interactions.add("web", provider, Interaction { description: description.to_string(), .. Interaction::default() });
+        }
+
+        let snapshot = interactions.snapshot();
+        let descriptions = snapshot.sources.iter()
+            .map(|pact| (pact.provider.name.clone(), pact.interactions.iter().map(|i| i.description.clone()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        expect!(descriptions).to(be_equal_to(vec![
+            (s!("orders"), vec![ s!("first"), s!("third") ]),
+            (s!("users"), vec![ s!("second") ])
+        ]));
+    }
+
+    #[test]
+    fn replaces_the_loaded_pacts_and_keeps_the_added_interactions() {
+        let pact = |description: &str| Arc::new(Pact {
+            interactions: vec![ Interaction { description: description.to_string(), .. Interaction::default() } ],
+            .. Pact::default()
+        });
+        let state_generators = StateGenerators::new(vec![ btreemap!{ 0 => vec![ StateGenerator {
+            category: GeneratorCategory::STATUS, path: s!(""), expression: s!("status") } ] } ]);
+        let mut interactions = Interactions::new(vec![ pact("v1") ], StateGenerators::default());
+        interactions.add("web", "orders", Interaction { description: s!("added"), .. Interaction::default() });
+        interactions.replace_loaded(vec![ pact("v2") ], state_generators);
+        let snapshot = interactions.snapshot();
+        expect!(snapshot.sources.len()).to(be_equal_to(2));
+        expect!(snapshot.sources[0].interactions[0].description.clone()).to(be_equal_to("added"));
+        expect!(snapshot.sources[1].interactions[0].description.clone()).to(be_equal_to("v2"));
+        expect!(snapshot.state_generators.get((0, 0)).is_empty()).to(be_true());
+        expect!(snapshot.state_generators.get((1, 0)).len()).to(be_equal_to(1));
+    }
+}
@@ -0,0 +1,156 @@
+use pact_matching::models::{HttpPart, OptionalBody, Pact, Request, Response};
+use pact_matching::models::build_query_string;
+use itertools::Itertools;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str;
+
+/// Converts a name into a lowercase directory name with only letters, digits and dashes
+pub fn slug(name: &str) -> String {
+    let slug = name.to_lowercase().chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .join("-");
+    if slug.is_empty() { s!("interaction") } else { slug }
+}
+
+fn sorted_headers(part: &dyn HttpPart) -> Vec<(String, String)> {
+    part.headers().clone().unwrap_or_default().into_iter()
+        .sorted_by(|a, b| Ord::cmp(&a.0, &b.0))
+        .into_iter()
+        .flat_map(|(name, values)| values.into_iter().map(move |value| (name.clone(), value)))
+        .collect()
+}
+
+/// Returns the request as an HTTP message. Bodies that are not text are written to a separate file, which the
+/// message refers to with a `< ./request.body` line.
+pub fn request_http(request: &Request) -> (String, Option<Vec<u8>>) {
+    let mut http = request.method.to_uppercase() + " " + request.path.as_str();
+    if let Some(ref query) = request.query {
+        http.push('?');
+        http.push_str(&build_query_string(query.clone()));
+    }
+    http.push_str(" HTTP/1.1\n");
+    for (name, value) in sorted_headers(request) {
+        http.push_str(&format!("{}: {}\n", name, value));
+    }
+    let mut body_file = None;
+    if let OptionalBody::Present(ref body) = request.body {
+        http.push('\n');
+        match str::from_utf8(body) {
+            Ok(text) => http.push_str(text),
+            Err(_) => {
+                http.push_str("< ./request.body");
+                body_file = Some(body.clone());
+            }
+        }
+        http.push('\n');
+    }
+    (http, body_file)
+}
+
+/// Returns the response as a JSON document, with the status, headers (with the values of repeated headers joined with
+/// commas) and body. JSON bodies are included as JSON, text bodies as a string, and other bodies as a base64 encoded
+/// `bodyBase64` string.
+pub fn response_json(response: &Response) -> Value {
+    let mut json = json!({
+        "status": response.status,
+        "headers": response.headers.clone().unwrap_or_default().into_iter()
+            .map(|(name, values)| (name, json!(values.join(", "))))
+            .collect::<serde_json::Map<String, Value>>()
+    });
+    if let OptionalBody::Present(ref body) = response.body {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(body) => json["body"] = body,
+            Err(_) => match str::from_utf8(body) {
+                Ok(text) => json["body"] = json!(text),
+                Err(_) => json["bodyBase64"] = json!(base64::encode(body))
+            }
+        }
+    }
+    json
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), String> {
+    fs::write(path, contents).map_err(|err| format!("Could not write '{}' - {}", path.display(), err))
+}
+
+/// Writes the request and response of each interaction as fixture files, in a
+/// `<consumer>-<provider>/<number>-<description>` directory for each interaction. Returns the number of interactions
+/// that were written.
+pub fn export_fixtures(pacts: &[Pact], dir: &Path) -> Result<usize, String> {
+    let mut count = 0;
+    let mut numbers: HashMap<PathBuf, usize> = HashMap::new();
+    for pact in pacts {
+        let pact_dir = dir.join(slug(&format!("{}-{}", pact.consumer.name, pact.provider.name)));
+        for interaction in &pact.interactions {
+            let number = numbers.entry(pact_dir.clone()).or_insert(0);
+            *number += 1;
+            let interaction_dir = pact_dir.join(format!("{:03}-{}", number, slug(&interaction.description)));
+            fs::create_dir_all(&interaction_dir)
+                .map_err(|err| format!("Could not create directory '{}' - {}", interaction_dir.display(), err))?;
+            let (http, body) = request_http(&interaction.request);
+            write_file(&interaction_dir.join("request.http"), http.as_bytes())?;
+            if let Some(body) = body {
+                write_file(&interaction_dir.join("request.body"), &body)?;
+            }
+            let response = serde_json::to_string_pretty(&response_json(&interaction.response)).unwrap();
+            write_file(&interaction_dir.join("response.json"), (response + "\n").as_bytes())?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Request, Response};
+    use super::*;
+
+    #[test]
+    fn creates_directory_names_from_descriptions() {
+        expect!(slug("Create an Order (with items)")).to(be_equal_to("create-an-order-with-items"));
+        expect!(slug("!!!")).to(be_equal_to("interaction"));
+    }
+
+    #[test]
+    fn writes_requests_as_http_messages() {
+        let request = Request {
+            method: s!("post"),
+            path: s!("/orders"),
+            query: Some(hashmap!{ s!("dryRun") => vec![ s!("true") ] }),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present("{\"sku\":\"1234\"}".into()),
+            .. Request::default_request()
+        };
+        expect!(request_http(&request)).to(be_equal_to((
+            s!("POST /orders?dryRun=true HTTP/1.1\nContent-Type: application/json\n\n{\"sku\":\"1234\"}\n"), None)));
+
+        let upload = Request { body: OptionalBody::Present(vec![ 0xFF, 0xFE ]), .. Request::default_request() };
+        expect!(request_http(&upload)).to(be_equal_to((
+            s!("GET / HTTP/1.1\n\n< ./request.body\n"), Some(vec![ 0xFF, 0xFE ]))));
+    }
+
+    #[test]
+    fn writes_responses_as_json() {
+        let response = Response {
+            status: 201,
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present("{\"id\":1}".into()),
+            .. Response::default_response()
+        };
+        expect!(response_json(&response)).to(be_equal_to(json!({
+            "status": 201,
+            "headers": { "Content-Type": "application/json" },
+            "body": { "id": 1 }
+        })));
+
+        let image = Response { body: OptionalBody::Present(vec![ 0xFF, 0xFE ]), .. Response::default_response() };
+        expect!(response_json(&image)["bodyBase64"].clone()).to(be_equal_to(json!("//4=")));
+    }
+}
@@ -0,0 +1,110 @@
+use pact_matching::{self, Mismatch};
+use pact_matching::models::{HttpPart, OptionalBody, Request};
+use pact_matching::models::matchingrules::{Category, MatchingRules};
+use pact_matching::models::parse_query_string;
+use std::collections::HashMap;
+use crate::pact_support;
+
+static FORM_URLENCODED: &str = "application/x-www-form-urlencoded";
+
+/// If the request has a URL encoded form body
+pub fn is_form(request: &Request) -> bool {
+    request.content_type() == FORM_URLENCODED
+}
+
+/// The fields of the form body, with their values in the order they were sent
+fn parse_form(body: &[u8]) -> HashMap<String, Vec<String>> {
+    parse_query_string(&String::from_utf8_lossy(body).trim().to_string()).unwrap_or_default()
+}
+
+/// The matching rules for the body fields as query parameter rules, which pact matching resolves with the same
+/// `$.<field name>` paths
+fn field_rules(matchers: &MatchingRules) -> MatchingRules {
+    let mut rules = MatchingRules::default();
+    if let Some(body) = matchers.rules_for_category(&s!("body")) {
+        rules.rules.insert(s!("query"), Category { name: s!("query"), .. body });
+    }
+    rules
+}
+
+/// Matches URL encoded form bodies field by field, in any order, instead of comparing the encoded text. The values of
+/// each field are compared in the order they were sent, using any matching rules for the field (with a path of
+/// `$.<field name>`). Fields that are not in the interaction are mismatches.
+pub fn match_form(expected: &Request, actual: &Request, mismatches: &mut Vec<Mismatch>, matchers: &MatchingRules) {
+    let expected_body = match expected.body {
+        OptionalBody::Present(ref body) => body,
+        _ => return
+    };
+    if !is_form(actual) {
+        mismatches.push(Mismatch::BodyTypeMismatch { expected: expected.content_type(), actual: actual.content_type() });
+        return;
+    }
+    let mut field_mismatches = vec![];
+    pact_matching::match_query(Some(parse_form(expected_body)), Some(parse_form(pact_support::body_bytes(&actual.body))),
+                               &mut field_mismatches, &field_rules(matchers));
+    mismatches.extend(field_mismatches.into_iter().map(|mismatch| match mismatch {
+        Mismatch::QueryMismatch { parameter, expected, actual, mismatch } => Mismatch::BodyMismatch {
+            path: format!("$.{}", parameter),
+            expected: Some(expected.into_bytes()),
+            actual: Some(actual.into_bytes()),
+            mismatch: mismatch.replace("query parameter", "form field")
+        },
+        mismatch => mismatch
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::matchingrules::*;
+    use super::*;
+
+    fn form_request(body: &str) -> Request {
+        Request {
+            method: s!("POST"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/x-www-form-urlencoded") ] }),
+            body: OptionalBody::Present(body.as_bytes().to_vec()),
+            .. Request::default_request()
+        }
+    }
+
+    #[test]
+    fn matches_the_fields_in_any_order() {
+        let expected = form_request("grant_type=password&username=fred&password=secret");
+        let mut mismatches = vec![];
+        match_form(&expected, &form_request("password=secret&grant_type=password&username=fred"), &mut mismatches,
+                   &MatchingRules::default());
+        expect!(mismatches.iter()).to(be_empty());
+
+        let mut mismatches = vec![];
+        match_form(&expected, &form_request("grant_type=password&username=barney&password=secret&scope=all"),
+                   &mut mismatches, &MatchingRules::default());
+        let mut paths = mismatches.iter().map(|mismatch| match mismatch {
+            Mismatch::BodyMismatch { path, .. } => path.clone(),
+            _ => String::new()
+        }).collect::<Vec<String>>();
+        paths.sort();
+        expect!(paths).to(be_equal_to(vec![ s!("$.scope"), s!("$.username") ]));
+
+        let mut mismatches = vec![];
+        match_form(&expected, &Request { headers: None, .. form_request("{}") }, &mut mismatches, &MatchingRules::default());
+        expect!(mismatches.len()).to(be_equal_to(1));
+    }
+
+    #[test]
+    fn applies_the_matching_rules_of_the_fields() {
+        let expected = Request {
+            matching_rules: matchingrules!{
+                "body" => { "$.username" => [ MatchingRule::Regex(s!("^[a-z]+$")) ], "$['client id']" => [ MatchingRule::Type ] }
+            },
+            .. form_request("username=fred&client+id=web")
+        };
+        let mut mismatches = vec![];
+        match_form(&expected, &form_request("client+id=mobile&username=barney"), &mut mismatches, &expected.matching_rules);
+        expect!(mismatches.iter()).to(be_empty());
+
+        let mut mismatches = vec![];
+        match_form(&expected, &form_request("username=Barney42&client+id=mobile"), &mut mismatches, &expected.matching_rules);
+        expect!(mismatches.len()).to(be_equal_to(1));
+    }
+}
@@ -0,0 +1,789 @@
+use chrono::Local;
+use pact_matching::models::{DetectedContentType, HttpPart, Interaction, OptionalBody, Pact, Request, Response};
+use pact_matching::models::generators::{Generator, GeneratorCategory};
+use pact_matching::models::parse_query_string;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::distributions::Alphanumeric;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::iter;
+use sxd_document::dom::Element;
+use crate::index::InteractionPosition;
+use crate::pact_support;
+use crate::xml;
+
+/// Request header with values for the provider state generators, like `id=42&name=Fred`
+pub const STATE_VALUES_HEADER: &str = "X-Pact-State-Values";
+/// Prefix of the query parameters with values for the provider state generators, like `pact-state.id=42`
+pub const STATE_VALUES_QUERY_PREFIX: &str = "pact-state.";
+
+/// A value of a response that is filled in from the provider state values, like
+/// `{ "type": "ProviderState", "expression": "/orders/${id}" }`. They are not loaded with the rest of the pact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateGenerator {
+    /// The status, a header or the body
+    pub category: GeneratorCategory,
+    /// Name of the header, or path of the value in the body
+    pub path: String,
+    /// Expression for the value, like `/orders/${id}`
+    pub expression: String
+}
+
+/// The provider state generators of the responses of a pact, by the index of their interaction
+pub type PactStateGenerators = BTreeMap<usize, Vec<StateGenerator>>;
+
+/// The provider state generators of the responses of the pacts, by the position of their interaction
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateGenerators {
+    pacts: Vec<PactStateGenerators>
+}
+
+impl StateGenerators {
+    /// The generators of the pacts, in the same order as the pacts
+    pub fn new(pacts: Vec<PactStateGenerators>) -> StateGenerators {
+        StateGenerators { pacts }
+    }
+
+    /// The generators of the interaction at the position
+    pub fn get(&self, position: InteractionPosition) -> &[StateGenerator] {
+        self.pacts.get(position.0).and_then(|pact| pact.get(&position.1)).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The generators with the positions moved for the number of pacts without any that come before them
+    pub fn after(&self, pacts: usize) -> StateGenerators {
+        StateGenerators { pacts: iter::repeat_with(BTreeMap::new).take(pacts).chain(self.pacts.iter().cloned()).collect() }
+    }
+}
+
+/// Keeps the interactions of the pact that the function returns true for, and the generators of their responses
+pub fn retain_interactions<F>(pact: &mut Pact, generators: &mut PactStateGenerators, mut keep: F) where F: FnMut(&Interaction) -> bool {
+    let mut kept = BTreeMap::new();
+    let interactions = pact.interactions.drain(..).enumerate()
+        .filter(|(_, interaction)| keep(interaction))
+        .enumerate()
+        .map(|(new_index, (index, interaction))| {
+            if let Some(state_generators) = generators.remove(&index) {
+                kept.insert(new_index, state_generators);
+            }
+            interaction
+        })
+        .collect();
+    pact.interactions = interactions;
+    *generators = kept;
+}
+
+/// Maximum number of times a repetition (like `*` or `+`) in a regex generator is repeated
+const MAX_REGEX_REPEAT: u32 = 20;
+
+/// A step in the path of a body generator, like `$.items[*].id`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathToken {
+    Field(String),
+    Index(usize),
+    /// Every field of an object, or every item of an array
+    Star
+}
+
+/// Parses the path of a body generator. The root (`$`) is optional.
+pub fn parse_path(path: &str) -> Result<Vec<PathToken>, String> {
+    let mut tokens = vec![];
+    let mut chars = path.trim().chars().peekable();
+    if chars.peek() == Some(&'$') {
+        chars.next();
+    }
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let field: String = iter::from_fn(|| chars.next_if(|c| *c != '.' && *c != '[')).collect();
+                match field.as_str() {
+                    "" => return Err(format!("'{}' has an empty field name", path)),
+                    "*" => tokens.push(PathToken::Star),
+                    _ => tokens.push(PathToken::Field(field))
+                }
+            },
+            '[' => {
+                let index: String = iter::from_fn(|| chars.next_if(|c| *c != ']')).collect();
+                if chars.next() != Some(']') {
+                    return Err(format!("'{}' has an unterminated '['", path));
+                }
+                let index = index.trim();
+                if index == "*" {
+                    tokens.push(PathToken::Star);
+                } else if index.len() >= 2 && (index.starts_with('\'') && index.ends_with('\'') ||
+                    index.starts_with('"') && index.ends_with('"')) {
+                    tokens.push(PathToken::Field(index[1..index.len() - 1].to_string()));
+                } else {
+                    let index = index.parse().map_err(|_| format!("'{}' is not a valid index in '{}'", index, path))?;
+                    tokens.push(PathToken::Index(index));
+                }
+            },
+            _ => {
+                // a path without the root, like `id.name`
+                let rest: String = iter::from_fn(|| chars.next_if(|c| *c != '.' && *c != '[')).collect();
+                if !tokens.is_empty() {
+                    return Err(format!("'{}' is missing a '.' before '{}{}'", path, c, rest));
+                }
+                tokens.push(PathToken::Field(format!("{}{}", c, rest)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Replaces the values at the path with the generated ones
+fn apply_path<F>(value: &mut Value, path: &[PathToken], generate: &mut F) where F: FnMut(&Value) -> Option<Value> {
+    match path.split_first() {
+        None => if let Some(generated) = generate(value) {
+            *value = generated;
+        },
+        Some((PathToken::Field(name), rest)) => if let Some(value) = value.get_mut(name.as_str()) {
+            apply_path(value, rest, generate);
+        },
+        Some((PathToken::Index(index), rest)) => if let Some(value) = value.get_mut(*index) {
+            apply_path(value, rest, generate);
+        },
+        Some((PathToken::Star, rest)) => match value {
+            Value::Object(map) => for value in map.values_mut() {
+                apply_path(value, rest, generate);
+            },
+            Value::Array(items) => for value in items.iter_mut() {
+                apply_path(value, rest, generate);
+            },
+            _ => ()
+        }
+    }
+}
+
+/// Replaces the values at the path in the XML document with the generated ones. The path starts with the name of the
+/// root element, then selects the child elements by name (or all of them with `*`) and index, and ends with `#text`
+/// for the text of the elements (which is the default) or `@name` for an attribute.
+fn apply_xml_path<F>(root: Element, path: &[PathToken], generate: &mut F) where F: FnMut() -> Option<String> {
+    let (first, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return
+    };
+    let mut elements = match first {
+        PathToken::Field(name) if name == root.name().local_part() => vec![ root ],
+        PathToken::Star => vec![ root ],
+        _ => return
+    };
+    for token in rest {
+        elements = match token {
+            PathToken::Field(name) if name == "#text" => {
+                for element in &elements {
+                    if let Some(value) = generate() {
+                        xml::set_text(element, &value);
+                    }
+                }
+                return;
+            },
+            PathToken::Field(name) if name.starts_with('@') => {
+                for element in &elements {
+                    if let Some(value) = generate() {
+                        element.set_attribute_value(&name[1..], &value);
+                    }
+                }
+                return;
+            },
+            PathToken::Field(name) => elements.iter()
+                .flat_map(xml::child_elements)
+                .filter(|child| child.name().local_part() == name)
+                .collect(),
+            PathToken::Index(index) => elements.get(*index).cloned().into_iter().collect(),
+            PathToken::Star => elements.iter().flat_map(xml::child_elements).collect()
+        };
+    }
+    for element in &elements {
+        if let Some(value) = generate() {
+            xml::set_text(element, &value);
+        }
+    }
+}
+
+/// Converts a Java date/time pattern (like `yyyy-MM-dd'T'HH:mm:ss`), which is what pacts use, into a chrono format
+fn chrono_format(pattern: &str) -> Result<String, String> {
+    let mut format = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            if chars.peek() == Some(&'\'') {
+                chars.next();
+                format.push('\'');
+                continue;
+            }
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.peek() == Some(&'\'') => { chars.next(); format.push('\''); },
+                    Some('\'') => break,
+                    Some('%') => format.push_str("%%"),
+                    Some(c) => format.push(c),
+                    None => return Err(format!("'{}' has an unterminated quote", pattern))
+                }
+            }
+        } else if c.is_ascii_alphabetic() {
+            let count = 1 + iter::from_fn(|| chars.next_if(|next| *next == c)).count();
+            format.push_str(match (c, count) {
+                ('G', _) => "AD",
+                ('y', 2) => "%y",
+                ('y', _) => "%Y",
+                ('Y', 2) => "%g",
+                ('Y', _) => "%G",
+                ('M', 1) | ('L', 1) => "%-m",
+                ('M', 2) | ('L', 2) => "%m",
+                ('M', 3) | ('L', 3) => "%b",
+                ('M', _) | ('L', _) => "%B",
+                ('w', _) => "%V",
+                ('D', _) => "%j",
+                ('d', 1) => "%-d",
+                ('d', _) => "%d",
+                ('E', 1..=3) => "%a",
+                ('E', _) => "%A",
+                ('u', _) => "%u",
+                ('a', _) => "%p",
+                ('H', 1) | ('k', 1) => "%-H",
+                ('H', _) | ('k', _) => "%H",
+                ('h', 1) | ('K', 1) => "%-I",
+                ('h', _) | ('K', _) => "%I",
+                ('m', 1) => "%-M",
+                ('m', _) => "%M",
+                ('s', 1) => "%-S",
+                ('s', _) => "%S",
+                ('S', 1..=3) => "%3f",
+                ('S', 4..=6) => "%6f",
+                ('S', _) => "%9f",
+                ('z', _) => "%Z",
+                ('Z', _) | ('X', 1..=2) => "%z",
+                ('X', _) => "%:z",
+                _ => return Err(format!("'{}' has the unsupported pattern letter '{}'", pattern, c))
+            });
+        } else if c == '%' {
+            format.push_str("%%");
+        } else {
+            format.push(c);
+        }
+    }
+    Ok(format)
+}
+
+fn formatted_now(pattern: &Option<String>, default: &str) -> Option<String> {
+    match pattern {
+        Some(pattern) => match chrono_format(pattern) {
+            Ok(format) => Some(Local::now().format(&format).to_string()),
+            Err(err) => {
+                warn!("Not generating a value, as the date/time format is invalid - {}", err);
+                None
+            }
+        },
+        None => Some(Local::now().format(default).to_string())
+    }
+}
+
+pub fn random_uuid<R: Rng>(rng: &mut R) -> String {
+    let mut bytes: [u8; 16] = rng.gen();
+    // a version 4 (random) UUID in the RFC 4122 variant
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn random_digits<R: Rng>(rng: &mut R, digits: usize) -> String {
+    // no leading zeros, so the value is the same when it is sent as a number
+    (0..digits).map(|index| std::char::from_digit(rng.gen_range(if index == 0 { 1 } else { 0 }, 10), 10).unwrap_or('0'))
+        .collect()
+}
+
+fn random_decimal<R: Rng>(rng: &mut R, digits: usize) -> String {
+    let digits = random_digits(rng, digits.max(1));
+    if digits.len() < 2 {
+        return digits;
+    }
+    let point = rng.gen_range(1, digits.len());
+    format!("{}.{}", &digits[..point], &digits[point..])
+}
+
+/// Generates the value of the generator as text
+fn generate_string<R: Rng>(generator: &Generator, rng: &mut R) -> Option<String> {
+    match generator {
+        Generator::RandomInt(min, max) => Some(rng.gen_range(i64::from(*min.min(max)), i64::from(*max.max(min)) + 1).to_string()),
+        Generator::Uuid => Some(random_uuid(rng)),
+        Generator::RandomDecimal(digits) => Some(random_decimal(rng, *digits as usize)),
+        Generator::RandomHexadecimal(digits) => Some((0..*digits)
+            .map(|_| std::char::from_digit(rng.gen_range(0, 16), 16).unwrap_or('0'))
+            .collect()),
+        Generator::RandomString(size) => Some(rng.sample_iter(&Alphanumeric).take(*size as usize).collect()),
+        Generator::Regex(regex) => {
+            let generator = regex_syntax::ParserBuilder::new().unicode(false).build().parse(regex)
+                .map_err(|err| err.to_string())
+                .and_then(|hir| rand_regex::Regex::with_hir(hir, MAX_REGEX_REPEAT).map_err(|err| err.to_string()));
+            match generator {
+                Ok(generator) => Some(rng.sample::<String, _>(&generator)),
+                Err(err) => {
+                    warn!("Not generating a value for the regex '{}' - {}", regex, err);
+                    None
+                }
+            }
+        },
+        Generator::Date(format) => formatted_now(format, "%Y-%m-%d"),
+        Generator::Time(format) => formatted_now(format, "%H:%M:%S"),
+        Generator::DateTime(format) => formatted_now(format, "%Y-%m-%dT%H:%M:%S%.3f%:z"),
+        Generator::RandomBoolean => Some(rng.gen::<bool>().to_string())
+    }
+}
+
+/// Generates the value of the generator as JSON. Numbers and booleans keep their type, unless the value in the pact
+/// is a string.
+fn generate_json<R: Rng>(generator: &Generator, current: &Value, rng: &mut R) -> Option<Value> {
+    let generated = generate_string(generator, rng)?;
+    let typed = match (generator, current) {
+        (_, Value::String(_)) => None,
+        (Generator::RandomInt(..), _) => generated.parse::<i64>().ok().map(Value::from),
+        (Generator::RandomDecimal(_), _) => generated.parse::<f64>().ok().map(Value::from),
+        (Generator::RandomBoolean, _) => generated.parse::<bool>().ok().map(Value::from),
+        _ => None
+    };
+    Some(typed.unwrap_or(Value::String(generated)))
+}
+
+fn generate_body<R: Rng>(response: &Response, generators: &HashMap<String, Generator>, rng: &mut R) -> OptionalBody {
+    if xml::is_xml(response) {
+        let generated = xml::update(pact_support::body_bytes(&response.body), |root| {
+            // in the order of the paths, so a seeded generator gives the same values every time
+            for (path, generator) in generators.iter().collect::<BTreeMap<_, _>>() {
+                match parse_path(path) {
+                    Ok(path) => apply_xml_path(root, &path, &mut || generate_string(generator, rng)),
+                    Err(err) => warn!("Ignoring the generator for the body, as its path is invalid - {}", err)
+                }
+            }
+        });
+        return match generated {
+            Ok(body) => OptionalBody::Present(body),
+            Err(err) => {
+                warn!("Not applying the body generators, as the body is not valid XML - {}", err);
+                response.body.clone()
+            }
+        };
+    }
+    match response.content_type_enum() {
+        DetectedContentType::Json => match serde_json::from_slice::<Value>(pact_support::body_bytes(&response.body)) {
+            Ok(mut body) => {
+                // in the order of the paths, so a seeded generator gives the same values every time
+                for (path, generator) in generators.iter().collect::<BTreeMap<_, _>>() {
+                    match parse_path(path) {
+                        Ok(path) => apply_path(&mut body, &path, &mut |current| generate_json(generator, current, rng)),
+                        Err(err) => warn!("Ignoring the generator for the body, as its path is invalid - {}", err)
+                    }
+                }
+                OptionalBody::Present(body.to_string().into_bytes())
+            },
+            Err(err) => {
+                warn!("Not applying the body generators, as the body is not valid JSON - {}", err);
+                response.body.clone()
+            }
+        },
+        _ => match generators.iter().find(|(path, _)| parse_path(path).map(|path| path.is_empty()).unwrap_or(false)) {
+            // a generator for the root replaces the whole of a text body
+            Some((_, generator)) => generate_string(generator, rng)
+                .map(|body| OptionalBody::Present(body.into_bytes()))
+                .unwrap_or_else(|| response.body.clone()),
+            None => response.body.clone()
+        }
+    }
+}
+
+/// A random number generator for the values of the interaction, seeded from the seed and the description and provider
+/// states of the interaction. This gives the same values for the interaction on every run, whatever order the requests
+/// are made in.
+pub fn seeded_rng(seed: u64, interaction: &Interaction) -> StdRng {
+    // FNV-1a, as the hash has to be the same for every build
+    let key = iter::once(interaction.description.as_str())
+        .chain(interaction.provider_states.iter().map(|state| state.name.as_str()))
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+    StdRng::seed_from_u64(seed ^ hash)
+}
+
+/// Replaces the status, header values and body values of the response that have generators (like `RandomInt`, `Uuid`
+/// or `DateTime`) with generated values, so each request gets a fresh response. This is used instead of
+/// `pact_matching::generate_response`, which can not take a random number generator and does not support XML bodies.
+pub fn generate_response<R: Rng>(response: &Response, rng: &mut R) -> Response {
+    let mut generated = response.clone();
+    let categories = &response.generators.categories;
+    if let Some(generator) = categories.get(&GeneratorCategory::STATUS).and_then(|generators| generators.values().next()) {
+        match generate_string(generator, rng).and_then(|status| status.parse::<u16>().ok()) {
+            Some(status) if (100..=999).contains(&status) => generated.status = status,
+            _ => warn!("Not generating the status, as {:?} does not generate a status code", generator)
+        }
+    }
+    if let Some(generators) = categories.get(&GeneratorCategory::HEADER) {
+        let headers = generated.headers.get_or_insert_with(HashMap::new);
+        for (name, generator) in generators.iter().collect::<BTreeMap<_, _>>() {
+            let name = headers.keys().find(|key| key.eq_ignore_ascii_case(name)).cloned().unwrap_or_else(|| name.clone());
+            let count = headers.get(&name).map(|values| values.len()).unwrap_or(1).max(1);
+            let values = (0..count).filter_map(|_| generate_string(generator, rng)).collect::<Vec<String>>();
+            if !values.is_empty() {
+                headers.insert(name, values);
+            }
+        }
+    }
+    if let Some(generators) = categories.get(&GeneratorCategory::BODY) {
+        if response.body.is_present() && !generators.is_empty() {
+            generated.body = generate_body(response, generators, rng);
+        }
+    }
+    generated
+}
+
+/// Reads the provider state generators of the responses from the JSON of a pact, by the index of their interaction
+pub fn provider_state_generators(pact_json: &Value) -> PactStateGenerators {
+    let mut generators = BTreeMap::new();
+    let interactions = pact_json.get("interactions").and_then(Value::as_array).cloned().unwrap_or_default();
+    for (index, interaction) in interactions.iter().enumerate() {
+        let description = interaction.get("description").and_then(Value::as_str).unwrap_or_default();
+        let response_generators = match interaction.pointer("/response/generators").and_then(Value::as_object) {
+            Some(response_generators) => response_generators,
+            None => continue
+        };
+        let mut interaction_generators = vec![];
+        let mut add = |category: GeneratorCategory, path: &str, generator: &Value| {
+            if generator.get("type").and_then(Value::as_str) == Some("ProviderState") {
+                match generator.get("expression").and_then(Value::as_str) {
+                    Some(expression) => interaction_generators.push(StateGenerator {
+                        category,
+                        path: path.to_string(),
+                        expression: expression.to_string()
+                    }),
+                    None => warn!("The provider state generator for '{}' of interaction '{}' does not have an expression",
+                                  path, description)
+                }
+            }
+        };
+        for (category, value) in response_generators {
+            match category.as_str() {
+                "status" => add(GeneratorCategory::STATUS, "", value),
+                "header" => for (path, generator) in value.as_object().into_iter().flatten() {
+                    add(GeneratorCategory::HEADER, path, generator)
+                },
+                "body" => for (path, generator) in value.as_object().into_iter().flatten() {
+                    add(GeneratorCategory::BODY, path, generator)
+                },
+                _ => ()
+            }
+        }
+        if !interaction_generators.is_empty() {
+            generators.insert(index, interaction_generators);
+        }
+    }
+    generators
+}
+
+/// Removes the values for the provider state generators from the request (the `X-Pact-State-Values` header and the
+/// query parameters starting with `pact-state.`), so they do not affect the matching, and returns them
+pub fn take_state_values(request: &mut Request) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let header = request.headers.as_ref()
+        .and_then(|headers| headers.iter().find(|(name, _)| name.eq_ignore_ascii_case(STATE_VALUES_HEADER)))
+        .map(|(_, values)| values.join("&"));
+    if let Some(query) = header.and_then(|header| parse_query_string(&header)) {
+        values.extend(query.into_iter().filter_map(|(name, values)| values.last().cloned().map(|value| (name, value))));
+    }
+    if let Some(query) = request.query.as_mut() {
+        let names = query.keys().filter(|name| name.starts_with(STATE_VALUES_QUERY_PREFIX)).cloned().collect::<Vec<String>>();
+        for name in names {
+            if let Some(value) = query.remove(&name).and_then(|values| values.last().cloned()) {
+                values.insert(name[STATE_VALUES_QUERY_PREFIX.len()..].to_string(), value);
+            }
+        }
+        if query.is_empty() {
+            request.query = None;
+        }
+    }
+    values
+}
+
+/// Evaluates an expression like `/orders/${id}`. An expression that is only one value keeps the type of the value.
+fn evaluate(expression: &str, values: &HashMap<String, Value>) -> Option<Value> {
+    let as_text = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        _ => value.to_string()
+    };
+    let mut result = String::new();
+    let mut rest = expression;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..].find('}').map(|end| start + end)?;
+        let value = values.get(rest[start + 2..end].trim())?;
+        if start == 0 && end == rest.len() - 1 && result.is_empty() {
+            return Some(value.clone());
+        }
+        result.push_str(&rest[..start]);
+        result.push_str(&as_text(value));
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Some(Value::String(result))
+}
+
+/// Converts text values to the type of the value in the pact, so `id=42` from a header fills in a number
+fn with_type_of(value: Value, current: &Value) -> Value {
+    match (value, current) {
+        (Value::String(text), Value::Number(_)) => text.parse::<i64>().map(Value::from)
+            .or_else(|_| text.parse::<f64>().map(Value::from))
+            .unwrap_or(Value::String(text)),
+        (Value::String(text), Value::Bool(_)) => text.parse::<bool>().map(Value::from).unwrap_or(Value::String(text)),
+        (value, Value::String(_)) if !value.is_string() => Value::String(value.to_string()),
+        (value, _) => value
+    }
+}
+
+/// Fills in the values of the response that have provider state generators, from the values given with the request or
+/// the parameters of the provider states of the interaction. Values with an expression that refers to a value that
+/// was not given are left as they are.
+pub fn apply_provider_state_generators(generators: &[StateGenerator], interaction: &Interaction, mut response: Response,
+                                       request_values: &HashMap<String, String>) -> Response {
+    if generators.is_empty() {
+        return response;
+    }
+    let mut values: HashMap<String, Value> = interaction.provider_states.iter()
+        .flat_map(|state| state.params.clone())
+        .collect();
+    values.extend(request_values.iter().map(|(name, value)| (name.clone(), Value::String(value.clone()))));
+    for StateGenerator { category, path, expression } in generators {
+        let path = path.clone();
+        let value = match evaluate(expression, &values) {
+            Some(value) => value,
+            None => {
+                debug!("No value for the provider state generator expression '{}', leaving '{}' as it is", expression, path);
+                continue;
+            }
+        };
+        match category {
+            GeneratorCategory::STATUS => match with_type_of(value, &json!(0)).as_u64().filter(|status| (100..=999).contains(status)) {
+                Some(status) => response.status = status as u16,
+                None => warn!("Not setting the status, as the provider state generator expression '{}' is not a status code", expression)
+            },
+            GeneratorCategory::HEADER => {
+                let headers = response.headers.get_or_insert_with(HashMap::new);
+                let name = headers.keys().find(|name| name.eq_ignore_ascii_case(&path)).cloned().unwrap_or(path);
+                headers.insert(name, vec![ with_type_of(value, &json!("")).as_str().unwrap_or_default().to_string() ]);
+            },
+            _ => if response.content_type_enum() == DetectedContentType::Json {
+                if let Ok(mut body) = serde_json::from_slice::<Value>(pact_support::body_bytes(&response.body)) {
+                    match parse_path(&path) {
+                        Ok(path) => {
+                            apply_path(&mut body, &path, &mut |current| Some(with_type_of(value.clone(), current)));
+                            response.body = OptionalBody::Present(body.to_string().into_bytes());
+                        },
+                        Err(err) => warn!("Ignoring the provider state generator for the body, as its path is invalid - {}", err)
+                    }
+                }
+            } else if parse_path(&path).map(|path| path.is_empty()).unwrap_or(false) {
+                response.body = OptionalBody::Present(with_type_of(value, &json!("")).as_str().unwrap_or_default().as_bytes().to_vec());
+            }
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Response};
+    use pact_matching::models::generators::{Generator, GeneratorCategory, Generators};
+    use rand::thread_rng;
+    use regex::Regex;
+    use serde_json::Value;
+    use super::*;
+
+    fn response(content_type: &str, body: &str, generators: HashMap<GeneratorCategory, HashMap<String, Generator>>) -> Response {
+        Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!(content_type) ], s!("x-request-id") => vec![ s!("1") ] }),
+            body: OptionalBody::Present(body.into()),
+            generators: Generators { categories: generators },
+            .. Response::default_response()
+        }
+    }
+
+    fn json_body(response: &Response) -> Value {
+        serde_json::from_slice(&response.body.value()).unwrap()
+    }
+
+    #[test]
+    fn parses_generator_paths() {
+        expect!(parse_path("$")).to(be_ok().value(vec![]));
+        expect!(parse_path("$.items[*].id")).to(be_ok().value(vec![
+            PathToken::Field(s!("items")), PathToken::Star, PathToken::Field(s!("id"))
+        ]));
+        expect!(parse_path("$['first name'][2].*")).to(be_ok().value(vec![
+            PathToken::Field(s!("first name")), PathToken::Index(2), PathToken::Star
+        ]));
+        expect!(parse_path("id")).to(be_ok().value(vec![ PathToken::Field(s!("id")) ]));
+        expect!(parse_path("$.items[x]")).to(be_err());
+        expect!(parse_path("$.items[0")).to(be_err());
+        expect!(parse_path("$..id")).to(be_err());
+    }
+
+    #[test]
+    fn converts_java_date_patterns() {
+        expect!(chrono_format("yyyy-MM-dd'T'HH:mm:ss.SSSXXX")).to(be_ok().value(s!("%Y-%m-%dT%H:%M:%S.%3f%:z")));
+        expect!(chrono_format("EEE, d MMM yyyy HH:mm:ss Z")).to(be_ok().value(s!("%a, %-d %b %Y %H:%M:%S %z")));
+        expect!(chrono_format("hh 'o''clock' a")).to(be_ok().value(s!("%I o'clock %p")));
+        expect!(chrono_format("yyyy 'week' ww")).to(be_ok().value(s!("%Y week %V")));
+        expect!(chrono_format("yyyy-MM-dd 'unterminated")).to(be_err());
+        expect!(chrono_format("yyyy-qq")).to(be_err());
+    }
+
+    #[test]
+    fn generates_random_ints_up_to_the_largest_int() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut generate = |min, max| generate_string(&Generator::RandomInt(min, max), &mut rng).and_then(|value| value.parse::<i64>().ok());
+        expect!(generate(i32::MAX, i32::MAX)).to(be_some().value(i64::from(i32::MAX)));
+        expect!(generate(i32::MIN, i32::MIN)).to(be_some().value(i64::from(i32::MIN)));
+        expect!(generate(i32::MAX - 1, i32::MAX).map(|value| value >= i64::from(i32::MAX - 1))).to(be_some().value(true));
+        expect!(generate(i32::MAX, i32::MIN).is_some()).to(be_true());
+    }
+
+    #[test]
+    fn generates_values_for_the_status_headers_and_body() {
+        let response = response("application/json", r#"{ "id": 1, "ref": "a", "created": "", "items": [ { "id": "x" }, { "id": "y" } ] }"#, hashmap!{
+            GeneratorCategory::STATUS => hashmap!{ s!("") => Generator::RandomInt(201, 201) },
+            GeneratorCategory::HEADER => hashmap!{ s!("X-Request-ID") => Generator::Uuid, s!("Expires") => Generator::Date(None) },
+            GeneratorCategory::BODY => hashmap!{
+                s!("$.id") => Generator::RandomInt(10, 99),
+                s!("$.ref") => Generator::Regex(s!("[A-Z]{3}-\\d{4}")),
+                s!("$.created") => Generator::DateTime(Some(s!("yyyy-MM-dd'T'HH:mm:ss"))),
+                s!("$.items[*].id") => Generator::RandomHexadecimal(8),
+                s!("$.missing") => Generator::RandomBoolean
+            }
+        });
+        let generated = generate_response(&response, &mut thread_rng());
+
+        expect!(generated.status).to(be_equal_to(201));
+        let headers = generated.headers.clone().unwrap();
+        let uuid = Regex::new("^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$").unwrap();
+        expect!(uuid.is_match(&headers["x-request-id"][0])).to(be_true());
+        expect!(headers.contains_key("X-Request-ID")).to(be_false());
+        expect!(Regex::new("^\\d{4}-\\d{2}-\\d{2}$").unwrap().is_match(&headers["Expires"][0])).to(be_true());
+
+        let body = json_body(&generated);
+        let id = body["id"].as_i64().unwrap();
+        expect!((10..=99).contains(&id)).to(be_true());
+        expect!(Regex::new("^[A-Z]{3}-\\d{4}$").unwrap().is_match(body["ref"].as_str().unwrap())).to(be_true());
+        expect!(Regex::new("^\\d{4}-\\d{2}-\\d{2}T\\d{2}:\\d{2}:\\d{2}$").unwrap().is_match(body["created"].as_str().unwrap())).to(be_true());
+        let hex = Regex::new("^[0-9a-f]{8}$").unwrap();
+        for item in body["items"].as_array().unwrap() {
+            expect!(hex.is_match(item["id"].as_str().unwrap())).to(be_true());
+        }
+        expect!(body.get("missing")).to(be_none());
+    }
+
+    #[test]
+    fn generates_values_in_xml_bodies() {
+        let response = response("text/xml", "<order id=\"1\"><ref>a</ref><items><item>x</item><item>y</item></items></order>", hashmap!{
+            GeneratorCategory::BODY => hashmap!{
+                s!("$.order.@id") => Generator::RandomInt(10, 99),
+                s!("$.order.ref.#text") => Generator::Regex(s!("[A-Z]{3}")),
+                s!("$.order.items.item[1]") => Generator::RandomInt(5, 5),
+                s!("$.other.ref") => Generator::RandomBoolean
+            }
+        });
+        let body = String::from_utf8(generate_response(&response, &mut thread_rng()).body.value()).unwrap();
+        let xml = Regex::new("^<\\?xml version='1.0'\\?><order id='\\d{2}'><ref>[A-Z]{3}</ref><items><item>x</item><item>5</item></items></order>$").unwrap();
+        expect!(xml.is_match(&body)).to(be_true());
+    }
+
+    #[test]
+    fn keeps_the_type_of_the_json_values() {
+        let response = response("application/json", r#"{ "int": "1", "decimal": 1.5, "flag": false, "text": 1 }"#, hashmap!{
+            GeneratorCategory::BODY => hashmap!{
+                s!("$.int") => Generator::RandomInt(1, 5),
+                s!("$.decimal") => Generator::RandomDecimal(4),
+                s!("$.flag") => Generator::RandomBoolean,
+                s!("$.text") => Generator::RandomString(6)
+            }
+        });
+        let body = json_body(&generate_response(&response, &mut thread_rng()));
+        expect!(body["int"].is_string()).to(be_true());
+        expect!(body["decimal"].is_f64()).to(be_true());
+        expect!(body["flag"].is_boolean()).to(be_true());
+        expect!(body["text"].as_str().map(|text| text.len())).to(be_some().value(6));
+    }
+
+    #[test]
+    fn does_not_change_bodies_it_can_not_generate_values_in() {
+        let generators = hashmap!{ GeneratorCategory::BODY => hashmap!{ s!("$.id") => Generator::Uuid } };
+        let xml = response("application/xml", "<id>1</i", generators.clone());
+        expect!(generate_response(&xml, &mut thread_rng()).body).to(be_equal_to(xml.body.clone()));
+        let invalid = response("application/json", "{ not json", generators);
+        expect!(generate_response(&invalid, &mut thread_rng()).body).to(be_equal_to(invalid.body.clone()));
+
+        let text = response("text/plain", "1", hashmap!{ GeneratorCategory::BODY => hashmap!{ s!("$") => Generator::RandomDecimal(3) } });
+        let body = generate_response(&text, &mut thread_rng()).body;
+        expect!(Regex::new("^\\d\\.\\d\\d$|^\\d\\d\\.\\d$").unwrap().is_match(body.str_value())).to(be_true());
+    }
+
+    #[test]
+    fn fills_in_provider_state_generators_from_the_request_values() {
+        let pact_json = json!({
+            "consumer": { "name": "ui" },
+            "provider": { "name": "orders" },
+            "interactions": [ {
+                "description": "get an order",
+                "providerStates": [ { "name": "an order exists", "params": { "id": 1, "status": "NEW" } } ],
+                "request": { "method": "GET", "path": "/orders/1" },
+                "response": {
+                    "status": 200,
+                    "headers": { "Content-Type": "application/json", "Location": "/orders/1" },
+                    "body": { "id": 1, "status": "NEW", "name": "Example" },
+                    "generators": {
+                        "header": { "Location": { "type": "ProviderState", "expression": "/orders/${id}" } },
+                        "body": {
+                            "$.id": { "type": "ProviderState", "expression": "${id}" },
+                            "$.status": { "type": "ProviderState", "expression": "${status}" },
+                            "$.name": { "type": "ProviderState", "expression": "${name}" }
+                        }
+                    }
+                }
+            } ],
+            "metadata": { "pactSpecification": { "version": "3.0.0" } }
+        });
+        let (pact, generators) = crate::pact_support::pact_from_json("test", &pact_json);
+        expect!(generators.get(&0).map(|generators| generators.len())).to(be_some().value(4));
+        let (interaction, generators) = (&pact.interactions[0], &generators[&0]);
+
+        let mut request = Request {
+            query: Some(hashmap!{ s!("pact-state.id") => vec![ s!("42") ], s!("page") => vec![ s!("1") ] }),
+            headers: Some(hashmap!{ s!("x-pact-state-values") => vec![ s!("name=Fred&id=7") ] }),
+            .. Request::default_request()
+        };
+        let values = take_state_values(&mut request);
+        expect!(values.clone()).to(be_equal_to(hashmap!{ s!("id") => s!("42"), s!("name") => s!("Fred") }));
+        expect!(request.query.clone()).to(be_some().value(hashmap!{ s!("page") => vec![ s!("1") ] }));
+
+        let response = apply_provider_state_generators(generators, interaction, interaction.response.clone(), &values);
+        expect!(json_body(&response)).to(be_equal_to(json!({ "id": 42, "status": "NEW", "name": "Fred" })));
+        expect!(response.headers.unwrap().get("Location").cloned()).to(be_some().value(vec![ s!("/orders/42") ]));
+
+        // without values from the request, the parameters of the provider state are used
+        let response = apply_provider_state_generators(generators, interaction, interaction.response.clone(), &HashMap::new());
+        expect!(json_body(&response)).to(be_equal_to(json!({ "id": 1, "status": "NEW", "name": "Example" })));
+    }
+
+    #[test]
+    fn generates_the_same_values_with_the_same_seed() {
+        let response = response("application/json", r#"{ "id": 1, "ref": "a", "uuid": "" }"#, hashmap!{
+            GeneratorCategory::BODY => hashmap!{
+                s!("$.id") => Generator::RandomInt(1, 1_000_000),
+                s!("$.ref") => Generator::RandomString(20),
+                s!("$.uuid") => Generator::Uuid
+            }
+        });
+        let interaction = Interaction { description: s!("get an order"), .. Interaction::default() };
+        let other = Interaction { description: s!("get another order"), .. Interaction::default() };
+        let generated = json_body(&generate_response(&response, &mut seeded_rng(42, &interaction)));
+        expect!(json_body(&generate_response(&response, &mut seeded_rng(42, &interaction)))).to(be_equal_to(generated.clone()));
+        expect!(json_body(&generate_response(&response, &mut seeded_rng(43, &interaction)))).to_not(be_equal_to(generated.clone()));
+        expect!(json_body(&generate_response(&response, &mut seeded_rng(42, &other)))).to_not(be_equal_to(generated));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::cache::PactCache;
+use crate::pact_support::FetchOptions;
+use hyper::Uri;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Reference fetched if no branch, tag or commit is given (the default branch of the repository)
+pub const DEFAULT_REF: &str = "HEAD";
+
+/// A git repository with pact files, with the branch, tag or commit and the sub-directory to load them from
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitSource {
+    pub url: String,
+    pub reference: Option<String>,
+    pub subdir: Option<String>
+}
+
+impl GitSource {
+    fn reference(&self) -> &str {
+        self.reference.as_deref().unwrap_or(DEFAULT_REF)
+    }
+
+    /// Directory the repository is checked out to. With a cache directory it is kept there between runs, otherwise it
+    /// is kept in a temporary directory of this process for the reloads. Either way, later checkouts only need to fetch
+    /// the changes.
+    pub fn checkout_dir(&self, cache: Option<&PactCache>) -> PathBuf {
+        let digest = Sha256::digest(format!("{}#{}", self.url, self.reference()).as_bytes());
+        let name: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+        match cache {
+            Some(cache) => cache.dir().join("git").join(name),
+            None => process_checkouts_dir().join(name)
+        }
+    }
+
+    /// Clones the repository (or fetches the reference if it was cloned before), checks it out and loads the pact files
+    /// from it. The checkout is locked until they are loaded, so other servers sharing the cache directory do not
+    /// change it in the meantime.
+    pub fn checkout<F, T>(&self, fetch: &FetchOptions, load: F) -> Result<T, String> where F: FnOnce(&Path) -> T {
+        let dir = self.checkout_dir(fetch.cache.as_ref());
+        let _lock = lock(&dir)?;
+        if !dir.join(".git").is_dir() {
+            fs::create_dir_all(&dir).map_err(|err| format!("Could not create '{}' - {}", dir.display(), err))?;
+            git(&dir, &self.url, &["init", "--quiet"], fetch)?;
+            git(&dir, &self.url, &["remote", "add", "origin", &self.url], fetch)?;
+        }
+        debug!("Fetching '{}' from {} into {}", self.reference(), self.url, dir.display());
+        git(&dir, &self.url, &["fetch", "--quiet", "--depth", "1", "--", "origin", self.reference()], fetch)?;
+        git(&dir, &self.url, &["checkout", "--quiet", "--force", "FETCH_HEAD"], fetch)?;
+        git(&dir, &self.url, &["clean", "--quiet", "--force", "-d", "-x"], fetch)?;
+        match self.subdir {
+            Some(ref subdir) => {
+                let path = dir.join(subdir);
+                if path.is_dir() {
+                    Ok(load(&path))
+                } else {
+                    Err(format!("'{}' is not a directory in the repository", subdir))
+                }
+            },
+            None => Ok(load(&dir))
+        }
+    }
+}
+
+/// Temporary directory with the checkouts of this process, when there is no cache directory
+fn process_checkouts_dir() -> PathBuf {
+    env::temp_dir().join(format!("pact-stub-server-git-{}", process::id()))
+}
+
+/// Removes the checkouts of this process when it is done with them. The ones in the cache directory are kept.
+pub fn remove_process_checkouts() {
+    let dir = process_checkouts_dir();
+    if dir.exists() {
+        if let Err(err) = fs::remove_dir_all(&dir) {
+            warn!("Could not remove the git checkouts in '{}' - {}", dir.display(), err);
+        }
+    }
+}
+
+/// Locks the checkout directory with a lock file next to it. The lock is released when the file is dropped.
+fn lock(dir: &Path) -> Result<File, String> {
+    let path = dir.with_extension("lock");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("Could not create '{}' - {}", parent.display(), err))?;
+    }
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)
+        .map_err(|err| format!("Could not open '{}' - {}", path.display(), err))?;
+    file.lock().map_err(|err| format!("Could not lock '{}' - {}", path.display(), err))?;
+    Ok(file)
+}
+
+/// Runs a git command in the directory, without prompting for credentials
+fn git(dir: &Path, url: &str, args: &[&str], fetch: &FetchOptions) -> Result<(), String> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).env("GIT_TERMINAL_PROMPT", "0");
+    if fetch.insecure {
+        command.args(["-c", "http.sslVerify=false"]);
+    } else if let Some(ref ca_cert) = fetch.ca_cert {
+        command.arg("-c").arg(format!("http.sslCAInfo={}", ca_cert.display()));
+    }
+    if let Some(proxy) = url.parse::<Uri>().ok().and_then(|uri| fetch.proxy.as_ref().and_then(|proxy| proxy.proxy_for(&uri).cloned())) {
+        command.arg("-c").arg(format!("http.proxy={}", proxy));
+    }
+    let output = command.args(args).output().map_err(|err| format!("Could not run git - {}", err))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("git {} failed - {}", args[0], String::from_utf8_lossy(&output.stderr).lines().next().unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn checks_out_each_repository_and_reference_to_its_own_directory() {
+        let source = GitSource { url: s!("https://git.example.com/contracts.git"), reference: None, subdir: None };
+        let branch = GitSource { reference: Some(s!("release")), ..source.clone() };
+        let subdir = GitSource { subdir: Some(s!("pacts")), ..source.clone() };
+        let other = GitSource { url: s!("https://git.example.com/other.git"), ..source.clone() };
+        expect!(source.checkout_dir(None).starts_with(process_checkouts_dir())).to(be_true());
+        expect!(source.checkout_dir(None)).to(be_equal_to(subdir.checkout_dir(None)));
+        expect!(source.checkout_dir(None)).to_not(be_equal_to(branch.checkout_dir(None)));
+        expect!(source.checkout_dir(None)).to_not(be_equal_to(other.checkout_dir(None)));
+    }
+
+    #[test]
+    fn keeps_the_checkouts_in_the_cache_directory() {
+        let source = GitSource { url: s!("https://git.example.com/contracts.git"), reference: None, subdir: None };
+        let cache = PactCache::new(Path::new("/var/cache/pacts"));
+        expect!(source.checkout_dir(Some(&cache)).starts_with("/var/cache/pacts/git")).to(be_true());
+        expect!(source.checkout_dir(Some(&cache)).file_name()).to(be_equal_to(source.checkout_dir(None).file_name()));
+    }
+}
@@ -0,0 +1,83 @@
+use pact_matching::models::{Interaction, Pact};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Position of an interaction in the loaded pacts (the index of the pact, and of the interaction in the pact)
+pub type InteractionPosition = (usize, usize);
+
+/// Index of the interactions by their request method and path, so that only the interactions that can match a request
+/// are compared with it. Interactions with a matching rule for the path can match any path, so they are only indexed
+/// by method.
+#[derive(Debug, Clone, Default)]
+pub struct InteractionIndex {
+    by_path: HashMap<(String, String), Vec<InteractionPosition>>,
+    by_method: HashMap<String, Vec<InteractionPosition>>
+}
+
+fn has_path_matcher(interaction: &Interaction) -> bool {
+    interaction.request.matching_rules.matcher_is_defined("path", &vec![])
+}
+
+impl InteractionIndex {
+    /// Builds the index for the interactions in the pacts
+    pub fn new(pacts: &[Arc<Pact>]) -> InteractionIndex {
+        let mut index = InteractionIndex::default();
+        for (pact_index, pact) in pacts.iter().enumerate() {
+            for (interaction_index, interaction) in pact.interactions.iter().enumerate() {
+                let method = interaction.request.method.to_uppercase();
+                let position = (pact_index, interaction_index);
+                if has_path_matcher(interaction) {
+                    index.by_method.entry(method).or_default().push(position);
+                } else {
+                    index.by_path.entry((method, interaction.request.path.clone())).or_default().push(position);
+                }
+            }
+        }
+        debug!("Indexed the interactions by {} method and path(s), with {} method(s) having path matchers",
+               index.by_path.len(), index.by_method.len());
+        index
+    }
+
+    /// Returns the positions of the interactions that could match a request with the method and path, in the order
+    /// they were loaded in
+    pub fn candidates(&self, method: &str, path: &str) -> Vec<InteractionPosition> {
+        let method = method.to_uppercase();
+        let mut positions = self.by_path.get(&(method.clone(), path.to_string())).cloned().unwrap_or_default();
+        if let Some(dynamic) = self.by_method.get(&method) {
+            positions.extend(dynamic.iter().cloned());
+            positions.sort();
+        }
+        positions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, Pact, Request};
+    use pact_matching::models::matchingrules::*;
+    use super::*;
+
+    fn interaction(method: &str, path: &str) -> Interaction {
+        Interaction {
+            request: Request { method: method.to_string(), path: path.to_string(), .. Request::default_request() },
+            .. Interaction::default()
+        }
+    }
+
+    #[test]
+    fn finds_the_candidate_interactions_by_method_and_path() {
+        let mut by_regex = interaction("GET", "/orders/1");
+        by_regex.request.matching_rules = matchingrules!{ "path" => { "" => [ MatchingRule::Regex(s!("/orders/\\\\d+")) ] } };
+        let pacts = vec![
+            Arc::new(Pact { interactions: vec![ interaction("GET", "/orders"), interaction("POST", "/orders") ], .. Pact::default() }),
+            Arc::new(Pact { interactions: vec![ by_regex, interaction("get", "/orders") ], .. Pact::default() })
+        ];
+        let index = InteractionIndex::new(&pacts);
+
+        expect!(index.candidates("GET", "/orders")).to(be_equal_to(vec![ (0, 0), (1, 0), (1, 1) ]));
+        expect!(index.candidates("post", "/orders")).to(be_equal_to(vec![ (0, 1) ]));
+        expect!(index.candidates("GET", "/orders/2")).to(be_equal_to(vec![ (1, 0) ]));
+        expect!(index.candidates("DELETE", "/orders")).to(be_equal_to(vec![]));
+    }
+}
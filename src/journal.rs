@@ -0,0 +1,125 @@
+use pact_matching::models::{OptionalBody, Request, parse_query_string};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Path of the endpoint that returns (and with a DELETE, clears) the recent requests
+pub const REQUESTS_PATH: &str = "/_pact-stub/requests";
+
+/// Default number of requests kept in the journal
+pub const DEFAULT_SIZE: usize = 100;
+
+/// A request the server received, with the status it was sent
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub timestamp: u64,
+    pub request: Request,
+    pub matched: bool,
+    pub status: u16
+}
+
+/// The most recent requests received by the server, so tests can check which requests their code made. The oldest
+/// requests are dropped once the journal is full.
+#[derive(Debug)]
+pub struct Journal {
+    size: usize,
+    entries: Mutex<VecDeque<JournalEntry>>
+}
+
+/// The body as a JSON string, or base64 encoded if it is binary
+pub fn body_json(body: &OptionalBody) -> Value {
+    match body {
+        OptionalBody::Present(bytes) => match std::str::from_utf8(bytes) {
+            Ok(text) => json!(text),
+            Err(_) => json!({ "base64": base64::encode(bytes) })
+        },
+        OptionalBody::Null => Value::Null,
+        _ => json!("")
+    }
+}
+
+impl JournalEntry {
+    fn to_json(&self) -> Value {
+        json!({
+            "timestamp": self.timestamp,
+            "method": self.request.method,
+            "path": self.request.path,
+            "query": self.request.query,
+            "headers": self.request.headers,
+            "body": body_json(&self.request.body),
+            "matched": self.matched,
+            "status": self.status
+        })
+    }
+}
+
+impl Journal {
+    pub fn new(size: usize) -> Journal {
+        Journal { size, entries: Mutex::new(VecDeque::with_capacity(size)) }
+    }
+
+    /// Records a request, and whether it matched an interaction
+    pub fn record(&self, request: &Request, matched: bool, status: u16) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0);
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.size {
+            entries.pop_front();
+        }
+        entries.push_back(JournalEntry { timestamp, request: request.clone(), matched, status });
+    }
+
+    /// The recorded requests, oldest first
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes all the recorded requests
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Returns the recorded requests as JSON, filtered by the `method` and `path` query parameters if they are given
+    pub fn report(&self, query: Option<&str>) -> Value {
+        let params = query.and_then(|query| parse_query_string(&query.to_string())).unwrap_or_default();
+        let param = |name: &str| params.get(name).and_then(|values| values.last().cloned());
+        let (method, path) = (param("method"), param("path"));
+        let requests = self.entries().iter()
+            .filter(|entry| method.as_ref().map(|method| entry.request.method.eq_ignore_ascii_case(method)).unwrap_or(true))
+            .filter(|entry| path.as_ref().map(|path| &entry.request.path == path).unwrap_or(true))
+            .map(JournalEntry::to_json)
+            .collect::<Vec<Value>>();
+        json!({ "count": requests.len(), "requests": requests })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Request};
+    use super::*;
+
+    #[test]
+    fn keeps_the_most_recent_requests() {
+        let journal = Journal::new(2);
+        for path in &[ "/one", "/two", "/three" ] {
+            journal.record(&Request { path: path.to_string(), .. Request::default_request() }, true, 200);
+        }
+        journal.record(&Request {
+            method: s!("POST"),
+            path: s!("/three"),
+            body: OptionalBody::Present(vec![ 0xff, 0xfe ]),
+            .. Request::default_request()
+        }, false, 404);
+
+        let report = journal.report(None);
+        expect!(report["count"].clone()).to(be_equal_to(json!(2)));
+        expect!(report["requests"][0]["path"].clone()).to(be_equal_to(json!("/three")));
+        expect!(report["requests"][1]["matched"].clone()).to(be_equal_to(json!(false)));
+        expect!(report["requests"][1]["body"].clone()).to(be_equal_to(json!({ "base64": "//4=" })));
+        expect!(journal.report(Some("method=post&path=/three"))["count"].clone()).to(be_equal_to(json!(1)));
+
+        journal.clear();
+        expect!(journal.entries().len()).to(be_equal_to(0));
+    }
+}
@@ -0,0 +1,179 @@
+//! # Standalone Pact Stub Server
+//!
+//! This project provides a server that can generate responses based on pact files. It is a single executable binary. It implements the [V2 Pact specification](https://github.com/pact-foundation/pact-specification/tree/version-2).
+//!
+//! [Online rust docs](https://docs.rs/pact-stub-server/)
+//!
+//! The stub server works by taking all the interactions (requests and responses) from a number of pact files. For each interaction, it will compare any incoming request against those defined in the pact files. If there is a match (based on method, path and query parameters), it will return the response from the pact file.
+//!
+//! ## Command line interface
+//!
+//! The pact stub server is bundled as a single binary executable `pact-stub-server`. Running this with out any options displays the standard help.
+//!
+//! ```console,ignore
+//! pact-stub-server v0.0.2
+//! Pact Stub Server
+//!
+//! USAGE:
+//!     pact-stub-server [OPTIONS] --file <file> --dir <dir> --url <url>
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -v, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -d, --dir <dir>              Directory of pact files to verify (can be repeated)
+//!     -f, --file <file>            Pact file to verify (can be repeated)
+//!     -l, --loglevel <loglevel>    Log level (defaults to info) [values: error, warn, info, debug, trace, none]
+//!     -p, --port <port>            Port to run on (defaults to random port assigned by the OS)
+//!     -s, --provider-state <provider-state>    Provider state regular expression to filter the responses by (can be repeated)
+//!         --provider-state-mode <mode>         How multiple provider state filters are combined [values: any, all]
+//!         --provider-state-header-name <name>  Name of the header parameter containing the
+//! provider state to be used in case multiple matching interactions are found
+//!     -u, --url <url>              URL of pact file to verify (can be repeated)
+//!
+//! ```
+//!
+//! ## Options
+//!
+//! ### Log Level
+//!
+//! You can control the log level with the `-l, --loglevel <loglevel>` option. It defaults to info, and the options that you can specify are: error, warn, info, debug, trace, none.
+//!
+//! ### Pact File Sources
+//!
+//! You can specify the pacts to verify with the following options. They can be repeated to set multiple sources.
+//!
+//! | Option | Type | Description |
+//! |--------|------|-------------|
+//! | `-f, --file <file>` | File | Loads a pact from the given file |
+//! | `-u, --url <url>` | URL | Loads a pact from a URL resource |
+//! | `-d, --dir <dir>` | Directory | Loads all the pacts from the given directory |
+//!
+//! ### Server Options
+//!
+//! The running server can be controlled with the following options:
+//!
+//! | Option | Description |
+//! |--------|-------------|
+//! | `-p, --port <port>` | The port to bind to. If not specified, a random port will be allocated by the operating system. |
+//!
+//!
+//! ## Using the stub server from Rust tests
+//!
+//! The stub server can also be run in the same process as Rust integration tests, instead of running the binary. The
+//! server runs on a background thread until the handle is shut down or dropped:
+//!
+//! ```rust,ignore
+//! use pact_stub_server::StubServer;
+//!
+//! let server = StubServer::builder()
+//!     .add_pact_file("pacts/consumer-provider.json")?
+//!     .port(0)
+//!     .start()?;
+//! let response = reqwest::blocking::get(format!("{}/orders/1", server.url()))?;
+//! server.shutdown();
+//! ```
+
+#![warn(missing_docs)]
+
+extern crate brotli;
+extern crate bytes;
+extern crate chrono;
+#[macro_use] extern crate clap;
+extern crate similar;
+#[cfg(test)]
+#[macro_use(expect)]
+extern crate expectest;
+extern crate flate2;
+extern crate handlebars;
+extern crate http;
+extern crate http_body_util;
+extern crate hyper;
+extern crate hyper_tls;
+extern crate hyper_util;
+extern crate tokio;
+extern crate tower_service;
+extern crate itertools;
+#[cfg(unix)]
+extern crate libc;
+#[macro_use] extern crate log;
+#[macro_use] extern crate maplit;
+#[macro_use] extern crate pact_matching;
+#[macro_use] extern crate serde_json;
+extern crate serde_yaml;
+extern crate simplelog;
+extern crate base64;
+extern crate native_tls;
+#[cfg(test)]
+extern crate quickcheck;
+extern crate rand;
+extern crate rand_regex;
+extern crate regex;
+extern crate regex_syntax;
+extern crate rhai;
+extern crate sha2;
+extern crate sxd_document;
+extern crate tracing;
+
+mod pact_support;
+mod access;
+mod admin;
+mod broker;
+mod builder;
+mod cache;
+mod capture;
+mod callbacks;
+mod cli;
+mod clients;
+mod compare;
+mod compression;
+mod control;
+mod coverage;
+mod diff;
+mod dynamic;
+mod fixtures;
+mod forms;
+mod fetch_proxy;
+mod generators;
+mod git;
+mod index;
+mod journal;
+mod matching;
+mod metrics;
+mod multipart;
+mod negotiation;
+mod openapi;
+mod overrides;
+mod plugins;
+mod priorities;
+mod privileges;
+mod proxy;
+mod quiet;
+mod ranges;
+mod ratelimit;
+mod recorder;
+mod report;
+mod response_cache;
+mod retry;
+mod rewrite;
+mod s3;
+mod scripts;
+mod server;
+mod telemetry;
+mod templates;
+mod tenants;
+mod timeouts;
+mod validate;
+mod wiremock;
+mod xml;
+
+pub use crate::builder::{StubServer, StubServerBuilder, StubServerHandle};
+pub use crate::callbacks::Callbacks;
+
+/// Runs the `pact-stub-server` command line interface. It is only public so the binary can call it, and is not part of
+/// the library API.
+#[doc(hidden)]
+pub fn run_cli() {
+    cli::main()
+}
@@ -0,0 +1,462 @@
+use pact_matching::{self, DiffConfig, Mismatch};
+use crate::forms;
+use crate::generators::{self, PathToken};
+use crate::multipart;
+use crate::plugins::Plugins;
+use crate::xml::{self, XmlPart};
+use pact_matching::models::{DetectedContentType, HttpPart, OptionalBody, Request};
+use pact_matching::models::matchingrules::MatchingRules;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// How query parameters that are repeated in a request (like `?id=1&id=2`) are matched
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DuplicateQueryKeys {
+    /// The values are matched as a list, in the order they appear in
+    #[default]
+    Ordered,
+    /// The values are matched as a set, in any order
+    Unordered,
+    /// Only the last value is used
+    LastValue
+}
+
+/// Which arrays in the JSON request bodies are matched in any order, as if they were sets (with duplicates), instead of
+/// item by item
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct UnorderedArrays {
+    all: bool,
+    paths: Vec<Vec<PathToken>>
+}
+
+impl UnorderedArrays {
+    /// Every array in the bodies is unordered
+    pub fn all() -> UnorderedArrays {
+        UnorderedArrays { all: true, paths: vec![] }
+    }
+
+    /// Only the arrays at the body paths (like `$.items` or `$.orders[*].lines`) are unordered. Returns an error if any
+    /// of the paths is not valid.
+    pub fn at_paths<S: AsRef<str>>(paths: &[S]) -> Result<UnorderedArrays, String> {
+        let paths = paths.iter()
+            .map(|path| generators::parse_path(path.as_ref()))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(UnorderedArrays { all: false, paths })
+    }
+
+    /// If any arrays are unordered
+    pub fn is_enabled(&self) -> bool {
+        self.all || !self.paths.is_empty()
+    }
+
+    /// If the array at the path (made of fields and indexes) is unordered
+    fn includes(&self, path: &[PathToken]) -> bool {
+        self.all || self.paths.iter().any(|tokens| tokens.len() == path.len() &&
+            tokens.iter().zip(path).all(|(token, step)| token == &PathToken::Star || token == step))
+    }
+}
+
+/// Options that control how incoming requests are matched against the interactions
+#[derive(Debug, Clone, Default)]
+pub struct MatchingOptions {
+    /// Ignore the request bodies, and only match on the method, path, query and headers
+    pub ignore_body: bool,
+    /// How query parameters with more than one value are matched
+    pub duplicate_query_keys: DuplicateQueryKeys,
+    /// Plugins that match (and generate) bodies of content types that are not otherwise supported
+    pub plugins: Option<Arc<Plugins>>,
+    /// Match the header names (including the ones in matching rules) in lowercase
+    pub lowercase_headers: bool,
+    /// Match the query parameter names (including the ones in matching rules) in lowercase
+    pub lowercase_query_keys: bool,
+    /// Match paths that only differ by a trailing slash
+    pub ignore_trailing_slash: bool,
+    /// Compare bodies that are both JSON without their whitespace and key order, even when they are not sent with a
+    /// JSON content type
+    pub json_ignore_whitespace: bool,
+    /// Compare JSON numbers by their value, so `1` and `1.0` are equal
+    pub json_lenient_numbers: bool,
+    /// Allow keys in the JSON request bodies that are not in the interactions
+    pub json_allow_extra_keys: bool,
+    /// Arrays in the JSON request bodies that are matched in any order
+    pub unordered_arrays: UnorderedArrays
+}
+
+fn normalise_query(query: &Option<HashMap<String, Vec<String>>>, mode: DuplicateQueryKeys) -> Option<HashMap<String, Vec<String>>> {
+    query.as_ref().map(|query| query.iter()
+        .map(|(key, values)| {
+            let values = match mode {
+                DuplicateQueryKeys::Ordered => values.clone(),
+                DuplicateQueryKeys::Unordered => {
+                    let mut values = values.clone();
+                    values.sort();
+                    values
+                },
+                DuplicateQueryKeys::LastValue => values.last().cloned().into_iter().collect()
+            };
+            (key.clone(), values)
+        })
+        .collect())
+}
+
+/// The JSON value with the numbers that are whole (like `1.0`) changed to integers
+fn normalise_numbers(value: Value) -> Value {
+    match value {
+        Value::Number(ref number) if number.is_f64() => match number.as_f64() {
+            Some(float) if float.fract() == 0.0 && float.abs() < 9_007_199_254_740_992.0 => Value::from(float as i64),
+            _ => value
+        },
+        Value::Array(values) => Value::Array(values.into_iter().map(normalise_numbers).collect()),
+        Value::Object(map) => Value::Object(map.into_iter().map(|(key, value)| (key, normalise_numbers(value))).collect()),
+        _ => value
+    }
+}
+
+/// Sorts the items of the unordered arrays in the JSON value, so arrays with the same items in another order are equal.
+/// The items are sorted by their JSON text, after any unordered arrays in them are sorted.
+fn sort_unordered_arrays(value: &mut Value, path: &mut Vec<PathToken>, arrays: &UnorderedArrays) {
+    match value {
+        Value::Object(map) => for (key, value) in map.iter_mut() {
+            path.push(PathToken::Field(key.clone()));
+            sort_unordered_arrays(value, path, arrays);
+            path.pop();
+        },
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push(PathToken::Index(index));
+                sort_unordered_arrays(item, path, arrays);
+                path.pop();
+            }
+            if arrays.includes(path) {
+                items.sort_by_cached_key(|item| item.to_string());
+            }
+        },
+        _ => ()
+    }
+}
+
+/// Copies of the requests with their JSON bodies written the same way, so the JSON leniency options apply. The bodies
+/// are written without whitespace, with the keys sorted, the unordered arrays sorted and, if the numbers are lenient,
+/// with whole numbers as integers. Returns `None` if the options do not apply to the bodies, or either body is not
+/// valid JSON.
+fn normalise_json_bodies(expected: &Request, actual: &Request, options: &MatchingOptions) -> Option<(Request, Request)> {
+    let is_json = |request: &Request| request.content_type_enum() == DetectedContentType::Json;
+    let json_options = options.json_lenient_numbers || options.unordered_arrays.is_enabled();
+    if !(options.json_ignore_whitespace || (json_options && is_json(expected) && is_json(actual))) {
+        return None;
+    }
+    let normalise = |request: &Request| -> Option<Request> {
+        let json = match request.body {
+            OptionalBody::Present(ref body) => serde_json::from_slice::<Value>(body).ok()?,
+            _ => return None
+        };
+        let mut json = if options.json_lenient_numbers { normalise_numbers(json) } else { json };
+        if options.unordered_arrays.is_enabled() {
+            sort_unordered_arrays(&mut json, &mut vec![], &options.unordered_arrays);
+        }
+        Some(Request { body: OptionalBody::Present(serde_json::to_vec(&json).ok()?), .. request.clone() })
+    };
+    Some((normalise(expected)?, normalise(actual)?))
+}
+
+/// Weight of a method or path mismatch in the score of an interaction. A method or path mismatch outweighs any number of
+/// the other mismatches a request is likely to have.
+pub const REQUEST_LINE_WEIGHT: usize = 1_000_000;
+/// Weight of a query parameter mismatch in the score of an interaction
+pub const QUERY_WEIGHT: usize = 10_000;
+/// Weight of a header mismatch in the score of an interaction
+pub const HEADER_WEIGHT: usize = 100;
+/// Weight of a body with the wrong content type in the score of an interaction
+pub const BODY_TYPE_WEIGHT: usize = 10;
+/// Weight of a difference in the body (like a field with another value) in the score of an interaction
+pub const BODY_WEIGHT: usize = 1;
+
+/// How far a request is from the interaction it was matched against. Each kind of mismatch has a weight, so a request
+/// whose body differs from an interaction in many small places is still closer to it than to an interaction with another
+/// query parameter. The lowest score is the closest match.
+pub fn mismatch_score(mismatches: &[Mismatch]) -> usize {
+    mismatches.iter().map(|mismatch| match mismatch {
+        Mismatch::MethodMismatch { .. } | Mismatch::PathMismatch { .. } | Mismatch::StatusMismatch { .. } => REQUEST_LINE_WEIGHT,
+        Mismatch::QueryMismatch { .. } => QUERY_WEIGHT,
+        Mismatch::HeaderMismatch { .. } => HEADER_WEIGHT,
+        Mismatch::BodyTypeMismatch { .. } => BODY_TYPE_WEIGHT,
+        Mismatch::BodyMismatch { .. } => BODY_WEIGHT
+    }).sum()
+}
+
+/// The values with their keys in lowercase. The values of keys that are only different in case are combined.
+fn lowercase_keys(values: Option<HashMap<String, Vec<String>>>) -> Option<HashMap<String, Vec<String>>> {
+    values.map(|values| {
+        let mut keys = values.keys().cloned().collect::<Vec<String>>();
+        keys.sort();
+        keys.into_iter().fold(HashMap::new(), |mut lowercased: HashMap<String, Vec<String>>, key| {
+            lowercased.entry(key.to_lowercase()).or_default().extend(values[&key].iter().cloned());
+            lowercased
+        })
+    })
+}
+
+/// The matching rules with the keys of the rules in the categories in lowercase
+fn lowercase_rule_keys(rules: &MatchingRules, categories: &[&str]) -> MatchingRules {
+    let mut rules = rules.clone();
+    for (name, category) in rules.rules.iter_mut() {
+        if categories.contains(&name.as_str()) {
+            category.rules = category.rules.drain().map(|(key, rules)| (key.to_lowercase(), rules)).collect();
+        }
+    }
+    rules
+}
+
+/// The path with a trailing slash added, or removed if it has one. The root path does not have another form.
+pub fn other_trailing_slash_form(path: &str) -> Option<String> {
+    if path == "/" || path.is_empty() {
+        None
+    } else if let Some(path) = path.strip_suffix('/') {
+        Some(path.to_string())
+    } else {
+        Some(format!("{}/", path))
+    }
+}
+
+fn match_path(expected: &Request, actual: &Request, options: &MatchingOptions, mismatches: &mut Vec<Mismatch>) {
+    let mut path_mismatches = vec![];
+    pact_matching::match_path(expected.path.clone(), actual.path.clone(), &mut path_mismatches, &expected.matching_rules);
+    if !path_mismatches.is_empty() && options.ignore_trailing_slash {
+        if let Some(path) = other_trailing_slash_form(&actual.path) {
+            let mut other_mismatches = vec![];
+            pact_matching::match_path(expected.path.clone(), path, &mut other_mismatches, &expected.matching_rules);
+            if other_mismatches.is_empty() {
+                path_mismatches = other_mismatches;
+            }
+        }
+    }
+    mismatches.extend(path_mismatches);
+}
+
+/// Matches the actual request against the expected one from the interaction, honouring the matching options
+pub fn match_request(expected: &Request, actual: &Request, options: &MatchingOptions) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+
+    debug!("comparing to expected {}", expected);
+    pact_matching::match_method(expected.method.clone(), actual.method.clone(), &mut mismatches);
+    match_path(expected, actual, options, &mut mismatches);
+    let is_multipart = multipart::is_multipart(expected);
+    if options.ignore_body {
+        // the request bodies are not matched
+    } else if is_multipart {
+        multipart::match_multipart(expected, actual, &mut mismatches, &expected.matching_rules);
+    } else if forms::is_form(expected) {
+        forms::match_form(expected, actual, &mut mismatches, &expected.matching_rules);
+    } else if let Some(plugin) = options.plugins.as_ref().and_then(|plugins| plugins.matcher_for(&expected.content_type())) {
+        plugin.match_body(expected, actual, &mut mismatches);
+    } else if xml::is_xml(expected) && xml::is_xml(actual) {
+        pact_matching::match_body(&XmlPart(expected), &XmlPart(actual), DiffConfig::NoUnexpectedKeys, &mut mismatches,
+                                  &expected.matching_rules);
+    } else {
+        let normalised = normalise_json_bodies(expected, actual, options);
+        let (expected, actual) = normalised.as_ref().map(|(expected, actual)| (expected, actual)).unwrap_or((expected, actual));
+        let config = if options.json_allow_extra_keys { DiffConfig::AllowUnexpectedKeys } else { DiffConfig::NoUnexpectedKeys };
+        pact_matching::match_body(expected, actual, config, &mut mismatches, &expected.matching_rules);
+    }
+    let lowercase_categories = [ (options.lowercase_headers, "header"), (options.lowercase_query_keys, "query") ].iter()
+        .filter(|(lowercase, _)| *lowercase)
+        .map(|(_, category)| *category)
+        .collect::<Vec<&str>>();
+    let lowercased_rules = if lowercase_categories.is_empty() {
+        None
+    } else {
+        Some(lowercase_rule_keys(&expected.matching_rules, &lowercase_categories))
+    };
+    let rules = lowercased_rules.as_ref().unwrap_or(&expected.matching_rules);
+    let (expected_query, actual_query) = (normalise_query(&expected.query, options.duplicate_query_keys),
+        normalise_query(&actual.query, options.duplicate_query_keys));
+    if options.lowercase_query_keys {
+        pact_matching::match_query(lowercase_keys(expected_query), lowercase_keys(actual_query), &mut mismatches, rules);
+    } else {
+        pact_matching::match_query(expected_query, actual_query, &mut mismatches, rules);
+    }
+    let (expected_headers, actual_headers) = if is_multipart && multipart::is_multipart(actual) {
+        (multipart::headers_without_boundary(&expected.headers), multipart::headers_without_boundary(&actual.headers))
+    } else {
+        (expected.headers.clone(), actual.headers.clone())
+    };
+    if options.lowercase_headers {
+        pact_matching::match_headers(lowercase_keys(expected_headers), lowercase_keys(actual_headers), &mut mismatches, rules);
+    } else {
+        pact_matching::match_headers(expected_headers, actual_headers, &mut mismatches, rules);
+    }
+
+    debug!("--> Mismatches: {:?}", mismatches);
+    mismatches
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Request};
+    use pact_matching::models::matchingrules::{MatchingRule, RuleLogic};
+    use super::*;
+
+    #[test]
+    fn ignores_the_body_if_body_matching_is_disabled() {
+        let expected = Request {
+            method: s!("POST"),
+            body: OptionalBody::Present("{\"a\": 1}".as_bytes().into()),
+            .. Request::default_request()
+        };
+        let actual = Request {
+            method: s!("POST"),
+            body: OptionalBody::Present("{\"b\": 2}".as_bytes().into()),
+            .. Request::default_request()
+        };
+
+        expect!(match_request(&expected, &actual, &MatchingOptions::default()).is_empty()).to(be_false());
+        expect!(match_request(&expected, &actual, &MatchingOptions { ignore_body: true, .. MatchingOptions::default() }).is_empty()).to(be_true());
+    }
+
+    #[test]
+    fn matches_xml_bodies_by_their_elements() {
+        let request = |content_type: &str, body: &str| Request {
+            method: s!("POST"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!(content_type) ] }),
+            body: OptionalBody::Present(body.as_bytes().into()),
+            .. Request::default_request()
+        };
+        let expected = request("text/xml", "<s:Envelope xmlns:s=\"urn:soap\"><s:Body><GetUser id=\"42\"/></s:Body></s:Envelope>");
+        let options = MatchingOptions::default();
+
+        let formatted = request("text/xml; charset=utf-8",
+            "<?xml version=\"1.0\"?>\n<soap:Envelope xmlns:soap=\"urn:soap\">\n  <soap:Body>\n    <GetUser id=\"42\"/>\n  </soap:Body>\n</soap:Envelope>");
+        expect!(match_request(&expected, &formatted, &options)).to(be_equal_to(vec![]));
+        let soap12 = request("application/soap+xml", "<s:Envelope xmlns:s=\"urn:soap\"><s:Body><GetUser id=\"42\"/></s:Body></s:Envelope>");
+        expect!(match_request(&expected, &soap12, &options).iter().all(|m| matches!(m, Mismatch::HeaderMismatch { .. }))).to(be_true());
+        let other_id = request("text/xml", "<s:Envelope xmlns:s=\"urn:soap\"><s:Body><GetUser id=\"7\"/></s:Body></s:Envelope>");
+        expect!(match_request(&expected, &other_id, &options).len()).to(be_equal_to(1));
+        let other_namespace = request("text/xml", "<s:Envelope xmlns:s=\"urn:other\"><s:Body><GetUser id=\"42\"/></s:Body></s:Envelope>");
+        expect!(match_request(&expected, &other_namespace, &options).is_empty()).to(be_false());
+    }
+
+    #[test]
+    fn matches_json_bodies_leniently_if_enabled() {
+        let request = |content_type: &str, body: &str| Request {
+            method: s!("POST"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!(content_type) ] }),
+            body: OptionalBody::Present(body.as_bytes().into()),
+            .. Request::default_request()
+        };
+        let expected = request("application/json", "{\"id\": 1, \"price\": 2.5, \"tags\": [3]}");
+        let strict = MatchingOptions::default();
+
+        let floats = request("application/json", "{\"id\": 1.0, \"price\": 2.50, \"tags\": [3.0]}");
+        expect!(match_request(&expected, &floats, &strict).len()).to(be_equal_to(2));
+        expect!(match_request(&expected, &floats, &MatchingOptions { json_lenient_numbers: true, .. strict.clone() })).to(be_equal_to(vec![]));
+
+        let extra_key = request("application/json", "{\"tags\": [3], \"id\": 1, \"price\": 2.5, \"note\": \"gift\"}");
+        expect!(match_request(&expected, &extra_key, &strict).len()).to(be_equal_to(1));
+        expect!(match_request(&expected, &extra_key, &MatchingOptions { json_allow_extra_keys: true, .. strict.clone() })).to(be_equal_to(vec![]));
+
+        let expected_text = request("text/plain", "{\"id\": 1, \"name\": \"fred\"}");
+        let text = request("text/plain", "{\n  \"name\": \"fred\",\n  \"id\": 1\n}");
+        expect!(match_request(&expected_text, &text, &strict).len()).to(be_equal_to(1));
+        expect!(match_request(&expected_text, &text, &MatchingOptions { json_ignore_whitespace: true, .. strict.clone() })).to(be_equal_to(vec![]));
+        let not_json = request("text/plain", "id=1");
+        expect!(match_request(&expected_text, &not_json, &MatchingOptions { json_ignore_whitespace: true, .. strict }).len()).to(be_equal_to(1));
+    }
+
+    #[test]
+    fn matches_unordered_arrays_in_any_order_if_enabled() {
+        let request = |body: &str| Request {
+            method: s!("POST"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present(body.as_bytes().into()),
+            .. Request::default_request()
+        };
+        let expected = request("{\"ids\": [1, 2, 2], \"orders\": [{\"lines\": [\"a\", \"b\"]}]}");
+        let reordered = request("{\"ids\": [2, 1, 2], \"orders\": [{\"lines\": [\"b\", \"a\"]}]}");
+        let with_arrays = |unordered_arrays| MatchingOptions { unordered_arrays, .. MatchingOptions::default() };
+
+        expect!(match_request(&expected, &reordered, &MatchingOptions::default()).is_empty()).to(be_false());
+        expect!(match_request(&expected, &reordered, &with_arrays(UnorderedArrays::all()))).to(be_equal_to(vec![]));
+        let paths = UnorderedArrays::at_paths(&[ "$.ids", "$.orders[*].lines" ]).unwrap();
+        expect!(match_request(&expected, &reordered, &with_arrays(paths))).to(be_equal_to(vec![]));
+        let ids_only = UnorderedArrays::at_paths(&[ "$.ids" ]).unwrap();
+        expect!(match_request(&expected, &reordered, &with_arrays(ids_only)).len()).to(be_equal_to(2));
+        let missing_duplicate = request("{\"ids\": [2, 1, 1], \"orders\": [{\"lines\": [\"a\", \"b\"]}]}");
+        expect!(match_request(&expected, &missing_duplicate, &with_arrays(UnorderedArrays::all())).is_empty()).to(be_false());
+        expect!(UnorderedArrays::at_paths(&[ "$.ids[" ])).to(be_err());
+    }
+
+    #[test]
+    fn matches_repeated_query_parameters_according_to_the_duplicate_key_mode() {
+        let expected = Request {
+            query: Some(hashmap!{ s!("id") => vec![ s!("1"), s!("2") ] }),
+            .. Request::default_request()
+        };
+        let reordered = Request {
+            query: Some(hashmap!{ s!("id") => vec![ s!("2"), s!("1") ] }),
+            .. Request::default_request()
+        };
+        let last_value = Request {
+            query: Some(hashmap!{ s!("id") => vec![ s!("3"), s!("2") ] }),
+            .. Request::default_request()
+        };
+        let with_mode = |mode| MatchingOptions { duplicate_query_keys: mode, .. MatchingOptions::default() };
+
+        expect!(match_request(&expected, &reordered, &with_mode(DuplicateQueryKeys::Ordered)).is_empty()).to(be_false());
+        expect!(match_request(&expected, &reordered, &with_mode(DuplicateQueryKeys::Unordered)).is_empty()).to(be_true());
+        expect!(match_request(&expected, &last_value, &with_mode(DuplicateQueryKeys::Unordered)).is_empty()).to(be_false());
+        expect!(match_request(&expected, &last_value, &with_mode(DuplicateQueryKeys::LastValue)).is_empty()).to(be_true());
+    }
+
+    #[test]
+    fn matches_header_and_query_parameter_names_in_lowercase_if_enabled() {
+        let expected = Request {
+            query: Some(hashmap!{ s!("pageSize") => vec![ s!("10") ] }),
+            headers: Some(hashmap!{ s!("x-request-id") => vec![ s!("1") ] }),
+            matching_rules: matchingrules!{
+                "header" => { "X-Request-Id" => [ MatchingRule::Regex(s!("\\d+")) ] }
+            },
+            .. Request::default_request()
+        };
+        let actual = Request {
+            query: Some(hashmap!{ s!("PAGESIZE") => vec![ s!("10") ] }),
+            headers: Some(hashmap!{ s!("X-REQUEST-ID") => vec![ s!("42") ] }),
+            .. Request::default_request()
+        };
+        let options = |lowercase_query_keys| MatchingOptions { lowercase_headers: true, lowercase_query_keys, .. MatchingOptions::default() };
+
+        expect!(match_request(&expected, &actual, &MatchingOptions::default()).len()).to(be_equal_to(3));
+        expect!(match_request(&expected, &actual, &options(false)).len()).to(be_equal_to(2));
+        expect!(match_request(&expected, &actual, &options(true)).is_empty()).to(be_true());
+    }
+
+    #[test]
+    fn matches_paths_that_only_differ_by_a_trailing_slash_if_enabled() {
+        let expected = Request { path: s!("/api/users"), .. Request::default_request() };
+        let with_slash = Request { path: s!("/api/users/"), .. Request::default_request() };
+        let other = Request { path: s!("/api/users/1"), .. Request::default_request() };
+        let options = MatchingOptions { ignore_trailing_slash: true, .. MatchingOptions::default() };
+
+        expect!(match_request(&expected, &with_slash, &MatchingOptions::default()).is_empty()).to(be_false());
+        expect!(match_request(&expected, &with_slash, &options).is_empty()).to(be_true());
+        expect!(match_request(&with_slash, &expected, &options).is_empty()).to(be_true());
+        expect!(match_request(&expected, &other, &options).is_empty()).to(be_false());
+        expect!(other_trailing_slash_form("/")).to(be_none());
+    }
+
+    #[test]
+    fn scores_the_mismatches_by_their_kind() {
+        let query = Mismatch::QueryMismatch { parameter: s!("page"), expected: s!("1"), actual: s!("2"), mismatch: s!("") };
+        let header = Mismatch::HeaderMismatch { key: s!("Accept"), expected: s!("a"), actual: s!("b"), mismatch: s!("") };
+        let body = Mismatch::BodyMismatch { path: s!("$.a"), expected: None, actual: None, mismatch: s!("") };
+        let path = Mismatch::PathMismatch { expected: s!("/a"), actual: s!("/b"), mismatch: s!("") };
+
+        expect!(mismatch_score(&[])).to(be_equal_to(0));
+        expect!(mismatch_score(&vec![ body; 20 ])).to(be_equal_to(20 * BODY_WEIGHT));
+        expect!(mismatch_score(&[ header ])).to(be_equal_to(HEADER_WEIGHT));
+        expect!(mismatch_score(&[ query ])).to(be_equal_to(QUERY_WEIGHT));
+        expect!(mismatch_score(&[ path ])).to(be_equal_to(REQUEST_LINE_WEIGHT));
+        expect!(20 * BODY_WEIGHT < HEADER_WEIGHT && 20 * HEADER_WEIGHT < QUERY_WEIGHT && 20 * QUERY_WEIGHT < REQUEST_LINE_WEIGHT).to(be_true());
+    }
+}
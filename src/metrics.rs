@@ -0,0 +1,153 @@
+use crate::pact_support;
+use pact_matching::models::{Interaction, Pact};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Path of the endpoint that returns the metrics
+pub const METRICS_PATH: &str = "/_pact-stub/metrics";
+
+/// Upper bounds (in bytes) of the body size histogram buckets
+static SIZE_BUCKETS: [usize; 9] = [ 0, 64, 256, 1024, 4096, 16384, 65536, 262144, 1048576 ];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; 9],
+    sum: u64,
+    count: u64
+}
+
+impl Histogram {
+    fn observe(&mut self, size: usize) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(SIZE_BUCKETS.iter()) {
+            if size <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += size as u64;
+        self.count += 1;
+    }
+
+    fn render(&self, name: &str, labels: &str, out: &mut String) {
+        for (bucket, bound) in self.buckets.iter().zip(SIZE_BUCKETS.iter()) {
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, bucket);
+        }
+        let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, self.count);
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct InteractionMetrics {
+    example_request_size: usize,
+    example_response_size: usize,
+    requests: Histogram,
+    responses: Histogram
+}
+
+/// Collects the sizes of the request and response bodies for each interaction, so they can be compared with the
+/// sizes of the example bodies in the pacts
+#[derive(Debug, Default)]
+pub struct Metrics {
+    interactions: Mutex<BTreeMap<(String, String), InteractionMetrics>>
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl Metrics {
+    /// Records the body sizes of a request that was matched to the interaction from the pact, and of the response
+    /// that was sent for it
+    pub fn record(&self, pact: &Pact, interaction: &Interaction, request_size: usize, response_size: usize) {
+        let mut interactions = self.interactions.lock().unwrap();
+        let metrics = interactions
+            .entry((pact.provider.name.clone(), interaction.description.clone()))
+            .or_insert_with(|| InteractionMetrics {
+                example_request_size: pact_support::body_bytes(&interaction.request.body).len(),
+                example_response_size: pact_support::body_bytes(&interaction.response.body).len(),
+                .. InteractionMetrics::default()
+            });
+        metrics.requests.observe(request_size);
+        metrics.responses.observe(response_size);
+    }
+
+    /// Returns the metrics of the interactions of the providers of the pacts in the Prometheus text format
+    pub fn render<'a, I: IntoIterator<Item = &'a Pact>>(&self, pacts: I) -> String {
+        let providers = pacts.into_iter().map(|pact| pact.provider.name.as_str()).collect::<BTreeSet<&str>>();
+        let interactions = self.interactions.lock().unwrap();
+        let interactions = interactions.iter()
+            .filter(|((provider, _), _)| providers.contains(provider.as_str()))
+            .collect::<BTreeMap<_, _>>();
+        let labels = interactions.keys()
+            .map(|(provider, interaction)| format!("provider=\"{}\",interaction=\"{}\"", escape_label(provider),
+                                                   escape_label(interaction)))
+            .collect::<Vec<String>>();
+        let mut out = String::new();
+        let header = |out: &mut String, name: &str, help: &str, kind: &str| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+        };
+
+        header(&mut out, "pact_stub_request_body_bytes", "Size of the request bodies matched to each interaction", "histogram");
+        for (metrics, labels) in interactions.values().zip(labels.iter()) {
+            metrics.requests.render("pact_stub_request_body_bytes", labels, &mut out);
+        }
+        header(&mut out, "pact_stub_response_body_bytes", "Size of the response bodies sent for each interaction", "histogram");
+        for (metrics, labels) in interactions.values().zip(labels.iter()) {
+            metrics.responses.render("pact_stub_response_body_bytes", labels, &mut out);
+        }
+        header(&mut out, "pact_stub_example_request_body_bytes", "Size of the example request body of each interaction", "gauge");
+        for (metrics, labels) in interactions.values().zip(labels.iter()) {
+            let _ = writeln!(out, "pact_stub_example_request_body_bytes{{{}}} {}", labels, metrics.example_request_size);
+        }
+        header(&mut out, "pact_stub_example_response_body_bytes", "Size of the example response body of each interaction", "gauge");
+        for (metrics, labels) in interactions.values().zip(labels.iter()) {
+            let _ = writeln!(out, "pact_stub_example_response_body_bytes{{{}}} {}", labels, metrics.example_response_size);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, OptionalBody, Pact, Provider, Request};
+    use super::*;
+
+    #[test]
+    fn renders_body_size_histograms_for_each_interaction() {
+        let pact = Pact { provider: Provider { name: s!("provider") }, .. Pact::default() };
+        let interaction = Interaction {
+            description: s!("say \"hello\""),
+            request: Request { body: OptionalBody::Present("hello".as_bytes().into()), .. Request::default_request() },
+            .. Interaction::default()
+        };
+        let metrics = Metrics::default();
+        metrics.record(&pact, &interaction, 10, 100);
+        metrics.record(&pact, &interaction, 2000, 0);
+
+        let output = metrics.render(&[ pact ]);
+        let labels = "provider=\"provider\",interaction=\"say \\\"hello\\\"\"";
+        expect!(output.contains("# TYPE pact_stub_request_body_bytes histogram")).to(be_true());
+        expect!(output.contains(&format!("pact_stub_request_body_bytes_bucket{{{},le=\"64\"}} 1", labels))).to(be_true());
+        expect!(output.contains(&format!("pact_stub_request_body_bytes_bucket{{{},le=\"4096\"}} 2", labels))).to(be_true());
+        expect!(output.contains(&format!("pact_stub_request_body_bytes_sum{{{}}} 2010", labels))).to(be_true());
+        expect!(output.contains(&format!("pact_stub_response_body_bytes_bucket{{{},le=\"0\"}} 1", labels))).to(be_true());
+        expect!(output.contains(&format!("pact_stub_response_body_bytes_count{{{}}} 2", labels))).to(be_true());
+        expect!(output.contains(&format!("pact_stub_example_request_body_bytes{{{}}} 5", labels))).to(be_true());
+    }
+
+    #[test]
+    fn only_renders_the_metrics_of_the_providers_of_the_pacts() {
+        let users = Pact { provider: Provider { name: s!("users") }, .. Pact::default() };
+        let orders = Pact { provider: Provider { name: s!("orders") }, .. Pact::default() };
+        let metrics = Metrics::default();
+        metrics.record(&users, &Interaction::default(), 10, 100);
+        metrics.record(&orders, &Interaction::default(), 10, 100);
+
+        let output = metrics.render(&[ users ]);
+        expect!(output.contains("provider=\"users\"")).to(be_true());
+        expect!(output.contains("provider=\"orders\"")).to(be_false());
+    }
+}
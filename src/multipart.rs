@@ -0,0 +1,280 @@
+use pact_matching::{self, DiffConfig, Mismatch};
+use pact_matching::models::{HttpPart, OptionalBody, Request};
+use pact_matching::models::matchingrules::{MatchingRule, MatchingRules, RuleLogic};
+use crate::pact_support;
+use regex::Regex;
+use std::collections::HashMap;
+
+static MULTIPART_FORM_DATA: &str = "multipart/form-data";
+
+/// A part of a multipart body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    /// Name of the form field
+    pub name: String,
+    /// Name of the uploaded file, if the part is a file
+    pub filename: Option<String>,
+    /// Content type of the part, if it has one
+    pub content_type: Option<String>,
+    /// Contents of the part
+    pub body: Vec<u8>
+}
+
+/// If the request has a multipart form data body
+pub fn is_multipart(request: &Request) -> bool {
+    request.content_type() == MULTIPART_FORM_DATA
+}
+
+/// Returns the boundary parameter from a multipart content type header value
+pub fn boundary(content_type: &str) -> Option<String> {
+    header_param(content_type, "boundary")
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from > haystack.len() || needle.is_empty() {
+        return None;
+    }
+    haystack[from..].windows(needle.len()).position(|window| window == needle).map(|pos| pos + from)
+}
+
+fn header_param(value: &str, name: &str) -> Option<String> {
+    value.split(';')
+        .skip(1)
+        .filter_map(|param| {
+            let mut kv = param.trim().splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) if key.trim().eq_ignore_ascii_case(name) =>
+                    Some(value.trim().trim_matches('"').to_string()),
+                _ => None
+            }
+        })
+        .next()
+}
+
+/// Splits a multipart body into its parts using the boundary
+pub fn parse(body: &[u8], boundary: &str) -> Result<Vec<Part>, String> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = vec![];
+    let mut pos = find(body, &delimiter, 0)
+        .ok_or_else(|| format!("Multipart body does not contain the boundary '{}'", boundary))?;
+    loop {
+        pos += delimiter.len();
+        if body[pos..].starts_with(b"--") {
+            break;
+        }
+        let headers_end = find(body, b"\r\n\r\n", pos)
+            .ok_or_else(|| s!("Multipart body has a part without a blank line after the headers"))?;
+        let next = find(body, &delimiter, headers_end)
+            .ok_or_else(|| s!("Multipart body is missing the closing boundary"))?;
+        let headers = String::from_utf8_lossy(&body[pos..headers_end]).to_string();
+        let mut content_end = next;
+        if body[headers_end + 4..content_end].ends_with(b"\r\n") {
+            content_end -= 2;
+        }
+
+        let mut part = Part { name: String::new(), filename: None, content_type: None,
+            body: body[headers_end + 4..content_end].to_vec() };
+        for header in headers.split("\r\n").filter(|header| !header.trim().is_empty()) {
+            let mut kv = header.splitn(2, ':');
+            let (name, value) = (kv.next().unwrap_or("").trim(), kv.next().unwrap_or("").trim());
+            if name.eq_ignore_ascii_case("content-disposition") {
+                part.name = header_param(value, "name").unwrap_or_default();
+                part.filename = header_param(value, "filename");
+            } else if name.eq_ignore_ascii_case("content-type") {
+                part.content_type = Some(value.to_string());
+            }
+        }
+        parts.push(part);
+        pos = next;
+    }
+    Ok(parts)
+}
+
+/// Removes the boundary from the content type header, as it is different for every request
+pub fn headers_without_boundary(headers: &Option<HashMap<String, Vec<String>>>) -> Option<HashMap<String, Vec<String>>> {
+    headers.as_ref().map(|headers| headers.iter()
+        .map(|(name, values)| if name.eq_ignore_ascii_case("content-type") {
+            (name.clone(), values.iter().map(|value| value.split(';').next().unwrap_or("").trim().to_string()).collect())
+        } else {
+            (name.clone(), values.clone())
+        })
+        .collect())
+}
+
+fn mime_type(content_type: &Option<String>) -> Option<String> {
+    content_type.as_ref().map(|content_type| content_type.split(';').next().unwrap_or("").trim().to_lowercase())
+}
+
+fn body_mismatch(path: &str, expected: Option<&[u8]>, actual: Option<&[u8]>, mismatch: String) -> Mismatch {
+    Mismatch::BodyMismatch {
+        path: path.to_string(),
+        expected: expected.map(|body| body.to_vec()),
+        actual: actual.map(|body| body.to_vec()),
+        mismatch
+    }
+}
+
+fn apply_rule(rule: &MatchingRule, expected: &str, actual: &str) -> Result<(), String> {
+    match rule {
+        MatchingRule::Equality if expected != actual => Err(format!("Expected '{}' to be equal to '{}'", actual, expected)),
+        MatchingRule::Regex(regex) => match Regex::new(regex) {
+            Ok(re) if re.is_match(actual) => Ok(()),
+            Ok(_) => Err(format!("Expected '{}' to match '{}'", actual, regex)),
+            Err(err) => Err(format!("'{}' is not a valid regular expression - {}", regex, err))
+        },
+        MatchingRule::Include(value) if !actual.contains(value.as_str()) =>
+            Err(format!("Expected '{}' to include '{}'", actual, value)),
+        _ => Ok(())
+    }
+}
+
+fn match_part(expected: &Part, actual: &Part, matchers: &MatchingRules, mismatches: &mut Vec<Mismatch>) {
+    let path = format!("$.{}", expected.name);
+    if let Some(ref expected_type) = mime_type(&expected.content_type) {
+        let actual_type = mime_type(&actual.content_type).unwrap_or_else(|| s!("text/plain"));
+        if *expected_type != actual_type {
+            mismatches.push(body_mismatch(&path, Some(&expected.body), Some(&actual.body),
+                format!("Expected part '{}' to have content type '{}' but was '{}'", expected.name, expected_type, actual_type)));
+            return;
+        }
+    }
+
+    match matchers.resolve_body_matchers_by_path(&vec![ s!("$"), expected.name.clone() ]) {
+        Some(rules) => {
+            let expected_value = String::from_utf8_lossy(&expected.body);
+            let actual_value = String::from_utf8_lossy(&actual.body);
+            let results = rules.rules.iter()
+                .map(|rule| apply_rule(rule, &expected_value, &actual_value))
+                .collect::<Vec<Result<(), String>>>();
+            let matched = match rules.rule_logic {
+                RuleLogic::And => results.iter().all(Result::is_ok),
+                RuleLogic::Or => results.iter().any(Result::is_ok)
+            };
+            if !matched {
+                for err in results.into_iter().filter_map(Result::err) {
+                    mismatches.push(body_mismatch(&path, Some(&expected.body), Some(&actual.body),
+                        format!("Part '{}': {}", expected.name, err)));
+                }
+            }
+        },
+        None => {
+            let as_request = |part: &Part| Request {
+                headers: part.content_type.as_ref().map(|content_type| hashmap!{ s!("Content-Type") => vec![ content_type.clone() ] }),
+                body: OptionalBody::Present(part.body.clone()),
+                .. Request::default_request()
+            };
+            let mut part_mismatches = vec![];
+            pact_matching::match_body(&as_request(expected), &as_request(actual), DiffConfig::NoUnexpectedKeys,
+                &mut part_mismatches, &MatchingRules::default());
+            if !part_mismatches.is_empty() {
+                mismatches.push(body_mismatch(&path, Some(&expected.body), Some(&actual.body),
+                    format!("Part '{}' does not match: {}", expected.name, part_mismatches.iter()
+                        .map(|mismatch| mismatch.description()).collect::<Vec<String>>().join(", "))));
+            }
+        }
+    }
+}
+
+/// Matches multipart form data bodies part by part, instead of comparing the raw bytes which include the random
+/// boundary. Parts are matched by name, and are compared using their content types and any matching rules for the
+/// part (with a path of `$.<part name>`).
+pub fn match_multipart(expected: &Request, actual: &Request, mismatches: &mut Vec<Mismatch>, matchers: &MatchingRules) {
+    let expected_body = match expected.body {
+        OptionalBody::Present(ref body) => body,
+        _ => return
+    };
+    if !is_multipart(actual) {
+        mismatches.push(Mismatch::BodyTypeMismatch { expected: expected.content_type(), actual: actual.content_type() });
+        return;
+    }
+    let parse_body = |request: &Request, body: &[u8]| request.lookup_header_value(&s!("content-type"))
+        .and_then(|content_type| boundary(&content_type))
+        .ok_or_else(|| s!("Multipart content type does not have a boundary"))
+        .and_then(|boundary| parse(body, &boundary));
+    let expected_parts = match parse_body(expected, expected_body) {
+        Ok(parts) => parts,
+        Err(err) => {
+            warn!("Could not parse the expected multipart body - {}", err);
+            return;
+        }
+    };
+    let actual_body = pact_support::body_bytes(&actual.body);
+    let actual_parts = match parse_body(actual, actual_body) {
+        Ok(parts) => parts,
+        Err(err) => {
+            mismatches.push(body_mismatch("$", Some(expected_body), Some(actual_body),
+                format!("Could not parse the multipart body - {}", err)));
+            return;
+        }
+    };
+
+    for expected_part in &expected_parts {
+        match actual_parts.iter().find(|part| part.name == expected_part.name) {
+            Some(actual_part) => match_part(expected_part, actual_part, matchers, mismatches),
+            None => mismatches.push(body_mismatch(&format!("$.{}", expected_part.name), Some(&expected_part.body), None,
+                format!("Expected a part named '{}' but was missing", expected_part.name)))
+        }
+    }
+    for actual_part in &actual_parts {
+        if !expected_parts.iter().any(|part| part.name == actual_part.name) {
+            mismatches.push(body_mismatch(&format!("$.{}", actual_part.name), None, Some(&actual_part.body),
+                format!("Unexpected part named '{}' received", actual_part.name)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::matchingrules::*;
+    use super::*;
+
+    fn multipart_request(boundary: &str, name: &str, file_contents: &str) -> Request {
+        let body = format!("--{b}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\n{name}\r\n\
+            --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"data.json\"\r\n\
+            Content-Type: application/json\r\n\r\n{file}\r\n--{b}--\r\n", b = boundary, name = name, file = file_contents);
+        Request {
+            method: s!("POST"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ format!("multipart/form-data; boundary={}", boundary) ] }),
+            body: OptionalBody::Present(body.into_bytes()),
+            .. Request::default_request()
+        }
+    }
+
+    #[test]
+    fn parses_the_parts_of_a_multipart_body() {
+        let request = multipart_request("abc123", "Tom", "{\"a\": 1}");
+        let parts = parse(&request.body.value(), "abc123").unwrap();
+        expect!(parts.len()).to(be_equal_to(2));
+        expect!(parts[0].name.as_str()).to(be_equal_to("name"));
+        expect!(parts[0].body.clone()).to(be_equal_to(b"Tom".to_vec()));
+        expect!(parts[1].filename.clone()).to(be_some().value(s!("data.json")));
+        expect!(parts[1].content_type.clone()).to(be_some().value(s!("application/json")));
+        expect!(boundary("multipart/form-data; boundary=\"xyz\"")).to(be_some().value(s!("xyz")));
+    }
+
+    #[test]
+    fn matches_multipart_bodies_by_part_ignoring_the_boundary() {
+        let expected = multipart_request("expected", "Tom", "{\"a\": 1, \"b\": 2}");
+        let mut mismatches = vec![];
+        match_multipart(&expected, &multipart_request("actual", "Tom", "{\"b\":2,\"a\":1}"), &mut mismatches,
+            &MatchingRules::default());
+        expect!(mismatches.iter()).to(be_empty());
+
+        match_multipart(&expected, &multipart_request("actual", "Jerry", "{\"a\": 1, \"b\": 2}"), &mut mismatches,
+            &MatchingRules::default());
+        expect!(mismatches.len()).to(be_equal_to(1));
+    }
+
+    #[test]
+    fn applies_the_matching_rules_for_a_part() {
+        let expected = multipart_request("expected", "Tom", "{}");
+        let matchers = matchingrules!{ "body" => { "$.name" => [ MatchingRule::Regex(s!("^[A-Z][a-z]+$")) ] } };
+        let mut mismatches = vec![];
+        match_multipart(&expected, &multipart_request("actual", "Jerry", "{}"), &mut mismatches, &matchers);
+        expect!(mismatches.iter()).to(be_empty());
+
+        match_multipart(&expected, &multipart_request("actual", "jerry", "{}"), &mut mismatches, &matchers);
+        expect!(mismatches.len()).to(be_equal_to(1));
+    }
+}
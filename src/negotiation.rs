@@ -0,0 +1,84 @@
+use pact_matching::models::{HttpPart, Request, Response};
+
+/// Quality of a media type that is acceptable without any preference (the `q` parameter is in thousandths)
+const DEFAULT_QUALITY: u16 = 1000;
+
+/// A media range from an `Accept` header (like `text/*;q=0.5`), with its quality in thousandths
+#[derive(Debug, Clone, PartialEq)]
+struct MediaRange {
+    main_type: String,
+    sub_type: String,
+    quality: u16
+}
+
+impl MediaRange {
+    /// How specific the range is, if it includes the media type: 2 for the exact type, 1 for `type/*` and 0 for `*/*`
+    fn specificity(&self, main_type: &str, sub_type: &str) -> Option<u8> {
+        match (self.main_type.as_str(), self.sub_type.as_str()) {
+            ("*", "*") => Some(0),
+            (range_type, "*") if range_type == main_type => Some(1),
+            (range_type, range_sub_type) if range_type == main_type && range_sub_type == sub_type => Some(2),
+            _ => None
+        }
+    }
+}
+
+fn parse_quality(value: &str) -> Option<u16> {
+    value.trim().parse::<f32>().ok()
+        .filter(|quality| (0.0..=1.0).contains(quality))
+        .map(|quality| (quality * 1000.0).round() as u16)
+}
+
+fn parse_accept(accept: &str) -> Vec<MediaRange> {
+    accept.split(',').filter_map(|range| {
+        let mut parts = range.split(';');
+        let media_type = parts.next().unwrap_or_default().trim().to_lowercase();
+        let (main_type, sub_type) = media_type.split_once('/')?;
+        let quality = parts
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .map(|(_, value)| parse_quality(value))
+            .unwrap_or(Some(DEFAULT_QUALITY))?;
+        Some(MediaRange { main_type: main_type.trim().to_string(), sub_type: sub_type.trim().to_string(), quality })
+    }).collect()
+}
+
+/// Quality (in thousandths) the `Accept` header value gives the content type, from the most specific media range that
+/// includes it. A quality of 0 means the content type is not acceptable.
+pub fn accept_quality(accept: &str, content_type: &str) -> u16 {
+    let content_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+    let (main_type, sub_type) = content_type.split_once('/').unwrap_or((content_type.as_str(), ""));
+    parse_accept(accept).iter()
+        .filter_map(|range| range.specificity(main_type, sub_type).map(|specificity| (specificity, range.quality)))
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, quality)| quality)
+        .unwrap_or(0)
+}
+
+/// Quality of the content type of the response for the `Accept` header of the request. Responses are equally
+/// acceptable if the request has no `Accept` header, or the response has no body.
+pub fn response_quality(request: &Request, response: &Response) -> u16 {
+    match request.lookup_header_value(&s!("accept")) {
+        Some(ref accept) if !accept.trim().is_empty() && response.body.is_present() =>
+            accept_quality(accept, &response.content_type()),
+        _ => DEFAULT_QUALITY
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn uses_the_quality_of_the_most_specific_media_range() {
+        let accept = "text/csv;q=0.9, application/json, text/*;q=0.2, */*;q=0";
+        expect!(accept_quality(accept, "application/json")).to(be_equal_to(1000));
+        expect!(accept_quality(accept, "Text/CSV; charset=UTF-8")).to(be_equal_to(900));
+        expect!(accept_quality(accept, "text/plain")).to(be_equal_to(200));
+        expect!(accept_quality(accept, "application/xml")).to(be_equal_to(0));
+        expect!(accept_quality("application/json", "text/csv")).to(be_equal_to(0));
+        expect!(accept_quality("*/*", "image/png")).to(be_equal_to(1000));
+        expect!(accept_quality("application/json;q=2, text/csv", "application/json")).to(be_equal_to(0));
+    }
+}
@@ -0,0 +1,491 @@
+use itertools::Itertools;
+use pact_matching::models::{Consumer, HttpPart, Interaction, OptionalBody, Pact, PactSpecification, Provider, Request, Response};
+use pact_matching::models::matchingrules::{MatchingRule, MatchingRules, RuleLogic};
+use pact_matching::models::provider_states::ProviderState;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Consumer name used for the pacts created from OpenAPI specifications
+static OPENAPI_CONSUMER: &str = "openapi";
+
+static HTTP_METHODS: [&str; 7] = [ "get", "put", "post", "delete", "options", "head", "patch" ];
+
+/// An example response for an operation
+struct ResponseExample {
+    status: u16,
+    name: Option<String>,
+    content_type: Option<String>,
+    body: Option<Value>
+}
+
+/// Loads an OpenAPI (or Swagger 2) specification from a JSON or YAML file, and converts the examples of the
+/// operations into interactions
+pub fn load_openapi(path: &Path) -> Result<Pact, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("Could not read the OpenAPI specification '{}' - {}", path.display(), err))?;
+    let spec: Value = serde_yaml::from_str(&contents)
+        .map_err(|err| format!("Could not parse the OpenAPI specification '{}' - {}", path.display(), err))?;
+    let default_name = path.file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    pact_from_openapi(&spec, &default_name)
+}
+
+/// Converts an OpenAPI specification into a pact. The provider is named after the title of the API, or the given
+/// default name if the specification does not have one.
+pub fn pact_from_openapi(spec: &Value, default_name: &str) -> Result<Pact, String> {
+    if spec.get("openapi").is_none() && spec.get("swagger").is_none() {
+        return Err(s!("Not an OpenAPI specification, it has no 'openapi' or 'swagger' version attribute"));
+    }
+    let paths = match spec.get("paths") {
+        Some(Value::Object(paths)) => paths,
+        _ => return Err(s!("The OpenAPI specification does not define any paths"))
+    };
+
+    let mut interactions = vec![];
+    for (path, path_item) in paths {
+        let path_item = resolve_ref(spec, path_item);
+        for method in HTTP_METHODS.iter() {
+            if let Some(operation) = path_item.get(*method) {
+                let parameters = parameters(spec, path_item, operation);
+                let request = operation_request(method, path, &parameters);
+                let name = operation.get("summary")
+                    .or_else(|| operation.get("operationId"))
+                    .and_then(Value::as_str)
+                    .map(String::from)
+                    .unwrap_or_else(|| format!("{} {}", method.to_uppercase(), path));
+                for (index, example) in response_examples(spec, operation).iter().enumerate() {
+                    let suffix = match example.name {
+                        Some(ref example_name) => format!("{} ({})", example.status, example_name),
+                        None => example.status.to_string()
+                    };
+                    interactions.push(Interaction {
+                        description: format!("{} - {}", name, suffix),
+                        provider_states: if index == 0 {
+                            vec![]
+                        } else {
+                            vec![ ProviderState::default(&format!("{} {}", method.to_uppercase(), suffix)) ]
+                        },
+                        request: request.clone(),
+                        response: example_response(example)
+                    });
+                }
+            }
+        }
+    }
+
+    let title = spec.pointer("/info/title").and_then(Value::as_str).unwrap_or(default_name);
+    Ok(Pact {
+        consumer: Consumer { name: s!(OPENAPI_CONSUMER) },
+        provider: Provider { name: title.to_string() },
+        interactions,
+        specification_version: PactSpecification::V3,
+        .. Pact::default()
+    })
+}
+
+/// Follows a local `$ref` (like `#/components/examples/user`) to the value it refers to. A `$ref` back to one that was
+/// already followed is left unresolved.
+fn resolve_ref<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    let mut followed = vec![];
+    let mut value = value;
+    while let Some(reference) = value.get("$ref").and_then(Value::as_str).filter(|reference| reference.starts_with('#')) {
+        if followed.contains(&reference) {
+            warn!("'{}' refers back to itself, leaving it unresolved", reference);
+            break;
+        }
+        match spec.pointer(&reference[1..]) {
+            Some(target) => {
+                followed.push(reference);
+                value = target;
+            },
+            None => break
+        }
+    }
+    value
+}
+
+/// The parameters of the operation, including the ones shared by all the operations of the path
+fn parameters<'a>(spec: &'a Value, path_item: &'a Value, operation: &'a Value) -> Vec<&'a Value> {
+    [ path_item, operation ].iter()
+        .filter_map(|item| item.get("parameters").and_then(Value::as_array))
+        .flat_map(|parameters| parameters.iter().map(|parameter| resolve_ref(spec, parameter)))
+        .collect()
+}
+
+fn parameter_example(parameter: &Value) -> Option<String> {
+    parameter.get("example")
+        .or_else(|| parameter.pointer("/schema/example"))
+        .or_else(|| parameter.get("default"))
+        .or_else(|| parameter.pointer("/schema/default"))
+        .map(|value| match value {
+            Value::String(s) => s.clone(),
+            _ => value.to_string()
+        })
+}
+
+/// Builds the expected request for the operation. Path parameters match any value of a path segment, and required
+/// query parameters match any value.
+fn operation_request(method: &str, path: &str, parameters: &[&Value]) -> Request {
+    let mut example_path = String::new();
+    let mut path_regex = String::from("^");
+    let mut rest = path;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break
+        };
+        let name = &rest[start + 1..end];
+        example_path.push_str(&rest[..start]);
+        path_regex.push_str(&regex::escape(&rest[..start]));
+        let example = parameters.iter()
+            .find(|parameter| parameter.get("in").and_then(Value::as_str) == Some("path") &&
+                parameter.get("name").and_then(Value::as_str) == Some(name))
+            .and_then(|parameter| parameter_example(parameter))
+            .unwrap_or_else(|| s!("1"));
+        example_path.push_str(&example);
+        path_regex.push_str("[^/]+");
+        rest = &rest[end + 1..];
+    }
+    example_path.push_str(rest);
+    path_regex.push_str(&regex::escape(rest));
+    path_regex.push('$');
+
+    let mut matching_rules = MatchingRules::default();
+    if path != example_path {
+        matching_rules.add_category("path").add_rule(&s!(""), MatchingRule::Regex(path_regex), &RuleLogic::And);
+    }
+    let mut query = HashMap::new();
+    for parameter in parameters {
+        let required = parameter.get("required").and_then(Value::as_bool).unwrap_or(false);
+        if let (Some("query"), Some(name), true) = (parameter.get("in").and_then(Value::as_str),
+                                                   parameter.get("name").and_then(Value::as_str), required) {
+            query.insert(name.to_string(), vec![ parameter_example(parameter).unwrap_or_default() ]);
+            matching_rules.add_category("query").add_rule(&name.to_string(), MatchingRule::Type, &RuleLogic::And);
+        }
+    }
+
+    Request {
+        method: method.to_uppercase(),
+        path: example_path,
+        query: if query.is_empty() { None } else { Some(query) },
+        matching_rules,
+        .. Request::default_request()
+    }
+}
+
+/// Collects the example responses of the operation. Successful responses come first, so the first example is the
+/// default response for the operation.
+fn response_examples(spec: &Value, operation: &Value) -> Vec<ResponseExample> {
+    let mut examples = vec![];
+    if let Some(Value::Object(responses)) = operation.get("responses") {
+        let mut statuses = responses.iter()
+            .filter_map(|(status, response)| status.parse::<u16>().ok().map(|status| (status, response)))
+            .collect::<Vec<(u16, &Value)>>();
+        statuses.sort_by_key(|&(status, _)| (!(200..300).contains(&status), status));
+        for (status, response) in statuses {
+            let response = resolve_ref(spec, response);
+            let count = examples.len();
+            if let Some(Value::Object(content)) = response.get("content") {
+                for (content_type, media) in content {
+                    let media = resolve_ref(spec, media);
+                    if let Some(Value::Object(named)) = media.get("examples") {
+                        for (name, example) in named {
+                            if let Some(value) = resolve_ref(spec, example).get("value") {
+                                examples.push(ResponseExample { status, name: Some(name.clone()),
+                                    content_type: Some(content_type.clone()), body: Some(value.clone()) });
+                            }
+                        }
+                    } else if let Some(value) = media.get("example").or_else(|| media.pointer("/schema/example")) {
+                        examples.push(ResponseExample { status, name: None,
+                            content_type: Some(content_type.clone()), body: Some(value.clone()) });
+                    }
+                }
+            } else if let Some(Value::Object(content)) = response.get("examples") {
+                for (content_type, value) in content {
+                    examples.push(ResponseExample { status, name: None,
+                        content_type: Some(content_type.clone()), body: Some(value.clone()) });
+                }
+            } else if let Some(value) = response.pointer("/schema/example") {
+                examples.push(ResponseExample { status, name: None, content_type: Some(s!("application/json")),
+                    body: Some(value.clone()) });
+            }
+            if examples.len() == count {
+                examples.push(ResponseExample { status, name: None, content_type: None, body: None });
+            }
+        }
+    }
+    examples
+}
+
+fn example_response(example: &ResponseExample) -> Response {
+    let body = match example.body {
+        Some(Value::String(ref s)) if !is_json(&example.content_type) => OptionalBody::Present(s.as_bytes().into()),
+        Some(ref value) => OptionalBody::Present(value.to_string().into_bytes()),
+        None => OptionalBody::Missing
+    };
+    Response {
+        status: example.status,
+        headers: example.content_type.as_ref().map(|content_type| hashmap!{
+            s!("Content-Type") => vec![ content_type.clone() ]
+        }),
+        body,
+        .. Response::default_response()
+    }
+}
+
+fn is_json(content_type: &Option<String>) -> bool {
+    content_type.as_ref().map(|content_type| content_type.contains("json")).unwrap_or(false)
+}
+
+/// Returns an example value for the body of a request or response, parsing JSON bodies
+fn body_example(part: &dyn HttpPart) -> Option<(String, Value)> {
+    match part.body() {
+        OptionalBody::Present(body) => {
+            let content_type = part.content_type();
+            let example = if content_type.contains("json") {
+                ::serde_json::from_slice(body).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).to_string()))
+            } else {
+                Value::String(String::from_utf8_lossy(body).to_string())
+            };
+            Some((content_type, example))
+        },
+        _ => None
+    }
+}
+
+fn add_example(content: &mut Map<String, Value>, content_type: String, description: &str, example: Value) {
+    let media = content.entry(content_type).or_insert_with(|| json!({ "examples": {} }));
+    if let Some(Value::Object(examples)) = media.get_mut("examples") {
+        examples.entry(description.to_string()).or_insert_with(|| json!({ "value": example }));
+    }
+}
+
+/// Creates an OpenAPI 3 document describing the paths, methods and example bodies of the interactions in the pacts
+pub fn openapi_from_pacts(pacts: &[Pact]) -> Value {
+    let mut paths = Map::new();
+    for pact in pacts {
+        for interaction in &pact.interactions {
+            let path_item = paths.entry(interaction.request.path.clone()).or_insert_with(|| json!({}));
+            let operation = path_item.as_object_mut().unwrap()
+                .entry(interaction.request.method.to_lowercase())
+                .or_insert_with(|| json!({ "summary": interaction.description, "responses": {} }));
+
+            if let Some(ref query) = interaction.request.query {
+                let parameters = operation.as_object_mut().unwrap().entry("parameters").or_insert_with(|| json!([]));
+                let parameters = parameters.as_array_mut().unwrap();
+                for (name, values) in query {
+                    if !parameters.iter().any(|parameter| parameter["name"] == json!(name)) {
+                        parameters.push(json!({ "name": name, "in": "query", "example": values.join(",") }));
+                    }
+                }
+            }
+
+            if let Some((content_type, example)) = body_example(&interaction.request) {
+                let request_body = operation.as_object_mut().unwrap().entry("requestBody")
+                    .or_insert_with(|| json!({ "content": {} }));
+                if let Some(Value::Object(content)) = request_body.get_mut("content") {
+                    add_example(content, content_type, &interaction.description, example);
+                }
+            }
+
+            let response = operation["responses"].as_object_mut().unwrap()
+                .entry(interaction.response.status.to_string())
+                .or_insert_with(|| json!({ "description": interaction.description }));
+            if let Some((content_type, example)) = body_example(&interaction.response) {
+                let content = response.as_object_mut().unwrap().entry("content").or_insert_with(|| json!({}));
+                if let Value::Object(content) = content {
+                    add_example(content, content_type, &interaction.description, example);
+                }
+            }
+        }
+    }
+
+    let providers = pacts.iter().map(|pact| pact.provider.name.as_str()).unique().collect_vec();
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": if providers.is_empty() { s!("Pact Stub Server") } else { providers.join(", ") },
+            "version": "1.0.0",
+            "description": format!("Generated from {} pact(s)", pacts.len())
+        },
+        "paths": paths
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::HttpPart;
+    use crate::matching::{match_request, MatchingOptions};
+    use super::*;
+
+    fn spec() -> Value {
+        json!({
+            "openapi": "3.0.0",
+            "info": { "title": "Pet Store", "version": "1.0" },
+            "paths": {
+                "/pets/{petId}": {
+                    "parameters": [ { "name": "petId", "in": "path", "required": true, "example": "42" } ],
+                    "get": {
+                        "summary": "Get a pet",
+                        "responses": {
+                            "404": { "description": "Not found" },
+                            "200": {
+                                "description": "A pet",
+                                "content": {
+                                    "application/json": {
+                                        "examples": {
+                                            "cat": { "$ref": "#/components/examples/cat" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                "/pets": {
+                    "get": {
+                        "operationId": "listPets",
+                        "parameters": [
+                            { "name": "limit", "in": "query", "required": true, "schema": { "example": 10 } },
+                            { "name": "offset", "in": "query" }
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "Pets",
+                                "content": { "text/plain": { "example": "cat, dog" } }
+                            }
+                        }
+                    }
+                }
+            },
+            "components": {
+                "examples": {
+                    "cat": { "value": { "id": 42, "name": "Tom" } }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn converts_the_operation_examples_into_interactions() {
+        let pact = pact_from_openapi(&spec(), "pets").unwrap();
+        expect!(pact.consumer.name.as_str()).to(be_equal_to("openapi"));
+        expect!(pact.provider.name.as_str()).to(be_equal_to("Pet Store"));
+        expect!(pact.interactions.len()).to(be_equal_to(3));
+
+        let list = pact.interactions.iter().find(|i| i.description == "listPets - 200").unwrap();
+        expect!(list.provider_states.is_empty()).to(be_true());
+        expect!(list.response.content_type()).to(be_equal_to("text/plain"));
+        expect!(list.response.body.str_value()).to(be_equal_to("cat, dog"));
+
+        let pet = pact.interactions.iter().find(|i| i.description == "Get a pet - 200 (cat)").unwrap();
+        expect!(pet.provider_states.is_empty()).to(be_true());
+        expect!(pet.request.path.as_str()).to(be_equal_to("/pets/42"));
+        expect!(pet.response.body.str_value()).to(be_equal_to("{\"id\":42,\"name\":\"Tom\"}"));
+
+        let not_found = pact.interactions.iter().find(|i| i.description == "Get a pet - 404").unwrap();
+        expect!(not_found.provider_states[0].name.as_str()).to(be_equal_to("GET 404"));
+        expect!(not_found.response.status).to(be_equal_to(404));
+        expect!(not_found.response.body.is_present()).to(be_false());
+    }
+
+    #[test]
+    fn requests_match_any_path_parameter_and_required_query_parameter_values() {
+        let pact = pact_from_openapi(&spec(), "pets").unwrap();
+        let pet = pact.interactions.iter().find(|i| i.description == "Get a pet - 200 (cat)").unwrap();
+        let list = pact.interactions.iter().find(|i| i.description == "listPets - 200").unwrap();
+
+        let request = Request { path: s!("/pets/7"), .. Request::default_request() };
+        expect!(match_request(&pet.request, &request, &MatchingOptions::default()).is_empty()).to(be_true());
+        let request = Request { path: s!("/pets/7/toys"), .. Request::default_request() };
+        expect!(match_request(&pet.request, &request, &MatchingOptions::default()).is_empty()).to(be_false());
+
+        let request = Request {
+            path: s!("/pets"),
+            query: Some(hashmap!{ s!("limit") => vec![ s!("5") ] }),
+            .. Request::default_request()
+        };
+        expect!(match_request(&list.request, &request, &MatchingOptions::default()).is_empty()).to(be_true());
+    }
+
+    #[test]
+    fn leaves_references_that_refer_back_to_themselves_unresolved() {
+        let spec = json!({
+            "openapi": "3.0.0",
+            "paths": {
+                "/pets": {
+                    "get": {
+                        "operationId": "listPets",
+                        "responses": {
+                            "200": { "$ref": "#/components/responses/pets" },
+                            "404": { "$ref": "#/components/responses/missing" }
+                        }
+                    }
+                }
+            },
+            "components": {
+                "responses": {
+                    "pets": { "$ref": "#/components/responses/pets" },
+                    "missing": { "$ref": "#/components/responses/gone" },
+                    "gone": { "$ref": "#/components/responses/missing" }
+                }
+            }
+        });
+        let pact = pact_from_openapi(&spec, "pets").unwrap();
+        expect!(pact.interactions.iter().map(|i| i.description.clone()).collect::<Vec<String>>())
+            .to(be_equal_to(vec![ s!("listPets - 200"), s!("listPets - 404") ]));
+    }
+
+    #[test]
+    fn rejects_documents_that_are_not_openapi_specifications() {
+        expect!(pact_from_openapi(&json!({ "consumer": { "name": "c" } }), "pact")).to(be_err());
+        expect!(pact_from_openapi(&json!({ "openapi": "3.0.0" }), "api")).to(be_err());
+    }
+
+    #[test]
+    fn exports_the_interactions_as_an_openapi_document() {
+        let pact = Pact {
+            provider: Provider { name: s!("Pet Store") },
+            interactions: vec![
+                Interaction {
+                    description: s!("create a pet"),
+                    request: Request {
+                        method: s!("POST"),
+                        path: s!("/pets"),
+                        query: Some(hashmap!{ s!("notify") => vec![ s!("true") ] }),
+                        headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+                        body: OptionalBody::Present("{\"name\":\"Tom\"}".as_bytes().into()),
+                        .. Request::default_request()
+                    },
+                    response: Response { status: 201, .. Response::default_response() },
+                    .. Interaction::default()
+                },
+                Interaction {
+                    description: s!("list the pets"),
+                    request: Request { path: s!("/pets"), .. Request::default_request() },
+                    response: Response {
+                        headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("text/plain") ] }),
+                        body: OptionalBody::Present("Tom".as_bytes().into()),
+                        .. Response::default_response()
+                    },
+                    .. Interaction::default()
+                }
+            ],
+            .. Pact::default()
+        };
+
+        let spec = openapi_from_pacts(&[ pact ]);
+        expect!(spec.pointer("/info/title")).to(be_some().value(&json!("Pet Store")));
+        expect!(spec.pointer("/paths/~1pets/post/parameters/0/name")).to(be_some().value(&json!("notify")));
+        expect!(spec.pointer("/paths/~1pets/post/requestBody/content/application~1json/examples/create a pet/value"))
+            .to(be_some().value(&json!({ "name": "Tom" })));
+        expect!(spec.pointer("/paths/~1pets/post/responses/201/description")).to(be_some().value(&json!("create a pet")));
+        expect!(spec.pointer("/paths/~1pets/get/responses/200/content/text~1plain/examples/list the pets/value"))
+            .to(be_some().value(&json!("Tom")));
+
+        let imported = pact_from_openapi(&spec, "pets").unwrap();
+        expect!(imported.interactions.len()).to(be_equal_to(2));
+    }
+}
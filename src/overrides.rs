@@ -0,0 +1,105 @@
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+/// Settings for an interaction that are not part of the pact
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InteractionOverride {
+    /// The interaction is a placeholder for an endpoint that is not implemented yet
+    pub draft: bool,
+    /// Priority of the interaction when several match a request equally well, replacing any in the pact metadata
+    pub priority: Option<i32>
+}
+
+/// Settings for interactions, keyed by the interaction description, that are loaded from a separate file so they can
+/// be applied to pacts that can not be changed (like the ones from a pact broker)
+#[derive(Debug, Clone, Default)]
+pub struct InteractionOverrides {
+    overrides: HashMap<String, InteractionOverride>
+}
+
+impl InteractionOverrides {
+    /// Loads the overrides from a JSON file, in the form `{ "interaction description": { "draft": true, "priority": 10 } }`
+    pub fn load(path: &Path) -> Result<InteractionOverrides, String> {
+        fs::read_to_string(path)
+            .map_err(|err| format!("Could not read the overrides file '{}' - {}", path.display(), err))
+            .and_then(|contents| serde_json::from_str(&contents)
+                .map_err(|err| format!("Could not parse the overrides file '{}' - {}", path.display(), err)))
+            .and_then(|json| InteractionOverrides::from_json(&json))
+    }
+
+    /// Creates the overrides from a JSON object mapping interaction descriptions to their settings
+    pub fn from_json(json: &Value) -> Result<InteractionOverrides, String> {
+        match json {
+            Value::Object(map) => {
+                let mut overrides = HashMap::new();
+                for (description, settings) in map {
+                    let draft = match settings.get("draft") {
+                        Some(Value::Bool(draft)) => *draft,
+                        Some(_) => return Err(format!("'draft' for interaction '{}' must be true or false", description)),
+                        None => false
+                    };
+                    let priority = match settings.get("priority") {
+                        Some(Value::Number(priority)) => Some(priority.as_i64().and_then(|priority| i32::try_from(priority).ok())
+                            .ok_or_else(|| format!("'priority' for interaction '{}' must be a whole number", description))?),
+                        Some(_) => return Err(format!("'priority' for interaction '{}' must be a whole number", description)),
+                        None => None
+                    };
+                    if !settings.is_object() {
+                        return Err(format!("Overrides for interaction '{}' must be a JSON object", description));
+                    }
+                    overrides.insert(description.clone(), InteractionOverride { draft, priority });
+                }
+                Ok(InteractionOverrides { overrides })
+            },
+            _ => Err(s!("Overrides must be a JSON object mapping interaction descriptions to their settings"))
+        }
+    }
+
+    /// Returns the overrides for the interaction with the given description
+    pub fn get(&self, description: &str) -> Option<&InteractionOverride> {
+        self.overrides.get(description)
+    }
+
+    /// Returns the priority given to the interaction with the given description, if it has one
+    pub fn priority(&self, description: &str) -> Option<i32> {
+        self.get(description).and_then(|settings| settings.priority)
+    }
+
+    /// If the interaction with the given description is marked as a draft
+    pub fn is_draft(&self, description: &str) -> bool {
+        self.get(description).map(|settings| settings.draft).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn loads_the_overrides_for_interactions() {
+        let overrides = InteractionOverrides::from_json(&json!({
+            "a new endpoint": { "draft": true },
+            "an existing endpoint": {},
+            "a preferred endpoint": { "priority": -5 }
+        })).unwrap();
+
+        expect!(overrides.is_draft("a new endpoint")).to(be_true());
+        expect!(overrides.is_draft("an existing endpoint")).to(be_false());
+        expect!(overrides.is_draft("an unknown endpoint")).to(be_false());
+        expect!(overrides.priority("a preferred endpoint")).to(be_some().value(-5));
+        expect!(overrides.priority("a new endpoint")).to(be_none());
+    }
+
+    #[test]
+    fn rejects_invalid_overrides() {
+        expect!(InteractionOverrides::from_json(&json!([]))).to(be_err());
+        expect!(InteractionOverrides::from_json(&json!({ "interaction": true }))).to(be_err());
+        expect!(InteractionOverrides::from_json(&json!({ "interaction": { "draft": "yes" } }))).to(be_err());
+        expect!(InteractionOverrides::from_json(&json!({ "interaction": { "priority": 1.5 } }))).to(be_err());
+        expect!(InteractionOverrides::from_json(&json!({ "interaction": { "priority": "high" } }))).to(be_err());
+    }
+}
@@ -1,11 +1,31 @@
-use http::{HeaderMap, Uri};
+use http::{HeaderMap, StatusCode, Uri};
 use http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE};
-use http::header::HeaderValue;
+use http::header::{HeaderName, HeaderValue};
 use http::request::Parts;
-use hyper::{Body, Response as HyperResponse};
-use pact_matching::models::{HttpPart, OptionalBody, Request, Response};
+use http::response::Parts as ResponseParts;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::Response as HyperResponse;
+use hyper_tls::HttpsConnector;
+use native_tls::{Certificate, TlsConnector};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use http::header::CONTENT_ENCODING;
+use crate::fetch_proxy::{FetchProxy, ProxyConnector};
+use crate::cache::PactCache;
+use crate::generators::{self, PactStateGenerators};
+use crate::retry::Retry;
+use pact_matching::models::{HttpPart, OptionalBody, Pact, Request, Response};
 use pact_matching::models::parse_query_string;
+use itertools::Itertools;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str;
+
+/// Response headers from the pact that are not sent, as they depend on how the body is sent
+static IGNORED_RESPONSE_HEADERS: [&str; 2] = [ "content-length", "transfer-encoding" ];
 
 fn extract_query_string(uri: &Uri) -> Option<HashMap<String, Vec<String>>> {
     match uri.query() {
@@ -14,7 +34,7 @@ fn extract_query_string(uri: &Uri) -> Option<HashMap<String, Vec<String>>> {
     }
 }
 
-fn extract_headers(headers: &HeaderMap<HeaderValue>) -> Option<HashMap<String, Vec<String>>> {
+pub fn extract_headers(headers: &HeaderMap<HeaderValue>) -> Option<HashMap<String, Vec<String>>> {
   if !headers.is_empty() {
     let result: HashMap<String, Vec<String>> = headers.keys()
       .map(|name| {
@@ -22,15 +42,13 @@ fn extract_headers(headers: &HeaderMap<HeaderValue>) -> Option<HashMap<String, V
         let parsed_vals: Vec<Result<String, ()>> = values.iter()
           .map(|val| val.to_str()
             .map(|v| v.to_string())
-            .map_err(|err| {
-              warn!("Failed to parse HTTP header value: {}", err);
-              ()
-            })
+            .map_err(|err| warn!("Failed to parse HTTP header value: {}", err))
           ).collect();
-        ((name.as_str().into(), parsed_vals.iter().cloned()
+        (name.as_str().into(), parsed_vals.iter()
           .filter(|val| val.is_ok())
+          .cloned()
           .map(|val| val.unwrap_or_default())
-          .collect()))
+          .collect())
       })
       .collect();
     Some(result)
@@ -39,7 +57,62 @@ fn extract_headers(headers: &HeaderMap<HeaderValue>) -> Option<HashMap<String, V
   }
 }
 
-pub fn hyper_request_to_pact_request(req: Parts, body: OptionalBody) -> Request {
+/// The bytes of the body, without copying them like `OptionalBody::value` does. Missing and empty bodies have no bytes.
+pub fn body_bytes(body: &OptionalBody) -> &[u8] {
+    match body {
+        OptionalBody::Present(body) => body,
+        _ => &[]
+    }
+}
+
+/// The body, which is moved into the pact body without copying it if it is not shared (like `Bytes` read from a request)
+pub fn optional_body<B: Into<Vec<u8>>>(body: B) -> OptionalBody {
+    let body = body.into();
+    if body.is_empty() {
+        OptionalBody::Empty
+    } else {
+        OptionalBody::Present(body)
+    }
+}
+
+fn decompress<R: Read>(decoder: R, max_size: usize) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![];
+    decoder.take((max_size as u64).saturating_add(1)).read_to_end(&mut buffer)
+        .map(|_| buffer).map_err(|err| err.to_string())
+}
+
+/// Decompresses a request body sent with a gzip or deflate Content-Encoding, so it can be matched against the
+/// interactions. The Content-Encoding header is removed if the body is decompressed. Decompressing stops once the body
+/// is larger than the maximum size, and an error is returned.
+pub fn decode_request_body(headers: &mut HeaderMap<HeaderValue>, body: OptionalBody, max_size: Option<usize>) -> Result<OptionalBody, String> {
+    let max_size = max_size.unwrap_or(usize::MAX);
+    let encoding = headers.get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_lowercase());
+    let decoded = match (encoding, &body) {
+        (Some(ref encoding), OptionalBody::Present(bytes)) if encoding == "gzip" || encoding == "x-gzip" =>
+            Some(decompress(GzDecoder::new(bytes.as_slice()), max_size)),
+        (Some(ref encoding), OptionalBody::Present(bytes)) if encoding == "deflate" =>
+            Some(decompress(ZlibDecoder::new(bytes.as_slice()), max_size)
+                .or_else(|_| decompress(DeflateDecoder::new(bytes.as_slice()), max_size))),
+        _ => None
+    };
+    match decoded {
+        Some(Ok(ref bytes)) if bytes.len() > max_size =>
+            Err(format!("is larger than the maximum of {} bytes once it is decompressed", max_size)),
+        Some(Ok(bytes)) => {
+            headers.remove(CONTENT_ENCODING);
+            Ok(optional_body(bytes))
+        },
+        Some(Err(err)) => {
+            warn!("Could not decompress the request body, matching it as it was received - {}", err);
+            Ok(body)
+        },
+        None => Ok(body)
+    }
+}
+
+pub fn hyper_request_to_pact_request(req: &Parts, body: OptionalBody) -> Request {
     Request {
         method: req.method.to_string(),
         path: req.uri.path().to_string(),
@@ -50,40 +123,270 @@ pub fn hyper_request_to_pact_request(req: Parts, body: OptionalBody) -> Request
     }
 }
 
-pub fn pact_response_to_hyper_response(response: &Response) -> HyperResponse<Body> {
+pub fn hyper_response_to_pact_response(res: ResponseParts, body: OptionalBody) -> Response {
+    Response {
+        status: res.status.as_u16(),
+        headers: extract_headers(&res.headers),
+        body,
+        .. Response::default_response()
+    }
+}
+
+pub fn add_header(response: &mut Response, name: &str, value: &str) {
+    response.headers.get_or_insert_with(HashMap::new)
+        .entry(name.to_string())
+        .or_default()
+        .push(value.to_string());
+}
+
+/// If the content type is for a binary format, like an image, PDF or protobuf
+pub fn is_binary_content_type(content_type: &str) -> bool {
+    let content_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    !(content_type.is_empty() || content_type.starts_with("text/") || content_type.starts_with("multipart/") ||
+        content_type.contains("json") || content_type.contains("xml") || content_type.contains("javascript") ||
+        content_type == "application/x-www-form-urlencoded")
+}
+
+/// Formats a body for logging, without assuming that it is text
+pub fn body_for_log(body: &OptionalBody) -> String {
+    match body {
+        OptionalBody::Present(bytes) => match str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => format!("<{} bytes of binary data>", bytes.len())
+        },
+        _ => String::new()
+    }
+}
+
+/// Formats the body of the request or response for logging in full, with JSON bodies pretty printed
+pub fn body_for_display(part: &dyn HttpPart) -> String {
+    match part.body() {
+        OptionalBody::Present(bytes) if part.content_type().contains("json") => serde_json::from_slice::<Value>(bytes)
+            .ok()
+            .and_then(|json| serde_json::to_string_pretty(&json).ok())
+            .unwrap_or_else(|| body_for_log(part.body())),
+        OptionalBody::Present(_) => body_for_log(part.body()),
+        OptionalBody::Null => s!("null"),
+        _ => s!("<no body>")
+    }
+}
+
+/// Returns the body as JSON if it is a JSON body, otherwise as a string
+pub fn body_json(part: &dyn HttpPart) -> Value {
+    match part.body() {
+        OptionalBody::Present(bytes) => {
+            let text = String::from_utf8_lossy(bytes).to_string();
+            if part.content_type().contains("json") {
+                serde_json::from_str(&text).unwrap_or(Value::String(text))
+            } else {
+                Value::String(text)
+            }
+        },
+        _ => Value::Null
+    }
+}
+
+/// Returns the headers or query parameters as a JSON object, with the values of each one joined by commas
+pub fn values_json(values: &Option<HashMap<String, Vec<String>>>, lowercase: bool) -> Value {
+    Value::Object(values.iter().flat_map(|values| values.iter())
+        .map(|(key, values)| (if lowercase { key.to_lowercase() } else { key.clone() }, Value::String(values.iter().join(", "))))
+        .collect::<Map<String, Value>>())
+}
+
+/// Returns the request as a JSON object, for the scripts and templates that use its values
+pub fn request_json(request: &Request) -> Value {
+    json!({
+        "method": request.method,
+        "path": request.path,
+        "query": values_json(&request.query, false),
+        "headers": values_json(&request.headers, true),
+        "body": body_json(request)
+    })
+}
+
+/// A pact, with the provider state generators of its responses
+pub type LoadedPact = (Pact, PactStateGenerators);
+
+/// Loads a pact from its JSON, with the provider state generators of the responses (which are not loaded with the rest
+/// of the pact)
+pub fn pact_from_json(source: &str, pact_json: &Value) -> LoadedPact {
+    (Pact::from_json(&source.to_string(), pact_json), generators::provider_state_generators(pact_json))
+}
+
+/// A step in the path to a value in a JSON document that is being scanned
+enum JsonFrame {
+    /// In an object, after the key if one has been read
+    Object(Option<String>),
+    /// In an array, at the index
+    Array(usize)
+}
+
+/// The JSON path (like `$.interactions[3].request`) of the value being parsed at the line and column (from 1) of a
+/// JSON document, which is where the parser gave up for a document that is not valid JSON
+pub fn json_path_at(json: &str, line: usize, column: usize) -> String {
+    let start = json.split_inclusive('\n').take(line.saturating_sub(1)).map(str::len).sum::<usize>();
+    let end = (start + column.saturating_sub(1)).min(json.len());
+    let mut stack = vec![];
+    let mut expecting_key = false;
+    let mut chars = json.as_bytes()[..end].iter();
+    while let Some(ch) = chars.next() {
+        match ch {
+            b'{' => {
+                stack.push(JsonFrame::Object(None));
+                expecting_key = true;
+            },
+            b'[' => stack.push(JsonFrame::Array(0)),
+            b'}' | b']' => {
+                stack.pop();
+                expecting_key = false;
+            },
+            b',' => match stack.last_mut() {
+                Some(JsonFrame::Object(key)) => {
+                    *key = None;
+                    expecting_key = true;
+                },
+                Some(JsonFrame::Array(index)) => *index += 1,
+                None => ()
+            },
+            b'"' => {
+                let mut value = vec![];
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        b'\\' => if let Some(escaped) = chars.next() { value.push(*escaped) },
+                        b'"' => break,
+                        _ => value.push(*ch)
+                    }
+                }
+                if expecting_key {
+                    if let Some(JsonFrame::Object(key)) = stack.last_mut() {
+                        *key = Some(String::from_utf8_lossy(&value).to_string());
+                    }
+                    expecting_key = false;
+                }
+            },
+            _ => ()
+        }
+    }
+    stack.iter().fold(s!("$"), |path, frame| match frame {
+        JsonFrame::Object(Some(key)) if !key.is_empty() && key.chars().all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-') =>
+            format!("{}.{}", path, key),
+        JsonFrame::Object(Some(key)) => format!("{}['{}']", path, key),
+        JsonFrame::Object(None) => path,
+        JsonFrame::Array(index) => format!("{}[{}]", path, index)
+    })
+}
+
+/// Parses the JSON of a pact, with the JSON path (and line and column) of the problem if it is not valid JSON
+pub fn parse_pact_json(contents: &[u8]) -> Result<Value, String> {
+    serde_json::from_slice(contents).map_err(|err| {
+        if err.is_syntax() || err.is_eof() {
+            let path = json_path_at(&String::from_utf8_lossy(contents), err.line(), err.column());
+            format!("Failed to parse Pact JSON at {} - {}", path, err)
+        } else {
+            format!("Failed to parse Pact JSON - {}", err)
+        }
+    })
+}
+
+/// Reads a pact file, like `Pact::read_pact` but with the provider state generators of the responses
+pub fn read_pact(file: &Path) -> io::Result<LoadedPact> {
+    let mut contents = vec![];
+    File::open(file)?.read_to_end(&mut contents)?;
+    let pact_json = parse_pact_json(&contents).map_err(io::Error::other)?;
+    Ok(pact_from_json(&format!("{:?}", file), &pact_json))
+}
+
+/// Settings for fetching pacts from URLs, pact brokers and the other remote sources
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FetchOptions {
+    /// Disables the TLS certificate validation
+    pub insecure: bool,
+    /// CA certificate (in PEM format) to trust as well as the system ones, for servers with internal or self-signed
+    /// certificates
+    pub ca_cert: Option<PathBuf>,
+    /// HTTP proxy to connect through
+    pub proxy: Option<FetchProxy>,
+    /// How many times downloads are retried
+    pub retry: Retry,
+    /// Keep a copy of the downloads, to use if they fail
+    pub cache: Option<PactCache>
+}
+
+impl FetchOptions {
+    /// HTTPS connector with the settings
+    pub fn connector(&self) -> Result<HttpsConnector<ProxyConnector>, String> {
+        let mut tls = TlsConnector::builder();
+        if self.insecure {
+            warn!("Disabling TLS certificate validation");
+            tls.danger_accept_invalid_hostnames(true).danger_accept_invalid_certs(true);
+        } else if let Some(ref ca_cert) = self.ca_cert {
+            tls.add_root_certificate(read_ca_cert(ca_cert)?);
+        }
+        let tls = tls.build().map_err(|err| format!("Could not set up TLS - {}", err))?;
+        Ok(HttpsConnector::from((ProxyConnector::new(self.proxy.clone()), tls.into())))
+    }
+}
+
+/// Reads a CA certificate in PEM format
+pub fn read_ca_cert(file: &Path) -> Result<Certificate, String> {
+    let pem = std::fs::read(file).map_err(|err| format!("Could not read '{}' - {}", file.display(), err))?;
+    Certificate::from_pem(&pem).map_err(|err| format!("'{}' is not a PEM certificate - {}", file.display(), err))
+}
+
+/// Converts the response to a hyper response, moving its body into the hyper response without copying it
+pub fn pact_response_to_hyper_response(response: Response) -> HyperResponse<Full<Bytes>> {
     info!("<=== Sending {}", response);
-    debug!("     body: '{}'", response.body.str_value());
+    debug!("     body: '{}'", body_for_log(&response.body));
     debug!("     matching_rules: {:?}", response.matching_rules);
     debug!("     generators: {:?}", response.generators);
-    let mut res = HyperResponse::builder();
-    {
-        res.status(response.status);
-
-        match response.headers {
-          Some(ref headers) => {
-            for (k, v) in headers.clone() {
-              for val in v {
-                res.header(k.as_str(), val);
-              }
-            }
-          },
-          None => ()
-        }
+    let status = StatusCode::from_u16(response.status).unwrap_or_else(|_| {
+        warn!("Response status {} is not a valid status code, sending {}", response.status, StatusCode::INTERNAL_SERVER_ERROR);
+        StatusCode::INTERNAL_SERVER_ERROR
+    });
+    let mut res_headers = HeaderMap::new();
 
-        if !response.has_header(&ACCESS_CONTROL_ALLOW_ORIGIN.as_str().into()) {
-            res.header(ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+    if let Some(ref headers) = response.headers {
+      for (k, v) in headers {
+        if IGNORED_RESPONSE_HEADERS.contains(&k.to_lowercase().as_str()) {
+          continue;
+        }
+        let name = match HeaderName::from_bytes(k.as_bytes()) {
+          Ok(name) => name,
+          Err(_) => {
+            warn!("Response header name '{}' is not valid, leaving the header out", k);
+            continue;
+          }
+        };
+        for val in v {
+          match HeaderValue::from_str(val) {
+            Ok(value) => { res_headers.append(name.clone(), value); },
+            Err(_) => warn!("Value {:?} of response header '{}' is not valid, leaving it out", val, k)
+          }
         }
+      }
+    }
 
-        match response.body {
-            OptionalBody::Present(ref body) => {
-                if !response.has_header(&CONTENT_TYPE.as_str().into()) {
-                    res.header(CONTENT_TYPE, response.content_type());
-                }
-                res.body(Body::from(body.clone()))
-            },
-            _ => res.body(Body::empty())
-        }.unwrap()
+    if !response.has_header(&ACCESS_CONTROL_ALLOW_ORIGIN.as_str().into()) {
+        res_headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
     }
+
+    let content_type = match response.body {
+        OptionalBody::Present(_) if !response.has_header(&CONTENT_TYPE.as_str().into()) =>
+            HeaderValue::from_str(&response.content_type()).ok(),
+        _ => None
+    };
+    let mut res = match response.body {
+        OptionalBody::Present(body) => {
+            if let Some(content_type) = content_type {
+                res_headers.insert(CONTENT_TYPE, content_type);
+            }
+            HyperResponse::new(Full::new(Bytes::from(body)))
+        },
+        _ => HyperResponse::new(Full::default())
+    };
+    *res.status_mut() = status;
+    *res.headers_mut() = res_headers;
+    res
 }
 
 #[cfg(test)]
@@ -91,9 +394,21 @@ mod test {
     use expectest::prelude::*;
     use http::header::HeaderValue;
     use http::status::StatusCode;
-    use pact_matching::models::{OptionalBody, Response};
+    use http_body_util::BodyExt;
+    use pact_matching::models::{OptionalBody, Pact, Response};
     use super::*;
 
+    #[test]
+    fn reports_where_the_pact_json_is_invalid() {
+        let json = "{\n  \"consumer\": { \"name\": \"web\" },\n  \"interactions\": [\n    { \"description\": \"a\" },\n    { \"description\": \"b\", \"request\": { \"path\": \"/\" \"method\": \"GET\" } }\n  ]\n}";
+        expect!(json_path_at(json, 5, 52)).to(be_equal_to(s!("$.interactions[1].request.path")));
+        expect!(parse_pact_json(json.as_bytes())).to(be_err().value(
+            s!("Failed to parse Pact JSON at $.interactions[1].request.path - expected `,` or `}` at line 5 column 52")));
+        expect!(json_path_at("{ \"a b\": [ 1, { \"c\": tru", 1, 24)).to(be_equal_to(s!("$['a b'][1].c")));
+        expect!(parse_pact_json(b"{ \"consumer\": ")).to(be_err().value(
+            s!("Failed to parse Pact JSON at $.consumer - EOF while parsing a value at line 1 column 14")));
+    }
+
     #[test]
     fn test_response() {
         let response = Response {
@@ -101,7 +416,7 @@ mod test {
             headers: Some(hashmap! {  }),
             .. Response::default_response()
         };
-        let hyper_response = pact_response_to_hyper_response(&response);
+        let hyper_response = pact_response_to_hyper_response(response);
 
         expect!(hyper_response.status()).to(be_equal_to(StatusCode::CREATED));
         expect!(hyper_response.headers().len()).to(be_equal_to(1));
@@ -116,20 +431,37 @@ mod test {
             body: OptionalBody::Present("{\"a\": 1, \"b\": 4, \"c\": 6}".as_bytes().into()),
             .. Response::default_response()
         };
-        let hyper_response = pact_response_to_hyper_response(&response);
+        let hyper_response = pact_response_to_hyper_response(response);
 
         expect!(hyper_response.status()).to(be_equal_to(StatusCode::CREATED));
         expect!(hyper_response.headers().is_empty()).to(be_false());
         expect!(hyper_response.headers().get("content-type")).to(be_some().value(HeaderValue::from_static("text/dizzy")));
     }
 
+    #[test]
+    fn leaves_out_invalid_headers_instead_of_failing() {
+        let response = Response {
+            status: 1000,
+            headers: Some(hashmap! {
+                s!("Location") => vec![ s!("/orders/a\nb"), s!("/orders/1") ],
+                s!("Bad Name") => vec![ s!("value") ]
+            }),
+            .. Response::default_response()
+        };
+        let hyper_response = pact_response_to_hyper_response(response);
+
+        expect!(hyper_response.status()).to(be_equal_to(StatusCode::INTERNAL_SERVER_ERROR));
+        expect!(hyper_response.headers().get_all("Location").iter().collect::<Vec<_>>()).to(be_equal_to(vec![ "/orders/1" ]));
+        expect!(hyper_response.headers().len()).to(be_equal_to(2));
+    }
+
     #[test]
     fn adds_a_content_type_if_there_is_not_one_and_there_is_a_body() {
         let response = Response {
             body: OptionalBody::Present("{\"a\": 1, \"b\": 4, \"c\": 6}".as_bytes().into()),
             .. Response::default_response()
         };
-        let hyper_response = pact_response_to_hyper_response(&response);
+        let hyper_response = pact_response_to_hyper_response(response);
 
         expect!(hyper_response.headers().is_empty()).to(be_false());
         expect!(hyper_response.headers().get("content-type")).to(be_some().value(HeaderValue::from_static("application/json")));
@@ -141,9 +473,150 @@ mod test {
             headers: Some(hashmap! { s!("Access-Control-Allow-Origin") => vec![s!("dodgy.com")] }),
             .. Response::default_response()
         };
-        let hyper_response = pact_response_to_hyper_response(&response);
+        let hyper_response = pact_response_to_hyper_response(response);
 
         expect!(hyper_response.headers().len()).to(be_equal_to(1));
         expect!(hyper_response.headers().get("Access-Control-Allow-Origin")).to(be_some().value(HeaderValue::from_static("dodgy.com")));
     }
+
+    static BINARY_FIXTURE: [u8; 12] = [ 0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0xFF, 0xFE, 0x80 ];
+
+    #[test]
+    fn serves_binary_bodies_verbatim_with_the_correct_content_length() {
+        let response = Response {
+            headers: Some(hashmap! {
+                s!("Content-Type") => vec![s!("image/png")],
+                s!("Content-Length") => vec![s!("16")]
+            }),
+            body: OptionalBody::Present(BINARY_FIXTURE.to_vec()),
+            .. Response::default_response()
+        };
+        let hyper_response = pact_response_to_hyper_response(response.clone());
+
+        expect!(hyper_response.headers().get("content-length")).to(be_none());
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let body = runtime.block_on(hyper_response.into_body().collect()).unwrap().to_bytes();
+        expect!(body.to_vec()).to(be_equal_to(BINARY_FIXTURE.to_vec()));
+        expect!(body_for_log(&response.body)).to(be_equal_to("<12 bytes of binary data>"));
+    }
+
+    #[test]
+    fn moves_the_bodies_without_copying_them() {
+        let bytes = Bytes::from(BINARY_FIXTURE.to_vec());
+        let address = bytes.as_ptr();
+        let body = optional_body(bytes);
+        expect!(body_bytes(&body).as_ptr()).to(be_equal_to(address));
+        expect!(optional_body(Bytes::new())).to(be_equal_to(OptionalBody::Empty));
+        expect!(body_bytes(&OptionalBody::Missing).is_empty()).to(be_true());
+
+        let response = Response { body, .. Response::default_response() };
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let body = runtime.block_on(pact_response_to_hyper_response(response).into_body().collect()).unwrap().to_bytes();
+        expect!(body.as_ptr()).to(be_equal_to(address));
+    }
+
+    #[test]
+    fn pretty_prints_json_bodies_for_display() {
+        let json = Request {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present(br#"{"id":1,"tags":["a"]}"#.to_vec()),
+            .. Request::default_request()
+        };
+        expect!(body_for_display(&json)).to(be_equal_to("{\n  \"id\": 1,\n  \"tags\": [\n    \"a\"\n  ]\n}"));
+        let text = Request {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("text/plain") ] }),
+            body: OptionalBody::Present(br#"{"id":1}"#.to_vec()),
+            .. Request::default_request()
+        };
+        expect!(body_for_display(&text)).to(be_equal_to("{\"id\":1}"));
+        expect!(body_for_display(&Request::default_request())).to(be_equal_to("<no body>"));
+    }
+
+    #[test]
+    fn loads_base64_bodies_with_binary_content_types_as_bytes() {
+        let pact_json = json!({
+            "consumer": { "name": "consumer" },
+            "provider": { "name": "provider" },
+            "interactions": [
+                {
+                    "description": "download an image",
+                    "request": { "method": "GET", "path": "/image.png" },
+                    "response": {
+                        "status": 200,
+                        "headers": { "Content-Type": "image/png" },
+                        "body": base64::encode(&BINARY_FIXTURE[..])
+                    }
+                },
+                {
+                    "description": "get some text",
+                    "request": { "method": "GET", "path": "/text" },
+                    "response": {
+                        "status": 200,
+                        "headers": { "Content-Type": "text/plain" },
+                        "body": "aGVsbG8="
+                    }
+                }
+            ],
+            "metadata": { "pactSpecification": { "version": "3.0.0" } }
+        });
+        let pact = Pact::from_json(&s!("test"), &pact_json);
+
+        expect!(pact.interactions[0].response.body.value()).to(be_equal_to(BINARY_FIXTURE.to_vec()));
+        expect!(pact.interactions[1].response.body.str_value()).to(be_equal_to("aGVsbG8="));
+        expect!(is_binary_content_type("application/octet-stream")).to(be_true());
+        expect!(is_binary_content_type("application/pdf")).to(be_true());
+        expect!(is_binary_content_type("application/vnd.api+json; charset=utf-8")).to(be_false());
+        expect!(is_binary_content_type("text/html")).to(be_false());
+    }
+
+    #[test]
+    fn decompresses_gzip_and_deflate_request_bodies() {
+        use flate2::Compression;
+        use flate2::write::{GzEncoder, ZlibEncoder};
+        use std::io::Write;
+
+        let json = b"{\"sku\": \"1234\"}";
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(json).unwrap();
+        let mut deflate = ZlibEncoder::new(vec![], Compression::default());
+        deflate.write_all(json).unwrap();
+        let headers_for = |encoding: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+            headers
+        };
+
+        let mut headers = headers_for("gzip");
+        let body = decode_request_body(&mut headers, OptionalBody::Present(gzip.finish().unwrap()), None).unwrap();
+        expect!(body.value()).to(be_equal_to(json.to_vec()));
+        expect!(headers.get(CONTENT_ENCODING)).to(be_none());
+
+        let mut headers = headers_for("deflate");
+        let body = decode_request_body(&mut headers, OptionalBody::Present(deflate.finish().unwrap()), None).unwrap();
+        expect!(body.value()).to(be_equal_to(json.to_vec()));
+
+        let mut headers = headers_for("gzip");
+        let body = decode_request_body(&mut headers, OptionalBody::Present(json.to_vec()), None).unwrap();
+        expect!(body.value()).to(be_equal_to(json.to_vec()));
+        expect!(headers.get(CONTENT_ENCODING)).to(be_some());
+    }
+
+    #[test]
+    fn stops_decompressing_request_bodies_that_are_too_large() {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut gzip = GzEncoder::new(vec![], Compression::best());
+        gzip.write_all(&vec![0; 1024 * 1024]).unwrap();
+        let compressed = gzip.finish().unwrap();
+        expect!(compressed.len() < 4096).to(be_true());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        expect!(decode_request_body(&mut headers, OptionalBody::Present(compressed.clone()), Some(4096))).to(be_err());
+        expect!(headers.get(CONTENT_ENCODING)).to(be_some());
+        let body = decode_request_body(&mut headers, OptionalBody::Present(compressed), Some(1024 * 1024)).unwrap();
+        expect!(body.value().len()).to(be_equal_to(1024 * 1024));
+    }
 }
@@ -0,0 +1,263 @@
+//! Plugins are dynamic libraries that match and generate the bodies of content types the stub server does not
+//! support, like proprietary binary formats. They export these C functions:
+//!
+//! * `uint32_t pact_stub_plugin_abi_version()` - the version of this interface the plugin implements
+//! * `const char *pact_stub_plugin_manifest()` - a JSON document with the `name` of the plugin and the `contentTypes`
+//!   it handles
+//! * `char *pact_stub_plugin_match_body(content_type, expected, expected_len, actual, actual_len)` (optional) - returns
+//!   NULL if the actual body matches the expected one, otherwise a description of the mismatch
+//! * `uint8_t *pact_stub_plugin_generate_body(content_type, body, body_len, request, request_len, size_t *out_len)`
+//!   (optional) - returns the response body to send instead of the one from the interaction, or NULL to send it as is
+//! * `void pact_stub_plugin_free(void *ptr, size_t len)` - frees a value returned by the plugin, with its length (not
+//!   counting the terminator of a string)
+
+use libloading::Library;
+use pact_matching::Mismatch;
+use pact_matching::models::{HttpPart, OptionalBody, Request, Response};
+use crate::pact_support;
+use serde_json::Value;
+use std::env::consts::DLL_EXTENSION;
+use std::ffi::{CStr, CString};
+use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::slice;
+
+/// Version of the plugin interface. Plugins built for another version are not loaded.
+pub const ABI_VERSION: u32 = 1;
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type ManifestFn = unsafe extern "C" fn() -> *const c_char;
+type MatchBodyFn = unsafe extern "C" fn(*const c_char, *const u8, usize, *const u8, usize) -> *mut c_char;
+type GenerateBodyFn = unsafe extern "C" fn(*const c_char, *const u8, usize, *const u8, usize, *mut usize) -> *mut u8;
+type FreeFn = unsafe extern "C" fn(*mut c_void, usize);
+
+/// A loaded plugin
+pub struct Plugin {
+    name: String,
+    content_types: Vec<String>,
+    match_body: Option<MatchBodyFn>,
+    generate_body: Option<GenerateBodyFn>,
+    free: FreeFn,
+    // the library has to stay loaded for as long as its functions can be called
+    _library: Option<Library>
+}
+
+impl Plugin {
+    fn new(manifest: &str, match_body: Option<MatchBodyFn>, generate_body: Option<GenerateBodyFn>, free: FreeFn,
+           library: Option<Library>) -> Result<Plugin, String> {
+        let manifest: Value = serde_json::from_str(manifest).map_err(|err| format!("Manifest is not valid JSON - {}", err))?;
+        let name = manifest.get("name").and_then(Value::as_str)
+            .ok_or_else(|| s!("Manifest does not have a name"))?
+            .to_string();
+        let content_types = manifest.get("contentTypes").and_then(Value::as_array)
+            .map(|types| types.iter().filter_map(Value::as_str).map(|content_type| content_type.to_lowercase()).collect::<Vec<String>>())
+            .unwrap_or_default();
+        if content_types.is_empty() {
+            return Err(format!("Plugin '{}' does not handle any content types", name));
+        }
+        Ok(Plugin { name, content_types, match_body, generate_body, free, _library: library })
+    }
+
+    /// Loads a plugin from a dynamic library
+    pub fn load(path: &Path) -> Result<Plugin, String> {
+        let library = unsafe { Library::new(path) }.map_err(|err| err.to_string())?;
+        let (manifest, match_body, generate_body, free) = unsafe {
+            let version = library.get::<AbiVersionFn>(b"pact_stub_plugin_abi_version\0")
+                .map_err(|_| s!("It is not a stub server plugin"))?;
+            let version = version();
+            if version != ABI_VERSION {
+                return Err(format!("It implements version {} of the plugin interface, but version {} is required",
+                                   version, ABI_VERSION));
+            }
+            let manifest = library.get::<ManifestFn>(b"pact_stub_plugin_manifest\0").map_err(|err| err.to_string())?;
+            let manifest = manifest();
+            if manifest.is_null() {
+                return Err(s!("It does not have a manifest"));
+            }
+            (
+                CStr::from_ptr(manifest).to_string_lossy().to_string(),
+                library.get::<MatchBodyFn>(b"pact_stub_plugin_match_body\0").ok().map(|function| *function),
+                library.get::<GenerateBodyFn>(b"pact_stub_plugin_generate_body\0").ok().map(|function| *function),
+                *library.get::<FreeFn>(b"pact_stub_plugin_free\0").map_err(|err| err.to_string())?
+            )
+        };
+        Plugin::new(&manifest, match_body, generate_body, free, Some(library))
+    }
+
+    /// If the plugin handles bodies of the content type
+    pub fn handles(&self, content_type: &str) -> bool {
+        let content_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        self.content_types.contains(&content_type)
+    }
+
+    /// Matches the body of the actual request against the expected one
+    pub fn match_body(&self, expected: &Request, actual: &Request, mismatches: &mut Vec<Mismatch>) {
+        let match_body = match self.match_body {
+            Some(match_body) => match_body,
+            None => return
+        };
+        if !expected.body.is_present() {
+            return;
+        }
+        let content_type = CString::new(expected.content_type()).unwrap_or_default();
+        let (expected_body, actual_body) = (pact_support::body_bytes(&expected.body), pact_support::body_bytes(&actual.body));
+        let result = unsafe {
+            match_body(content_type.as_ptr(), expected_body.as_ptr(), expected_body.len(), actual_body.as_ptr(),
+                       actual_body.len())
+        };
+        if !result.is_null() {
+            let mismatch = unsafe {
+                let message = CStr::from_ptr(result);
+                let mismatch = message.to_string_lossy().to_string();
+                (self.free)(result as *mut c_void, message.to_bytes().len());
+                mismatch
+            };
+            mismatches.push(Mismatch::BodyMismatch {
+                path: s!("$"),
+                expected: Some(expected_body.to_vec()),
+                actual: Some(actual_body.to_vec()),
+                mismatch: format!("{} (from plugin '{}')", mismatch, self.name)
+            });
+        }
+    }
+
+    /// Returns the body to respond with instead of the body from the interaction, if the plugin generates one
+    pub fn generate_body(&self, content_type: &str, body: &[u8], request: &Request) -> Option<Vec<u8>> {
+        let generate_body = self.generate_body?;
+        let content_type = CString::new(content_type).unwrap_or_default();
+        let request_body = pact_support::body_bytes(&request.body);
+        let mut len = 0;
+        unsafe {
+            let result = generate_body(content_type.as_ptr(), body.as_ptr(), body.len(), request_body.as_ptr(),
+                                       request_body.len(), &mut len);
+            if result.is_null() {
+                None
+            } else {
+                let generated = slice::from_raw_parts(result, len).to_vec();
+                (self.free)(result as *mut c_void, len);
+                Some(generated)
+            }
+        }
+    }
+}
+
+impl Debug for Plugin {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Plugin {{ name: {:?}, content_types: {:?} }}", self.name, self.content_types)
+    }
+}
+
+/// The loaded plugins
+#[derive(Debug, Default)]
+pub struct Plugins {
+    plugins: Vec<Plugin>
+}
+
+impl Plugins {
+    /// Loads the dynamic libraries (with the extension used by the platform, like `.so`) in the directory as plugins
+    pub fn load_dir(dir: &Path) -> Result<Plugins, String> {
+        let mut paths = fs::read_dir(dir)
+            .map_err(|err| format!("Could not read the plugin directory '{}' - {}", dir.display(), err))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().map(|ext| ext == DLL_EXTENSION).unwrap_or(false))
+            .collect::<Vec<_>>();
+        paths.sort();
+        let plugins = paths.iter()
+            .map(|path| Plugin::load(path).map_err(|err| format!("Could not load plugin '{}' - {}", path.display(), err)))
+            .collect::<Result<Vec<Plugin>, String>>()?;
+        Ok(Plugins { plugins })
+    }
+
+    /// The loaded plugins
+    pub fn plugins(&self) -> &[Plugin] {
+        &self.plugins
+    }
+
+    /// The first plugin that can match bodies of the content type
+    pub fn matcher_for(&self, content_type: &str) -> Option<&Plugin> {
+        self.plugins.iter().find(|plugin| plugin.match_body.is_some() && plugin.handles(content_type))
+    }
+
+    /// Replaces the body of the response with one generated by the first plugin that can generate bodies of its
+    /// content type
+    pub fn generate_response(&self, request: &Request, mut response: Response) -> Response {
+        if !response.body.is_present() {
+            return response;
+        }
+        let content_type = response.content_type();
+        let plugin = self.plugins.iter().find(|plugin| plugin.generate_body.is_some() && plugin.handles(&content_type));
+        if let Some(plugin) = plugin {
+            if let Some(body) = plugin.generate_body(&content_type, pact_support::body_bytes(&response.body), request) {
+                response.body = OptionalBody::Present(body);
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Request, Response};
+    use std::ptr;
+    use super::*;
+
+    // a plugin that matches bodies of the same length, and responds with the request body
+    unsafe extern "C" fn same_length(_: *const c_char, _: *const u8, expected_len: usize, _: *const u8, actual_len: usize) -> *mut c_char {
+        if expected_len == actual_len {
+            ptr::null_mut()
+        } else {
+            CString::new(format!("expected {} bytes but got {}", expected_len, actual_len)).unwrap().into_raw()
+        }
+    }
+
+    unsafe extern "C" fn echo(_: *const c_char, _: *const u8, _: usize, request: *const u8, request_len: usize, out_len: *mut usize) -> *mut u8 {
+        let body = slice::from_raw_parts(request, request_len).to_vec().into_boxed_slice();
+        *out_len = body.len();
+        Box::into_raw(body) as *mut u8
+    }
+
+    // the values are leaked, as the test does not know how they were allocated
+    unsafe extern "C" fn free(_: *mut c_void, _: usize) {}
+
+    fn request(body: &[u8]) -> Request {
+        Request {
+            method: s!("POST"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/x-custom; version=2") ] }),
+            body: OptionalBody::Present(body.to_vec()),
+            .. Request::default_request()
+        }
+    }
+
+    #[test]
+    fn matches_and_generates_bodies_with_the_plugin() {
+        let plugin = Plugin::new(r#"{ "name": "custom", "contentTypes": [ "application/x-custom" ] }"#,
+                                 Some(same_length), Some(echo), free, None).unwrap();
+        let plugins = Plugins { plugins: vec![ plugin ] };
+        expect!(plugins.matcher_for("application/json")).to(be_none());
+        let plugin = plugins.matcher_for("application/X-Custom; version=2").unwrap();
+
+        let mut mismatches = vec![];
+        plugin.match_body(&request(&[1, 2, 3]), &request(&[4, 5, 6]), &mut mismatches);
+        expect!(mismatches.len()).to(be_equal_to(0));
+        plugin.match_body(&request(&[1, 2, 3]), &request(&[4, 5]), &mut mismatches);
+        expect!(mismatches.iter().map(Mismatch::description).collect::<Vec<String>>()).to(be_equal_to(vec![
+            s!("$ -> expected 3 bytes but got 2 (from plugin 'custom')")
+        ]));
+
+        let response = Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/x-custom") ] }),
+            body: OptionalBody::Present(vec![ 0 ]),
+            .. Response::default_response()
+        };
+        expect!(plugins.generate_response(&request(&[7, 8]), response).body).to(be_equal_to(OptionalBody::Present(vec![ 7, 8 ])));
+    }
+
+    #[test]
+    fn does_not_load_plugins_without_content_types() {
+        expect!(Plugin::new(r#"{ "name": "custom" }"#, None, None, free, None)).to(be_err());
+        expect!(Plugin::new("not json", None, None, free, None)).to(be_err());
+    }
+}
@@ -0,0 +1,215 @@
+use pact_matching::models::Pact;
+use crate::generators::{self, PactStateGenerators};
+use crate::pact_support::LoadedPact;
+use std::collections::HashMap;
+
+/// Section of the pact metadata with the priorities of interactions, by their description
+pub const INTERACTION_PRIORITIES_SECTION: &str = "interactionPriorities";
+
+/// The kinds of sources that can be given a priority
+pub static SOURCE_KINDS: [&str; 8] = [ "file", "dir", "url", "openapi", "wiremock-dir", "s3", "git", "broker" ];
+
+/// Priorities of the kinds of pact sources. Interactions from a source with a higher priority shadow the interactions
+/// with the same consumer, provider and description from sources with a lower priority. All sources have a priority
+/// of 0 unless given one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourcePriorities {
+    priorities: HashMap<String, i32>
+}
+
+impl SourcePriorities {
+    /// Parses the priorities from values in `kind=priority` form (e.g. `file=10`)
+    pub fn parse<'a, I: IntoIterator<Item = &'a str>>(values: I) -> Result<SourcePriorities, String> {
+        let mut priorities = HashMap::new();
+        for value in values {
+            let (kind, priority) = parse_priority(value)?;
+            priorities.insert(kind, priority);
+        }
+        Ok(SourcePriorities { priorities })
+    }
+
+    /// Returns the priority for the kind of source
+    pub fn get(&self, kind: &str) -> i32 {
+        self.priorities.get(kind).cloned().unwrap_or(0)
+    }
+}
+
+/// Parses a source priority in `kind=priority` form
+pub fn parse_priority(value: &str) -> Result<(String, i32), String> {
+    let mut split = value.splitn(2, '=');
+    let kind = split.next().unwrap_or_default().trim();
+    let priority = split.next().ok_or_else(|| s!("must be in kind=priority form"))?.trim();
+    if !SOURCE_KINDS.contains(&kind) {
+        return Err(format!("'{}' is not a source kind (one of {})", kind, SOURCE_KINDS.join(", ")));
+    }
+    priority.parse::<i32>()
+        .map(|priority| (kind.to_string(), priority))
+        .map_err(|err| format!("'{}' is not a valid priority - {}", priority, err))
+}
+
+/// Returns the priority the pact metadata gives the interaction with the description. When several interactions match
+/// a request equally well, the one with the highest priority is used.
+pub fn interaction_priority(pact: &Pact, description: &str) -> Option<i32> {
+    pact.metadata.get(INTERACTION_PRIORITIES_SECTION)
+        .and_then(|priorities| priorities.get(description))
+        .and_then(|priority| match priority.trim().parse::<i32>() {
+            Ok(priority) => Some(priority),
+            Err(err) => {
+                warn!("Ignoring the priority '{}' of interaction '{}' - {}", priority, description, err);
+                None
+            }
+        })
+}
+
+/// A pact that was loaded, with the source it was loaded from and the priority of the source
+#[derive(Debug, Clone)]
+pub struct SourcedPact {
+    /// The loaded pact
+    pub pact: Pact,
+    /// The provider state generators of the responses of the pact
+    pub state_generators: PactStateGenerators,
+    /// Description of the source the pact was loaded from
+    pub source: String,
+    /// Priority of the source
+    pub priority: i32
+}
+
+/// An interaction that was removed because a source with a higher priority has an interaction with the same
+/// consumer, provider and description
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowedInteraction {
+    /// Consumer of the pact with the interaction
+    pub consumer: String,
+    /// Provider of the pact with the interaction
+    pub provider: String,
+    /// Description of the interaction
+    pub description: String,
+    /// Source (and its priority) of the interaction that was removed
+    pub shadowed: (String, i32),
+    /// Source (and its priority) of the interaction that is used instead
+    pub shadowed_by: (String, i32)
+}
+
+/// Removes the interactions that are shadowed by interactions from sources with a higher priority, returning the
+/// remaining pacts (without any that are left with no interactions) with their provider state generators, and the
+/// interactions that were removed
+pub fn resolve_priorities(pacts: Vec<SourcedPact>) -> (Vec<LoadedPact>, Vec<ShadowedInteraction>) {
+    let mut highest: HashMap<(String, String, String), (String, i32)> = HashMap::new();
+    for sourced in &pacts {
+        for interaction in &sourced.pact.interactions {
+            let key = (sourced.pact.consumer.name.clone(), sourced.pact.provider.name.clone(),
+                       interaction.description.clone());
+            let replace = highest.get(&key).map(|&(_, priority)| sourced.priority > priority).unwrap_or(true);
+            if replace {
+                highest.insert(key, (sourced.source.clone(), sourced.priority));
+            }
+        }
+    }
+
+    let mut shadowed = vec![];
+    let mut result = vec![];
+    for sourced in pacts {
+        let SourcedPact { mut pact, mut state_generators, source: location, priority: own_priority } = sourced;
+        let total = pact.interactions.len();
+        let (consumer, provider) = (pact.consumer.name.clone(), pact.provider.name.clone());
+        generators::retain_interactions(&mut pact, &mut state_generators, |interaction| {
+            let key = (consumer.clone(), provider.clone(), interaction.description.clone());
+            match highest.get(&key) {
+                Some(&(ref source, priority)) if priority > own_priority => {
+                    shadowed.push(ShadowedInteraction {
+                        consumer: consumer.clone(),
+                        provider: provider.clone(),
+                        description: interaction.description.clone(),
+                        shadowed: (location.clone(), own_priority),
+                        shadowed_by: (source.clone(), priority)
+                    });
+                    false
+                },
+                _ => true
+            }
+        });
+        if total == 0 || !pact.interactions.is_empty() {
+            result.push((pact, state_generators));
+        }
+    }
+    (result, shadowed)
+}
+
+/// Logs the interactions that were shadowed by sources with a higher priority
+pub fn log_shadowed_interactions(shadowed: &[ShadowedInteraction]) {
+    if !shadowed.is_empty() {
+        info!("{} interaction(s) are shadowed by sources with a higher priority", shadowed.len());
+        for interaction in shadowed {
+            info!("  - '{}' ('{}' -> '{}') from {} (priority {}) is shadowed by {} (priority {})",
+                  interaction.description, interaction.consumer, interaction.provider, interaction.shadowed.0,
+                  interaction.shadowed.1, interaction.shadowed_by.0, interaction.shadowed_by.1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Consumer, Interaction, Pact, Provider};
+    use super::*;
+
+    fn pact(consumer: &str, descriptions: &[&str]) -> Pact {
+        Pact {
+            consumer: Consumer { name: consumer.to_string() },
+            provider: Provider { name: s!("provider") },
+            interactions: descriptions.iter()
+                .map(|description| Interaction { description: description.to_string(), .. Interaction::default() })
+                .collect(),
+            .. Pact::default()
+        }
+    }
+
+    fn sourced(pact: Pact, source: &str, priority: i32) -> SourcedPact {
+        SourcedPact { pact, state_generators: PactStateGenerators::new(), source: source.to_string(), priority }
+    }
+
+    #[test]
+    fn parses_source_priorities() {
+        let priorities = SourcePriorities::parse(vec!["file=10", "url = -1"]).unwrap();
+        expect!(priorities.get("file")).to(be_equal_to(10));
+        expect!(priorities.get("url")).to(be_equal_to(-1));
+        expect!(priorities.get("dir")).to(be_equal_to(0));
+
+        expect!(parse_priority("file")).to(be_err());
+        expect!(parse_priority("files=1")).to(be_err());
+        expect!(parse_priority("file=high")).to(be_err());
+    }
+
+    #[test]
+    fn higher_priority_sources_shadow_interactions() {
+        let (pacts, shadowed) = resolve_priorities(vec![
+            sourced(pact("consumer", &["get a", "get b"]), "https://broker/pact", 0),
+            sourced(pact("consumer", &["get b", "get c"]), "local.json", 10),
+            sourced(pact("other", &["get b"]), "https://broker/other", 0)
+        ]);
+
+        let descriptions = pacts.iter()
+            .map(|(pact, _)| pact.interactions.iter().map(|i| i.description.clone()).collect::<Vec<String>>())
+            .collect::<Vec<Vec<String>>>();
+        expect!(descriptions).to(be_equal_to(vec![
+            vec![s!("get a")], vec![s!("get b"), s!("get c")], vec![s!("get b")]
+        ]));
+        expect!(shadowed).to(be_equal_to(vec![ShadowedInteraction {
+            consumer: s!("consumer"),
+            provider: s!("provider"),
+            description: s!("get b"),
+            shadowed: (s!("https://broker/pact"), 0),
+            shadowed_by: (s!("local.json"), 10)
+        }]));
+    }
+
+    #[test]
+    fn sources_with_the_same_priority_keep_all_their_interactions() {
+        let (pacts, shadowed) = resolve_priorities(vec![
+            sourced(pact("consumer", &["get a"]), "one.json", 0),
+            sourced(pact("consumer", &["get a"]), "two.json", 0)
+        ]);
+        expect!(pacts.len()).to(be_equal_to(2));
+        expect!(shadowed.is_empty()).to(be_true());
+    }
+}
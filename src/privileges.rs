@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+/// The user and group the server switches to once its port is bound, and the directories it is confined to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivilegeDrop {
+    /// User name or id
+    pub user: String,
+    /// Group name or id (defaults to the primary group of the user)
+    pub group: Option<String>,
+    /// Directory to change the root directory of the process to
+    pub chroot: Option<PathBuf>,
+    /// Directory to change the working directory of the process to (inside the chroot directory, if there is one)
+    pub working_dir: Option<PathBuf>
+}
+
+impl PrivilegeDrop {
+    /// Parses a `user:group` or `user` value
+    pub fn parse(value: &str) -> Result<PrivilegeDrop, String> {
+        let (user, group) = match value.find(':') {
+            Some(index) => (&value[..index], Some(&value[index + 1..])),
+            None => (value, None)
+        };
+        if user.trim().is_empty() {
+            return Err(s!("the user is missing"));
+        }
+        if group.map(|group| group.trim().is_empty()).unwrap_or(false) {
+            return Err(s!("the group is missing"));
+        }
+        Ok(PrivilegeDrop {
+            user: user.trim().to_string(),
+            group: group.map(|group| group.trim().to_string()),
+            chroot: None,
+            working_dir: None
+        })
+    }
+
+    /// Confines the process to the directories, and switches to the user and group. This can not be undone.
+    #[cfg(unix)]
+    pub fn apply(&self) -> Result<(), String> {
+        let (uid, primary_gid) = unix::user_ids(&self.user)?;
+        let gid = match self.group {
+            Some(ref group) => unix::group_id(group)?,
+            None => primary_gid.ok_or_else(|| format!("User '{}' does not have a primary group, a group is required", self.user))?
+        };
+        if let Some(ref dir) = self.chroot {
+            unix::chroot(dir)?;
+        }
+        if let Some(ref dir) = self.working_dir {
+            std::env::set_current_dir(dir)
+                .map_err(|err| format!("Could not change the working directory to '{}' - {}", dir.display(), err))?;
+        }
+        unix::switch_user(uid, gid)?;
+        info!("Dropped privileges to user '{}' (uid {}, gid {})", self.user, uid, gid);
+        Ok(())
+    }
+
+    /// Confines the process to the directories, and switches to the user and group. This can not be undone.
+    #[cfg(not(unix))]
+    pub fn apply(&self) -> Result<(), String> {
+        Err(s!("Dropping privileges is not supported on this platform"))
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use libc::{gid_t, uid_t};
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    fn c_string(value: &[u8]) -> Result<CString, String> {
+        CString::new(value).map_err(|err| err.to_string())
+    }
+
+    /// Looks up the id and primary group of a user by name or id
+    pub fn user_ids(user: &str) -> Result<(uid_t, Option<gid_t>), String> {
+        let name = c_string(user.as_bytes())?;
+        let passwd = match user.parse::<uid_t>() {
+            Ok(uid) => unsafe { libc::getpwuid(uid) },
+            Err(_) => unsafe { libc::getpwnam(name.as_ptr()) }
+        };
+        if passwd.is_null() {
+            user.parse::<uid_t>().map(|uid| (uid, None)).map_err(|_| format!("User '{}' does not exist", user))
+        } else {
+            let passwd = unsafe { &*passwd };
+            Ok((passwd.pw_uid, Some(passwd.pw_gid)))
+        }
+    }
+
+    /// Looks up the id of a group by name or id
+    pub fn group_id(group: &str) -> Result<gid_t, String> {
+        if let Ok(gid) = group.parse::<gid_t>() {
+            return Ok(gid);
+        }
+        let name = c_string(group.as_bytes())?;
+        let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+        if entry.is_null() {
+            Err(format!("Group '{}' does not exist", group))
+        } else {
+            Ok(unsafe { (*entry).gr_gid })
+        }
+    }
+
+    pub fn chroot(dir: &Path) -> Result<(), String> {
+        let path = c_string(dir.as_os_str().as_bytes())?;
+        if unsafe { libc::chroot(path.as_ptr()) } != 0 {
+            return Err(format!("Could not change the root directory to '{}' - {}", dir.display(), io::Error::last_os_error()));
+        }
+        std::env::set_current_dir("/").map_err(|err| format!("Could not change to the new root directory - {}", err))
+    }
+
+    /// Switches the groups first, as a process that is no longer root can not change them
+    pub fn switch_user(uid: uid_t, gid: gid_t) -> Result<(), String> {
+        if unsafe { libc::setgroups(1, &gid) } != 0 {
+            return Err(format!("Could not set the supplementary groups - {}", io::Error::last_os_error()));
+        }
+        if unsafe { libc::setgid(gid) } != 0 {
+            return Err(format!("Could not switch to group {} - {}", gid, io::Error::last_os_error()));
+        }
+        if unsafe { libc::setuid(uid) } != 0 {
+            return Err(format!("Could not switch to user {} - {}", uid, io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn parses_the_user_and_group() {
+        expect!(PrivilegeDrop::parse("nobody:nogroup")).to(be_ok().value(PrivilegeDrop {
+            user: s!("nobody"),
+            group: Some(s!("nogroup")),
+            chroot: None,
+            working_dir: None
+        }));
+        expect!(PrivilegeDrop::parse("1000").map(|drop| drop.group)).to(be_ok().value(None));
+        expect!(PrivilegeDrop::parse(":staff")).to(be_err());
+        expect!(PrivilegeDrop::parse("nobody:")).to(be_err());
+    }
+}
@@ -0,0 +1,116 @@
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{Request as HyperRequest, Uri};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use pact_matching::models::{build_query_string, Request, Response};
+use crate::fetch_proxy::ProxyConnector;
+use crate::pact_support::{self, FetchOptions};
+use std::fmt::{self, Debug, Formatter};
+
+/// Headers that only apply to a single connection, and must not be forwarded
+static HOP_BY_HOP_HEADERS: [&str; 4] = [ "connection", "content-length", "host", "transfer-encoding" ];
+
+/// Forwards requests to a real provider
+#[derive(Clone)]
+pub struct ProxyClient {
+    base_url: String,
+    client: Client<HttpsConnector<ProxyConnector>, Full<Bytes>>
+}
+
+impl ProxyClient {
+    /// Creates a proxy client that forwards to the given base URL
+    pub fn new(base_url: &str, insecure_tls: bool) -> Result<ProxyClient, String> {
+        base_url.parse::<Uri>()
+            .map_err(|err| format!("'{}' is not a valid URL - {}", base_url, err))
+            .and_then(|uri| if uri.scheme().is_some() && uri.authority().is_some() {
+                Ok(uri)
+            } else {
+                Err(format!("'{}' is not an absolute URL", base_url))
+            })
+            .and_then(|_| {
+                let connector = FetchOptions { insecure: insecure_tls, .. FetchOptions::default() }.connector()?;
+                Ok(ProxyClient {
+                    base_url: base_url.trim_end_matches('/').to_string(),
+                    client: Client::builder(TokioExecutor::new()).build(connector)
+                })
+            })
+    }
+
+    /// The base URL requests are forwarded to
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Builds the URL of the real provider for the request
+    pub fn url_for(&self, request: &Request) -> String {
+        match request.query {
+            Some(ref query) => format!("{}{}?{}", self.base_url, request.path, build_query_string(query.clone())),
+            None => format!("{}{}", self.base_url, request.path)
+        }
+    }
+
+    /// Forwards the request to the real provider, returning its response
+    pub async fn forward(&self, request: &Request) -> Result<Response, String> {
+        let url = self.url_for(request);
+        let mut builder = HyperRequest::builder().method(request.method.as_str()).uri(url.as_str());
+        if let Some(ref headers) = request.headers {
+            for (name, values) in headers {
+                if !HOP_BY_HOP_HEADERS.contains(&name.to_lowercase().as_str()) {
+                    for value in values {
+                        builder = builder.header(name.as_str(), value.as_str());
+                    }
+                }
+            }
+        }
+        let hyper_request = builder.body(Full::new(Bytes::from(request.body.value())))
+            .map_err(|err| format!("Failed to build the request to {} - {}", url, err))?;
+
+        debug!("Forwarding request to {}", url);
+        let res = self.client.request(hyper_request).await
+            .map_err(|err| format!("Request to {} failed - {}", url, err))?;
+        let (mut parts, body) = res.into_parts();
+        for header in HOP_BY_HOP_HEADERS.iter() {
+            parts.headers.remove(*header);
+        }
+        let body = body.collect().await
+            .map_err(|err| format!("Failed to read the response body - {}", err))?
+            .to_bytes();
+        Ok(pact_support::hyper_response_to_pact_response(parts, pact_support::optional_body(body)))
+    }
+}
+
+impl Debug for ProxyClient {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "ProxyClient({})", self.base_url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::Request;
+    use super::*;
+
+    #[test]
+    fn requires_an_absolute_base_url() {
+        expect!(ProxyClient::new("http://localhost:8080", false)).to(be_ok());
+        expect!(ProxyClient::new("https://localhost:8443", true)).to(be_ok());
+        expect!(ProxyClient::new("/api", false)).to(be_err());
+        expect!(ProxyClient::new("not a url", false)).to(be_err());
+    }
+
+    #[test]
+    fn builds_the_url_from_the_base_url_and_request() {
+        let proxy = ProxyClient::new("http://localhost:8080/base/", false).unwrap();
+        let request = Request {
+            path: s!("/api/users"),
+            query: Some(hashmap!{ s!("name") => vec![ s!("a b") ] }),
+            .. Request::default_request()
+        };
+
+        expect!(proxy.url_for(&request)).to(be_equal_to("http://localhost:8080/base/api/users?name=a+b"));
+        expect!(proxy.url_for(&Request::default_request())).to(be_equal_to("http://localhost:8080/base/"));
+    }
+}
@@ -0,0 +1,72 @@
+use log::{Log, LogLevel, LogMetadata, LogRecord};
+use regex::Regex;
+use std::future::Future;
+
+tokio::task_local! {
+    static QUIET: bool;
+}
+
+/// If requests to the path are handled quietly (like health checks or polling), because one of the regular
+/// expressions matches it
+pub fn is_quiet_path(quiet_paths: &[Regex], path: &str) -> bool {
+    quiet_paths.iter().any(|regex| regex.is_match(path))
+}
+
+/// Runs the future (like the handling of a request), only logging its errors if it is quiet
+pub async fn run_quietly<F: Future>(quiet: bool, future: F) -> F::Output {
+    QUIET.scope(quiet, future).await
+}
+
+/// If the current task is handling a request quietly
+fn is_quiet() -> bool {
+    QUIET.try_with(|quiet| *quiet).unwrap_or(false)
+}
+
+/// Logger that drops the messages (other than errors) logged while a request is handled quietly, and passes the others
+/// on to the logger it wraps
+pub struct QuietLogger {
+    logger: Box<dyn Log>
+}
+
+impl QuietLogger {
+    pub fn new(logger: Box<dyn Log>) -> QuietLogger {
+        QuietLogger { logger }
+    }
+
+    fn is_dropped(level: LogLevel) -> bool {
+        level > LogLevel::Error && is_quiet()
+    }
+}
+
+impl Log for QuietLogger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        !QuietLogger::is_dropped(metadata.level()) && self.logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if !QuietLogger::is_dropped(record.level()) {
+            self.logger.log(record)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn only_handles_the_matching_paths_quietly() {
+        let quiet_paths = vec![ Regex::new("^/health$").unwrap(), Regex::new("^/api/poll").unwrap() ];
+        expect!(is_quiet_path(&quiet_paths, "/health")).to(be_true());
+        expect!(is_quiet_path(&quiet_paths, "/api/poll/jobs")).to(be_true());
+        expect!(is_quiet_path(&quiet_paths, "/health/details")).to(be_false());
+        expect!(is_quiet_path(&[], "/health")).to(be_false());
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        expect!(runtime.block_on(run_quietly(true, async { QuietLogger::is_dropped(LogLevel::Warn) }))).to(be_true());
+        expect!(runtime.block_on(run_quietly(true, async { QuietLogger::is_dropped(LogLevel::Error) }))).to(be_false());
+        expect!(runtime.block_on(run_quietly(false, async { QuietLogger::is_dropped(LogLevel::Info) }))).to(be_false());
+        expect!(QuietLogger::is_dropped(LogLevel::Info)).to(be_false());
+    }
+}
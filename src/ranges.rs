@@ -0,0 +1,131 @@
+use http::StatusCode;
+use pact_matching::models::{HttpPart, OptionalBody, Request, Response};
+use crate::pact_support;
+
+/// A satisfiable byte range of a body, with inclusive start and end offsets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteRange {
+    pub start: usize,
+    pub end: usize
+}
+
+/// Parses a `Range` header value for a body of the given length. Returns `None` if the header is not a single byte
+/// range (which is ignored, and the full body sent), or `Some(Err(()))` if the range can not be satisfied.
+pub fn parse_range(value: &str, length: usize) -> Option<Result<ByteRange, ()>> {
+    let value = value.trim();
+    if !value.to_lowercase().starts_with("bytes=") || value.contains(',') {
+        return None;
+    }
+    let spec = value[6..].trim();
+    let dash = spec.find('-')?;
+    let (start, end) = (spec[..dash].trim(), spec[dash + 1..].trim());
+    let range = if start.is_empty() {
+        let suffix = end.parse::<usize>().ok()?;
+        if suffix == 0 || length == 0 {
+            return Some(Err(()));
+        }
+        ByteRange { start: length.saturating_sub(suffix), end: length - 1 }
+    } else {
+        let start = start.parse::<usize>().ok()?;
+        let end = if end.is_empty() { None } else { Some(end.parse::<usize>().ok()?) };
+        if end.map(|end| end < start).unwrap_or(false) {
+            return None;
+        }
+        if start >= length {
+            return Some(Err(()));
+        }
+        ByteRange { start, end: end.map(|end| end.min(length - 1)).unwrap_or(length - 1) }
+    };
+    Some(Ok(range))
+}
+
+fn is_ranged(request: &Request, response: &Response) -> bool {
+    request.method.to_uppercase() == "GET" && response.status == StatusCode::OK.as_u16() &&
+        response.lookup_header_value(&s!("content-type"))
+            .map(|content_type| pact_support::is_binary_content_type(&content_type))
+            .unwrap_or(false)
+}
+
+/// Applies the `Range` header of a request to the response for a binary body, responding with a 206 (Partial
+/// Content) with the requested bytes, or a 416 (Range Not Satisfiable) if the range is past the end of the body.
+/// Binary responses advertise that they accept byte ranges.
+pub fn apply_range(request: &Request, mut response: Response) -> Response {
+    if !is_ranged(request, &response) {
+        return response;
+    }
+    let length = match response.body {
+        OptionalBody::Present(ref body) => body.len(),
+        _ => return response
+    };
+    pact_support::add_header(&mut response, "Accept-Ranges", "bytes");
+    match request.lookup_header_value(&s!("range")).and_then(|range| parse_range(&range, length)) {
+        Some(Ok(range)) => {
+            debug!("Sending bytes {}-{} of the {} byte body for range request", range.start, range.end, length);
+            response.status = StatusCode::PARTIAL_CONTENT.as_u16();
+            pact_support::add_header(&mut response, "Content-Range",
+                                     &format!("bytes {}-{}/{}", range.start, range.end, length));
+            // the range is cut out of the body in place, instead of copying it
+            if let OptionalBody::Present(ref mut body) = response.body {
+                body.truncate(range.end + 1);
+                body.drain(..range.start);
+            }
+        },
+        Some(Err(())) => {
+            debug!("Range request is past the end of the {} byte body, sending {}", length,
+                   StatusCode::RANGE_NOT_SATISFIABLE);
+            response.status = StatusCode::RANGE_NOT_SATISFIABLE.as_u16();
+            pact_support::add_header(&mut response, "Content-Range", &format!("bytes */{}", length));
+            response.body = OptionalBody::Empty;
+        },
+        None => ()
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Request, Response};
+    use super::*;
+
+    #[test]
+    fn parses_single_byte_ranges() {
+        expect!(parse_range("bytes=0-99", 1000)).to(be_some().value(Ok(ByteRange { start: 0, end: 99 })));
+        expect!(parse_range("bytes=900-", 1000)).to(be_some().value(Ok(ByteRange { start: 900, end: 999 })));
+        expect!(parse_range("bytes=-100", 1000)).to(be_some().value(Ok(ByteRange { start: 900, end: 999 })));
+        expect!(parse_range("bytes=500-2000", 1000)).to(be_some().value(Ok(ByteRange { start: 500, end: 999 })));
+        expect!(parse_range("bytes=1000-", 1000)).to(be_some().value(Err(())));
+        expect!(parse_range("bytes=0-1,5-6", 1000)).to(be_none());
+        expect!(parse_range("items=0-1", 1000)).to(be_none());
+        expect!(parse_range("bytes=5-1", 1000)).to(be_none());
+    }
+
+    #[test]
+    fn responds_with_partial_content_for_binary_bodies() {
+        let response = Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![s!("video/mp4")] }),
+            body: OptionalBody::Present(b"0123456789".to_vec()),
+            .. Response::default_response()
+        };
+        let request = |range: &str| Request {
+            headers: Some(hashmap!{ s!("Range") => vec![range.to_string()] }),
+            .. Request::default_request()
+        };
+
+        let partial = apply_range(&request("bytes=2-5"), response.clone());
+        expect!(partial.status).to(be_equal_to(206));
+        expect!(partial.body.value()).to(be_equal_to(b"2345".to_vec()));
+        expect!(partial.lookup_header_value(&s!("Content-Range"))).to(be_some().value("bytes 2-5/10"));
+
+        let unsatisfiable = apply_range(&request("bytes=20-"), response.clone());
+        expect!(unsatisfiable.status).to(be_equal_to(416));
+        expect!(unsatisfiable.lookup_header_value(&s!("Content-Range"))).to(be_some().value("bytes */10"));
+
+        let full = apply_range(&Request::default_request(), response.clone());
+        expect!(full.status).to(be_equal_to(200));
+        expect!(full.lookup_header_value(&s!("Accept-Ranges"))).to(be_some().value("bytes"));
+
+        let text = Response { headers: None, .. response };
+        expect!(apply_range(&request("bytes=2-5"), text.clone())).to(be_equal_to(text));
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Number of clients tracked before the ones whose window has ended are removed
+const PRUNE_AT: usize = 1024;
+
+/// Start of the current window, and the number of requests in it, for each client (or for all of them)
+type Windows = HashMap<Option<IpAddr>, (Instant, u32)>;
+
+/// Limits the number of requests that are answered in each period, so consumers can test how they handle 429 (Too Many
+/// Requests) responses. The limit is for all the clients together, or for each client IP address.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    limit: u32,
+    period: Duration,
+    per_ip: bool,
+    windows: Arc<Mutex<Windows>>
+}
+
+fn parse_period(unit: &str) -> Option<Duration> {
+    match unit.to_lowercase().as_str() {
+        "s" | "sec" | "second" => Some(Duration::from_secs(1)),
+        "m" | "min" | "minute" => Some(Duration::from_secs(60)),
+        "h" | "hour" => Some(Duration::from_secs(60 * 60)),
+        _ => None
+    }
+}
+
+impl RateLimit {
+    /// Parses a `<count>/<period>` limit (like `10/min`), with the period in seconds (`s`), minutes (`min`) or hours
+    /// (`h`). The limit is for each client IP address if it is followed by `per-ip`.
+    pub fn parse(value: &str) -> Result<RateLimit, String> {
+        let mut parts = value.split_whitespace();
+        let (rate, scope) = (parts.next().unwrap_or_default(), parts.next());
+        if parts.next().is_some() {
+            return Err(s!("must be in the form <count>/<period> [per-ip]"));
+        }
+        let per_ip = match scope {
+            None => false,
+            Some(scope) if scope.eq_ignore_ascii_case("per-ip") => true,
+            Some(scope) => return Err(format!("'{}' is not a valid scope, it can only be per-ip", scope))
+        };
+        match rate.split_once('/') {
+            Some((count, unit)) => {
+                let limit = count.parse::<u32>().ok().filter(|limit| *limit > 0)
+                    .ok_or_else(|| format!("'{}' is not a valid number of requests", count))?;
+                let period = parse_period(unit)
+                    .ok_or_else(|| format!("'{}' is not a valid period, it must be s, min or h", unit))?;
+                Ok(RateLimit { limit, period, per_ip, windows: Arc::default() })
+            },
+            None => Err(s!("must be in the form <count>/<period> [per-ip]"))
+        }
+    }
+
+    /// Counts a request from the client, and returns how long until it can make another one if it is over the limit
+    pub fn check(&self, client: IpAddr) -> Option<Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, now: Instant) -> Option<Duration> {
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= PRUNE_AT {
+            let period = self.period;
+            windows.retain(|_, (start, _)| now.duration_since(*start) < period);
+        }
+        let key = if self.per_ip { Some(client) } else { None };
+        let (start, count) = windows.entry(key).or_insert((now, 0));
+        if now.duration_since(*start) >= self.period {
+            *start = now;
+            *count = 0;
+        }
+        if *count < self.limit {
+            *count += 1;
+            None
+        } else {
+            Some(self.period - now.duration_since(*start))
+        }
+    }
+}
+
+impl Display for RateLimit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} request(s) every {:?}{}", self.limit, self.period, if self.per_ip { " for each client" } else { "" })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn parses_rate_limits() {
+        let limit = RateLimit::parse("10/min per-ip").unwrap();
+        expect!(limit.to_string()).to(be_equal_to(s!("10 request(s) every 60s for each client")));
+        expect!(RateLimit::parse("5/s").unwrap().to_string()).to(be_equal_to(s!("5 request(s) every 1s")));
+        expect!(RateLimit::parse("100/hour").unwrap().to_string()).to(be_equal_to(s!("100 request(s) every 3600s")));
+        expect!(RateLimit::parse("10")).to(be_err());
+        expect!(RateLimit::parse("0/min")).to(be_err());
+        expect!(RateLimit::parse("10/day")).to(be_err());
+        expect!(RateLimit::parse("10/min per-user")).to(be_err());
+        expect!(RateLimit::parse("10/min per-ip now")).to(be_err());
+    }
+
+    #[test]
+    fn limits_the_requests_in_each_period() {
+        let (client, other) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        let start = Instant::now();
+        let per_ip = RateLimit::parse("2/min per-ip").unwrap();
+        expect!(per_ip.check_at(client, start)).to(be_none());
+        expect!(per_ip.check_at(client, start + Duration::from_secs(10))).to(be_none());
+        expect!(per_ip.check_at(client, start + Duration::from_secs(20))).to(be_some().value(Duration::from_secs(40)));
+        expect!(per_ip.check_at(other, start + Duration::from_secs(20))).to(be_none());
+        expect!(per_ip.check_at(client, start + Duration::from_secs(60))).to(be_none());
+
+        let shared = RateLimit::parse("1/s").unwrap();
+        expect!(shared.check_at(client, start)).to(be_none());
+        expect!(shared.check_at(other, start)).to(be_some());
+    }
+}
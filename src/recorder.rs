@@ -0,0 +1,187 @@
+use pact_matching::models::{build_query_string, Consumer, Interaction, OptionalBody, Pact, PactSpecification, Provider, Request, Response};
+use crate::pact_support;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Request headers that are kept when recording an interaction. Other headers are specific to the client.
+static RECORDED_REQUEST_HEADERS: [&str; 1] = [ "content-type" ];
+
+/// Response headers that are not kept when recording an interaction
+static IGNORED_RESPONSE_HEADERS: [&str; 1] = [ "date" ];
+
+/// Records proxied requests and responses as interactions in a pact file
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    pact: Mutex<Pact>
+}
+
+fn filter_headers<F>(headers: &Option<HashMap<String, Vec<String>>>, keep: F) -> Option<HashMap<String, Vec<String>>>
+    where F: Fn(&str) -> bool {
+    headers.as_ref()
+        .map(|headers| headers.iter()
+            .filter(|(name, _)| keep(&name.to_lowercase()))
+            .map(|(name, values)| (name.clone(), values.clone()))
+            .collect::<HashMap<String, Vec<String>>>())
+        .and_then(|headers| if headers.is_empty() { None } else { Some(headers) })
+}
+
+/// The headers with lower case names, to compare them
+fn lowercase_headers(headers: &Option<HashMap<String, Vec<String>>>) -> BTreeMap<String, Vec<String>> {
+    headers.iter().flatten().map(|(name, values)| (name.to_lowercase(), values.clone())).collect()
+}
+
+/// If the bodies are the same, or the same JSON (which is formatted differently once it is written to the pact file)
+fn same_body(a: &OptionalBody, b: &OptionalBody) -> bool {
+    let (a, b) = (pact_support::body_bytes(a), pact_support::body_bytes(b));
+    a == b || match (serde_json::from_slice::<Value>(a), serde_json::from_slice::<Value>(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false
+    }
+}
+
+/// If the request of an interaction is the same as the recorded one, which only has the recorded headers
+fn same_request(interaction: &Request, recorded: &Request) -> bool {
+    interaction.method.eq_ignore_ascii_case(&recorded.method) && interaction.path == recorded.path
+        && interaction.query == recorded.query
+        && lowercase_headers(&interaction.headers) == lowercase_headers(&recorded.headers)
+        && same_body(&interaction.body, &recorded.body)
+}
+
+impl Recorder {
+    /// Creates a recorder that writes the interactions to a pact file in the given directory. If the pact file already
+    /// exists, the interactions are added to the ones it has.
+    pub fn new(dir: &Path, consumer: &str, provider: &str) -> Result<Recorder, String> {
+        let pact = Pact {
+            consumer: Consumer { name: consumer.to_string() },
+            provider: Provider { name: provider.to_string() },
+            interactions: vec![],
+            specification_version: PactSpecification::V3,
+            .. Pact::default()
+        };
+        let path = dir.join(pact.default_file_name());
+        let pact = if path.exists() {
+            let pact = Pact::read_pact(&path)
+                .map_err(|err| format!("Could not read the pact file '{}' to record to - {}", path.display(), err))?;
+            info!("Adding the recorded interactions to the {} interaction(s) of {}", pact.interactions.len(), path.display());
+            pact
+        } else {
+            pact
+        };
+        Ok(Recorder { path, pact: Mutex::new(pact) })
+    }
+
+    /// The pact file the interactions are written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records the request and response as an interaction, and writes out the pact file. Requests that are the same as
+    /// the one of an interaction of the pact (with the same method, path, query parameters, recorded headers and body)
+    /// are ignored.
+    pub fn record(&self, request: &Request, response: &Response) -> io::Result<()> {
+        let request = Request {
+            headers: filter_headers(&request.headers, |name| RECORDED_REQUEST_HEADERS.contains(&name)),
+            .. request.clone()
+        };
+        let name = match request.query {
+            Some(ref query) => format!("{} {}?{}", request.method, request.path, build_query_string(query.clone())),
+            None => format!("{} {}", request.method, request.path)
+        };
+        let mut pact = self.pact.lock().unwrap();
+        if let Some(interaction) = pact.interactions.iter().find(|i| same_request(&i.request, &request)) {
+            debug!("Request {} has already been recorded as interaction '{}'", name, interaction.description);
+            return Ok(());
+        }
+
+        // requests that only differ in their headers or body get a description of their own
+        let description = (1..)
+            .map(|n| if n == 1 { name.clone() } else { format!("{} ({})", name, n) })
+            .find(|description| pact.interactions.iter().all(|i| &i.description != description))
+            .unwrap();
+        pact.interactions.push(Interaction {
+            description: description.clone(),
+            request,
+            response: Response {
+                headers: filter_headers(&response.headers, |name| !IGNORED_RESPONSE_HEADERS.contains(&name)),
+                .. response.clone()
+            },
+            .. Interaction::default()
+        });
+        info!("Recording interaction '{}' to {}", description, self.path.display());
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&pact.to_json(PactSpecification::V3))?;
+        fs::write(&self.path, json)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Pact, Request, Response};
+    use std::env;
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn records_interactions_to_a_pact_file() {
+        let dir = env::temp_dir().join(format!("pact-stub-server-recorder-{}", ::std::process::id()));
+        let recorder = Recorder::new(&dir, "consumer", "provider").unwrap();
+        let request = Request {
+            path: s!("/api/users"),
+            headers: Some(hashmap!{ s!("content-type") => vec![s!("application/json")], s!("user-agent") => vec![s!("curl")] }),
+            .. Request::default_request()
+        };
+        let response = Response {
+            headers: Some(hashmap!{ s!("content-type") => vec![s!("application/json")], s!("date") => vec![s!("today")] }),
+            body: OptionalBody::Present("[]".as_bytes().into()),
+            .. Response::default_response()
+        };
+
+        expect!(recorder.record(&request, &response)).to(be_ok());
+        expect!(recorder.record(&request, &Response::default_response())).to(be_ok());
+
+        let pact = Pact::read_pact(recorder.path()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        expect!(pact.interactions.len()).to(be_equal_to(1));
+        let interaction = &pact.interactions[0];
+        expect!(interaction.description.as_str()).to(be_equal_to("GET /api/users"));
+        expect!(interaction.request.headers.clone()).to(be_some().value(hashmap!{ s!("content-type") => vec![s!("application/json")] }));
+        expect!(interaction.response.headers.clone()).to(be_some().value(hashmap!{ s!("content-type") => vec![s!("application/json")] }));
+        expect!(interaction.response.body.str_value()).to(be_equal_to("[]"));
+    }
+
+    #[test]
+    fn adds_the_interactions_to_the_existing_pact_file() {
+        let dir = env::temp_dir().join(format!("pact-stub-server-recorder-existing-{}", ::std::process::id()));
+        let order = |body: &str| Request {
+            method: s!("POST"),
+            path: s!("/orders"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![s!("application/json")] }),
+            body: OptionalBody::Present(body.as_bytes().into()),
+            .. Request::default_request()
+        };
+        let recorder = Recorder::new(&dir, "consumer", "provider").unwrap();
+        expect!(recorder.record(&order(r#"{"id": 1}"#), &Response::default_response())).to(be_ok());
+
+        let recorder = Recorder::new(&dir, "consumer", "provider").unwrap();
+        expect!(recorder.record(&order(r#"{ "id" : 1 }"#), &Response::default_response())).to(be_ok());
+        expect!(recorder.record(&order(r#"{"id": 2}"#), &Response::default_response())).to(be_ok());
+        expect!(recorder.record(&Request { headers: None, .. order(r#"{"id": 2}"#) }, &Response::default_response())).to(be_ok());
+
+        let pact = Pact::read_pact(recorder.path()).unwrap();
+        fs::write(recorder.path(), "not a pact").unwrap();
+        let invalid = Recorder::new(&dir, "consumer", "provider");
+        fs::remove_dir_all(&dir).unwrap();
+        expect!(pact.interactions.iter().map(|i| i.description.clone()).collect::<Vec<String>>()).to(be_equal_to(vec![
+            s!("POST /orders"), s!("POST /orders (2)"), s!("POST /orders (3)")
+        ]));
+        expect!(invalid).to(be_err());
+    }
+}
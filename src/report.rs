@@ -0,0 +1,176 @@
+use chrono::Local;
+use crate::journal;
+use crate::matching;
+use crate::server::describe_mismatch;
+use itertools::Itertools;
+use pact_matching::Mismatch;
+use pact_matching::models::{Interaction, Request};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Number of the closest interactions listed for each unmatched request
+const CLOSEST_INTERACTIONS: usize = 3;
+
+/// Format of the mismatch report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    /// JSON document with the unmatched requests
+    Json,
+    /// JUnit XML, with a failed test case for each unmatched request
+    JUnit
+}
+
+impl ReportFormat {
+    /// JUnit XML for files with an `.xml` extension, otherwise JSON
+    pub fn for_path(path: &Path) -> ReportFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("xml") => ReportFormat::JUnit,
+            _ => ReportFormat::Json
+        }
+    }
+}
+
+/// File with all the requests that did not match any interaction while the server was running, and how they differed
+/// from the closest interactions. It is written again after each unmatched request, so it is complete even if the
+/// server is killed.
+#[derive(Debug)]
+pub struct MismatchReport {
+    path: PathBuf,
+    format: ReportFormat,
+    requests: Mutex<Vec<Value>>
+}
+
+impl MismatchReport {
+    /// Writes an empty report to the file, so there is one even if every request matches
+    pub fn create(path: &Path) -> Result<MismatchReport, String> {
+        let report = MismatchReport { path: path.to_path_buf(), format: ReportFormat::for_path(path), requests: Mutex::new(vec![]) };
+        report.write(&[]).map(|_| report)
+    }
+
+    /// The file the report is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds the request, with the mismatches of the closest interactions, and writes the report
+    pub fn record(&self, request: &Request, mismatches: &[(&Interaction, Vec<Mismatch>)]) {
+        let mut requests = self.requests.lock().unwrap();
+        requests.push(unmatched_request(request, mismatches));
+        if let Err(err) = self.write(&requests) {
+            error!("{}", err);
+        }
+    }
+
+    fn write(&self, requests: &[Value]) -> Result<(), String> {
+        let contents = match self.format {
+            ReportFormat::Json => serde_json::to_string_pretty(&json!({ "unmatched": requests.len(), "requests": requests })).unwrap(),
+            ReportFormat::JUnit => junit_report(requests)
+        };
+        fs::write(&self.path, contents)
+            .map_err(|err| format!("Could not write the mismatch report to '{}' - {}", self.path.display(), err))
+    }
+}
+
+fn unmatched_request(request: &Request, mismatches: &[(&Interaction, Vec<Mismatch>)]) -> Value {
+    let mut closest: Vec<&(&Interaction, Vec<Mismatch>)> = mismatches.iter().collect();
+    closest.sort_by_key(|(_, mismatches)| matching::mismatch_score(mismatches));
+    let closest: Vec<Value> = closest.into_iter()
+        .take(CLOSEST_INTERACTIONS)
+        .map(|(interaction, mismatches)| json!({
+            "description": interaction.description,
+            "mismatches": mismatches.iter().map(describe_mismatch).collect::<Vec<String>>()
+        }))
+        .collect();
+    json!({
+        "time": Local::now().to_rfc3339(),
+        "method": request.method,
+        "path": request.path,
+        "query": request.query,
+        "headers": request.headers,
+        "body": journal::body_json(&request.body),
+        "closestInteractions": closest
+    })
+}
+
+fn xml_escape(value: &str) -> String {
+    value.chars().map(|c| match c {
+        '&' => s!("&amp;"),
+        '<' => s!("&lt;"),
+        '>' => s!("&gt;"),
+        '"' => s!("&quot;"),
+        '\'' => s!("&apos;"),
+        c if c.is_control() && c != '\n' && c != '\t' => String::new(),
+        c => c.to_string()
+    }).collect()
+}
+
+fn junit_report(requests: &[Value]) -> String {
+    let text = |value: &Value, key: &str| value[key].as_str().unwrap_or_default().to_string();
+    let mut xml = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"pact-stub-server\" tests=\"{0}\" failures=\"{0}\">\n  \
+        <testsuite name=\"unmatched requests\" tests=\"{0}\" failures=\"{0}\" timestamp=\"{1}\">\n", requests.len(),
+        Local::now().format("%Y-%m-%dT%H:%M:%S"));
+    for request in requests {
+        let name = format!("{} {}", text(request, "method"), text(request, "path"));
+        let details = match request["closestInteractions"].as_array() {
+            Some(closest) if !closest.is_empty() => closest.iter().map(|interaction| {
+                let mismatches = interaction["mismatches"].as_array().map(|mismatches| mismatches.iter()
+                    .map(|mismatch| format!("  - {}", mismatch.as_str().unwrap_or_default()))
+                    .join("\n")).unwrap_or_default();
+                format!("'{}':\n{}", text(interaction, "description"), mismatches)
+            }).join("\n"),
+            _ => s!("There are no interactions")
+        };
+        xml.push_str(&format!("    <testcase classname=\"pact-stub-server\" name=\"{}\" timestamp=\"{}\">\n      \
+            <failure message=\"No interaction matched {}\">{}</failure>\n    </testcase>\n",
+            xml_escape(&name), xml_escape(&text(request, "time")), xml_escape(&name), xml_escape(&details)));
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::OptionalBody;
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn lists_the_mismatches_of_the_closest_interactions() {
+        let request = Request { path: s!("/orders/1"), body: OptionalBody::Present(b"{}".to_vec()), .. Request::default_request() };
+        let near = Interaction { description: s!("get an order"), .. Interaction::default() };
+        let far = Interaction { description: s!("create an order"), .. Interaction::default() };
+        let json = unmatched_request(&request, &[
+            (&far, vec![ Mismatch::MethodMismatch { expected: s!("POST"), actual: s!("GET") },
+                         Mismatch::PathMismatch { expected: s!("/orders"), actual: s!("/orders/1"), mismatch: s!("different") } ]),
+            (&near, vec![ Mismatch::PathMismatch { expected: s!("/orders/2"), actual: s!("/orders/1"), mismatch: s!("different") } ])
+        ]);
+        expect!(json["body"].clone()).to(be_equal_to(json!("{}")));
+        expect!(json["closestInteractions"].clone()).to(be_equal_to(json!([
+            { "description": "get an order", "mismatches": [ "Path does not match: different" ] },
+            { "description": "create an order", "mismatches": [
+                "HTTP Method does not match, expected: POST, actual: GET", "Path does not match: different" ] }
+        ])));
+    }
+
+    #[test]
+    fn writes_json_or_junit_reports() {
+        expect!(ReportFormat::for_path(Path::new("target/mismatches.XML"))).to(be_equal_to(ReportFormat::JUnit));
+        expect!(ReportFormat::for_path(Path::new("target/mismatches.json"))).to(be_equal_to(ReportFormat::Json));
+        expect!(ReportFormat::for_path(Path::new("target/mismatches"))).to(be_equal_to(ReportFormat::Json));
+
+        let path = env::temp_dir().join(format!("pact-stub-server-mismatches-{}.xml", ::std::process::id()));
+        let report = MismatchReport::create(&path).unwrap();
+        let empty = fs::read_to_string(&path).unwrap();
+        let request = Request { path: s!("/a&b"), .. Request::default_request() };
+        report.record(&request, &[]);
+        let xml = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        expect!(empty.contains("tests=\"0\" failures=\"0\"")).to(be_true());
+        expect!(xml.contains("tests=\"1\" failures=\"1\"")).to(be_true());
+        expect!(xml.contains("<testcase classname=\"pact-stub-server\" name=\"GET /a&amp;b\"")).to(be_true());
+        expect!(xml.contains("<failure message=\"No interaction matched GET /a&amp;b\">There are no interactions</failure>")).to(be_true());
+    }
+}
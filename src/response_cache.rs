@@ -0,0 +1,146 @@
+use crate::index::InteractionPosition;
+use itertools::Itertools;
+use pact_matching::models::{Pact, Request, Response};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Maximum number of responses kept. The cache is emptied when it is full, as polling clients quickly fill it again.
+const MAX_ENTRIES: usize = 10_000;
+
+/// A response that was generated for a request, with the interaction it is from
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// Position of the interaction in the pacts
+    pub position: InteractionPosition,
+    /// Number of mismatches the request had with the interaction
+    pub mismatches: usize,
+    pub response: Response
+}
+
+/// Responses of the GET requests that matched an interaction, so identical requests (like a client polling) are
+/// answered without matching them against the interactions again. A request is identical if it has the same path,
+/// query parameters and values of the headers that the interactions match on.
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    headers: BTreeSet<String>,
+    responses: Mutex<HashMap<String, CachedResponse>>
+}
+
+impl ResponseCache {
+    /// Cache for the responses of the interactions in the pacts
+    pub fn new(pacts: &[Arc<Pact>]) -> ResponseCache {
+        let mut headers = pacts.iter()
+            .flat_map(|pact| pact.interactions.iter())
+            .flat_map(|interaction| {
+                let names = interaction.request.headers.iter().flat_map(|headers| headers.keys().cloned()).collect_vec();
+                let rules = interaction.request.matching_rules.rules_for_category(&s!("header"))
+                    .map(|category| category.rules.keys().cloned().collect_vec())
+                    .unwrap_or_default();
+                names.into_iter().chain(rules)
+            })
+            .map(|name| name.to_lowercase())
+            .collect::<BTreeSet<String>>();
+        // responses are chosen by the content types the request accepts
+        headers.insert(s!("accept"));
+        ResponseCache { headers, responses: Mutex::new(HashMap::new()) }
+    }
+
+    /// The key of the response to the request, with anything else that selects the interactions (like the provider
+    /// state filter). Returns `None` if the response can not be cached, as it is not a GET request without a body.
+    pub fn key(&self, request: &Request, selection: &str) -> Option<String> {
+        if !request.method.eq_ignore_ascii_case("GET") || request.body.is_present() {
+            return None;
+        }
+        let query = request.query.iter()
+            .flat_map(|query| query.iter())
+            .sorted_by(|(a, _), (b, _)| Ord::cmp(a, b))
+            .into_iter()
+            .map(|(name, values)| format!("{}={}", name, values.join(",")))
+            .join("&");
+        let headers = request.headers.iter()
+            .flat_map(|headers| headers.iter())
+            .map(|(name, values)| (name.to_lowercase(), values.join(",")))
+            .filter(|(name, _)| self.headers.contains(name))
+            .sorted()
+            .into_iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .join("\n");
+        Some(format!("{}\n{}?{}\n{}\n{}", selection, request.path, query, headers, request.method.to_uppercase()))
+    }
+
+    /// The cached response for the key
+    pub fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.responses.lock().unwrap().get(key).cloned()
+    }
+
+    /// Caches the response for the key
+    pub fn insert(&self, key: String, response: CachedResponse) {
+        let mut responses = self.responses.lock().unwrap();
+        if responses.len() >= MAX_ENTRIES {
+            debug!("Response cache has {} responses, emptying it", responses.len());
+            responses.clear();
+        }
+        responses.insert(key, response);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, OptionalBody};
+    use super::*;
+
+    fn cache() -> ResponseCache {
+        ResponseCache::new(&[ Arc::new(Pact {
+            interactions: vec![ Interaction {
+                request: Request {
+                    headers: Some(hashmap!{ s!("X-Tenant") => vec![ s!("a") ] }),
+                    .. Request::default_request()
+                },
+                .. Interaction::default()
+            } ],
+            .. Pact::default()
+        }) ])
+    }
+
+    fn request(query: &str, headers: HashMap<String, Vec<String>>) -> Request {
+        Request {
+            path: s!("/orders"),
+            query: pact_matching::models::parse_query_string(&query.to_string()),
+            headers: Some(headers),
+            .. Request::default_request()
+        }
+    }
+
+    #[test]
+    fn keys_the_requests_by_the_headers_the_interactions_match_on() {
+        let cache = cache();
+        let key = cache.key(&request("page=1&size=10", hashmap!{
+            s!("x-tenant") => vec![ s!("a") ], s!("X-Request-Id") => vec![ s!("1") ]
+        }), "");
+        expect!(key.clone()).to(be_some());
+        expect!(cache.key(&request("size=10&page=1", hashmap!{
+            s!("X-Tenant") => vec![ s!("a") ], s!("X-Request-Id") => vec![ s!("2") ]
+        }), "")).to(be_equal_to(key.clone()));
+        expect!(cache.key(&request("page=1&size=10", hashmap!{ s!("X-Tenant") => vec![ s!("b") ] }), ""))
+            .to_not(be_equal_to(key.clone()));
+        expect!(cache.key(&request("page=1&size=10", hashmap!{
+            s!("Accept") => vec![ s!("text/csv") ], s!("X-Tenant") => vec![ s!("a") ]
+        }), "")).to_not(be_equal_to(key.clone()));
+        expect!(cache.key(&request("page=1&size=10", hashmap!{ s!("X-Tenant") => vec![ s!("a") ] }), "provider state"))
+            .to_not(be_equal_to(key));
+
+        let post = Request { method: s!("POST"), .. request("", hashmap!{}) };
+        expect!(cache.key(&post, "")).to(be_none());
+        let with_body = Request { body: OptionalBody::Present(b"{}".to_vec()), .. request("", hashmap!{}) };
+        expect!(cache.key(&with_body, "")).to(be_none());
+    }
+
+    #[test]
+    fn caches_the_responses() {
+        let cache = cache();
+        expect!(cache.get("key").is_none()).to(be_true());
+        cache.insert(s!("key"), CachedResponse { position: (0, 0), mismatches: 0, response: Response { status: 201, .. Response::default_response() } });
+        expect!(cache.get("key").map(|cached| cached.response.status)).to(be_some().value(201));
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+/// Delay before the first retry, which doubles for each of the following ones
+pub const INITIAL_DELAY: Duration = Duration::from_millis(500);
+/// Longest delay between retries
+pub const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// How many times a download that fails in a way that may be transient (like the server not being up yet) is retried,
+/// with an exponential backoff between the attempts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retry {
+    pub retries: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration
+}
+
+impl Default for Retry {
+    fn default() -> Retry {
+        Retry { retries: 0, initial_delay: INITIAL_DELAY, max_delay: MAX_DELAY }
+    }
+}
+
+impl Retry {
+    /// Retries the downloads the number of times
+    pub fn new(retries: u32) -> Retry {
+        Retry { retries, .. Retry::default() }
+    }
+
+    /// How long to wait before the retry (starting from 1), or None if there are no more retries
+    pub fn delay(&self, retry: u32) -> Option<Duration> {
+        if retry == 0 || retry > self.retries {
+            None
+        } else {
+            let delay = self.initial_delay.checked_mul(2u32.saturating_pow(retry - 1)).unwrap_or(self.max_delay);
+            Some(delay.min(self.max_delay))
+        }
+    }
+}
+
+/// If a response with the status may succeed when the request is retried: timeouts, too many requests and server errors
+pub fn is_transient_status(status: u16) -> bool {
+    status == 408 || status == 429 || (500..600).contains(&status)
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn doubles_the_delay_for_each_retry() {
+        let retry = Retry::new(8);
+        expect!(retry.delay(1)).to(be_some().value(Duration::from_millis(500)));
+        expect!(retry.delay(2)).to(be_some().value(Duration::from_secs(1)));
+        expect!(retry.delay(4)).to(be_some().value(Duration::from_secs(4)));
+        expect!(retry.delay(8)).to(be_some().value(Duration::from_secs(30)));
+        expect!(retry.delay(9)).to(be_none());
+        expect!(Retry::default().delay(1)).to(be_none());
+        expect!(Retry { max_delay: Duration::from_secs(3600), .. Retry::new(100) }.delay(100)).to(be_some().value(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn retries_timeouts_and_server_errors() {
+        expect!(is_transient_status(503)).to(be_true());
+        expect!(is_transient_status(429)).to(be_true());
+        expect!(is_transient_status(408)).to(be_true());
+        expect!(is_transient_status(404)).to(be_false());
+        expect!(is_transient_status(401)).to(be_false());
+    }
+}
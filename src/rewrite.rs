@@ -0,0 +1,75 @@
+use regex::Regex;
+use std::fmt::{self, Display, Formatter};
+
+/// Separator between the regular expression and the replacement of a rewrite rule
+const SEPARATOR: &str = "->";
+
+/// Rewrites the request paths that match a regular expression before they are matched against the interactions, like
+/// `^/v2/(.*)$ -> /v1/$1` for consumers that use a newer version of the paths than the pacts
+#[derive(Debug, Clone)]
+pub struct PathRewrite {
+    regex: Regex,
+    replacement: String
+}
+
+impl PathRewrite {
+    /// Parses a `<regex> -> <replacement>` rule. The replacement can refer to the groups of the regular expression with
+    /// `$1` or `$name`.
+    pub fn parse(value: &str) -> Result<PathRewrite, String> {
+        let (regex, replacement) = value.rsplit_once(SEPARATOR)
+            .map(|(regex, replacement)| (regex.trim(), replacement.trim()))
+            .filter(|(regex, _)| !regex.is_empty())
+            .ok_or_else(|| s!("must be in the form <regex> -> <replacement>"))?;
+        let regex = Regex::new(regex).map_err(|err| format!("invalid regular expression - {}", err))?;
+        Ok(PathRewrite { regex, replacement: replacement.to_string() })
+    }
+
+    /// The path with the replacement, if it matches the regular expression
+    pub fn apply(&self, path: &str) -> Option<String> {
+        if self.regex.is_match(path) {
+            Some(self.regex.replace(path, self.replacement.as_str()).to_string())
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for PathRewrite {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.regex, SEPARATOR, self.replacement)
+    }
+}
+
+/// Applies each of the rules that match the path in turn
+pub fn rewrite_path(rewrites: &[PathRewrite], path: &str) -> Option<String> {
+    rewrites.iter().fold(None, |rewritten: Option<String>, rewrite| {
+        rewrite.apply(rewritten.as_deref().unwrap_or(path)).or(rewritten)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn parses_rewrite_rules() {
+        expect!(PathRewrite::parse("^/v2/(.*)$ -> /v1/$1").map(|rewrite| rewrite.to_string())).to(be_ok().value(s!("^/v2/(.*)$ -> /v1/$1")));
+        expect!(PathRewrite::parse("^/legacy->/").map(|rewrite| rewrite.to_string())).to(be_ok().value(s!("^/legacy -> /")));
+        expect!(PathRewrite::parse("^/v2/(.*)$")).to(be_err());
+        expect!(PathRewrite::parse(" -> /v1")).to(be_err());
+        expect!(PathRewrite::parse("^/v2/(.*$ -> /v1/$1")).to(be_err());
+    }
+
+    #[test]
+    fn rewrites_the_paths_that_match() {
+        let rewrites = vec![
+            PathRewrite::parse("^/v2/(.*)$ -> /v1/$1").unwrap(),
+            PathRewrite::parse("^/v1/people/(?P<id>\\d+)$ -> /v1/users/$id").unwrap()
+        ];
+        expect!(rewrite_path(&rewrites, "/v2/orders")).to(be_some().value(s!("/v1/orders")));
+        expect!(rewrite_path(&rewrites, "/v2/people/42")).to(be_some().value(s!("/v1/users/42")));
+        expect!(rewrite_path(&rewrites, "/v1/people/42")).to(be_some().value(s!("/v1/users/42")));
+        expect!(rewrite_path(&rewrites, "/health")).to(be_none());
+    }
+}
@@ -0,0 +1,232 @@
+use pact_matching::models::{Interaction, OptionalBody, Pact, Request, Response};
+use crate::pact_support;
+use regex::Regex;
+use rhai::{AST, Dynamic, Engine, Scope};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Section of the pact metadata that maps interaction descriptions to the script files for their responses
+pub const METADATA_SECTION: &str = "scripts";
+
+/// Limit on the number of operations a script can run, so a script that loops forever does not hang the request
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// A compiled script
+struct Script {
+    file: PathBuf,
+    ast: AST
+}
+
+/// Scripts that post-process the responses of interactions, so they can contain values from the requests (like an
+/// endpoint that echoes back the request body). A script has the `request` and the `response` in its scope, and can
+/// change the status, headers and body of the response.
+pub struct Scripts {
+    engine: Engine,
+    by_path: Vec<(Regex, Arc<Script>)>,
+    by_interaction: HashMap<(String, String, String), Arc<Script>>
+}
+
+/// Parses a `<path regex>=<script file>` value
+pub fn parse_path_script(value: &str) -> Result<(Regex, PathBuf), String> {
+    match value.rfind('=') {
+        Some(index) if index > 0 && index < value.len() - 1 => {
+            let regex = Regex::new(&value[..index]).map_err(|err| format!("invalid path regular expression - {}", err))?;
+            Ok((regex, PathBuf::from(&value[index + 1..])))
+        },
+        _ => Err(s!("must be in the form <path regex>=<script file>"))
+    }
+}
+
+fn key(pact: &Pact, interaction: &Interaction) -> (String, String, String) {
+    (pact.consumer.name.clone(), pact.provider.name.clone(), interaction.description.clone())
+}
+
+fn response_value(response: &Response) -> Value {
+    json!({
+        "status": response.status,
+        "headers": pact_support::values_json(&response.headers, false),
+        "body": pact_support::body_json(response)
+    })
+}
+
+/// Applies the changes the script made to the response value
+fn updated_response(value: Value, response: Response) -> Result<Response, String> {
+    let status = match value.get("status") {
+        Some(status) => status.as_u64().filter(|status| (100..=999).contains(status))
+            .ok_or_else(|| format!("response.status must be a status code, but was {}", status))? as u16,
+        None => response.status
+    };
+    let headers = match value.get("headers") {
+        Some(Value::Object(headers)) => Some(headers.iter()
+            .map(|(name, value)| (name.clone(), vec![ match value {
+                Value::String(value) => value.clone(),
+                _ => value.to_string()
+            } ]))
+            .collect()),
+        Some(Value::Null) | None => None,
+        Some(headers) => return Err(format!("response.headers must be a map, but was {}", headers))
+    };
+    let body = match value.get("body") {
+        Some(Value::Null) | None => OptionalBody::Missing,
+        Some(Value::String(body)) => OptionalBody::Present(body.clone().into_bytes()),
+        Some(body) => OptionalBody::Present(body.to_string().into_bytes())
+    };
+    Ok(Response { status, headers, body, .. response })
+}
+
+impl Scripts {
+    /// Compiles the scripts for the paths, and the ones for interactions given in the metadata of the pacts
+    pub fn load(path_scripts: Vec<(Regex, PathBuf)>, pacts: &[Pact]) -> Result<Scripts, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| info!("Script: {}", text));
+        engine.on_debug(|text, _, position| debug!("Script ({}): {}", position, text));
+        let mut compiled: HashMap<PathBuf, Arc<Script>> = HashMap::new();
+        let mut compile = |file: &Path| -> Result<Arc<Script>, String> {
+            if let Some(script) = compiled.get(file) {
+                return Ok(script.clone());
+            }
+            let ast = engine.compile_file(file.to_path_buf())
+                .map_err(|err| format!("Could not load the script '{}' - {}", file.display(), err))?;
+            let script = Arc::new(Script { file: file.to_path_buf(), ast });
+            compiled.insert(file.to_path_buf(), script.clone());
+            Ok(script)
+        };
+        let by_path = path_scripts.into_iter()
+            .map(|(regex, file)| compile(&file).map(|script| (regex, script)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut by_interaction = HashMap::new();
+        for pact in pacts {
+            if let Some(scripts) = pact.metadata.get(METADATA_SECTION) {
+                for (description, file) in scripts {
+                    match pact.interactions.iter().find(|interaction| &interaction.description == description) {
+                        Some(interaction) => { by_interaction.insert(key(pact, interaction), compile(Path::new(file))?); },
+                        None => warn!("The pact for '{}' -> '{}' has a script for interaction '{}', but does not have that interaction",
+                                      pact.consumer.name, pact.provider.name, description)
+                    }
+                }
+            }
+        }
+        Ok(Scripts { engine, by_path, by_interaction })
+    }
+
+    /// If there are no scripts
+    pub fn is_empty(&self) -> bool {
+        self.by_path.is_empty() && self.by_interaction.is_empty()
+    }
+
+    fn script_for(&self, pact: &Pact, interaction: &Interaction, request: &Request) -> Option<&Arc<Script>> {
+        self.by_interaction.get(&key(pact, interaction))
+            .or_else(|| self.by_path.iter().find(|(regex, _)| regex.is_match(&request.path)).map(|(_, script)| script))
+    }
+
+    /// Runs the script for the interaction (or for the request path) on the response, if there is one
+    pub fn apply(&self, pact: &Pact, interaction: &Interaction, request: &Request, response: Response) -> Result<Response, String> {
+        let script = match self.script_for(pact, interaction, request) {
+            Some(script) => script,
+            None => return Ok(response)
+        };
+        let to_dynamic = |value: Value| rhai::serde::to_dynamic(value).map_err(|err| err.to_string());
+        let mut scope = Scope::new();
+        scope.push("request", to_dynamic(pact_support::request_json(request))?);
+        scope.push("response", to_dynamic(response_value(&response))?);
+        self.engine.run_ast_with_scope(&mut scope, &script.ast)
+            .map_err(|err| format!("Script '{}' failed - {}", script.file.display(), err))?;
+        let value = scope.get_value::<Dynamic>("response")
+            .ok_or_else(|| format!("Script '{}' removed the response", script.file.display()))?;
+        let value: Value = rhai::serde::from_dynamic(&value)
+            .map_err(|err| format!("Script '{}' set an invalid response - {}", script.file.display(), err))?;
+        updated_response(value, response)
+            .map_err(|err| format!("Script '{}' set an invalid response - {}", script.file.display(), err))
+    }
+}
+
+impl Debug for Scripts {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Scripts {{ by_path: {:?}, by_interaction: {:?} }}",
+               self.by_path.iter().map(|(regex, script)| (regex.as_str(), &script.file)).collect::<Vec<_>>(),
+               self.by_interaction.iter().map(|((_, _, description), script)| (description, &script.file)).collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{Interaction, OptionalBody, Pact, Request, Response};
+    use std::env;
+    use std::fs;
+    use super::*;
+
+    fn script_file(name: &str, script: &str) -> PathBuf {
+        let file = env::temp_dir().join(format!("pact-stub-server-{}-{}.rhai", name, std::process::id()));
+        fs::write(&file, script).unwrap();
+        file
+    }
+
+    #[test]
+    fn parses_path_scripts() {
+        let (regex, file) = parse_path_script("^/orders/\\d+=scripts/echo.rhai").unwrap();
+        expect!(regex.as_str()).to(be_equal_to("^/orders/\\d+"));
+        expect!(file).to(be_equal_to(PathBuf::from("scripts/echo.rhai")));
+        expect!(parse_path_script("scripts/echo.rhai")).to(be_err());
+        expect!(parse_path_script("/orders=")).to(be_err());
+        expect!(parse_path_script("(=echo.rhai")).to(be_err());
+    }
+
+    #[test]
+    fn changes_the_response_with_the_script_for_the_interaction() {
+        let echo = script_file("echo", r#"
+            response.body.name = request.body.name;
+            response.headers["X-Order-Id"] = request.query.id;
+            response.status = 201;
+        "#);
+        let pact = Pact {
+            interactions: vec![
+                Interaction { description: s!("create an order"), .. Interaction::default() },
+                Interaction { description: s!("get an order"), .. Interaction::default() }
+            ],
+            metadata: btreemap!{ s!("scripts") => btreemap!{ s!("create an order") => echo.to_string_lossy().to_string() } },
+            .. Pact::default()
+        };
+        let scripts = Scripts::load(vec![], std::slice::from_ref(&pact)).unwrap();
+        let request = Request {
+            method: s!("POST"),
+            query: Some(hashmap!{ s!("id") => vec![ s!("42") ] }),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present("{\"name\": \"Fred\"}".into()),
+            .. Request::default_request()
+        };
+        let response = Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present("{\"id\": 1, \"name\": \"Example\"}".into()),
+            .. Response::default_response()
+        };
+
+        let scripted = scripts.apply(&pact, &pact.interactions[0], &request, response.clone()).unwrap();
+        expect!(scripted.status).to(be_equal_to(201));
+        expect!(scripted.headers.unwrap().get("X-Order-Id").cloned()).to(be_some().value(vec![ s!("42") ]));
+        let body: Value = serde_json::from_slice(scripted.body.value().as_slice()).unwrap();
+        expect!(body).to(be_equal_to(json!({ "id": 1, "name": "Fred" })));
+
+        let unscripted = scripts.apply(&pact, &pact.interactions[1], &request, response.clone()).unwrap();
+        expect!(unscripted).to(be_equal_to(response));
+        fs::remove_file(echo).unwrap();
+    }
+
+    #[test]
+    fn uses_the_script_for_the_request_path() {
+        let failing = script_file("failing", "response.status = \"not a status\";");
+        let scripts = Scripts::load(vec![ parse_path_script(&format!("^/orders={}", failing.display())).unwrap() ], &[]).unwrap();
+        let pact = Pact::default();
+        let interaction = Interaction::default();
+        let orders = Request { path: s!("/orders"), .. Request::default_request() };
+        let customers = Request { path: s!("/customers"), .. Request::default_request() };
+
+        expect!(scripts.apply(&pact, &interaction, &orders, Response::default_response())).to(be_err());
+        expect!(scripts.apply(&pact, &interaction, &customers, Response::default_response())).to(be_ok());
+        fs::remove_file(failing).unwrap();
+    }
+}
@@ -1,48 +1,341 @@
-use http::StatusCode;
-use hyper::{Body, Error as HyperError, Request as HyperRequest, Response as HyperResponse, Server};
-use hyper::rt::Future;
-use hyper::rt::Stream;
-use hyper::service::NewService;
-use hyper::service::Service;
+use bytes::Bytes;
+use http::{Method, StatusCode, Uri};
+use http::HeaderMap;
+use http::request::Parts;
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, EXPECT, HOST, HeaderName, HeaderValue};
+use http_body_util::{BodyExt, Full, Limited};
+use http_body_util::LengthLimitError;
+use hyper::{Request as HyperRequest, Response as HyperResponse};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioIo, TokioTimer};
+use hyper_util::server::graceful::GracefulShutdown;
+use crate::index::{InteractionIndex, InteractionPosition};
 use itertools::Itertools;
 use pact_matching::{self, Mismatch};
 use pact_matching::models::{Interaction, Pact, Request, Response};
-use pact_matching::models::OptionalBody;
-use pact_support;
-use std::sync::Arc;
-use tokio::prelude::Async;
-use tokio::prelude::future;
-use tokio::prelude::future::FutureResult;
-use tokio::prelude::IntoFuture;
+use pact_matching::models::{DetectedContentType, HttpPart, OptionalBody};
+use pact_matching::models::parse_query_string;
+use crate::pact_support;
+use crate::access::AccessCredentials;
+use crate::admin;
+use crate::callbacks::Callbacks;
+use crate::capture::{self, CaptureDir};
+use crate::clients::ClientFilter;
+use crate::compare;
+use crate::compression;
+use crate::control::RequestControls;
+use crate::coverage::{self, Coverage};
+use crate::diff;
+use crate::dynamic::{self, Interactions, Snapshot};
+use crate::generators::{self, StateGenerator, StateGenerators};
+use crate::journal::{self, Journal};
+use crate::matching::{self, MatchingOptions, other_trailing_slash_form};
+use crate::metrics::{self, Metrics};
+use crate::negotiation;
+use crate::overrides::InteractionOverrides;
+use crate::priorities;
+use crate::privileges::PrivilegeDrop;
+use crate::proxy::ProxyClient;
+use crate::quiet;
+use crate::ranges;
+use crate::ratelimit::RateLimit;
+use crate::recorder::Recorder;
+use crate::response_cache::CachedResponse;
+use crate::rewrite::{self, PathRewrite};
+use crate::scripts::Scripts;
+use crate::telemetry::{self, Tracer, TRACEPARENT};
+use crate::templates::ResponseTemplates;
+use crate::tenants::TenantTokens;
+use crate::timeouts::WriteTimeout;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fs;
+use std::future::{self, Future};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
 use tokio::runtime::Runtime;
+use tokio::signal;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore, watch};
+use tokio::time;
 use regex::Regex;
+use serde_json::Value;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// How multiple provider state filters are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProviderStateMode {
+    /// The interaction must have a provider state matching at least one of the filters
+    Any,
+    /// Every filter must match at least one of the interaction's provider states
+    All,
+    /// The interaction must not have any provider states (there are no filters)
+    Stateless
+}
+
+/// Filters the interactions by their provider states
+#[derive(Debug, Clone)]
+pub struct ProviderStateFilter {
+    pub states: Vec<Regex>,
+    pub mode: ProviderStateMode
+}
+
+impl ProviderStateFilter {
+    pub fn new(states: Vec<Regex>, mode: ProviderStateMode) -> ProviderStateFilter {
+        ProviderStateFilter { states, mode }
+    }
+
+    /// Only the interactions without any provider states
+    pub fn stateless() -> ProviderStateFilter {
+        ProviderStateFilter::new(vec![], ProviderStateMode::Stateless)
+    }
+
+    pub fn matches(&self, interaction: &Interaction) -> bool {
+        let state_matches = |regex: &Regex| interaction.provider_states.iter()
+            .any(|state| regex.is_match(state.name.as_str()));
+        match self.mode {
+            ProviderStateMode::Any => self.states.iter().any(state_matches),
+            ProviderStateMode::All => self.states.iter().all(state_matches),
+            ProviderStateMode::Stateless => interaction.provider_states.is_empty()
+        }
+    }
+}
+
+impl From<Regex> for ProviderStateFilter {
+    fn from(regex: Regex) -> Self {
+        ProviderStateFilter::new(vec![ regex ], ProviderStateMode::Any)
+    }
+}
+
+impl Display for ProviderStateFilter {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let separator = match self.mode {
+            ProviderStateMode::Any => "' or '",
+            ProviderStateMode::All => "' and '",
+            ProviderStateMode::Stateless => return write!(f, "no provider states")
+        };
+        write!(f, "'{}'", self.states.iter().map(|state| state.as_str()).join(separator))
+    }
+}
+
+/// How requests with an `Expect: 100-continue` header are handled
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExpectContinue {
+    /// Send the 100 (Continue) interim response, and read the body
+    #[default]
+    Always,
+    /// Check that an interaction matches the request without its body first, and respond with a 404 straight away
+    /// (without reading the body) if none do
+    Check
+}
+
+/// Where the server listens for requests
+#[derive(Debug, Clone, Default)]
+pub struct ListenOptions {
+    /// Port to listen on, or 0 for a random port
+    pub port: u16,
+    /// Number of following ports to try if the port is already in use
+    pub port_range: u16,
+    /// File to write the port the server is listening on to
+    pub port_file: Option<PathBuf>,
+    /// Listen on this Unix domain socket instead of a port
+    pub unix_socket: Option<PathBuf>,
+    /// Also listen on these ports, serving only the interactions of the providers for each one
+    pub provider_ports: BTreeMap<u16, Vec<String>>
+}
+
+/// Settings for the connections clients make to the server
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionOptions {
+    /// Keep connections open between requests, instead of closing them after each response
+    pub keep_alive: bool,
+    /// Close kept-alive connections that have not started a request for this long
+    pub idle_timeout: Option<Duration>,
+    /// Maximum number of connections that are served at once. Further connections wait until one is closed.
+    pub max_connections: Option<usize>,
+    /// Respond with a 408 if the request body has not been received within this time. If there is no idle timeout, it
+    /// also limits how long the request headers can take.
+    pub read_timeout: Option<Duration>,
+    /// Close the connection if the client does not accept any of the response for this long
+    pub write_timeout: Option<Duration>
+}
+
+type MatchResult<'a> = (&'a Interaction, Vec<Mismatch>);
+/// An interaction that matches a request, with its position in the pacts
+type MatchedInteraction<'a> = (InteractionPosition, &'a Interaction, Vec<Mismatch>);
+
+/// Options that control how the stub server responds to requests
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptions {
+    /// Automatically respond to OPTIONS requests and add CORS headers
+    pub auto_cors: bool,
+    /// Log the expected and actual bodies of mismatched requests, and a diff of them
+    pub print_missmatching_bodies: bool,
+    /// Only use the interactions with provider states matching this filter
+    pub provider_state: Option<ProviderStateFilter>,
+    /// Name of the request header that can be used to supply the provider state filter
+    pub provider_state_header_name: Option<String>,
+    /// Name of the request header that can be used to supply a regex for the descriptions of the interactions
+    pub interaction_header_name: Option<String>,
+    /// Only use the interactions from pacts with these providers
+    pub providers: Option<Vec<String>>,
+    /// Respond with a 403 to clients with other IP addresses
+    pub clients: Option<ClientFilter>,
+    /// Rules that rewrite the request paths (after the base path is removed) before they are handled
+    pub rewrites: Vec<PathRewrite>,
+    /// Only use the interactions of the provider named by the first label of the Host header of the request (like
+    /// `users` for `users.localhost:8080`), if there is one with that name
+    pub virtual_hosts: bool,
+    /// Remove this prefix from the request paths (like `/mock/users` for a gateway that routes the requests with it)
+    /// before handling them
+    pub base_path: Option<String>,
+    /// Credentials the clients must send to use any of the endpoints
+    pub access: Option<AccessCredentials>,
+    /// Restrict each client to the providers mapped to its bearer token
+    pub tenants: Option<TenantTokens>,
+    /// Options used when matching requests against the interactions
+    pub matching: MatchingOptions,
+    /// Forward requests that do not match any interaction to a real provider
+    pub proxy: Option<ProxyClient>,
+    /// Also send the matched requests to a real provider, and log the differences from the stubbed responses
+    pub compare_with: Option<ProxyClient>,
+    /// Record the proxied requests and responses as interactions
+    pub recorder: Option<Arc<Recorder>>,
+    /// Respond with this status if the request body is not valid JSON, but an interaction would otherwise match
+    pub malformed_json_status: Option<u16>,
+    /// Collect the body sizes of the matched requests and their responses, and serve them from the metrics endpoint
+    pub metrics: Option<Arc<Metrics>>,
+    /// Count the requests each interaction is used for, and serve them from the coverage endpoint
+    pub coverage: Option<Arc<Coverage>>,
+    /// Keep the most recent requests, and serve them from the requests endpoint
+    pub journal: Option<Arc<Journal>>,
+    /// Render the response bodies and headers as templates with values from the request
+    pub templates: Option<Arc<ResponseTemplates>>,
+    /// Send the responses as they are in the pacts, instead of replacing the values that have generators with
+    /// generated ones
+    pub skip_generators: bool,
+    /// Seed the random generators with this, so each interaction has the same generated values every time
+    pub generator_seed: Option<u64>,
+    /// Scripts that change the responses of interactions, like copying values from the request into them
+    pub scripts: Option<Arc<Scripts>>,
+    /// Functions to call for each request that matches an interaction, or does not match any
+    pub callbacks: Callbacks,
+    /// When the server shuts down, exit with an error if any of the interactions were not used
+    pub fail_on_unused: bool,
+    /// Respond with a 204 to requests that browsers make on their own (like `/favicon.ico`), if no interaction
+    /// matches them
+    pub answer_browser_probes: bool,
+    /// Settings for interactions from outside the pacts, like marking them as drafts
+    pub overrides: Option<Arc<InteractionOverrides>>,
+    /// Only match interactions that expect an Authorization header if the request has one of the same shape
+    pub enforce_auth: bool,
+    /// Compress the response bodies with an encoding from the Accept-Encoding header of the request
+    pub compress_responses: bool,
+    /// How requests with an `Expect: 100-continue` header are handled
+    pub expect_continue: ExpectContinue,
+    /// Settings for the client connections
+    pub connections: ConnectionOptions,
+    /// Respond with a 413 to requests with a body larger than this, without reading all of it
+    pub max_body_size: Option<usize>,
+    /// Respond with a 429 to the requests over this limit
+    pub rate_limit: Option<RateLimit>,
+    /// Switch to another user and group once the port is bound
+    pub drop_privileges: Option<PrivilegeDrop>,
+    /// Replace the loaded pacts with the ones sent on this channel (like when the sources are loaded again)
+    pub refreshed_pacts: Option<watch::Receiver<(Vec<Arc<Pact>>, StateGenerators)>>,
+    /// Serve the webhook endpoint, which notifies this to load the pacts from the sources again
+    pub webhook_reload: Option<Arc<Notify>>,
+    /// Export a span for each request to an OpenTelemetry collector
+    pub tracer: Option<Arc<Tracer>>,
+    /// Name of the request header with the correlation ID, that is sent back with every response (and generated if
+    /// the request does not have one)
+    pub correlation_header: Option<String>,
+    /// Only log the errors of requests with paths matching any of these (like health checks or polling)
+    pub quiet_paths: Vec<Regex>,
+    /// Write each request and the response served for it to this directory
+    pub capture: Option<Arc<CaptureDir>>,
+    /// Headers added to every response that does not already have them
+    pub added_response_headers: Vec<(HeaderName, HeaderValue)>,
+    /// Headers set on every response, replacing the ones it has with the same names
+    pub overridden_response_headers: Vec<(HeaderName, HeaderValue)>,
+    /// Only match the interactions with descriptions matching this regex (like the one from the interaction control
+    /// header of the request)
+    pub description_filter: Option<Regex>,
+    /// Send headers identifying the interaction (its key, consumer and description) with the responses of the matched
+    /// requests
+    pub interaction_headers: bool,
+    /// Cache the responses of GET requests that are the same for every request, so identical requests are answered
+    /// without matching them again
+    pub response_cache: bool
+}
+
+/// What is recorded about the request being handled, and the values given with it
+#[derive(Clone, Default)]
+struct RequestContext {
+    /// Span that the matched interaction is recorded in
+    span: Option<tracing::Span>,
+    /// Body of the request once it is read, for the capture directory
+    body: Option<Arc<Mutex<Bytes>>>,
+    /// Values for the provider state generators of the responses
+    state_values: HashMap<String, String>,
+    /// Status code to send instead of the one of the response, from the control headers
+    status: Option<u16>
+}
 
 #[derive(Clone)]
 pub struct ServerHandler {
-    sources: Arc<Vec<Pact>>,
-    auto_cors: bool,
-    provider_state: Option<Regex>,
-    provider_state_header_name: Option<String>,
-    print_missmatching_bodies: bool,
+    interactions: Arc<RwLock<Interactions>>,
+    options: ServerOptions,
+    last_request: Arc<Mutex<Instant>>
+}
+
+/// Limits on how long the server runs for before it shuts itself down
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServeLimits {
+    /// Shut down after running for this long
+    pub serve_for: Option<Duration>,
+    /// Shut down once no requests have been received for this long
+    pub until_idle: Option<Duration>
 }
 
 fn method_supports_payload(request: &Request) -> bool {
-    match request.method.to_uppercase().as_str() {
-        "POST" | "PUT" | "PATCH" => true,
-        _ => false
+    matches!(request.method.to_uppercase().as_str(), "POST" | "PUT" | "PATCH")
+}
+
+pub fn describe_mismatch(mismatch: &Mismatch) -> String {
+    match mismatch {
+        Mismatch::MethodMismatch { expected, actual } =>
+            format!("HTTP Method does not match, expected: {}, actual: {}", expected, actual),
+        Mismatch::PathMismatch { mismatch, .. } =>
+            format!("Path does not match: {}", mismatch),
+        Mismatch::QueryMismatch { mismatch, .. } =>
+            format!("Query does not match: {}", mismatch),
+        Mismatch::HeaderMismatch { mismatch, .. } =>
+            format!("Header does not match: {}", mismatch),
+        Mismatch::BodyTypeMismatch { expected, actual } =>
+            format!("Body type does not match, expected: {}, actual: {}", expected, actual),
+        Mismatch::BodyMismatch { path, mismatch, .. } =>
+            format!("Body does not match at path '{}': {}", path, mismatch),
+        _ => String::from("Unexpected Mismatch type"),
     }
 }
 
-fn explain_mismatches(request: &Request, mismatches: &Vec<(Interaction, Vec<Mismatch>)>) {
+fn explain_mismatches(request: &Request, mismatches: &[MatchResult], options: &ServerOptions) {
     warn!("");
     warn!("No pact request matched out of a total of {}", mismatches.len());
     warn!("Received request: {} {}", request.method, request.path);
     let interactions_with_path_match = mismatches.iter()
         .filter(|(_, ref ms)|
-            !ms.iter().any(|x| match x {
-                Mismatch::PathMismatch { .. } => true,
-                _ => false
-            }))
+            !ms.iter().any(|x| matches!(x, Mismatch::PathMismatch { .. })))
         .collect_vec();
     match interactions_with_path_match.len() {
         0 => warn!("Mismatch reason: No expected request with path {} found", request.path),
@@ -52,72 +345,397 @@ fn explain_mismatches(request: &Request, mismatches: &Vec<(Interaction, Vec<Mism
                 .iter()
                 .enumerate()
                 .map(|(i, (interaction, m))| {
+                    // only log body if both the expected request and the incoming request has a body
+                    let has_bodies = method_supports_payload(request) && method_supports_payload(&interaction.request);
                     let description = m.iter()
                         .filter(|m| match m {
-                            Mismatch::BodyMismatch { .. } => {
-                                // only log body if both the expected request and the incoming request has a body
-                                method_supports_payload(request) && method_supports_payload(&interaction.request)
-                            }
+                            Mismatch::BodyMismatch { .. } => has_bodies,
                             _ => true
                         })
-                        .map(|m| match m {
-                            Mismatch::MethodMismatch { expected, actual } =>
-                                format!("HTTP Method does not match, expected: {}, actual: {}", expected, actual),
-                            Mismatch::QueryMismatch { mismatch, .. } =>
-                                format!("Query does not match: {}", mismatch),
-                            Mismatch::HeaderMismatch { mismatch, .. } =>
-                                format!("Header does not match: {}", mismatch),
-                            Mismatch::BodyTypeMismatch { expected, actual } =>
-                                format!("Body type does not match, expected: {}, actual: {}", expected, actual),
-                            Mismatch::BodyMismatch { path, mismatch, .. } =>
-                                format!("Body does not match at path '{}': {}", path, mismatch),
-                            _ => String::from("Unexpected Mismatch type"),
-                        }).join("\n");
-                    return format!("Mismatched request {} ({}):\n{}", i + 1, request, description);
+                        .map(describe_mismatch)
+                        .join("\n");
+                    let body_mismatch = m.iter().any(|m| matches!(m, Mismatch::BodyMismatch { .. } | Mismatch::BodyTypeMismatch { .. }));
+                    if options.print_missmatching_bodies && has_bodies && body_mismatch {
+                        format!("Mismatched request {} ({}):\n{}\nExpected body:\n{}\nActual body:\n{}\n{}", i + 1, request,
+                                description, pact_support::body_for_display(&interaction.request),
+                                pact_support::body_for_display(request),
+                                diff::body_diff(&interaction.request.body, &request.body, diff::use_colors()))
+                    } else {
+                        format!("Mismatched request {} ({}):\n{}", i + 1, request, description)
+                    }
                 })
                 .for_each(|m| warn!("{}", m));
         }
     }
 }
 
-fn find_matching_request(request: &Request, auto_cors: bool, sources: &Vec<Pact>, provider_state: Option<Regex>, print_missmatching_bodies: bool) -> Result<Response, String> {
-    if let Some(ref state) = provider_state {
-        info!("Filtering interactions by provider state regex '{}'", state)
-    }
-    let (matches, mismatches): (Vec<(Interaction, Vec<Mismatch>)>, Vec<(Interaction, Vec<Mismatch>)>) =
-        sources
-            .iter()
-            .flat_map(|pact| &pact.interactions)
-            .filter(|i| match provider_state {
-                Some(ref regex) => i.provider_states.iter()
-                    .any(|state| regex.is_match(state.name.as_str())),
-                None => true
-            })
-            .map(|i| (i.clone(), pact_matching::match_request(i.request.clone(), request.clone())))
-            .partition(|&(_, ref mismatches)| mismatches.iter().all(|mismatch| {
-                match mismatch {
-                    Mismatch::MethodMismatch { .. } => false,
-                    Mismatch::PathMismatch { .. } => false,
-                    Mismatch::QueryMismatch { .. } => false,
-                    Mismatch::BodyMismatch { .. } =>
-                        !(method_supports_payload(request) && request.body.is_present()),
-                    _ => true
-                }
-            }));
+fn is_candidate(request: &Request, mismatches: &[Mismatch]) -> bool {
+    mismatches.iter().all(|mismatch| {
+        match mismatch {
+            Mismatch::MethodMismatch { .. } => false,
+            Mismatch::PathMismatch { .. } => false,
+            Mismatch::QueryMismatch { .. } => false,
+            Mismatch::BodyMismatch { .. } =>
+                !(method_supports_payload(request) && request.body.is_present()),
+            _ => true
+        }
+    })
+}
+
+fn auth_scheme(value: &str) -> String {
+    value.split_whitespace().next().unwrap_or("").to_lowercase()
+}
+
+/// Checks the Authorization header of the request against the one the interaction expects. The request must have the
+/// header with the same scheme (like `Bearer`), and if the interaction has a matching rule for the header, it must
+/// match it.
+fn is_authorised(interaction: &Interaction, request: &Request, mismatches: &[Mismatch]) -> bool {
+    let authorization = s!("authorization");
+    match (interaction.request.lookup_header_value(&authorization), request.lookup_header_value(&authorization)) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(expected), Some(actual)) => {
+            let has_rule = interaction.request.matching_rules.rules_for_category(&s!("header"))
+                .map(|category| category.rules.keys().any(|key| key.eq_ignore_ascii_case(&authorization)))
+                .unwrap_or(false);
+            if has_rule {
+                !mismatches.iter().any(|mismatch| matches!(mismatch,
+                    Mismatch::HeaderMismatch { key, .. } if key.eq_ignore_ascii_case(&authorization)))
+            } else {
+                auth_scheme(&expected) == auth_scheme(&actual)
+            }
+        }
+    }
+}
+
+fn unauthorised_response(request: &Request, mismatches: &[MatchResult], options: &ServerOptions) -> Option<Response> {
+    mismatches.iter()
+        .find(|(interaction, mismatches)| is_candidate(request, mismatches) &&
+            !is_authorised(interaction, request, mismatches))
+        .map(|(interaction, _)| {
+            warn!("Request {} {} does not have the Authorization header expected by '{}', sending {}", request.method,
+                  request.path, interaction.description, StatusCode::UNAUTHORIZED);
+            let mut response = error_response(StatusCode::UNAUTHORIZED, options);
+            let scheme = interaction.request.lookup_header_value(&s!("authorization"))
+                .and_then(|value| value.split_whitespace().next().map(String::from))
+                .unwrap_or_else(|| s!("Bearer"));
+            pact_support::add_header(&mut response, "WWW-Authenticate", &scheme);
+            response
+        })
+}
+
+/// Response header with the key of the interaction the response is from, which is the same each time it is loaded
+pub const INTERACTION_ID_HEADER: &str = "X-Pact-Stub-Interaction-Id";
+/// Response header with the consumer of the pact the interaction is from
+pub const INTERACTION_CONSUMER_HEADER: &str = "X-Pact-Stub-Interaction-Consumer";
+/// Response header with the description of the interaction the response is from
+pub const INTERACTION_DESCRIPTION_HEADER: &str = "X-Pact-Stub-Interaction-Description";
+
+/// Paths that browsers (and their developer tools) request without being asked to
+static BROWSER_PROBE_PATHS: [&str; 3] = [ "/favicon.ico", "/robots.txt", "/.well-known/appspecific/com.chrome.devtools.json" ];
+
+fn is_browser_probe(request: &Request) -> bool {
+    let method = request.method.to_uppercase();
+    (method == "GET" || method == "HEAD") && BROWSER_PROBE_PATHS.contains(&request.path.as_str())
+}
+
+fn is_body_mismatch(mismatch: &Mismatch) -> bool {
+    matches!(mismatch, Mismatch::BodyMismatch { .. } | Mismatch::BodyTypeMismatch { .. })
+}
+
+fn malformed_json_response(request: &Request, mismatches: &[MatchResult], status: u16) -> Option<Response> {
+    if !request.body.is_present() || !matches!(request.content_type_enum(), DetectedContentType::Json) {
+        return None;
+    }
+    let error = match serde_json::from_slice::<Value>(pact_support::body_bytes(&request.body)) {
+        Ok(_) => return None,
+        Err(err) => err
+    };
+    let otherwise_matches = mismatches.iter().any(|(_, ms)| {
+        let other_mismatches = ms.iter().filter(|m| !is_body_mismatch(m)).cloned().collect_vec();
+        is_candidate(request, &other_mismatches)
+    });
+    if otherwise_matches {
+        warn!("Request body is not valid JSON ({}), sending {}", error, status);
+        Some(Response {
+            status,
+            headers: Some(hashmap!{ s!("Content-Type") => vec![s!("application/json")] }),
+            body: OptionalBody::Present(json!({
+                "error": "Malformed JSON request body",
+                "message": error.to_string()
+            }).to_string().into()),
+            .. Response::default_response()
+        })
+    } else {
+        None
+    }
+}
+
+/// Response to an OPTIONS request for a path that there are interactions for, allowing the methods of those
+/// interactions (with HEAD if there is a GET interaction, as they are answered from it)
+fn allowed_methods_response(mismatches: &[MatchResult]) -> Option<Response> {
+    let mut methods = mismatches.iter()
+        .filter(|(_, mismatches)| !mismatches.iter().any(|m| matches!(m, Mismatch::PathMismatch { .. })))
+        .map(|(interaction, _)| interaction.request.method.to_uppercase())
+        .collect_vec();
+    if methods.is_empty() {
+        return None;
+    }
+    if methods.iter().any(|method| method == "GET") {
+        methods.push(s!("HEAD"));
+    }
+    methods.push(s!("OPTIONS"));
+    let allow = methods.into_iter().unique().join(", ");
+    Some(Response {
+        status: StatusCode::NO_CONTENT.as_u16(),
+        headers: Some(hashmap!{
+            s!("Allow") => vec![ allow.clone() ],
+            s!("Access-Control-Allow-Methods") => vec![ allow ]
+        }),
+        .. Response::default_response()
+    })
+}
+
+fn draft_response(interaction: &Interaction) -> Response {
+    Response {
+        status: StatusCode::NOT_IMPLEMENTED.as_u16(),
+        headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+        body: OptionalBody::Present(json!({
+            "error": "Not yet implemented",
+            "interaction": interaction.description
+        }).to_string().into_bytes()),
+        .. Response::default_response()
+    }
+}
+
+/// Emits a debug event about a matching decision. `tracing` only sends its events to the log until a subscriber has
+/// been set anywhere (like the one of the OpenTelemetry spans), so after that the event is logged here when no
+/// subscriber gets it.
+macro_rules! matching_event {
+    ($message:literal $(, $field:ident = $value:expr)*) => {{
+        let ($($field,)*) = ($($value,)*);
+        tracing::debug!($($field = %$field,)* $message);
+        if tracing::dispatcher::has_been_set() &&
+            tracing::dispatcher::get_default(|dispatch| dispatch.is::<tracing::subscriber::NoSubscriber>()) {
+            debug!(concat!($message $(, " ", stringify!($field), "={}")*) $(, $field)*);
+        }
+    }};
+}
+
+fn match_interactions<'a, I>(request: &Request, sources: &'a [Arc<Pact>], positions: I, options: &ServerOptions)
+    -> (Vec<MatchedInteraction<'a>>, Vec<MatchResult<'a>>) where I: Iterator<Item=InteractionPosition> {
+    let (matches, mismatches): (Vec<MatchedInteraction>, Vec<MatchedInteraction>) = positions
+        .map(|position| (position, &sources[position.0]))
+        .filter(|(_, pact)| match options.providers {
+            Some(ref providers) => providers.contains(&pact.provider.name),
+            None => true
+        })
+        .map(|(position, pact)| (position, &pact.interactions[position.1]))
+        .filter(|(_, i)| match options.provider_state {
+            Some(ref filter) => filter.matches(i),
+            None => true
+        })
+        .filter(|(_, i)| match options.description_filter {
+            Some(ref filter) => filter.is_match(&i.description),
+            None => true
+        })
+        .map(|(position, i)| (position, i, matching::match_request(&i.request, request, &options.matching)))
+        .partition(|(_, interaction, mismatches)| {
+            let candidate = is_candidate(request, mismatches) &&
+                (!options.enforce_auth || is_authorised(interaction, request, mismatches));
+            matching_event!("considered interaction", interaction = &interaction.description,
+                mismatches = format!("{:?}", mismatches.iter().map(describe_mismatch).collect_vec()),
+                score = matching::mismatch_score(mismatches), candidate = candidate);
+            candidate
+        });
+    (matches, mismatches.into_iter().map(|(_, interaction, mismatches)| (interaction, mismatches)).collect())
+}
+
+/// Generates the response of the interaction, and renders its templates, then applies the plugins and scripts to it
+fn generate_response(request: &Request, pact: &Pact, interaction: &Interaction, state_generators: &[StateGenerator],
+                     context: &RequestContext, options: &ServerOptions) -> Result<Response, String> {
+    let mut response = if options.skip_generators {
+        interaction.response.clone()
+    } else {
+        let response = match options.generator_seed {
+            Some(seed) => generators::generate_response(&interaction.response, &mut generators::seeded_rng(seed, interaction)),
+            None => generators::generate_response(&interaction.response, &mut rand::thread_rng())
+        };
+        generators::apply_provider_state_generators(state_generators, interaction, response, &context.state_values)
+    };
+    if let Some(ref templates) = options.templates {
+        response = templates.render(request, response)?;
+    }
+    if let Some(ref plugins) = options.matching.plugins {
+        response = plugins.generate_response(request, response);
+    }
+    if let Some(ref scripts) = options.scripts {
+        response = scripts.apply(pact, interaction, request, response)?;
+    }
+    Ok(response)
+}
+
+/// The priority of the interaction from the overrides, or else the metadata of its pact
+fn interaction_priority(pact: &Pact, interaction: &Interaction, options: &ServerOptions) -> i32 {
+    options.overrides.as_ref()
+        .and_then(|overrides| overrides.priority(&interaction.description))
+        .or_else(|| priorities::interaction_priority(pact, &interaction.description))
+        .unwrap_or(0)
+}
+
+/// The interactions that match the request, and the mismatches with the others
+fn matching_interactions<'a>(request: &Request, sources: &'a [Arc<Pact>], index: &InteractionIndex, options: &ServerOptions)
+    -> (Vec<MatchedInteraction<'a>>, Vec<MatchResult<'a>>) {
+    let mut positions = index.candidates(&request.method, &request.path);
+    if let Some(path) = other_trailing_slash_form(&request.path).filter(|_| options.matching.ignore_trailing_slash) {
+        positions.extend(index.candidates(&request.method, &path));
+        positions.sort();
+        positions.dedup();
+    }
+    match match_interactions(request, sources, positions.into_iter(), options) {
+        (ref matches, _) if matches.is_empty() => {
+            // compare the request with all the interactions, so the closest ones can be explained
+            let all = sources.iter().enumerate()
+                .flat_map(|(pact_index, pact)| (0..pact.interactions.len()).map(move |index| (pact_index, index)));
+            match_interactions(request, sources, all, options)
+        },
+        result => result
+    }
+}
+
+fn find_matching_request(request: &Request, snapshot: &Snapshot, context: &RequestContext, options: &ServerOptions) -> Result<Response, String> {
+    find_matching_interaction(request, snapshot, context, options).map(|(response, _)| response)
+}
+
+/// Finds the response for the request, from the response cache if the same request was answered before. Only the
+/// responses that are the same for every request that matches the interaction are cached.
+fn find_response(request: &Request, snapshot: &Snapshot, context: &RequestContext, options: &ServerOptions) -> Result<Response, String> {
+    let key = match snapshot.responses.key(request, &response_selection(options)).filter(|_| options.response_cache) {
+        Some(key) => key,
+        None => return find_matching_request(request, snapshot, context, options)
+    };
+    if let Some(cached) = snapshot.responses.get(&key) {
+        let pact = &snapshot.sources[cached.position.0];
+        let interaction = &pact.interactions[cached.position.1];
+        debug!("Using the cached response of '{}' for {} {}", interaction.description, request.method, request.path);
+        record_match(request, pact, interaction, cached.mismatches, &cached.response, context, options);
+        return Ok(cached.response);
+    }
+    let (response, matched) = find_matching_interaction(request, snapshot, context, options)?;
+    if let Some((position, mismatches)) = matched {
+        let interaction = &snapshot.sources[position.0].interactions[position.1];
+        if is_cacheable(interaction, snapshot.state_generators.get(position), context, options) {
+            snapshot.responses.insert(key, CachedResponse { position, mismatches, response: response.clone() });
+        }
+    }
+    Ok(response)
+}
+
+/// What selects the interactions for a request, other than the request itself
+fn response_selection(options: &ServerOptions) -> String {
+    format!("{:?} {:?} {:?}", options.providers, options.provider_state.as_ref().map(|state| state.to_string()),
+            options.description_filter.as_ref().map(|regex| regex.as_str()))
+}
+
+/// If the response of the interaction is the same for every request that matches it, as it does not have any generated
+/// values or values from the request
+fn is_cacheable(interaction: &Interaction, state_generators: &[StateGenerator], context: &RequestContext, options: &ServerOptions) -> bool {
+    let generated = !options.skip_generators && (!interaction.response.generators.categories.is_empty() ||
+        !state_generators.is_empty());
+    !generated && options.templates.is_none() && options.scripts.is_none() && options.matching.plugins.is_none() &&
+        context.state_values.is_empty()
+}
+
+/// Records that the request matched the interaction, with the callbacks, trace span, metrics and coverage
+fn record_match(request: &Request, pact: &Pact, interaction: &Interaction, mismatches: usize, response: &Response,
+                context: &RequestContext, options: &ServerOptions) {
+    options.callbacks.matched(request, interaction);
+    if let Some(ref span) = context.span {
+        telemetry::set_attribute(span, "pact.matched", true);
+        telemetry::set_attribute(span, "pact.interaction", interaction.description.clone());
+        telemetry::set_attribute(span, "pact.provider_state", opentelemetry::Value::Array(opentelemetry::Array::String(
+            interaction.provider_states.iter().map(|state| state.name.clone().into()).collect())));
+        telemetry::set_attribute(span, "pact.mismatches", mismatches as i64);
+    }
+    if let Some(ref metrics) = options.metrics {
+        metrics.record(pact, interaction, pact_support::body_bytes(&request.body).len(), pact_support::body_bytes(&response.body).len());
+    }
+    if let Some(ref coverage) = options.coverage {
+        coverage.record(pact, interaction);
+    }
+}
+
+/// Finds the interaction that matches the request and generates its response. Also returns the position of the
+/// interaction and the number of mismatches the request had with it, if one matched.
+fn find_matching_interaction(request: &Request, snapshot: &Snapshot, context: &RequestContext, options: &ServerOptions)
+    -> Result<(Response, Option<(InteractionPosition, usize)>), String> {
+    let (sources, index) = (&snapshot.sources, &snapshot.index);
+    let span = tracing::debug_span!("find_matching_request", method = %request.method, path = %request.path);
+    let _entered = span.enter();
+    if let Some(ref state) = options.provider_state {
+        info!("Filtering interactions by provider state {}", state)
+    }
+    let (matches, mismatches) = matching_interactions(request, sources, index, options);
+    if matches.is_empty() && request.method.eq_ignore_ascii_case("HEAD") {
+        let get = Request { method: s!("GET"), .. request.clone() };
+        if !matching_interactions(&get, sources, index, options).0.is_empty() {
+            // hyper sends the length of the body, but not the body, in the responses to HEAD requests
+            debug!("No interaction for HEAD {}, responding with the status and headers of the GET interaction", request.path);
+            return find_matching_interaction(&get, snapshot, context, options);
+        }
+    }
+    // prefer the interactions with an acceptable response, then the lowest mismatch score, then the highest priority,
+    // then the preferred content type
     match matches
         .iter()
-        .sorted_by(|(_, missmatches_a), (_, missmatches_b)| Ord::cmp(&missmatches_a.len(), &missmatches_b.len()))
-        .iter()
-        .map(|(i, _)| i)
-        .collect::<Vec<&Interaction>>()
+        .map(|(position, interaction, mismatches)| {
+            let quality = negotiation::response_quality(request, &interaction.response);
+            let priority = interaction_priority(&sources[position.0], interaction, options);
+            ((quality == 0, matching::mismatch_score(mismatches), Reverse(priority), Reverse(quality)), (*position, *interaction, mismatches.len()))
+        })
+        .sorted_by(|(a, _), (b, _)| Ord::cmp(a, b))
         .first() {
-        Some(interaction) => {
-            warn!("Found more than one pact request for {} {}, using the first one with the least number of mismatches",
-                  request.method, request.path);
-            Ok(pact_matching::generate_response(&interaction.response))
+        Some(&((_, score, _, _), (position, interaction, mismatches))) => {
+            if matches.len() > 1 {
+                warn!("Found more than one pact request for {} {}, using '{}' (mismatch score {}) as it has an acceptable content type, the lowest mismatch score and the highest priority",
+                      request.method, request.path, interaction.description, score);
+            }
+            matching_event!("selected interaction", interaction = &interaction.description, score = score,
+                candidates = matches.len());
+            let is_draft = options.overrides.as_ref()
+                .map(|overrides| overrides.is_draft(&interaction.description))
+                .unwrap_or(false);
+            let pact = &sources[position.0];
+            let response = if is_draft {
+                info!("Interaction '{}' is a draft, sending {}", interaction.description, StatusCode::NOT_IMPLEMENTED);
+                draft_response(interaction)
+            } else {
+                generate_response(request, pact, interaction, snapshot.state_generators.get(position), context, options).unwrap_or_else(|err| {
+                    error!("{}, sending {}", err, StatusCode::INTERNAL_SERVER_ERROR);
+                    json_response(StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": err }))
+                })
+            };
+            let response = if options.interaction_headers {
+                with_interaction_headers(response, pact, interaction)
+            } else {
+                response
+            };
+            record_match(request, pact, interaction, mismatches, &response, context, options);
+            Ok((response, Some((position, mismatches))))
         },
         None => {
-            if auto_cors && request.method.to_uppercase() == "OPTIONS" {
+            matching_event!("no matching interaction", considered = mismatches.len());
+            options.callbacks.mismatched(request, &mismatches);
+            if let Some(ref coverage) = options.coverage {
+                coverage.record_unmatched();
+            }
+            if let Some(ref span) = context.span {
+                // the mismatches of the closest interaction
+                let closest = mismatches.iter().map(|(_, m)| m.len()).min().unwrap_or(0);
+                telemetry::set_attribute(span, "pact.matched", false);
+                telemetry::set_attribute(span, "pact.mismatches", closest as i64);
+            }
+            let response = if options.auto_cors && request.method.to_uppercase() == "OPTIONS" {
                 Ok(Response {
                     headers: Some(hashmap! {
                     s!("Access-Control-Allow-Headers") => vec![s!("*")],
@@ -126,145 +744,1024 @@ fn find_matching_request(request: &Request, auto_cors: bool, sources: &Vec<Pact>
                   }),
                     ..Response::default_response()
                 })
+            } else if let Some(response) = if request.method.to_uppercase() == "OPTIONS" { allowed_methods_response(&mismatches) } else { None } {
+                debug!("No interaction for OPTIONS {}, sending the methods of the interactions for the path", request.path);
+                Ok(response)
+            } else if let Some(response) = if options.enforce_auth { unauthorised_response(request, &mismatches, options) } else { None } {
+                Ok(response)
+            } else if options.answer_browser_probes && is_browser_probe(request) {
+                debug!("No interaction for browser request {} {}, sending {}", request.method, request.path,
+                       StatusCode::NO_CONTENT);
+                Ok(error_response(StatusCode::NO_CONTENT, options))
+            } else if let Some(response) = options.malformed_json_status
+                .and_then(|status| malformed_json_response(request, &mismatches, status)) {
+                Ok(response)
             } else {
-                explain_mismatches(request, &mismatches);
+                explain_mismatches(request, &mismatches, options);
                 Err(s!("No matching request found"))
+            };
+            response.map(|response| (response, None))
+        }
+    }
+}
+
+/// Number of times each interaction request is matched when warming up the server
+pub const WARMUP_ITERATIONS: u32 = 10;
+
+/// Runs the request of each interaction through the matcher a number of times, so the first real request is not
+/// slower than the others, and logs the average time taken to match each one. Returns the average time for each
+/// interaction, or `None` if its request does not match any interaction with the server options.
+pub fn warm_up(sources: &[Arc<Pact>], state_generators: &StateGenerators, options: &ServerOptions, iterations: u32) -> Vec<(String, Option<Duration>)> {
+    let iterations = iterations.max(1);
+    let options = ServerOptions { metrics: None, coverage: None, journal: None, callbacks: Callbacks::default(), .. options.clone() };
+    let options = &options;
+    let snapshot = Snapshot::new(sources.to_vec(), state_generators.clone());
+    let context = RequestContext::default();
+    let start = Instant::now();
+    let results = sources.iter()
+        .flat_map(|pact| &pact.interactions)
+        .map(|interaction| {
+            let interaction_start = Instant::now();
+            let matched = (0..iterations)
+                .filter(|_| find_matching_request(&interaction.request, &snapshot, &context, options).is_ok())
+                .count() == iterations as usize;
+            let average = interaction_start.elapsed() / iterations;
+            if matched {
+                info!("Warm-up: '{}' took {:?} to match on average", interaction.description, average);
+                (interaction.description.clone(), Some(average))
+            } else {
+                warn!("Warm-up: the request for '{}' does not match any interaction", interaction.description);
+                (interaction.description.clone(), None)
+            }
+        })
+        .collect_vec();
+    info!("Warm-up of {} interactions ({} iterations each) took {:?}", results.len(), iterations, start.elapsed());
+    results
+}
+
+/// Outcome of checking a request against the interactions, without serving it
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestCheck {
+    /// The request matched the interaction (with the consumer, provider and description), and would get the response
+    Matched { consumer: String, provider: String, description: String, response: Box<Response> },
+    /// The request did not match any interaction. Has the description of each interaction it was compared with and its
+    /// mismatches, with the closest ones first.
+    Mismatched(Vec<(String, Vec<String>)>)
+}
+
+/// Checks which interaction matches the request, the same way the server would when it receives it
+pub fn check_request(request: &Request, sources: &[Arc<Pact>], state_generators: &StateGenerators, options: &ServerOptions) -> RequestCheck {
+    let mismatched = Arc::new(Mutex::new(vec![]));
+    let mut callbacks = Callbacks::default();
+    let on_mismatch = mismatched.clone();
+    callbacks.on_mismatch(move |_, mismatches| *on_mismatch.lock().unwrap() = mismatches.iter()
+        .sorted_by(|(_, a), (_, b)| Ord::cmp(&matching::mismatch_score(a), &matching::mismatch_score(b)))
+        .into_iter()
+        .map(|(interaction, mismatches)| (interaction.description.clone(), mismatches.iter().map(describe_mismatch).collect()))
+        .collect());
+    let options = ServerOptions {
+        auto_cors: false,
+        answer_browser_probes: false,
+        malformed_json_status: None,
+        metrics: None,
+        coverage: None,
+        journal: None,
+        callbacks,
+        interaction_headers: false,
+        .. options.clone()
+    };
+    let snapshot = Snapshot::new(sources.to_vec(), state_generators.clone());
+    match find_matching_interaction(request, &snapshot, &RequestContext::default(), &options) {
+        Ok((response, Some((position, _)))) => {
+            let pact = &snapshot.sources[position.0];
+            RequestCheck::Matched {
+                consumer: pact.consumer.name.clone(),
+                provider: pact.provider.name.clone(),
+                description: pact.interactions[position.1].description.clone(),
+                response: Box::new(response)
+            }
+        },
+        _ => RequestCheck::Mismatched(mismatched.lock().unwrap().clone())
+    }
+}
+
+fn error_response(status: StatusCode, options: &ServerOptions) -> Response {
+    let mut response = Response {
+        status: status.as_u16(),
+        .. Response::default_response()
+    };
+    if options.auto_cors {
+        response.headers = Some(hashmap!{ s!("Access-Control-Allow-Origin") => vec![s!("*")] })
+    }
+    response
+}
+
+/// Reads the request body, responding with an error if it is too large or is not received in time
+async fn read_body(parts: &Parts, body: Incoming, context: &RequestContext, options: &ServerOptions) -> Result<OptionalBody, Response> {
+    let max_body_size = options.max_body_size.unwrap_or(usize::MAX);
+    if let Some(length) = content_length(&parts.headers).filter(|length| *length > max_body_size) {
+        warn!("Request body for {} {} is {} bytes, which is larger than the maximum of {} bytes, sending {}",
+              parts.method, parts.uri.path(), length, max_body_size, StatusCode::PAYLOAD_TOO_LARGE);
+        return Err(closing_response(StatusCode::PAYLOAD_TOO_LARGE, options));
+    }
+    let body = Limited::new(body, max_body_size);
+    let collected = match options.connections.read_timeout {
+        Some(timeout) => match time::timeout(timeout, body.collect()).await {
+            Ok(collected) => collected,
+            Err(_) => {
+                warn!("Request body for {} {} was not received within {:?}, sending {}", parts.method,
+                      parts.uri.path(), timeout, StatusCode::REQUEST_TIMEOUT);
+                return Err(closing_response(StatusCode::REQUEST_TIMEOUT, options));
+            }
+        },
+        None => body.collect().await
+    };
+    match collected {
+        Ok(collected) => {
+            let body = collected.to_bytes();
+            if let Some(ref captured) = context.body {
+                *captured.lock().unwrap() = body.clone();
+            }
+            Ok(pact_support::optional_body(body))
+        },
+        Err(ref err) if err.is::<LengthLimitError>() => {
+            warn!("Request body for {} {} is larger than the maximum of {} bytes, sending {}", parts.method,
+                  parts.uri.path(), max_body_size, StatusCode::PAYLOAD_TOO_LARGE);
+            Err(closing_response(StatusCode::PAYLOAD_TOO_LARGE, options))
+        },
+        Err(err) => {
+            warn!("Failed to read request body: {}", err);
+            Ok(OptionalBody::Empty)
+        }
+    }
+}
+
+/// Error response that closes the connection, for when the rest of the request body is not read
+fn closing_response(status: StatusCode, options: &ServerOptions) -> Response {
+    let mut response = error_response(status, options);
+    pact_support::add_header(&mut response, "Connection", "close");
+    response
+}
+
+fn content_length(headers: &HeaderMap<HeaderValue>) -> Option<usize> {
+    headers.get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+fn expects_continue(headers: &HeaderMap<HeaderValue>) -> bool {
+    headers.get(EXPECT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().eq_ignore_ascii_case("100-continue"))
+        .unwrap_or(false)
+}
+
+/// Checks if an interaction matches a request that expects a 100 (Continue) before its body is read. Returns the
+/// response to reject it with if none do, unless unmatched requests are proxied (which needs the body).
+fn reject_expectation(request: &Request, snapshot: &Snapshot, options: &ServerOptions) -> Option<Response> {
+    if options.proxy.is_some() {
+        return None;
+    }
+    let check_options = ServerOptions {
+        matching: MatchingOptions { ignore_body: true, .. options.matching.clone() },
+        metrics: None,
+        coverage: None,
+        journal: None,
+        // the request is only matched once its body is read, but a rejected one is never matched again
+        callbacks: options.callbacks.mismatches_only(),
+        .. options.clone()
+    };
+    match find_matching_request(request, snapshot, &RequestContext::default(), &check_options) {
+        Ok(_) => None,
+        Err(msg) => {
+            warn!("{} for {} {} that expects a 100 (Continue), sending {} without reading the body", msg,
+                  request.method, request.path, StatusCode::NOT_FOUND);
+            Some(error_response(StatusCode::NOT_FOUND, options))
+        }
+    }
+}
+
+/// The URI with another path, keeping its query string
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string()
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// The correlation ID from the header of the request. If the request does not have one, a generated one is added to
+/// it, so it is also the one that is logged, recorded and forwarded.
+fn correlation_id(headers: &mut HeaderMap, name: &str) -> Option<(HeaderName, HeaderValue)> {
+    let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+    let value = match headers.get(&name) {
+        Some(value) => value.clone(),
+        None => {
+            let value = HeaderValue::from_str(&generators::random_uuid(&mut rand::thread_rng())).ok()?;
+            debug!("The request has no {} header, using the generated correlation ID {:?}", name, value);
+            headers.insert(name.clone(), value.clone());
+            value
+        }
+    };
+    Some((name, value))
+}
+
+/// Adds the headers identifying the interaction to its response
+fn with_interaction_headers(response: Response, pact: &Pact, interaction: &Interaction) -> Response {
+    let mut headers = response.headers.clone().unwrap_or_default();
+    let values = [
+        (INTERACTION_ID_HEADER, admin::interaction_key(pact, interaction)),
+        (INTERACTION_CONSUMER_HEADER, pact.consumer.name.clone()),
+        (INTERACTION_DESCRIPTION_HEADER, interaction.description.clone())
+    ];
+    for (name, value) in values {
+        headers.retain(|key, _| !key.eq_ignore_ascii_case(name));
+        headers.insert(name.to_string(), vec![ header_text(&value) ]);
+    }
+    Response { headers: Some(headers), .. response }
+}
+
+/// The text with the characters that can not be sent in a header (like line breaks and non-ASCII characters) and `%`
+/// percent-encoded
+fn header_text(text: &str) -> String {
+    text.bytes()
+        .map(|byte| if (b' '..=b'~').contains(&byte) && byte != b'%' { (byte as char).to_string() } else { format!("%{:02X}", byte) })
+        .collect()
+}
+
+/// Adds the headers that the response does not have, and replaces the ones it has with the overridden headers
+fn set_response_headers(headers: &mut HeaderMap, added: &[(HeaderName, HeaderValue)], overridden: &[(HeaderName, HeaderValue)]) {
+    let missing = added.iter().filter(|(name, _)| !headers.contains_key(name)).cloned().collect_vec();
+    for (name, _) in overridden {
+        headers.remove(name);
+    }
+    for (name, value) in missing.into_iter().chain(overridden.iter().cloned()) {
+        headers.append(name, value);
+    }
+}
+
+/// The URI without the base path at the start of its path, or `None` if the path does not start with it
+fn strip_base_path(uri: &Uri, base_path: &str) -> Option<Uri> {
+    let base_path = base_path.trim_end_matches('/');
+    let rest = uri.path().strip_prefix(base_path)?;
+    if !(rest.is_empty() || rest.starts_with('/')) {
+        return None;
+    }
+    with_path(uri, if rest.is_empty() { "/" } else { rest })
+}
+
+/// Name of a provider as a host name label: in lowercase, with dashes instead of spaces and other characters
+fn host_label(provider: &str) -> String {
+    provider.trim().to_lowercase().chars().map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' }).collect()
+}
+
+/// The provider named by the first label of the host (without the port), if one of the pacts is for it
+fn host_provider(host: &str, sources: &[Arc<Pact>]) -> Option<String> {
+    let label = host.split(['.', ':']).next().unwrap_or_default().to_lowercase();
+    sources.iter()
+        .map(|pact| &pact.provider.name)
+        .find(|provider| !label.is_empty() && host_label(provider) == label)
+        .cloned()
+}
+
+fn visible_pacts<'a>(sources: &'a [Arc<Pact>], options: &'a ServerOptions) -> impl Iterator<Item = &'a Pact> {
+    sources.iter().map(|pact| pact.as_ref()).filter(move |pact| match options.providers {
+        Some(ref providers) => providers.contains(&pact.provider.name),
+        None => true
+    })
+}
+
+fn json_response(status: StatusCode, json: Value) -> Response {
+    Response {
+        status: status.as_u16(),
+        headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+        body: OptionalBody::Present(json.to_string().into_bytes()),
+        .. Response::default_response()
+    }
+}
+
+fn search_response(sources: &[Arc<Pact>], query: Option<&str>, options: &ServerOptions) -> Response {
+    match admin::InteractionQuery::parse(query) {
+        Ok(query) => json_response(StatusCode::OK, admin::search(visible_pacts(sources, options), &query)),
+        Err(err) => json_response(StatusCode::BAD_REQUEST, json!({ "error": err }))
+    }
+}
+
+/// The request with the `traceparent` header of the span, so the provider it is forwarded to continues the trace
+fn with_traceparent(request: &Request, traceparent: &str) -> Request {
+    let mut headers = request.headers.clone().unwrap_or_default();
+    headers.retain(|name, _| !name.eq_ignore_ascii_case(TRACEPARENT));
+    headers.insert(TRACEPARENT.to_string(), vec![ traceparent.to_string() ]);
+    Request { headers: Some(headers), .. request.clone() }
+}
+
+/// The regular expression of the header (like the provider state or interaction header), if the request has it
+fn header_regex(headers: &HeaderMap, header_name: &str) -> Result<Option<Regex>, String> {
+    headers.get(header_name)
+        .map(|header| header.to_str().map_err(|err| err.to_string())
+            .and_then(|value| Regex::new(value).map_err(|err| err.to_string()))
+            .map_err(|err| format!("The {} header is not a valid regular expression - {}", header_name, err)))
+        .transpose()
+}
+
+async fn handle_request(request: Request, snapshot: &Snapshot, context: &RequestContext, options: &ServerOptions) -> Response {
+    info! ("===> Received {}", request);
+    debug!("     body: '{}'", pact_support::body_for_log(&request.body));
+    debug!("     matching_rules: {:?}", request.matching_rules);
+    debug!("     generators: {:?}", request.generators);
+    let (matched, response) = match find_response(&request, snapshot, context, options) {
+        Ok(response) => (true, {
+            if let Some(ref client) = options.compare_with {
+                let (client, request, stubbed) = (client.clone(), request.clone(), response.clone());
+                tokio::spawn(async move { compare::compare_with_provider(&client, request, stubbed).await });
+            }
+            let response = ranges::apply_range(&request, response);
+            if options.compress_responses {
+                compression::compress_response(&request, response)
+            } else {
+                response
+            }
+        }),
+        Err(msg) => (false, match options.proxy {
+            Some(ref proxy) => {
+                warn!("{}, forwarding the request to {}", msg, proxy.base_url());
+                let forwarded = match context.span.as_ref().and_then(telemetry::traceparent) {
+                    Some(traceparent) => with_traceparent(&request, &traceparent),
+                    None => request.clone()
+                };
+                match proxy.forward(&forwarded).await {
+                    Ok(response) => {
+                        if let Some(ref recorder) = options.recorder {
+                            if let Err(err) = recorder.record(&request, &response) {
+                                error!("Failed to record the interaction to {} - {}", recorder.path().display(), err);
+                            }
+                        }
+                        response
+                    },
+                    Err(err) => {
+                        error!("Failed to proxy the request: {}, sending {}", err, StatusCode::BAD_GATEWAY);
+                        error_response(StatusCode::BAD_GATEWAY, options)
+                    }
+                }
+            },
+            None => {
+                warn!("{}, sending {}", msg, StatusCode::NOT_FOUND);
+                error_response(StatusCode::NOT_FOUND, options)
+            }
+        })
+    };
+    let response = match context.status {
+        Some(status) => Response { status, .. response },
+        None => response
+    };
+    if let Some(ref journal) = options.journal {
+        journal.record(&request, matched, response.status);
+    }
+    response
+}
+
+impl ServerHandler {
+    pub fn new(sources: Vec<Arc<Pact>>, state_generators: StateGenerators, options: ServerOptions) ->  ServerHandler {
+        ServerHandler {
+            interactions: Arc::new(RwLock::new(Interactions::new(sources, state_generators))),
+            options,
+            last_request: Arc::new(Mutex::new(Instant::now()))
+        }
+    }
+
+    /// A handler for the same interactions, that only uses the ones of the providers
+    fn for_providers(&self, providers: Vec<String>) -> ServerHandler {
+        ServerHandler {
+            interactions: self.interactions.clone(),
+            options: ServerOptions { providers: Some(providers), .. self.options.clone() },
+            last_request: self.last_request.clone()
+        }
+    }
+
+    fn add_interaction(&self, query: Option<&str>, body: &OptionalBody, options: &ServerOptions) -> Response {
+        let params = query.and_then(|query| parse_query_string(&query.to_string())).unwrap_or_default();
+        let name = |key: &str| params.get(key).and_then(|values| values.last().cloned())
+            .unwrap_or_else(|| dynamic::DEFAULT_NAME.to_string());
+        let (consumer, provider) = (name("consumer"), name("provider"));
+        if options.providers.as_ref().map(|providers| !providers.contains(&provider)).unwrap_or(false) {
+            return json_response(StatusCode::FORBIDDEN,
+                json!({ "error": format!("Interactions can not be added for provider '{}'", provider) }));
+        }
+        match dynamic::parse_interaction(pact_support::body_bytes(body)) {
+            Ok(interaction) => {
+                let description = interaction.description.clone();
+                let id = self.interactions.write().unwrap().add(&consumer, &provider, interaction);
+                let mut response = json_response(StatusCode::CREATED, json!({
+                    "id": id,
+                    "consumer": consumer,
+                    "provider": provider,
+                    "description": description
+                }));
+                pact_support::add_header(&mut response, "Location", &format!("{}/{}", admin::INTERACTIONS_PATH, id));
+                response
+            },
+            Err(err) => json_response(StatusCode::BAD_REQUEST, json!({ "error": err }))
+        }
+    }
+
+    fn remove_interaction(&self, id: &str, options: &ServerOptions) -> Response {
+        let mut interactions = self.interactions.write().unwrap();
+        let visible = interactions.get(id)
+            .map(|dynamic| options.providers.as_ref().map(|providers| providers.contains(&dynamic.provider)).unwrap_or(true))
+            .unwrap_or(false);
+        if visible && interactions.remove(id) {
+            Response { status: StatusCode::NO_CONTENT.as_u16(), .. Response::default_response() }
+        } else {
+            json_response(StatusCode::NOT_FOUND, json!({ "error": format!("There is no added interaction with id '{}'", id) }))
+        }
+    }
+
+    /// Logs the interactions that were not used, returning an error if there were any and the server should fail
+    /// because of them
+    fn check_unused(&self) -> Result<(), i32> {
+        let coverage = match self.options.coverage {
+            Some(ref coverage) if self.options.fail_on_unused => coverage,
+            _ => return Ok(())
+        };
+        let snapshot = self.interactions.read().unwrap().snapshot();
+        let unused = coverage.unused(snapshot.sources.iter().map(|pact| pact.as_ref()));
+        if unused.is_empty() {
+            info!("All {} interaction(s) were used", snapshot.sources.iter().map(|pact| pact.interactions.len()).sum::<usize>());
+            Ok(())
+        } else {
+            error!("{} interaction(s) were not used:", unused.len());
+            for (pact, interaction) in unused {
+                error!("  - '{}' ('{}' -> '{}')", interaction.description, pact.consumer.name, pact.provider.name);
+            }
+            Err(5)
+        }
+    }
+
+    async fn handle(&self, req: HyperRequest<Incoming>, client: IpAddr) -> HyperResponse<Full<Bytes>> {
+        let quiet = quiet::is_quiet_path(&self.options.quiet_paths, req.uri().path());
+        quiet::run_quietly(quiet, self.handle_recorded(req, client)).await
+    }
+
+    /// Handles the request, recording it in its span and the capture directory if they are enabled
+    async fn handle_recorded(&self, mut req: HyperRequest<Incoming>, client: IpAddr) -> HyperResponse<Full<Bytes>> {
+        let correlation = self.options.correlation_header.as_ref().and_then(|name| correlation_id(req.headers_mut(), name));
+        let mut context = RequestContext::default();
+        if let Some(ref tracer) = self.options.tracer {
+            let traceparent = req.headers().get(TRACEPARENT).and_then(|h| h.to_str().ok());
+            context.span = Some(tracer.start_span(traceparent, req.method().as_str(), req.uri().path(), client));
+        }
+        let captured = self.options.capture.as_ref().map(|capture| {
+            context.body = Some(Arc::default());
+            (capture, req.method().clone(), req.uri().clone(), req.version(), req.headers().clone())
+        });
+        let mut response = self.respond(req, client, context.clone()).await;
+        if let Some((name, value)) = correlation {
+            response.headers_mut().insert(name, value);
+        }
+        set_response_headers(response.headers_mut(), &self.options.added_response_headers,
+                             &self.options.overridden_response_headers);
+        if let Some(span) = context.span {
+            telemetry::finish(&span, response.status().as_u16());
+        }
+        if let Some((capture, method, uri, version, headers)) = captured {
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await.map(|collected| collected.to_bytes()).unwrap_or_default();
+            let request_body = context.body.map(|request_body| request_body.lock().unwrap().clone()).unwrap_or_default();
+            let request = capture::request_message(&method, &uri, version, &headers, &request_body);
+            match capture.write(&request, &capture::response_message(parts.status, parts.version, &parts.headers, &body)) {
+                Ok(number) => debug!("Captured {} {} as number {}", method, uri.path(), number),
+                Err(err) => error!("Failed to write {} {} to the capture directory {} - {}", method, uri.path(),
+                                   capture.dir().display(), err)
+            }
+            response = HyperResponse::from_parts(parts, Full::new(body));
+        }
+        response
+    }
+
+    // TODO make the parameter name configurable so there are no collisions with the actual server to be stubbed.
+    async fn respond(&self, req: HyperRequest<Incoming>, client: IpAddr, mut context: RequestContext) -> HyperResponse<Full<Bytes>> {
+        *self.last_request.lock().unwrap() = Instant::now();
+        let mut options = self.options.clone();
+        let (mut parts, body) = req.into_parts();
+        if let Some(uri) = self.options.base_path.as_ref().and_then(|base_path| strip_base_path(&parts.uri, base_path)) {
+            parts.uri = uri;
+        }
+        if let Some(path) = rewrite::rewrite_path(&self.options.rewrites, parts.uri.path()) {
+            match with_path(&parts.uri, &path) {
+                Some(uri) => {
+                    debug!("Rewrote the request path {} to {}", parts.uri.path(), path);
+                    parts.uri = uri;
+                },
+                None => warn!("The request path {} was rewritten to {}, which is not a valid path", parts.uri.path(), path)
+            }
+        }
+        if let Some(ref clients) = self.options.clients {
+            if !clients.allows(client) {
+                warn!("Request {} {} from {} is from a client that is not allowed, sending {}", parts.method, parts.uri.path(),
+                      client, StatusCode::FORBIDDEN);
+                return pact_support::pact_response_to_hyper_response(error_response(StatusCode::FORBIDDEN, &options));
+            }
+        }
+        if let Some(ref access) = self.options.access {
+            // browsers do not send the credentials with CORS pre-flight requests
+            let preflight = self.options.auto_cors && parts.method == Method::OPTIONS;
+            if !preflight && !access.allows(&parts.headers) {
+                warn!("Request {} {} does not have the credentials for the server, sending {}", parts.method, parts.uri.path(),
+                      StatusCode::UNAUTHORIZED);
+                let mut response = error_response(StatusCode::UNAUTHORIZED, &options);
+                pact_support::add_header(&mut response, "WWW-Authenticate", &access.challenge());
+                return pact_support::pact_response_to_hyper_response(response);
+            }
+        }
+        let snapshot = self.interactions.read().unwrap().snapshot();
+        if let Some(ref tenants) = self.options.tenants {
+            match tenants.providers_for(parts.headers.get(AUTHORIZATION).and_then(|h| h.to_str().ok())) {
+                Some(providers) => options.providers = Some(providers.clone()),
+                None => {
+                    warn!("Request {} {} does not have a known bearer token, sending {}", parts.method, parts.uri.path(),
+                          StatusCode::UNAUTHORIZED);
+                    let mut response = error_response(StatusCode::UNAUTHORIZED, &options);
+                    pact_support::add_header(&mut response, "WWW-Authenticate", "Bearer");
+                    return pact_support::pact_response_to_hyper_response(response);
+                }
+            }
+        }
+        if self.options.virtual_hosts {
+            let host = parts.headers.get(HOST).and_then(|host| host.to_str().ok()).or_else(|| parts.uri.host());
+            if let Some(provider) = host.and_then(|host| host_provider(host, &snapshot.sources)) {
+                debug!("Using the interactions for provider '{}' from the host of the request", provider);
+                options.providers = Some(match options.providers {
+                    Some(ref providers) => providers.iter().filter(|p| **p == provider).cloned().collect(),
+                    None => vec![ provider ]
+                });
+            }
+        }
+        if let Some(ref metrics) = self.options.metrics {
+            if parts.method == Method::GET && parts.uri.path() == metrics::METRICS_PATH {
+                let response = Response {
+                    headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("text/plain; version=0.0.4") ] }),
+                    body: OptionalBody::Present(metrics.render(visible_pacts(&snapshot.sources, &options)).into_bytes()),
+                    .. Response::default_response()
+                };
+                return pact_support::pact_response_to_hyper_response(response);
+            }
+        }
+        if let Some(ref coverage) = self.options.coverage {
+            if parts.method == Method::GET && parts.uri.path() == coverage::COVERAGE_PATH {
+                let response = json_response(StatusCode::OK, coverage.report(visible_pacts(&snapshot.sources, &options)));
+                return pact_support::pact_response_to_hyper_response(response);
+            }
+            if parts.method == Method::GET && parts.uri.path() == coverage::STATS_PATH {
+                let response = json_response(StatusCode::OK, coverage.stats(visible_pacts(&snapshot.sources, &options)));
+                return pact_support::pact_response_to_hyper_response(response);
+            }
+        }
+        if let Some(ref journal) = self.options.journal {
+            if parts.uri.path() == journal::REQUESTS_PATH {
+                if parts.method == Method::GET {
+                    let response = json_response(StatusCode::OK, journal.report(parts.uri.query()));
+                    return pact_support::pact_response_to_hyper_response(response);
+                } else if parts.method == Method::DELETE {
+                    journal.clear();
+                    let response = Response { status: StatusCode::NO_CONTENT.as_u16(), .. Response::default_response() };
+                    return pact_support::pact_response_to_hyper_response(response);
+                }
+            }
+        }
+        if parts.method == Method::GET && parts.uri.path() == admin::INTERACTIONS_PATH {
+            let response = json_response(StatusCode::OK, admin::list(visible_pacts(&snapshot.sources, &options)));
+            return pact_support::pact_response_to_hyper_response(response);
+        }
+        if parts.method == Method::POST && parts.uri.path() == admin::INTERACTIONS_PATH {
+            let response = match read_body(&parts, body, &context, &options).await {
+                Ok(body) => self.add_interaction(parts.uri.query(), &body, &options),
+                Err(response) => response
+            };
+            return pact_support::pact_response_to_hyper_response(response);
+        }
+        if let Some(id) = admin::interaction_id(parts.uri.path()).filter(|_| parts.method == Method::DELETE) {
+            let response = self.remove_interaction(id, &options);
+            return pact_support::pact_response_to_hyper_response(response);
+        }
+        if parts.method == Method::GET && parts.uri.path() == admin::SEARCH_PATH {
+            let response = search_response(&snapshot.sources, parts.uri.query(), &options);
+            return pact_support::pact_response_to_hyper_response(response);
+        }
+        if let Some(ref reload) = self.options.webhook_reload {
+            if parts.method == Method::POST && parts.uri.path() == admin::WEBHOOK_PATH {
+                let response = match read_body(&parts, body, &context, &options).await {
+                    Ok(body) => {
+                        info!("Received {}, reloading the pacts", admin::webhook_event(&body));
+                        reload.notify_one();
+                        Response { status: StatusCode::ACCEPTED.as_u16(), .. Response::default_response() }
+                    },
+                    Err(response) => response
+                };
+                return pact_support::pact_response_to_hyper_response(response);
+            }
+        }
+        if let Some(ref header_name) = self.options.provider_state_header_name {
+            match header_regex(&parts.headers, header_name) {
+                Ok(Some(filter)) => options.provider_state = Some(filter.into()),
+                Ok(None) => (),
+                Err(err) => {
+                    warn!("{}, sending {}", err, StatusCode::BAD_REQUEST);
+                    return pact_support::pact_response_to_hyper_response(json_response(StatusCode::BAD_REQUEST, json!({ "error": err })));
+                }
+            }
+        }
+        if let Some(ref header_name) = self.options.interaction_header_name {
+            match header_regex(&parts.headers, header_name) {
+                Ok(Some(filter)) => options.description_filter = Some(filter),
+                Ok(None) => (),
+                Err(err) => {
+                    warn!("{}, sending {}", err, StatusCode::BAD_REQUEST);
+                    return pact_support::pact_response_to_hyper_response(json_response(StatusCode::BAD_REQUEST, json!({ "error": err })));
+                }
+            }
+        }
+        let controls = match RequestControls::take(&mut parts.headers) {
+            Ok(controls) => controls,
+            Err(err) => {
+                warn!("{}, sending {}", err, StatusCode::BAD_REQUEST);
+                return pact_support::pact_response_to_hyper_response(json_response(StatusCode::BAD_REQUEST, json!({ "error": err })));
+            }
+        };
+        if !controls.is_empty() {
+            debug!("Request {} {} has the control headers {:?}", parts.method, parts.uri.path(), controls);
+        }
+        if let Some(filter) = controls.interaction_filter() {
+            options.description_filter = Some(filter);
+        }
+
+        if let Some(retry_after) = self.options.rate_limit.as_ref().and_then(|limit| limit.check(client)) {
+            warn!("Request {} {} from {} is over the rate limit, sending {}", parts.method, parts.uri.path(), client,
+                  StatusCode::TOO_MANY_REQUESTS);
+            let mut response = error_response(StatusCode::TOO_MANY_REQUESTS, &options);
+            let seconds = retry_after.as_secs() + if retry_after.subsec_nanos() > 0 { 1 } else { 0 };
+            pact_support::add_header(&mut response, "Retry-After", &seconds.to_string());
+            return pact_support::pact_response_to_hyper_response(response);
+        }
+
+        if self.options.expect_continue == ExpectContinue::Check && expects_continue(&parts.headers) {
+            let mut request = pact_support::hyper_request_to_pact_request(&parts, OptionalBody::Missing);
+            generators::take_state_values(&mut request);
+            if let Some(response) = reject_expectation(&request, &snapshot, &options) {
+                return pact_support::pact_response_to_hyper_response(response);
+            }
+        }
+
+        let body = match read_body(&parts, body, &context, &options).await {
+            Ok(body) => body,
+            Err(response) => return pact_support::pact_response_to_hyper_response(response)
+        };
+        let body = match pact_support::decode_request_body(&mut parts.headers, body, options.max_body_size) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!("Request body for {} {} {}, sending {}", parts.method, parts.uri.path(), err,
+                      StatusCode::PAYLOAD_TOO_LARGE);
+                return pact_support::pact_response_to_hyper_response(closing_response(StatusCode::PAYLOAD_TOO_LARGE, &options));
             }
+        };
+        let mut request = pact_support::hyper_request_to_pact_request(&parts, body);
+        context.state_values = generators::take_state_values(&mut request);
+        context.status = controls.status;
+        let response = handle_request(request, &snapshot, &context, &options).await;
+        if let Some(delay) = controls.delay {
+            time::sleep(delay).await;
         }
+        pact_support::pact_response_to_hyper_response(response)
     }
 }
 
-fn handle_request(request: Request, auto_cors: bool, sources: Arc<Vec<Pact>>, provider_state: Option<Regex>, print_missmatching_bodies: bool) -> Response {
-    info! ("===> Received {}", request);
-    debug!("     body: '{}'", request.body.str_value());
-    debug!("     matching_rules: {:?}", request.matching_rules);
-    debug!("     generators: {:?}", request.generators);
-    match find_matching_request(&request, auto_cors, &sources, provider_state, print_missmatching_bodies) {
-        Ok(response) => response,
-        Err(msg) => {
-            warn!("{}, sending {}", msg, StatusCode::NOT_FOUND);
-            let mut response = Response {
-                status: StatusCode::NOT_FOUND.as_u16(),
-                .. Response::default_response()
-            };
-            if auto_cors {
-                response.headers = Some(hashmap!{ s!("Access-Control-Allow-Origin") => vec![s!("*")] })
+/// Completes when the process is asked to stop with Ctrl+C (or SIGTERM on Unix platforms)
+async fn stop_requested() {
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => { sigterm.recv().await; },
+            Err(err) => {
+                warn!("Could not listen for the terminate signal - {}", err);
+                future::pending::<()>().await
             }
-            response
         }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+    let interrupt = async {
+        if let Err(err) = signal::ctrl_c().await {
+            warn!("Could not listen for Ctrl+C - {}", err);
+            future::pending::<()>().await
+        }
+    };
+    tokio::select! {
+        _ = terminate => (),
+        _ = interrupt => ()
     }
+    info!("Shutting down the server");
 }
 
-impl ServerHandler {
-    pub fn new(sources: Vec<Pact>, auto_cors: bool, provider_state: Option<Regex>,
-               provider_state_header_name: Option<String>,print_missmatching_bodies: bool) ->  ServerHandler {
-        ServerHandler {
-            sources: Arc::new(sources),
-            auto_cors,
-            provider_state,
-            provider_state_header_name,
-            print_missmatching_bodies,
+/// Completes when the server should shut down because of the limits (or because it was asked to stop, if it handles
+/// the stop signals itself), or never if there are none
+async fn shutdown_signal(limits: ServeLimits, last_request: Arc<Mutex<Instant>>, handle_stop: bool) {
+    let serve_for = async {
+        match limits.serve_for {
+            Some(duration) => {
+                time::sleep(duration).await;
+                info!("Shutting down the server after running for {:?}", duration);
+            },
+            None => future::pending().await
+        }
+    };
+    let until_idle = async {
+        match limits.until_idle {
+            Some(idle) => {
+                let check_every = idle.min(Duration::from_secs(1));
+                let mut interval = time::interval_at(time::Instant::now() + check_every, check_every);
+                loop {
+                    interval.tick().await;
+                    if last_request.lock().unwrap().elapsed() >= idle {
+                        break;
+                    }
+                }
+                info!("Shutting down the server after no requests for {:?}", idle);
+            },
+            None => future::pending().await
         }
+    };
+    let stopped = async {
+        if handle_stop {
+            stop_requested().await
+        } else {
+            future::pending().await
+        }
+    };
+    tokio::select! {
+        _ = serve_for => (),
+        _ = until_idle => (),
+        _ = stopped => ()
     }
 }
 
-impl Service for ServerHandler {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = HyperError;
-    type Future = ServerHandlerFuture;
+/// Waits for a connection slot if the number of connections is limited
+async fn acquire_connection(limit: &Option<Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+    match limit {
+        Some(semaphore) => {
+            if semaphore.available_permits() == 0 {
+                debug!("The maximum number of connections are open, waiting for one to close");
+            }
+            semaphore.clone().acquire_owned().await.ok()
+        },
+        None => None
+    }
+}
 
-    // TODO make the parameter name configurable so there are no collisions with the actual server to be stubbed.
-    fn call(&mut self, req: HyperRequest<Body>) -> <Self as Service>::Future {
-        let auto_cors = self.auto_cors;
-        let sources = self.sources.clone();
-        let print_missmatching_bodies = self.print_missmatching_bodies;
-        let mut provider_state = self.provider_state.clone();
-        let (parts, body) = req.into_parts();
-        if self.provider_state_header_name.is_some() {
-            let parts_value = &parts;
-            let provider_state_header = parts_value.headers.get(self.provider_state_header_name
-                .clone().unwrap());
-            if let Some(header) = provider_state_header {
-                provider_state = Some(Regex::new(header.to_str().unwrap()).unwrap());
-            }
-        }
-
-        let future = body.concat2()
-            .then(|body| future::ok(match body {
-                Ok(chunk) => if chunk.is_empty() {
-                    OptionalBody::Empty
-                } else {
-                    OptionalBody::Present(chunk.iter().cloned().collect())
-                },
-                Err(err) => {
-                    warn!("Failed to read request body: {}", err);
-                    OptionalBody::Empty
-                }
-            }))
-            .map(move |body| pact_support::hyper_request_to_pact_request(parts, body))
-            .map(move |req| handle_request(req, auto_cors, sources, provider_state, print_missmatching_bodies))
-            .map(|res| pact_support::pact_response_to_hyper_response(&res))
-            .into_future();
-        ServerHandlerFuture { future: Box::new(future) }
+/// A listener the server accepts connections from
+pub trait Accept {
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    /// Accepts the next connection, with the IP address of the client
+    fn accept_client(&self) -> impl Future<Output = io::Result<(Self::Stream, IpAddr)>> + Send;
+}
+
+impl Accept for TcpListener {
+    type Stream = TcpStream;
+
+    async fn accept_client(&self) -> io::Result<(TcpStream, IpAddr)> {
+        self.accept().await.map(|(stream, client)| (stream, client.ip()))
     }
 }
 
-pub struct ServerHandlerFuture {
-    future: Box<dyn Future<Item=HyperResponse<Body>, Error=HyperError> + Send>
+#[cfg(unix)]
+impl Accept for UnixListener {
+    type Stream = UnixStream;
+
+    async fn accept_client(&self) -> io::Result<(UnixStream, IpAddr)> {
+        // the clients of the socket are on the same host
+        self.accept().await.map(|(stream, _)| (stream, IpAddr::V4(Ipv4Addr::LOCALHOST)))
+    }
 }
 
-impl Future for ServerHandlerFuture {
-    type Item = HyperResponse<Body>;
-    type Error = HyperError;
+/// Serves the requests on the listener until the shutdown signal completes, then waits for the requests that are being
+/// handled to finish
+pub async fn serve<L: Accept, F: Future<Output = ()>>(listener: L, handler: ServerHandler, signal: F) {
+    tokio::pin!(signal);
+    let settings = handler.options.connections;
+    let mut builder = http1::Builder::new();
+    builder.keep_alive(settings.keep_alive);
+    if let Some(timeout) = settings.idle_timeout.or(settings.read_timeout) {
+        builder.timer(TokioTimer::new()).header_read_timeout(timeout);
+    }
+    let limit = settings.max_connections.map(|max| Arc::new(Semaphore::new(max)));
+    let graceful = GracefulShutdown::new();
+    loop {
+        let next = async {
+            let permit = acquire_connection(&limit).await;
+            (listener.accept_client().await, permit)
+        };
+        tokio::select! {
+            (accepted, permit) = next => match accepted {
+                Ok((stream, client)) => {
+                    let handler = handler.clone();
+                    let service = service_fn(move |req| {
+                        let handler = handler.clone();
+                        async move { Ok::<_, Infallible>(handler.handle(req, client).await) }
+                    });
+                    let connection = graceful.watch(builder.serve_connection(TokioIo::new(WriteTimeout::new(stream, settings.write_timeout)), service));
+                    tokio::spawn(async move {
+                        if let Err(err) = connection.await {
+                            debug!("Failed to serve the connection: {}", err);
+                        }
+                        drop(permit);
+                    });
+                },
+                Err(err) => error!("Failed to accept a connection: {}", err)
+            },
+            _ = &mut signal => break
+        }
+    }
+    graceful.shutdown().await;
+}
 
-    fn poll(&mut self) -> Result<Async<<Self as Future>::Item>, <Self as Future>::Error> {
-        self.future.poll()
+/// Binds the port, trying the following ports in the range if it is already in use
+pub async fn bind(listen: &ListenOptions) -> Result<TcpListener, String> {
+    let last = listen.port.saturating_add(listen.port_range);
+    let mut port = listen.port;
+    loop {
+        match TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port))).await {
+            Ok(listener) => return Ok(listener),
+            Err(ref err) if err.kind() == io::ErrorKind::AddrInUse && port != 0 && port < last => {
+                warn!("Port {} is already in use, trying port {}", port, port + 1);
+                port += 1;
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::AddrInUse && listen.port_range > 0 =>
+                return Err(format!("ports {} to {} are all in use", listen.port, last)),
+            Err(ref err) if err.kind() == io::ErrorKind::AddrInUse =>
+                return Err(format!("port {} is already in use by another process. Use --port-range to also try the \
+                following ports, or --port 0 to use a random port", port)),
+            Err(err) => return Err(format!("could not listen on port {} - {}", port, err))
+        }
     }
 }
 
-impl NewService for ServerHandler {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = HyperError;
-    type Service = ServerHandler;
-    type Future = FutureResult<ServerHandler, HyperError>;
-    type InitError = HyperError;
+/// Listens on the Unix domain socket, replacing the socket file if it was left behind by a server that is no longer
+/// running
+#[cfg(unix)]
+fn bind_unix(path: &Path) -> Result<UnixListener, String> {
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(format!("socket {} is already in use by another process", path.display()));
+        }
+        fs::remove_file(path).map_err(|err| format!("could not remove the old socket {} - {}", path.display(), err))?;
+    }
+    UnixListener::bind(path).map_err(|err| format!("could not listen on socket {} - {}", path.display(), err))
+}
 
-    fn new_service(&self) -> <Self as NewService>::Future {
-        future::ok(self.clone())
+/// Binds the ports that serve the interactions of specific providers
+async fn bind_provider_ports(listen: &ListenOptions) -> Result<Vec<(TcpListener, Vec<String>)>, String> {
+    let mut listeners = vec![];
+    for (port, providers) in &listen.provider_ports {
+        let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], *port))).await
+            .map_err(|err| format!("could not listen on port {} for {} - {}", port, providers.join(", "), err))?;
+        info!("Serving the interactions for {} on port {}", providers.join(", "), port);
+        listeners.push((listener, providers.clone()));
     }
+    Ok(listeners)
 }
 
-pub fn start_server(port: u16, sources: Vec<Pact>, auto_cors: bool, print_missmatching_bodies: bool, provider_state:
-Option<Regex>, provider_state_header_name: Option<String>, runtime: &mut Runtime) -> Result<(),
-    i32> {
-    let addr = ([0, 0, 0, 0], port).into();
-    match Server::try_bind(&addr) {
-        Ok(builder) => {
-            let server = builder.http1_keepalive(false)
-                .serve(ServerHandler::new(sources, auto_cors, provider_state, provider_state_header_name, print_missmatching_bodies));
-            info!("Server started on port {}", server.local_addr().port());
-            runtime.block_on(server.map_err(|err| error!("could not start server: {}", err)))
-                .map_err(|_| {
-                    format!("error occurred scheduling server future on Tokio runtime");
-                    2
-                })
-        },
-        Err(err) => {
-            error!("could not start server: {}", err);
-            Err(1)
+/// Drops the privileges once the listeners are bound, then serves the requests until the server is shut down
+async fn run<L: Accept + Send + Sync + 'static>(listener: L, provider_listeners: Vec<(TcpListener, Vec<String>)>, sources: Vec<Arc<Pact>>,
+                                                 state_generators: StateGenerators, options: ServerOptions, limits: ServeLimits) -> Result<(), i32> {
+    if let Some(ref privileges) = options.drop_privileges {
+        if let Err(err) = privileges.apply() {
+            error!("could not drop privileges: {}", err);
+            return Err(1);
+        }
+    }
+    let handler = ServerHandler::new(sources, state_generators, options);
+    if let Some(ref tracer) = handler.options.tracer {
+        info!("Exporting the spans of the requests to {}", tracer.endpoint());
+    }
+    if let Some(mut refreshed) = handler.options.refreshed_pacts.clone() {
+        let interactions = handler.interactions.clone();
+        tokio::spawn(async move {
+            while refreshed.changed().await.is_ok() {
+                let (pacts, state_generators) = refreshed.borrow_and_update().clone();
+                interactions.write().unwrap().replace_loaded(pacts, state_generators);
+            }
+        });
+    }
+    let signal = shutdown_signal(limits, handler.last_request.clone(), handler.options.fail_on_unused);
+    let (stop, stopped) = watch::channel(false);
+    let stop_when = |mut stopped: watch::Receiver<bool>| async move {
+        let _ = stopped.changed().await;
+    };
+    let mut servers = vec![ tokio::spawn(serve(listener, handler.clone(), stop_when(stopped.clone()))) ];
+    servers.extend(provider_listeners.into_iter()
+        .map(|(listener, providers)| tokio::spawn(serve(listener, handler.for_providers(providers), stop_when(stopped.clone())))));
+    signal.await;
+    let _ = stop.send(true);
+    for server in servers {
+        if let Err(err) = server.await {
+            error!("Failed to serve the requests: {}", err);
         }
     }
+    if let Some(tracer) = handler.options.tracer.clone() {
+        // waits for the last export
+        let _ = tokio::task::spawn_blocking(move || tracer.shutdown()).await;
+    }
+    handler.check_unused()
+}
+
+pub fn start_server(listen: ListenOptions, sources: Vec<Arc<Pact>>, state_generators: StateGenerators, options: ServerOptions,
+                    limits: ServeLimits, runtime: &mut Runtime) -> Result<(), i32> {
+    runtime.block_on(async {
+        if let Some(ref path) = listen.unix_socket {
+            #[cfg(unix)]
+            return match bind_unix(path) {
+                Ok(listener) => {
+                    info!("Server started on socket {}", path.display());
+                    let provider_listeners = match bind_provider_ports(&listen).await {
+                        Ok(listeners) => listeners,
+                        Err(err) => {
+                            error!("could not start server: {}", err);
+                            return Err(1);
+                        }
+                    };
+                    let result = run(listener, provider_listeners, sources, state_generators, options, limits).await;
+                    if let Err(err) = fs::remove_file(path) {
+                        warn!("could not remove the socket {} - {}", path.display(), err);
+                    }
+                    result
+                },
+                Err(err) => {
+                    error!("could not start server: {}", err);
+                    Err(1)
+                }
+            };
+            #[cfg(not(unix))]
+            {
+                error!("could not start server: Unix domain sockets ({}) are not supported on this platform", path.display());
+                return Err(1);
+            }
+        }
+        match bind(&listen).await {
+            Ok(listener) => {
+                let port = match listener.local_addr() {
+                    Ok(addr) => addr.port(),
+                    Err(err) => {
+                        error!("could not get the address the server is listening on: {}", err);
+                        return Err(1);
+                    }
+                };
+                if let Some(ref file) = listen.port_file {
+                    if let Err(err) = fs::write(file, port.to_string()) {
+                        error!("could not write the port to '{}': {}", file.display(), err);
+                        return Err(1);
+                    }
+                }
+                info!("Server started on port {}", port);
+                match bind_provider_ports(&listen).await {
+                    Ok(provider_listeners) => run(listener, provider_listeners, sources, state_generators, options, limits).await,
+                    Err(err) => {
+                        error!("could not start server: {}", err);
+                        Err(1)
+                    }
+                }
+            },
+            Err(err) => {
+                error!("could not start server: {}", err);
+                Err(1)
+            }
+        }
+    })
 }
 
 #[cfg(test)]
 mod test {
     use expectest::prelude::*;
-    use pact_matching::models::{Interaction, OptionalBody, Pact, Request, Response};
+    use http::HeaderMap;
+    use http::header::{HeaderName, HeaderValue};
+    use pact_matching::models::{Consumer, Interaction, OptionalBody, Pact, Provider, Request, Response};
+    use pact_matching::models::generators::{Generator, GeneratorCategory, Generators};
     use pact_matching::models::matchingrules::*;
     use pact_matching::models::provider_states::*;
+    use crate::overrides::InteractionOverrides;
     use regex::Regex;
+    use std::sync::{Arc, Mutex};
+    use super::{ListenOptions, ProviderStateFilter, ProviderStateMode, RequestCheck, RequestContext, ServerOptions};
+    use super::{INTERACTION_CONSUMER_HEADER, INTERACTION_DESCRIPTION_HEADER, INTERACTION_ID_HEADER};
+    use crate::admin;
+    use crate::coverage::Coverage;
+    use crate::dynamic::Snapshot;
+    use crate::generators::StateGenerators;
+
+    fn find_matching_request(request: &Request, sources: &[Pact], options: &ServerOptions) -> Result<Response, String> {
+        let sources = sources.iter().cloned().map(Arc::new).collect::<Vec<_>>();
+        super::find_matching_request(request, &Snapshot::new(sources, StateGenerators::default()), &RequestContext::default(), options)
+    }
 
     #[test]
     fn match_request_finds_the_most_appropriate_response() {
@@ -277,7 +1774,7 @@ mod test {
 
         let request1 = Request::default_request();
 
-        expect!(super::find_matching_request(&request1, false, &vec![pact1, pact2], None, false)).to(be_ok().value(interaction1.response));
+        expect!(find_matching_request(&request1, &[pact1, pact2], &ServerOptions::default())).to(be_ok().value(interaction1.response));
     }
 
     #[test]
@@ -292,7 +1789,93 @@ mod test {
 
         let request1 = Request { method: s!("POST"), .. Request::default_request() };
 
-        expect!(super::find_matching_request(&request1, false, &vec![pact1, pact2], None, false)).to(be_err());
+        expect!(find_matching_request(&request1, &[pact1, pact2], &ServerOptions::default())).to(be_err());
+    }
+
+    #[test]
+    fn match_request_answers_head_requests_from_the_get_interactions() {
+        let get = Interaction { request: Request { path: s!("/orders"), .. Request::default_request() },
+            response: Response { status: 200, .. Response::default_response() }, .. Interaction::default() };
+        let head = Interaction { request: Request { method: s!("HEAD"), path: s!("/items"), .. Request::default_request() },
+            response: Response { status: 204, .. Response::default_response() }, .. Interaction::default() };
+        let pacts = [ Pact { interactions: vec![ get, head ], .. Pact::default() } ];
+
+        let request = |path: &str| Request { method: s!("HEAD"), path: path.to_string(), .. Request::default_request() };
+        expect!(find_matching_request(&request("/orders"), &pacts, &ServerOptions::default()).map(|response| response.status))
+            .to(be_ok().value(200));
+        expect!(find_matching_request(&request("/items"), &pacts, &ServerOptions::default()).map(|response| response.status))
+            .to(be_ok().value(204));
+        expect!(find_matching_request(&request("/other"), &pacts, &ServerOptions::default())).to(be_err());
+    }
+
+    #[test]
+    fn match_request_allows_the_methods_of_the_interactions_for_options_requests() {
+        let interaction = |method: &str, path: &str| Interaction { description: format!("{} {}", method, path),
+            request: Request { method: s!(method), path: s!(path), .. Request::default_request() }, .. Interaction::default() };
+        let pacts = [ Pact { interactions: vec![ interaction("GET", "/orders"), interaction("post", "/orders"),
+            interaction("DELETE", "/items") ], .. Pact::default() } ];
+
+        let request = |path: &str| Request { method: s!("OPTIONS"), path: path.to_string(), .. Request::default_request() };
+        let response = find_matching_request(&request("/orders"), &pacts, &ServerOptions::default()).unwrap();
+        expect!(response.status).to(be_equal_to(204));
+        expect!(response.headers.unwrap().get("Allow").cloned()).to(be_some().value(vec![ s!("GET, POST, HEAD, OPTIONS") ]));
+        expect!(find_matching_request(&request("/other"), &pacts, &ServerOptions::default())).to(be_err());
+    }
+
+    #[test]
+    fn match_request_only_uses_the_interactions_with_matching_descriptions() {
+        let interaction = |description: &str, status: u16| Interaction { description: s!(description),
+            response: Response { status, .. Response::default_response() }, .. Interaction::default() };
+        let pacts = [ Pact { interactions: vec![ interaction("a request for user 4", 200),
+            interaction("a request for user 42", 201) ], .. Pact::default() } ];
+
+        let options = |regex: &str| ServerOptions { description_filter: Some(Regex::new(regex).unwrap()), .. ServerOptions::default() };
+        let request = Request::default_request();
+        expect!(find_matching_request(&request, &pacts, &options("user 42")).map(|response| response.status)).to(be_ok().value(201));
+        expect!(find_matching_request(&request, &pacts, &options("user 4$")).map(|response| response.status)).to(be_ok().value(200));
+        expect!(find_matching_request(&request, &pacts, &options("user 5"))).to(be_err());
+    }
+
+    #[test]
+    fn match_request_sends_headers_identifying_the_interaction_if_enabled() {
+        let interaction = Interaction { description: s!("a request for Zoë\nwith a line break"), .. Interaction::default() };
+        let pacts = [ Pact { consumer: Consumer { name: s!("web") }, interactions: vec![ interaction ], .. Pact::default() } ];
+        let options = ServerOptions { interaction_headers: true, .. ServerOptions::default() };
+
+        let headers = find_matching_request(&Request::default_request(), &pacts, &options).unwrap().headers.unwrap();
+        expect!(headers.get(INTERACTION_ID_HEADER).cloned())
+            .to(be_some().value(vec![ admin::interaction_key(&pacts[0], &pacts[0].interactions[0]) ]));
+        expect!(headers.get(INTERACTION_CONSUMER_HEADER).cloned()).to(be_some().value(vec![ s!("web") ]));
+        expect!(headers.get(INTERACTION_DESCRIPTION_HEADER).cloned())
+            .to(be_some().value(vec![ s!("a request for Zo%C3%AB%0Awith a line break") ]));
+        let response = find_matching_request(&Request::default_request(), &pacts, &ServerOptions::default()).unwrap();
+        expect!(response.headers.unwrap_or_default().contains_key(INTERACTION_ID_HEADER)).to(be_false());
+    }
+
+    #[test]
+    fn answers_identical_get_requests_from_the_response_cache_if_enabled() {
+        let interaction = |path: &str, generators| Interaction { description: s!(path),
+            request: Request { path: s!(path), .. Request::default_request() },
+            response: Response { status: 200, generators, .. Response::default_response() }, .. Interaction::default() };
+        let pacts = vec![ Arc::new(Pact { interactions: vec![ interaction("/static", Generators::default()),
+            interaction("/generated", Generators { categories: hashmap!{
+                GeneratorCategory::BODY => hashmap!{ s!("$.id") => Generator::RandomInt(1, 10) } } }) ], .. Pact::default() }) ];
+        let snapshot = Snapshot::new(pacts.clone(), StateGenerators::default());
+        let context = RequestContext::default();
+        let coverage = Arc::new(Coverage::default());
+        let options = ServerOptions { response_cache: true, coverage: Some(coverage.clone()), .. ServerOptions::default() };
+        let request = |path: &str| Request { path: path.to_string(), .. Request::default_request() };
+
+        for _ in 0..2 {
+            expect!(super::find_response(&request("/static"), &snapshot, &context, &options).map(|response| response.status))
+                .to(be_ok().value(200));
+            expect!(super::find_response(&request("/generated"), &snapshot, &context, &options)).to(be_ok());
+        }
+        let key = |path: &str| snapshot.responses.key(&request(path), &super::response_selection(&options)).unwrap();
+        expect!(snapshot.responses.get(&key("/static")).map(|cached| cached.position)).to(be_some().value((0, 0)));
+        expect!(snapshot.responses.get(&key("/generated")).is_none()).to(be_true());
+        expect!(coverage.hits(&pacts[0], &pacts[0].interactions[0])).to(be_equal_to(2));
+        expect!(super::find_response(&request("/other"), &snapshot, &context, &options)).to(be_err());
     }
 
     #[test]
@@ -306,7 +1889,7 @@ mod test {
 
         let request1 = Request { path: s!("/two"), .. Request::default_request() };
 
-        expect!(super::find_matching_request(&request1, false, &vec![pact1, pact2], None, false)).to(be_err());
+        expect!(find_matching_request(&request1, &[pact1, pact2], &ServerOptions::default())).to(be_err());
     }
 
     #[test]
@@ -324,7 +1907,7 @@ mod test {
             query: Some(hashmap!{ s!("A") => vec![ s!("C") ] }),
             .. Request::default_request() };
 
-        expect!(super::find_matching_request(&request1, false, &vec![pact1, pact2], None, false)).to(be_err());
+        expect!(find_matching_request(&request1, &[pact1, pact2], &ServerOptions::default())).to(be_err());
     }
 
     #[test]
@@ -360,10 +1943,10 @@ mod test {
         let request4 = Request { method: s!("PUT"), headers: Some(hashmap!{ s!("Content-Type") => vec![s!("application/json")] }),
             .. Request::default_request() };
 
-        expect!(super::find_matching_request(&request1, false, &vec![pact1.clone(), pact2.clone()], None, false)).to(be_ok());
-        expect!(super::find_matching_request(&request2, false, &vec![pact1.clone(), pact2.clone()], None, false)).to(be_err());
-        expect!(super::find_matching_request(&request3, false, &vec![pact1.clone(), pact2.clone()], None, false)).to(be_ok());
-        expect!(super::find_matching_request(&request4, false, &vec![pact1.clone(), pact2.clone()], None, false)).to(be_ok());
+        expect!(find_matching_request(&request1, &[pact1.clone(), pact2.clone()], &ServerOptions::default())).to(be_ok());
+        expect!(find_matching_request(&request2, &[pact1.clone(), pact2.clone()], &ServerOptions::default())).to(be_err());
+        expect!(find_matching_request(&request3, &[pact1.clone(), pact2.clone()], &ServerOptions::default())).to(be_ok());
+        expect!(find_matching_request(&request4, &[pact1.clone(), pact2.clone()], &ServerOptions::default())).to(be_ok());
     }
 
     #[test]
@@ -387,20 +1970,86 @@ mod test {
             body: OptionalBody::Present("{\"a\": 1, \"b\": 4, \"c\": 6}".as_bytes().into()),
             .. Request::default_request() };
 
-        expect!(super::find_matching_request(&request1, false, &vec![pact1, pact2], None, false)).to(be_ok().value(interaction2.response));
+        expect!(find_matching_request(&request1, &[pact1, pact2], &ServerOptions::default())).to(be_ok().value(interaction2.response));
+    }
+
+    #[test]
+    fn match_request_prefers_the_response_content_type_the_request_accepts() {
+        let response = |content_type: &str, body: &str| Response {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!(content_type) ] }),
+            body: OptionalBody::Present(body.as_bytes().into()),
+            .. Response::default_response()
+        };
+        let json = Interaction { description: s!("json"), response: response("application/json", "[]"), .. Interaction::default() };
+        let csv = Interaction { description: s!("csv"), response: response("text/csv", "id,name"), .. Interaction::default() };
+        let pacts = [ Pact { interactions: vec![ json.clone(), csv.clone() ], .. Pact::default() } ];
+        let accepting = |accept: &str| Request {
+            headers: Some(hashmap!{ s!("Accept") => vec![ s!(accept) ] }),
+            .. Request::default_request()
+        };
+
+        expect!(find_matching_request(&Request::default_request(), &pacts, &ServerOptions::default())).to(be_ok().value(json.response.clone()));
+        expect!(find_matching_request(&accepting("text/csv"), &pacts, &ServerOptions::default())).to(be_ok().value(csv.response.clone()));
+        expect!(find_matching_request(&accepting("application/json;q=0.5, text/*"), &pacts, &ServerOptions::default())).to(be_ok().value(csv.response));
+        expect!(find_matching_request(&accepting("*/*"), &pacts, &ServerOptions::default())).to(be_ok().value(json.response));
+    }
+
+    #[test]
+    fn match_request_uses_the_interaction_with_the_highest_priority_if_several_match() {
+        let first = Interaction { description: s!("first"), response: Response { status: 200, .. Response::default_response() }, .. Interaction::default() };
+        let second = Interaction { description: s!("second"), response: Response { status: 201, .. Response::default_response() }, .. Interaction::default() };
+        let third = Interaction { description: s!("third"), response: Response { status: 202, .. Response::default_response() }, .. Interaction::default() };
+        let pacts = [ Pact {
+            interactions: vec![ first.clone(), second.clone(), third.clone() ],
+            metadata: btreemap!{ s!("interactionPriorities") => btreemap!{ s!("second") => s!("5") } },
+            .. Pact::default()
+        } ];
+        let request = Request::default_request();
+
+        expect!(find_matching_request(&request, &pacts, &ServerOptions::default())).to(be_ok().value(second.response));
+        let overrides = InteractionOverrides::from_json(&json!({ "third": { "priority": 10 } })).unwrap();
+        let options = ServerOptions { overrides: Some(Arc::new(overrides)), .. ServerOptions::default() };
+        expect!(find_matching_request(&request, &pacts, &options)).to(be_ok().value(third.response));
+        let overrides = InteractionOverrides::from_json(&json!({ "second": { "priority": -1 } })).unwrap();
+        let options = ServerOptions { overrides: Some(Arc::new(overrides)), .. ServerOptions::default() };
+        expect!(find_matching_request(&request, &pacts, &options)).to(be_ok().value(first.response));
+    }
+
+    #[test]
+    fn match_request_weighs_header_mismatches_more_than_body_differences() {
+        let with_header = Interaction { request: Request {
+            headers: Some(hashmap!{ s!("X-Trace") => vec![ s!("1") ] }),
+            body: OptionalBody::Present("{\"a\": 1, \"b\": 2, \"c\": 3}".as_bytes().into()),
+            .. Request::default_request() },
+            response: Response { status: 200, .. Response::default_response() },
+            .. Interaction::default() };
+        let other_body = Interaction { request: Request {
+            body: OptionalBody::Present("{\"a\": 2, \"b\": 4, \"c\": 6}".as_bytes().into()),
+            .. Request::default_request() },
+            response: Response { status: 201, .. Response::default_response() },
+            .. Interaction::default() };
+        let pacts = [ Pact { interactions: vec![ with_header, other_body.clone() ], .. Pact::default() } ];
+
+        let request = Request {
+            body: OptionalBody::Present("{\"a\": 1, \"b\": 2, \"c\": 3}".as_bytes().into()),
+            .. Request::default_request() };
+
+        expect!(find_matching_request(&request, &pacts, &ServerOptions::default())).to(be_ok().value(other_body.response));
     }
 
     #[test]
     fn with_auto_cors_return_200_with_an_option_request() {
         let interaction1 = Interaction::default();
-        let pact1 = Pact { interactions: vec![ interaction1 ], .. Pact::default() };
+        let pacts = vec![ Pact { interactions: vec![ interaction1 ], .. Pact::default() } ];
 
         let request1 = Request {
             method: s!("OPTIONS"),
             .. Request::default_request() };
 
-        expect!(super::find_matching_request(&request1, true, &vec![pact1.clone()], None, false)).to(be_ok());
-        expect!(super::find_matching_request(&request1, false, &vec![pact1.clone()], None, false)).to(be_err());
+        expect!(find_matching_request(&request1, &pacts, &ServerOptions { auto_cors: true, .. ServerOptions::default() })).to(be_ok());
+        let request2 = Request { path: s!("/other"), .. request1.clone() };
+        expect!(find_matching_request(&request2, &pacts, &ServerOptions { auto_cors: true, .. ServerOptions::default() })).to(be_ok());
+        expect!(find_matching_request(&request2, &pacts, &ServerOptions::default())).to(be_err());
     }
 
     #[test]
@@ -437,7 +2086,7 @@ mod test {
             query: Some(hashmap!{ s!("page") => vec![ s!("3") ] }),
             .. Request::default_request() };
 
-        expect!(super::find_matching_request(&request1, false, &vec![pact1, pact2.clone()], None, false)).to(be_ok());
+        expect!(find_matching_request(&request1, &[pact1, pact2.clone()], &ServerOptions::default())).to(be_ok());
     }
 
     #[test]
@@ -465,16 +2114,20 @@ mod test {
             response: Response { status: 203, .. Response::default_response() },
             .. Interaction::default() };
 
-        let pact = Pact { interactions: vec![ interaction1, interaction2, interaction3 ],
-            .. Pact::default() };
+        let pacts = vec![ Pact { interactions: vec![ interaction1, interaction2, interaction3 ],
+            .. Pact::default() } ];
 
         let request = Request::default_request();
+        let filtered_by = |state: &str| ServerOptions {
+            provider_state: Some(Regex::new(state).unwrap().into()),
+            .. ServerOptions::default()
+        };
 
-        expect!(super::find_matching_request(&request, false, &vec![pact.clone()], Some(Regex::new("state one").unwrap()), false)).to(be_ok().value(response1.clone()));
-        expect!(super::find_matching_request(&request, false, &vec![pact.clone()], Some(Regex::new("state two").unwrap()), false)).to(be_ok().value(response2.clone()));
-        expect!(super::find_matching_request(&request, false, &vec![pact.clone()], Some(Regex::new("state three").unwrap()), false)).to(be_ok().value(response3.clone()));
-        expect!(super::find_matching_request(&request, false, &vec![pact.clone()], Some(Regex::new("state four").unwrap()), false)).to(be_err());
-        expect!(super::find_matching_request(&request, false, &vec![pact.clone()], Some(Regex::new("state .*").unwrap()), false)).to(be_ok().value(response1.clone()));
+        expect!(find_matching_request(&request, &pacts, &filtered_by("state one"))).to(be_ok().value(response1.clone()));
+        expect!(find_matching_request(&request, &pacts, &filtered_by("state two"))).to(be_ok().value(response2.clone()));
+        expect!(find_matching_request(&request, &pacts, &filtered_by("state three"))).to(be_ok().value(response3.clone()));
+        expect!(find_matching_request(&request, &pacts, &filtered_by("state four"))).to(be_err());
+        expect!(find_matching_request(&request, &pacts, &filtered_by("state .*"))).to(be_ok().value(response1.clone()));
     }
 
     #[test]
@@ -487,7 +2140,375 @@ mod test {
 
         let request = Request { headers: Some(hashmap!{ s!("TEST-X") => vec![s!("X, Y")] }), .. Request::default_request() };
 
-        let result = super::find_matching_request(&request, false, &vec![pact], None, false);
+        let result = find_matching_request(&request, &[pact], &ServerOptions::default());
         expect!(result).to(be_ok().value(interaction.response));
     }
+
+    #[test]
+    fn match_request_combines_multiple_provider_state_filters() {
+        let interaction1 = Interaction {
+            provider_states: vec![ ProviderState::default(&"state one".into()) ],
+            response: Response { status: 201, .. Response::default_response() },
+            .. Interaction::default() };
+        let interaction2 = Interaction {
+            provider_states: vec![ ProviderState::default(&"state one".into()),
+                                   ProviderState::default(&"state two".into()) ],
+            response: Response { status: 202, .. Response::default_response() },
+            .. Interaction::default() };
+        let pacts = vec![ Pact { interactions: vec![ interaction1, interaction2.clone() ], .. Pact::default() } ];
+        let request = Request::default_request();
+
+        let any = ProviderStateFilter::new(vec![ Regex::new("state two").unwrap(), Regex::new("state three").unwrap() ],
+                                           ProviderStateMode::Any);
+        let all = ProviderStateFilter::new(vec![ Regex::new("state one").unwrap(), Regex::new("state two").unwrap() ],
+                                           ProviderStateMode::All);
+        let all_missing = ProviderStateFilter::new(vec![ Regex::new("state one").unwrap(), Regex::new("state three").unwrap() ],
+                                                   ProviderStateMode::All);
+
+        expect!(find_matching_request(&request, &pacts, &ServerOptions { provider_state: Some(any), .. ServerOptions::default() })).to(be_ok().value(interaction2.response.clone()));
+        expect!(find_matching_request(&request, &pacts, &ServerOptions { provider_state: Some(all), .. ServerOptions::default() })).to(be_ok().value(interaction2.response.clone()));
+        expect!(find_matching_request(&request, &pacts, &ServerOptions { provider_state: Some(all_missing), .. ServerOptions::default() })).to(be_err());
+    }
+
+    #[test]
+    fn match_request_can_only_use_interactions_without_provider_states() {
+        let stateful = Interaction {
+            provider_states: vec![ ProviderState::default(&"state one".into()) ],
+            response: Response { status: 201, .. Response::default_response() },
+            .. Interaction::default() };
+        let stateless = Interaction { response: Response { status: 200, .. Response::default_response() }, .. Interaction::default() };
+        let request = Request::default_request();
+        let options = ServerOptions { provider_state: Some(ProviderStateFilter::stateless()), .. ServerOptions::default() };
+
+        let pacts = vec![ Pact { interactions: vec![ stateful.clone(), stateless.clone() ], .. Pact::default() } ];
+        expect!(find_matching_request(&request, &pacts, &options)).to(be_ok().value(stateless.response));
+        let pacts = vec![ Pact { interactions: vec![ stateful ], .. Pact::default() } ];
+        expect!(find_matching_request(&request, &pacts, &options)).to(be_err());
+        expect!(ProviderStateFilter::stateless().to_string()).to(be_equal_to(s!("no provider states")));
+    }
+
+    #[test]
+    fn match_request_can_reject_malformed_json_bodies() {
+        let interaction = Interaction {
+            request: Request {
+                method: s!("POST"),
+                headers: Some(hashmap!{ s!("Content-Type") => vec![s!("application/json")] }),
+                body: OptionalBody::Present("{\"a\": 1}".as_bytes().into()),
+                .. Request::default_request() },
+            .. Interaction::default() };
+        let pacts = vec![ Pact { interactions: vec![ interaction ], .. Pact::default() } ];
+
+        let request = Request {
+            method: s!("POST"),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![s!("application/json")] }),
+            body: OptionalBody::Present("{\"a\": 1".as_bytes().into()),
+            .. Request::default_request() };
+        let other_path = Request { path: s!("/other"), .. request.clone() };
+        let options = ServerOptions { malformed_json_status: Some(400), .. ServerOptions::default() };
+
+        expect!(find_matching_request(&request, &pacts, &ServerOptions::default())).to(be_err());
+        let response = find_matching_request(&request, &pacts, &options);
+        expect!(response.clone().map(|r| r.status)).to(be_ok().value(400));
+        expect!(response.unwrap().body.str_value().contains("Malformed JSON")).to(be_true());
+        expect!(find_matching_request(&other_path, &pacts, &options)).to(be_err());
+    }
+
+    #[test]
+    fn match_request_only_uses_the_pacts_for_the_given_providers() {
+        let pact1 = Pact {
+            provider: Provider { name: s!("provider-a") },
+            interactions: vec![ Interaction { response: Response { status: 201, .. Response::default_response() }, .. Interaction::default() } ],
+            .. Pact::default() };
+        let pact2 = Pact {
+            provider: Provider { name: s!("provider-b") },
+            interactions: vec![ Interaction { response: Response { status: 202, .. Response::default_response() }, .. Interaction::default() } ],
+            .. Pact::default() };
+        let pacts = vec![ pact1, pact2 ];
+        let request = Request::default_request();
+        let for_providers = |providers: Vec<String>| ServerOptions { providers: Some(providers), .. ServerOptions::default() };
+
+        expect!(find_matching_request(&request, &pacts, &for_providers(vec![ s!("provider-b") ])).map(|r| r.status)).to(be_ok().value(202));
+        expect!(find_matching_request(&request, &pacts, &for_providers(vec![ s!("provider-c") ]))).to(be_err());
+    }
+
+    #[test]
+    fn warm_up_reports_the_interactions_that_do_not_match_their_own_request() {
+        let interaction1 = Interaction {
+            description: s!("get users"),
+            provider_states: vec![ ProviderState::default(&s!("state one")) ],
+            request: Request { path: s!("/users"), .. Request::default_request() },
+            .. Interaction::default()
+        };
+        let interaction2 = Interaction {
+            description: s!("get orders"),
+            provider_states: vec![ ProviderState::default(&s!("state two")) ],
+            request: Request { path: s!("/orders"), .. Request::default_request() },
+            .. Interaction::default()
+        };
+        let pacts = vec![ Arc::new(Pact { interactions: vec![ interaction1, interaction2 ], .. Pact::default() }) ];
+        let options = ServerOptions {
+            provider_state: Some(Regex::new("one").unwrap().into()),
+            .. ServerOptions::default()
+        };
+
+        let results = super::warm_up(&pacts, &StateGenerators::default(), &options, 2);
+        expect!(results.len()).to(be_equal_to(2));
+        expect!(results[0].1.is_some()).to(be_true());
+        expect!(results[1].1.is_some()).to(be_false());
+    }
+
+    #[test]
+    fn check_request_returns_the_matched_interaction_or_the_mismatches() {
+        let interaction = |description: &str, path: &str| Interaction {
+            description: s!(description),
+            request: Request { path: s!(path), .. Request::default_request() },
+            response: Response { status: 201, .. Response::default_response() },
+            .. Interaction::default()
+        };
+        let pacts = vec![ Arc::new(Pact {
+            consumer: Consumer { name: s!("web") },
+            provider: Provider { name: s!("users") },
+            interactions: vec![ interaction("get users", "/users"), interaction("get orders", "/orders") ],
+            .. Pact::default()
+        }) ];
+
+        let users = Request { path: s!("/users"), .. Request::default_request() };
+        expect!(super::check_request(&users, &pacts, &StateGenerators::default(), &ServerOptions::default())).to(be_equal_to(RequestCheck::Matched {
+            consumer: s!("web"),
+            provider: s!("users"),
+            description: s!("get users"),
+            response: Box::new(Response { status: 201, .. Response::default_response() })
+        }));
+        let posted = Request { method: s!("POST"), path: s!("/orders"), .. Request::default_request() };
+        match super::check_request(&posted, &pacts, &StateGenerators::default(), &ServerOptions::default()) {
+            RequestCheck::Mismatched(mismatches) => {
+                expect!(mismatches.len()).to(be_equal_to(2));
+                expect!(mismatches[0].0.clone()).to(be_equal_to(s!("get orders")));
+                expect!(mismatches[0].1.clone()).to(be_equal_to(vec![ s!("HTTP Method does not match, expected: GET, actual: POST") ]));
+            },
+            check => panic!("Expected the request to not match, but got {:?}", check)
+        }
+
+        // the same interaction in the pacts of two providers
+        let pacts = vec![ pacts[0].clone(), Arc::new(Pact { provider: Provider { name: s!("accounts") }, .. pacts[0].as_ref().clone() }) ];
+        let options = ServerOptions { providers: Some(vec![ s!("accounts") ]), .. ServerOptions::default() };
+        let provider = match super::check_request(&users, &pacts, &StateGenerators::default(), &options) {
+            RequestCheck::Matched { provider, .. } => provider,
+            check => panic!("Expected the request to match, but got {:?}", check)
+        };
+        expect!(provider).to(be_equal_to("accounts"));
+    }
+
+    #[test]
+    fn match_request_answers_browser_probes_if_no_interaction_matches() {
+        let interaction = Interaction {
+            request: Request { path: s!("/robots.txt"), .. Request::default_request() },
+            .. Interaction::default()
+        };
+        let pacts = vec![ Pact { interactions: vec![ interaction ], .. Pact::default() } ];
+        let options = ServerOptions { answer_browser_probes: true, .. ServerOptions::default() };
+        let favicon = Request { path: s!("/favicon.ico"), .. Request::default_request() };
+        let robots = Request { path: s!("/robots.txt"), .. Request::default_request() };
+
+        expect!(find_matching_request(&favicon, &pacts, &options).map(|r| r.status)).to(be_ok().value(204));
+        expect!(find_matching_request(&robots, &pacts, &options).map(|r| r.status)).to(be_ok().value(200));
+        expect!(find_matching_request(&favicon, &pacts, &ServerOptions::default())).to(be_err());
+        let post = Request { method: s!("POST"), .. favicon.clone() };
+        expect!(find_matching_request(&post, &pacts, &options)).to(be_err());
+    }
+
+    #[test]
+    fn match_request_responds_with_not_implemented_for_draft_interactions() {
+        let interaction = Interaction {
+            description: s!("an upcoming endpoint"),
+            request: Request { path: s!("/upcoming"), .. Request::default_request() },
+            .. Interaction::default()
+        };
+        let pacts = vec![ Pact { interactions: vec![ interaction ], .. Pact::default() } ];
+        let request = Request { path: s!("/upcoming"), .. Request::default_request() };
+        let overrides = InteractionOverrides::from_json(&json!({ "an upcoming endpoint": { "draft": true } })).unwrap();
+        let options = ServerOptions { overrides: Some(Arc::new(overrides)), .. ServerOptions::default() };
+
+        let response = find_matching_request(&request, &pacts, &options).unwrap();
+        expect!(response.status).to(be_equal_to(501));
+        expect!(response.body.str_value()).to(be_equal_to("{\"error\":\"Not yet implemented\",\"interaction\":\"an upcoming endpoint\"}"));
+        expect!(find_matching_request(&request, &pacts, &ServerOptions::default()).map(|r| r.status)).to(be_ok().value(200));
+    }
+
+    #[test]
+    fn match_request_enforces_the_authorization_header_of_protected_interactions() {
+        let protected = Interaction {
+            description: s!("protected"),
+            request: Request {
+                path: s!("/protected"),
+                headers: Some(hashmap!{ s!("Authorization") => vec![ s!("Bearer 1234") ] }),
+                .. Request::default_request()
+            },
+            .. Interaction::default()
+        };
+        let public = Interaction {
+            description: s!("public"),
+            request: Request { path: s!("/public"), .. Request::default_request() },
+            .. Interaction::default()
+        };
+        let pacts = vec![ Pact { interactions: vec![ protected, public ], .. Pact::default() } ];
+        let options = ServerOptions { enforce_auth: true, .. ServerOptions::default() };
+        let request = |path: &str, auth: Option<&str>| Request {
+            path: path.to_string(),
+            headers: auth.map(|auth| hashmap!{ s!("Authorization") => vec![ auth.to_string() ] }),
+            .. Request::default_request()
+        };
+
+        expect!(find_matching_request(&request("/protected", Some("Bearer abcd")), &pacts, &options).map(|r| r.status)).to(be_ok().value(200));
+        let response = find_matching_request(&request("/protected", None), &pacts, &options).unwrap();
+        expect!(response.status).to(be_equal_to(401));
+        expect!(response.headers.unwrap().get("WWW-Authenticate").cloned()).to(be_some().value(vec![ s!("Bearer") ]));
+        expect!(find_matching_request(&request("/protected", Some("Basic abcd")), &pacts, &options).map(|r| r.status)).to(be_ok().value(401));
+        expect!(find_matching_request(&request("/public", None), &pacts, &options).map(|r| r.status)).to(be_ok().value(200));
+        expect!(find_matching_request(&request("/protected", None), &pacts, &ServerOptions::default()).map(|r| r.status)).to(be_ok().value(200));
+    }
+
+    #[test]
+    fn rejects_requests_expecting_continue_if_no_interaction_matches_without_the_body() {
+        let interaction = Interaction {
+            request: Request {
+                method: s!("POST"),
+                path: s!("/upload"),
+                body: OptionalBody::Present("{\"name\": \"file\"}".into()),
+                .. Request::default_request()
+            },
+            .. Interaction::default()
+        };
+        let pacts = vec![ Arc::new(Pact { interactions: vec![ interaction ], .. Pact::default() }) ];
+        let request = |path: &str| Request {
+            method: s!("POST"),
+            path: path.to_string(),
+            body: OptionalBody::Missing,
+            .. Request::default_request()
+        };
+
+        let calls = Arc::new(Mutex::new(vec![]));
+        let mut options = ServerOptions::default();
+        let (matched, mismatched) = (calls.clone(), calls.clone());
+        options.callbacks.on_match(move |request, _| matched.lock().unwrap().push(format!("matched {}", request.path)));
+        options.callbacks.on_mismatch(move |request, _| mismatched.lock().unwrap().push(format!("mismatched {}", request.path)));
+
+        let snapshot = Snapshot::new(pacts, StateGenerators::default());
+        expect!(super::reject_expectation(&request("/upload"), &snapshot, &options)).to(be_none());
+        expect!(super::reject_expectation(&request("/other"), &snapshot, &options).map(|r| r.status))
+            .to(be_some().value(404));
+        expect!(calls.lock().unwrap().clone()).to(be_equal_to(vec![ s!("mismatched /other") ]));
+    }
+
+    #[test]
+    fn records_the_status_of_the_control_header_in_the_journal() {
+        let interaction = Interaction {
+            request: Request { path: s!("/users/42"), .. Request::default_request() },
+            response: Response { status: 200, .. Response::default_response() },
+            .. Interaction::default()
+        };
+        let snapshot = Snapshot::new(vec![ Arc::new(Pact { interactions: vec![ interaction ], .. Pact::default() }) ],
+                                     StateGenerators::default());
+        let journal = Arc::new(super::Journal::new(10));
+        let options = ServerOptions { journal: Some(journal.clone()), .. ServerOptions::default() };
+        let context = RequestContext { status: Some(503), .. RequestContext::default() };
+        let request = Request { path: s!("/users/42"), .. Request::default_request() };
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let response = runtime.block_on(super::handle_request(request, &snapshot, &context, &options));
+        expect!(response.status).to(be_equal_to(503));
+        expect!(journal.entries().iter().map(|entry| entry.status).collect::<Vec<u16>>()).to(be_equal_to(vec![ 503 ]));
+    }
+
+    #[test]
+    fn rejects_filter_headers_that_are_not_valid_regular_expressions() {
+        let mut headers = http::HeaderMap::new();
+        expect!(super::header_regex(&headers, "X-Provider-State").map(|regex| regex.is_none())).to(be_ok().value(true));
+        headers.insert("X-Provider-State", http::HeaderValue::from_static("^an order (exists|is open)$"));
+        expect!(super::header_regex(&headers, "X-Provider-State").map(|regex| regex.map(|regex| regex.as_str().to_string())))
+            .to(be_ok().value(Some(s!("^an order (exists|is open)$"))));
+        headers.insert("X-Provider-State", http::HeaderValue::from_static("("));
+        expect!(super::header_regex(&headers, "X-Provider-State")).to(be_err());
+        headers.insert("X-Provider-State", http::HeaderValue::from_bytes("ordre créé".as_bytes()).unwrap());
+        expect!(super::header_regex(&headers, "X-Provider-State")).to(be_err());
+    }
+
+    #[test]
+    fn strips_the_base_path() {
+        let strip = |uri: &str, base_path: &str| super::strip_base_path(&uri.parse().unwrap(), base_path).map(|uri| uri.to_string());
+        expect!(strip("/mock/users/1?active=true", "/mock/users")).to(be_some().value(s!("/1?active=true")));
+        expect!(strip("/mock/users", "/mock/users/")).to(be_some().value(s!("/")));
+        expect!(strip("http://localhost:8080/mock/orders", "/mock")).to(be_some().value(s!("http://localhost:8080/orders")));
+        expect!(strip("/mock/usersettings", "/mock/users")).to(be_none());
+        expect!(strip("/users/1", "/mock")).to(be_none());
+    }
+
+    #[test]
+    fn keeps_or_generates_the_correlation_id() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("abc-123"));
+        expect!(super::correlation_id(&mut headers, "X-Request-Id"))
+            .to(be_some().value((HeaderName::from_static("x-request-id"), HeaderValue::from_static("abc-123"))));
+
+        let mut headers = HeaderMap::new();
+        let (name, value) = super::correlation_id(&mut headers, "X-Correlation-Id").unwrap();
+        expect!(name.as_str()).to(be_equal_to("x-correlation-id"));
+        expect!(value.len()).to(be_equal_to(36));
+        expect!(headers.get("x-correlation-id")).to(be_some().value(&value));
+        expect!(super::correlation_id(&mut HeaderMap::new(), "not a header")).to(be_none());
+    }
+
+    #[test]
+    fn adds_and_overrides_the_response_headers() {
+        let header = |name: &'static str, value: &'static str| (HeaderName::from_static(name), HeaderValue::from_static(value));
+        let mut headers = HeaderMap::new();
+        headers.insert("cache-control", HeaderValue::from_static("max-age=60"));
+        headers.insert("x-frame-options", HeaderValue::from_static("SAMEORIGIN"));
+        super::set_response_headers(&mut headers,
+            &[ header("cache-control", "no-store"), header("x-content-type-options", "nosniff") ],
+            &[ header("x-frame-options", "DENY"), header("set-cookie", "a=1"), header("set-cookie", "b=2") ]);
+        expect!(headers.get("cache-control")).to(be_some().value(HeaderValue::from_static("max-age=60")));
+        expect!(headers.get("x-content-type-options")).to(be_some().value(HeaderValue::from_static("nosniff")));
+        expect!(headers.get_all("x-frame-options").iter().collect::<Vec<_>>()).to(be_equal_to(vec![ "DENY" ]));
+        expect!(headers.get_all("set-cookie").iter().collect::<Vec<_>>()).to(be_equal_to(vec![ "a=1", "b=2" ]));
+    }
+
+    #[test]
+    fn finds_the_provider_for_the_host() {
+        let pact = |provider: &str| Arc::new(Pact { provider: Provider { name: s!(provider) }, .. Pact::default() });
+        let pacts = vec![ pact("users"), pact("Order Service") ];
+        expect!(super::host_provider("users.localhost:8080", &pacts)).to(be_some().value(s!("users")));
+        expect!(super::host_provider("Users", &pacts)).to(be_some().value(s!("users")));
+        expect!(super::host_provider("order-service.example.com", &pacts)).to(be_some().value(s!("Order Service")));
+        expect!(super::host_provider("localhost:8080", &pacts)).to(be_none());
+        expect!(super::host_provider("", &pacts)).to(be_none());
+    }
+
+    #[test]
+    fn tries_the_following_ports_if_the_port_is_in_use() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let taken = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let listener = runtime.block_on(super::bind(&ListenOptions { port, port_range: 10, .. ListenOptions::default() }));
+        expect!(listener.unwrap().local_addr().unwrap().port()).to(be_greater_than(port));
+        let error = runtime.block_on(super::bind(&ListenOptions { port, .. ListenOptions::default() })).unwrap_err();
+        expect!(error.contains("already in use")).to(be_true());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn replaces_unix_sockets_that_are_no_longer_used() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let path = std::env::temp_dir().join(format!("pact-stub-server-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let listener = runtime.block_on(async { super::bind_unix(&path) });
+        expect!(listener.is_ok()).to(be_true());
+        let error = runtime.block_on(async { super::bind_unix(&path) }).unwrap_err();
+        expect!(error.contains("already in use")).to(be_true());
+        drop(listener);
+        expect!(runtime.block_on(async { super::bind_unix(&path) }).is_ok()).to(be_true());
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,239 @@
+use opentelemetry::{InstrumentationScope, Value};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{Status, TracerProvider};
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanExporter};
+use hyper::Uri;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::net::IpAddr;
+use tracing::{Dispatch, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::Registry;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Header with the trace a request is part of
+pub const TRACEPARENT: &str = "traceparent";
+/// Path the spans are sent to on the OTLP/HTTP collector, if the endpoint does not already include it
+pub const TRACES_PATH: &str = "/v1/traces";
+/// Service name of the spans, if `OTEL_SERVICE_NAME` is not set
+pub const DEFAULT_SERVICE_NAME: &str = "pact-stub-server";
+
+/// The URL the spans are sent to: the endpoint of the collector (like `http://localhost:4318`), with the traces path
+/// added if it does not end with it
+pub fn traces_endpoint(endpoint: &str) -> Result<String, String> {
+    let trimmed = endpoint.trim_end_matches('/');
+    let url = if trimmed.ends_with(TRACES_PATH) { trimmed.to_string() } else { format!("{}{}", trimmed, TRACES_PATH) };
+    url.parse::<Uri>()
+        .map_err(|err| format!("'{}' is not a valid URL - {}", endpoint, err))
+        .and_then(|uri| if uri.scheme().is_some() && uri.authority().is_some() {
+            Ok(url)
+        } else {
+            Err(format!("'{}' is not an absolute URL", endpoint))
+        })
+}
+
+/// Sends the spans of the requests to an OpenTelemetry collector with OTLP/HTTP. The spans are `tracing` spans of a
+/// subscriber of their own, so the log output of the server does not change.
+pub struct Tracer {
+    endpoint: String,
+    provider: SdkTracerProvider,
+    dispatch: Dispatch
+}
+
+impl Debug for Tracer {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Tracer({})", self.endpoint)
+    }
+}
+
+impl Tracer {
+    /// Exports to the collector at the endpoint, with JSON bodies
+    pub fn new(endpoint: &str, service_name: &str) -> Result<Tracer, String> {
+        let endpoint = traces_endpoint(endpoint)?;
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_protocol(Protocol::HttpJson)
+            .with_endpoint(endpoint.as_str())
+            .build()
+            .map_err(|err| format!("Could not create the exporter for '{}' - {}", endpoint, err))?;
+        Ok(Tracer::with_exporter(endpoint, service_name, exporter))
+    }
+
+    /// Exports the spans in batches with the exporter, from a thread of its own
+    fn with_exporter<E: SpanExporter + 'static>(endpoint: String, service_name: &str, exporter: E) -> Tracer {
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(service_name.to_string()).build())
+            .build();
+        let scope = InstrumentationScope::builder(DEFAULT_SERVICE_NAME).with_version(env!("CARGO_PKG_VERSION")).build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer_with_scope(scope));
+        Tracer { endpoint, provider, dispatch: Dispatch::new(Registry::default().with(layer)) }
+    }
+
+    /// The URL the spans are sent to
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Starts the span of a request the server received, as part of the trace of the `traceparent` header if there is
+    /// one. Clients that send a `traceparent` that is not sampled get no span. The span is exported when it is dropped.
+    pub fn start_span(&self, traceparent: Option<&str>, method: &str, path: &str, client: IpAddr) -> Span {
+        let name = format!("{} {}", method, path);
+        let client = client.to_string();
+        let span = tracing::dispatcher::with_default(&self.dispatch, || tracing::info_span!("request",
+            otel.name = name.as_str(), otel.kind = "server", http.request.method = method, url.path = path,
+            client.address = client.as_str()));
+        if let Some(traceparent) = traceparent {
+            let carrier = hashmap! { TRACEPARENT.to_string() => traceparent.to_string() };
+            if let Err(err) = span.set_parent(TraceContextPropagator::new().extract(&carrier)) {
+                warn!("Could not continue the trace of the request - {}", err);
+            }
+        }
+        span
+    }
+
+    /// Sends the spans that have not been exported yet, and stops the export
+    pub fn shutdown(&self) {
+        if let Err(err) = self.provider.shutdown() {
+            warn!("Failed to export the spans to {} - {}", self.endpoint, err);
+        }
+    }
+}
+
+/// Sets the attribute of the span, replacing the value it already has
+pub fn set_attribute<V: Into<Value>>(span: &Span, key: &'static str, value: V) {
+    span.set_attribute(key, value);
+}
+
+/// Ends the span with the status of the response. Server errors mark the span as failed.
+pub fn finish(span: &Span, status: u16) {
+    span.set_attribute("http.response.status_code", i64::from(status));
+    if status >= 500 {
+        span.set_status(Status::error(status.to_string()));
+    }
+}
+
+/// The `traceparent` header for requests that are part of the span
+pub fn traceparent(span: &Span) -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&span.context(), &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use log::{Log, LogLevelFilter, LogMetadata, LogRecord};
+    use opentelemetry::trace::{SpanKind, Status, TraceContextExt};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use std::env;
+    use std::net::Ipv4Addr;
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    /// Keeps the messages logged about the spans of this module
+    struct CapturingLogger {
+        messages: Arc<Mutex<Vec<String>>>
+    }
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, _: &LogMetadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &LogRecord) {
+            let message = record.args().to_string();
+            // the other tests log the spans of the matching with the same target
+            if record.target() == "pact_stub_server::telemetry" || (record.target() == "tracing::span" && message.contains(" request;")) {
+                self.messages.lock().unwrap().push(message);
+            }
+        }
+    }
+
+    fn attribute(span: &opentelemetry_sdk::trace::SpanData, key: &str) -> Option<Value> {
+        span.attributes.iter().find(|kv| kv.key.as_str() == key).map(|kv| kv.value.clone())
+    }
+
+    #[test]
+    fn continues_the_trace_of_the_traceparent_header() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer = Tracer::with_exporter(s!("http://localhost:4318/v1/traces"), "stub", exporter.clone());
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        let span = tracer.start_span(Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"), "GET", "/orders", client);
+        let context = span.context();
+        expect!(context.span().span_context().trace_id().to_string()).to(be_equal_to(s!("4bf92f3577b34da6a3ce929d0e0e4736")));
+        expect!(context.span().span_context().span_id().to_string()).to_not(be_equal_to(s!("00f067aa0ba902b7")));
+        expect!(traceparent(&span).map(|header| header.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"))).to(be_some().value(true));
+        drop(span);
+        drop(tracer.start_span(Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"), "GET", "/not-sampled", client));
+        drop(tracer.start_span(Some("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"), "GET", "/invalid", client));
+        tracer.provider.force_flush().unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        expect!(spans.iter().map(|span| span.name.to_string()).collect::<Vec<String>>())
+            .to(be_equal_to(vec![ s!("GET /orders"), s!("GET /invalid") ]));
+        expect!(spans[0].parent_span_id.to_string()).to(be_equal_to(s!("00f067aa0ba902b7")));
+        expect!(spans[1].span_context.trace_id().to_string()).to_not(be_equal_to(s!("4bf92f3577b34da6a3ce929d0e0e4736")));
+        expect!(spans[1].parent_span_is_remote).to(be_false());
+    }
+
+    #[test]
+    fn does_not_log_the_spans() {
+        // the logger can only be set once, and the quickcheck tests set theirs, so this runs in a process of its own
+        if env::var_os("PACT_STUB_LOGGER_TEST").is_none() {
+            let output = Command::new(env::current_exe().unwrap())
+                .args([ "--exact", "telemetry::test::does_not_log_the_spans" ])
+                .env("PACT_STUB_LOGGER_TEST", "1")
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            expect!(stdout.contains("1 passed")).to(be_true());
+            return;
+        }
+        let messages = Arc::new(Mutex::new(vec![]));
+        let captured = messages.clone();
+        log::set_logger(|max_log_level| {
+            max_log_level.set(LogLevelFilter::Trace);
+            Box::new(CapturingLogger { messages: captured })
+        }).unwrap();
+        let tracer = Tracer::with_exporter(s!("http://localhost:4318/v1/traces"), "stub", InMemorySpanExporter::default());
+        let span = tracer.start_span(None, "GET", "/orders", IpAddr::V4(Ipv4Addr::LOCALHOST));
+        set_attribute(&span, "pact.matched", true);
+        finish(&span, 200);
+        drop(span);
+        tracer.provider.force_flush().unwrap();
+        expect!(messages.lock().unwrap().clone()).to(be_equal_to(Vec::<String>::new()));
+    }
+
+    #[test]
+    fn exports_the_attributes_and_status_of_the_spans() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer = Tracer::with_exporter(s!("http://localhost:4318/v1/traces"), "orders-stub", exporter.clone());
+        let span = tracer.start_span(None, "GET", "/orders", IpAddr::V4(Ipv4Addr::LOCALHOST));
+        set_attribute(&span, "pact.interaction", "a request for orders");
+        set_attribute(&span, "pact.mismatches", 0);
+        finish(&span, 503);
+        drop(span);
+        tracer.provider.force_flush().unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = &spans[0];
+        expect!(span.span_kind.clone()).to(be_equal_to(SpanKind::Server));
+        expect!(attribute(span, "url.path")).to(be_some().value(Value::from("/orders")));
+        expect!(attribute(span, "client.address")).to(be_some().value(Value::from("127.0.0.1")));
+        expect!(attribute(span, "pact.interaction")).to(be_some().value(Value::from("a request for orders")));
+        expect!(attribute(span, "pact.mismatches")).to(be_some().value(Value::from(0)));
+        expect!(attribute(span, "http.response.status_code")).to(be_some().value(Value::from(503)));
+        expect!(span.status.clone()).to(be_equal_to(Status::error("503")));
+        expect!(span.instrumentation_scope.name()).to(be_equal_to(DEFAULT_SERVICE_NAME));
+
+        expect!(traces_endpoint("http://localhost:4318/")).to(be_ok().value(s!("http://localhost:4318/v1/traces")));
+        expect!(traces_endpoint("http://collector/v1/traces")).to(be_ok().value(s!("http://collector/v1/traces")));
+        expect!(traces_endpoint("localhost:4318")).to(be_err());
+    }
+}
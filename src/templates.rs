@@ -0,0 +1,87 @@
+use handlebars::{Handlebars, no_escape};
+use pact_matching::models::{OptionalBody, Request, Response};
+use crate::pact_support;
+use std::str;
+
+/// Renders the bodies and header values of responses as Handlebars templates, with values from the request like
+/// `{{request.path}}`, `{{request.query.id}}` or `{{request.body.name}}`
+#[derive(Debug)]
+pub struct ResponseTemplates {
+    registry: Handlebars<'static>
+}
+
+/// If the text contains a template expression, so text without any is not rendered
+fn is_template(text: &str) -> bool {
+    text.contains("{{")
+}
+
+impl Default for ResponseTemplates {
+    fn default() -> ResponseTemplates {
+        let mut registry = Handlebars::new();
+        // the responses are not HTML, so the values are inserted as they are
+        registry.register_escape_fn(no_escape);
+        ResponseTemplates { registry }
+    }
+}
+
+impl ResponseTemplates {
+    /// Renders the response body and header values with the values from the request. Bodies that are not text are
+    /// left as they are.
+    pub fn render(&self, request: &Request, response: Response) -> Result<Response, String> {
+        let context = json!({ "request": pact_support::request_json(request) });
+        let render = |template: &str| if is_template(template) {
+            self.registry.render_template(template, &context).map_err(|err| format!("Could not render the response template - {}", err))
+        } else {
+            Ok(template.to_string())
+        };
+        let body = match response.body {
+            OptionalBody::Present(ref bytes) => match str::from_utf8(bytes) {
+                Ok(text) => OptionalBody::Present(render(text)?.into_bytes()),
+                Err(_) => response.body.clone()
+            },
+            ref body => body.clone()
+        };
+        let headers = match response.headers {
+            Some(ref headers) => Some(headers.iter()
+                .map(|(name, values)| values.iter()
+                    .map(|value| render(value))
+                    .collect::<Result<Vec<String>, String>>()
+                    .map(|values| (name.clone(), values)))
+                .collect::<Result<_, String>>()?),
+            None => None
+        };
+        Ok(Response { body, headers, .. response })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::{OptionalBody, Request, Response};
+    use super::*;
+
+    #[test]
+    fn renders_values_from_the_request() {
+        let request = Request {
+            method: s!("POST"),
+            path: s!("/orders/42"),
+            query: Some(hashmap!{ s!("id") => vec![ s!("7") ] }),
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }),
+            body: OptionalBody::Present("{\"name\": \"<Fred>\"}".into()),
+            .. Request::default_request()
+        };
+        let response = Response {
+            headers: Some(hashmap!{ s!("Location") => vec![ s!("{{request.path}}/items/{{request.query.id}}") ] }),
+            body: OptionalBody::Present("{\"name\": \"{{request.body.name}}\", \"missing\": \"{{request.body.age}}\"}".into()),
+            .. Response::default_response()
+        };
+        let rendered = ResponseTemplates::default().render(&request, response).unwrap();
+        expect!(rendered.body).to(be_equal_to(OptionalBody::Present("{\"name\": \"<Fred>\", \"missing\": \"\"}".into())));
+        expect!(rendered.headers.unwrap().get("Location").cloned()).to(be_some().value(vec![ s!("/orders/42/items/7") ]));
+
+        let binary = Response { body: OptionalBody::Present(vec![ 0xff, 0x7b, 0x7b ]), .. Response::default_response() };
+        expect!(ResponseTemplates::default().render(&request, binary.clone())).to(be_ok().value(binary));
+        let invalid = Response { body: OptionalBody::Present("{{#if}}".into()), .. Response::default_response() };
+        expect!(ResponseTemplates::default().render(&request, invalid)).to(be_err());
+    }
+}
@@ -0,0 +1,83 @@
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Maps the bearer tokens of the clients to the providers whose interactions they can use
+#[derive(Debug, Clone, Default)]
+pub struct TenantTokens {
+    tokens: HashMap<String, Vec<String>>
+}
+
+impl TenantTokens {
+    /// Loads the token mappings from a JSON file, in the form `{ "token": ["provider", ...] }`
+    pub fn load(path: &Path) -> Result<TenantTokens, String> {
+        fs::read_to_string(path)
+            .map_err(|err| format!("Could not read the tenant tokens file '{}' - {}", path.display(), err))
+            .and_then(|contents| serde_json::from_str(&contents)
+                .map_err(|err| format!("Could not parse the tenant tokens file '{}' - {}", path.display(), err)))
+            .and_then(|json| TenantTokens::from_json(&json))
+    }
+
+    /// Creates the token mappings from a JSON object. A token can be mapped to a single provider or a list of them.
+    pub fn from_json(json: &Value) -> Result<TenantTokens, String> {
+        match json {
+            Value::Object(map) => {
+                let mut tokens = HashMap::new();
+                for (token, providers) in map {
+                    let providers = match providers {
+                        Value::String(provider) => vec![ provider.clone() ],
+                        Value::Array(values) => values.iter()
+                            .map(|value| value.as_str().map(String::from)
+                                .ok_or_else(|| format!("Provider names for token '{}' must be strings", token)))
+                            .collect::<Result<Vec<String>, String>>()?,
+                        _ => return Err(format!("Token '{}' must map to a provider name or a list of provider names", token))
+                    };
+                    tokens.insert(token.clone(), providers);
+                }
+                Ok(TenantTokens { tokens })
+            },
+            _ => Err(s!("Tenant tokens must be a JSON object mapping tokens to provider names"))
+        }
+    }
+
+    /// Returns the providers for the bearer token in the Authorization header value, if the token is known
+    pub fn providers_for(&self, authorization: Option<&str>) -> Option<&Vec<String>> {
+        authorization
+            .and_then(|value| {
+                let mut parts = value.trim().splitn(2, ' ');
+                match (parts.next(), parts.next()) {
+                    (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => Some(token.trim()),
+                    _ => None
+                }
+            })
+            .and_then(|token| self.tokens.get(token))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn maps_bearer_tokens_to_providers() {
+        let tenants = TenantTokens::from_json(&json!({
+            "token-a": "provider-a",
+            "token-b": ["provider-b", "provider-c"]
+        })).unwrap();
+
+        expect!(tenants.providers_for(Some("Bearer token-a"))).to(be_some().value(&vec![ s!("provider-a") ]));
+        expect!(tenants.providers_for(Some("bearer  token-b"))).to(be_some().value(&vec![ s!("provider-b"), s!("provider-c") ]));
+        expect!(tenants.providers_for(Some("Bearer token-c"))).to(be_none());
+        expect!(tenants.providers_for(Some("Basic token-a"))).to(be_none());
+        expect!(tenants.providers_for(None)).to(be_none());
+    }
+
+    #[test]
+    fn rejects_invalid_token_mappings() {
+        expect!(TenantTokens::from_json(&json!(["token-a"]))).to(be_err());
+        expect!(TenantTokens::from_json(&json!({ "token-a": 1 }))).to(be_err());
+        expect!(TenantTokens::from_json(&json!({ "token-a": [1] }))).to(be_err());
+    }
+}
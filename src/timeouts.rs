@@ -0,0 +1,95 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{self, Sleep};
+
+/// Wraps a connection so that writing to it fails if the client does not accept any data for longer than the timeout
+/// (like a client that has stopped reading the response)
+pub struct WriteTimeout<S> {
+    stream: S,
+    timeout: Option<Duration>,
+    deadline: Option<Pin<Box<Sleep>>>
+}
+
+impl<S> WriteTimeout<S> {
+    pub fn new(stream: S, timeout: Option<Duration>) -> WriteTimeout<S> {
+        WriteTimeout { stream, timeout, deadline: None }
+    }
+
+    /// Starts the timer when a write can not make progress, and returns an error once it expires
+    fn poll_stalled<T>(&mut self, cx: &mut Context, result: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        match (result, self.timeout) {
+            (Poll::Pending, Some(timeout)) => {
+                let deadline = self.deadline.get_or_insert_with(|| Box::pin(time::sleep(timeout)));
+                match deadline.as_mut().poll(cx) {
+                    Poll::Ready(()) => {
+                        self.deadline = None;
+                        Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut,
+                            format!("client did not accept the response for {:?}", timeout))))
+                    },
+                    Poll::Pending => Poll::Pending
+                }
+            },
+            (result, _) => {
+                if result.is_ready() {
+                    self.deadline = None;
+                }
+                result
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for WriteTimeout<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for WriteTimeout<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.stream).poll_write(cx, buf);
+        self.poll_stalled(cx, result)
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context, bufs: &[io::IoSlice]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.stream).poll_write_vectored(cx, bufs);
+        self.poll_stalled(cx, result)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.stream).poll_flush(cx);
+        self.poll_stalled(cx, result)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+    use super::*;
+
+    #[test]
+    fn fails_writes_the_client_does_not_accept_in_time() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            // nothing reads from the other end of the pipe, so writing stalls once its buffer is full
+            let (client, _server) = tokio::io::duplex(16);
+            let mut stream = WriteTimeout::new(client, Some(Duration::from_millis(50)));
+            let result = stream.write_all(&[0; 64]).await;
+            expect!(result.map_err(|err| err.kind())).to(be_err().value(io::ErrorKind::TimedOut));
+        });
+    }
+}
@@ -0,0 +1,282 @@
+use pact_matching::models::{Interaction, Pact, Request};
+use pact_matching::models::generators::Generator;
+use pact_matching::models::matchingrules::MatchingRule;
+use crate::pact_support;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+/// The result of validating a pact file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validation {
+    pub consumer: String,
+    pub provider: String,
+    /// Version of the pact specification from the metadata, if it has one
+    pub spec_version: Option<String>,
+    pub interactions: usize,
+    /// Problems that would stop the pact from being served the way it was written
+    pub problems: Vec<String>
+}
+
+impl Display for Validation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "pact between '{}' and '{}', specification version {}, {} interaction(s)", self.consumer, self.provider,
+               self.spec_version.as_deref().unwrap_or("unknown (treated as 3.0.0)"),
+               self.interactions)
+    }
+}
+
+fn spec_version(pact_json: &Value) -> Option<String> {
+    [ "/metadata/pactSpecification/version", "/metadata/pact-specification/version", "/metadata/pactSpecificationVersion" ]
+        .iter()
+        .filter_map(|pointer| pact_json.pointer(pointer))
+        .map(|version| version.as_str().map(String::from).unwrap_or_else(|| version.to_string()))
+        .next()
+}
+
+/// The matchers in the matching rules of a request or response, with where they apply. The rules are in the V3 format
+/// (by category, with a list of matchers for each path) or the V2 format (a matcher for each path).
+fn matchers(rules: &Value) -> Vec<(String, &Value)> {
+    let mut found = vec![];
+    for (key, value) in rules.as_object().into_iter().flatten() {
+        if let Some(list) = value.get("matchers").and_then(Value::as_array) {
+            found.extend(list.iter().map(|matcher| (key.clone(), matcher)));
+        } else if key.starts_with('$') {
+            found.push((key.clone(), value));
+        } else {
+            for (path, rule) in value.as_object().into_iter().flatten() {
+                let list = rule.get("matchers").and_then(Value::as_array);
+                found.extend(list.into_iter().flatten().map(|matcher| (format!("{} {}", key, path), matcher)));
+            }
+        }
+    }
+    found
+}
+
+fn check_matchers(part: &str, part_json: &Value, problems: &mut Vec<String>) {
+    for (path, matcher) in matchers(part_json.get("matchingRules").unwrap_or(&Value::Null)) {
+        match MatchingRule::from_json(matcher) {
+            Some(MatchingRule::Regex(regex)) => if let Err(err) = Regex::new(&regex) {
+                problems.push(format!("{} matcher for '{}' has an invalid regular expression - {}", part, path, err));
+            },
+            Some(_) => (),
+            None => problems.push(format!("{} matcher for '{}' is not supported, and is ignored: {}", part, path, matcher))
+        }
+    }
+}
+
+fn check_generators(part: &str, part_json: &Value, problems: &mut Vec<String>) {
+    for (category, generators) in part_json.get("generators").and_then(Value::as_object).into_iter().flatten() {
+        let generators = match generators.get("type") {
+            // the status, path and method generators are not by path
+            Some(_) => vec![ (String::new(), generators) ],
+            None => generators.as_object().into_iter().flatten().map(|(path, generator)| (path.clone(), generator)).collect()
+        };
+        for (path, generator) in generators {
+            let supported = match (generator.get("type").and_then(Value::as_str), generator.as_object()) {
+                (Some("ProviderState"), _) => true,
+                (Some(generator_type), Some(map)) => Generator::from_map(&generator_type.to_string(), map).is_some(),
+                _ => false
+            };
+            if !supported {
+                problems.push(format!("{} generator for {} '{}' is not supported, and is ignored: {}", part, category, path, generator));
+            }
+        }
+    }
+}
+
+fn check_body(part: &str, part_json: &Value, problems: &mut Vec<String>) {
+    let content_type = part_json.get("headers").and_then(Value::as_object)
+        .and_then(|headers| headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("content-type")))
+        .map(|(_, value)| match value {
+            Value::Array(values) => values.iter().filter_map(Value::as_str).collect::<Vec<&str>>().join(", "),
+            _ => value.as_str().unwrap_or_default().to_string()
+        })
+        .unwrap_or_default();
+    if let Some(Value::String(body)) = part_json.get("body") {
+        if content_type.to_lowercase().contains("json") {
+            if let Err(err) = serde_json::from_str::<Value>(body) {
+                problems.push(format!("{} body is not valid JSON, but the content type is '{}' - {}", part, content_type, err));
+            }
+        }
+    }
+}
+
+/// If the requests have the same method, path, query, headers and body (whatever their matching rules are)
+fn same_request(a: &Request, b: &Request) -> bool {
+    a.method.eq_ignore_ascii_case(&b.method) && a.path == b.path && a.query == b.query && a.headers == b.headers && a.body == b.body
+}
+
+/// Interactions from the loaded pacts that have the same request and provider states, so a request can never tell them
+/// apart and only one of them is used
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbiguousInteractions {
+    pub method: String,
+    pub path: String,
+    /// The provider, consumer and description of each of the interactions, in the order they were loaded in
+    pub interactions: Vec<(String, String, String)>
+}
+
+/// Finds the interactions in the pacts that have the same method, path, query parameters, headers, body and provider
+/// states as another interaction
+pub fn ambiguous_interactions(pacts: &[Pact]) -> Vec<AmbiguousInteractions> {
+    let mut groups: Vec<(&Interaction, AmbiguousInteractions)> = vec![];
+    for pact in pacts {
+        for interaction in &pact.interactions {
+            let name = (pact.provider.name.clone(), pact.consumer.name.clone(), interaction.description.clone());
+            match groups.iter_mut().find(|(first, _)| same_request(&first.request, &interaction.request) &&
+                first.provider_states == interaction.provider_states) {
+                Some((_, group)) => group.interactions.push(name),
+                None => groups.push((interaction, AmbiguousInteractions {
+                    method: interaction.request.method.to_uppercase(),
+                    path: interaction.request.path.clone(),
+                    interactions: vec![ name ]
+                }))
+            }
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).filter(|group| group.interactions.len() > 1).collect()
+}
+
+/// Checks a pact file for problems: interactions with the same description (or the same request) and provider states,
+/// JSON bodies that can not be parsed, and matching rules and generators that are not supported
+pub fn validate_pact(source: &str, pact_json: &Value) -> Validation {
+    let mut problems = vec![];
+    let spec_version = spec_version(pact_json);
+    if let Some(major) = spec_version.as_ref().and_then(|version| version.split('.').next()).and_then(|major| major.parse::<u32>().ok()) {
+        if major > 3 {
+            problems.push(format!("Version {} of the pact specification is not supported (only versions 1 to 3 are)",
+                                  spec_version.clone().unwrap_or_default()));
+        }
+    }
+    let (pact, _) = pact_support::pact_from_json(source, pact_json);
+    let interactions_json = pact_json.get("interactions").and_then(Value::as_array).cloned().unwrap_or_default();
+    let mut descriptions = HashSet::new();
+    for (index, interaction) in pact.interactions.iter().enumerate() {
+        let states = interaction.provider_states.iter().map(|state| state.name.clone()).collect::<Vec<String>>();
+        let name = if states.is_empty() {
+            format!("Interaction '{}'", interaction.description)
+        } else {
+            format!("Interaction '{}' (given '{}')", interaction.description, states.join("', '"))
+        };
+        if !descriptions.insert((interaction.description.clone(), states.clone())) {
+            problems.push(format!("{} is in the pact more than once", name));
+        } else if let Some(other) = pact.interactions[..index].iter()
+            .find(|other| same_request(&other.request, &interaction.request) && other.provider_states == interaction.provider_states) {
+            problems.push(format!("{} has the same request as interaction '{}', so only one of them can be used",
+                                  name, other.description));
+        }
+        let mut interaction_problems = vec![];
+        if let Some(interaction_json) = interactions_json.get(index) {
+            for part in &[ "request", "response" ] {
+                let part_json = interaction_json.get(part).unwrap_or(&Value::Null);
+                let part = if *part == "request" { "Request" } else { "Response" };
+                check_body(part, part_json, &mut interaction_problems);
+                check_matchers(part, part_json, &mut interaction_problems);
+                check_generators(part, part_json, &mut interaction_problems);
+            }
+        }
+        problems.extend(interaction_problems.into_iter().map(|problem| format!("{}: {}", name, problem)));
+    }
+    Validation {
+        consumer: pact.consumer.name.clone(),
+        provider: pact.provider.name.clone(),
+        spec_version,
+        interactions: pact.interactions.len(),
+        problems
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use super::*;
+
+    #[test]
+    fn reports_the_problems_with_the_pact() {
+        let pact_json = json!({
+            "consumer": { "name": "ui" },
+            "provider": { "name": "orders" },
+            "interactions": [
+                {
+                    "description": "get an order",
+                    "request": {
+                        "method": "GET",
+                        "path": "/orders/1",
+                        "matchingRules": {
+                            "path": { "matchers": [ { "match": "regex", "regex": "/orders/\\d+" } ] },
+                            "header": { "Accept": { "matchers": [ { "match": "semver" } ] } }
+                        }
+                    },
+                    "response": {
+                        "status": 200,
+                        "headers": { "Content-Type": "application/json" },
+                        "body": "{ not json",
+                        "generators": { "body": { "$.id": { "type": "MockServerURL" }, "$.ref": { "type": "Uuid" } } }
+                    }
+                },
+                { "description": "get an order", "request": { "method": "GET", "path": "/orders/2" }, "response": {} },
+                { "description": "get the first order", "request": { "method": "GET", "path": "/orders/1" }, "response": {} },
+                {
+                    "description": "create an order",
+                    "request": { "method": "POST", "path": "/orders", "matchingRules": { "$.body.id": { "match": "regex", "regex": "(" } } },
+                    "response": { "status": 201 }
+                }
+            ],
+            "metadata": { "pactSpecification": { "version": "3.0.0" } }
+        });
+        let validation = validate_pact("test", &pact_json);
+        expect!(validation.to_string()).to(be_equal_to("pact between 'ui' and 'orders', specification version 3.0.0, 4 interaction(s)"));
+        expect!(validation.problems.len()).to(be_equal_to(6));
+        let expected = [
+            "Interaction 'get an order': Request matcher for 'header Accept' is not supported",
+            "Interaction 'get an order': Response body is not valid JSON",
+            "Interaction 'get an order': Response generator for body '$.id' is not supported",
+            "Interaction 'get an order' is in the pact more than once",
+            "Interaction 'get the first order' has the same request as interaction 'get an order'",
+            "Interaction 'create an order': Request matcher for '$.body.id' has an invalid regular expression"
+        ];
+        for (problem, expected) in validation.problems.iter().zip(expected.iter()) {
+            expect!(problem.starts_with(expected)).to(be_true());
+        }
+    }
+
+    #[test]
+    fn finds_the_interactions_with_the_same_request() {
+        let pact_json = |consumer: &str, interactions: Value| json!({
+            "consumer": { "name": consumer }, "provider": { "name": "orders" }, "interactions": interactions
+        });
+        let (web, _) = pact_support::pact_from_json("web", &pact_json("web", json!([
+            { "description": "get an order", "request": { "method": "GET", "path": "/orders/1" }, "response": {} },
+            { "description": "get a missing order", "providerState": "no orders", "request": { "method": "GET", "path": "/orders/1" }, "response": {} },
+            { "description": "create an order", "request": { "method": "POST", "path": "/orders", "body": { "id": 1 } }, "response": {} }
+        ])));
+        let (mobile, _) = pact_support::pact_from_json("mobile", &pact_json("mobile", json!([
+            { "description": "get the first order", "request": { "method": "get", "path": "/orders/1" }, "response": { "status": 404 } },
+            { "description": "create another order", "request": { "method": "POST", "path": "/orders", "body": { "id": 2 } }, "response": {} }
+        ])));
+
+        expect!(ambiguous_interactions(&[ web, mobile ])).to(be_equal_to(vec![
+            AmbiguousInteractions {
+                method: s!("GET"),
+                path: s!("/orders/1"),
+                interactions: vec![
+                    (s!("orders"), s!("web"), s!("get an order")),
+                    (s!("orders"), s!("mobile"), s!("get the first order"))
+                ]
+            }
+        ]));
+    }
+
+    #[test]
+    fn reports_unsupported_specification_versions() {
+        let pact_json = json!({ "consumer": { "name": "ui" }, "provider": { "name": "orders" }, "interactions": [],
+            "metadata": { "pactSpecification": { "version": "4.0" } } });
+        expect!(validate_pact("test", &pact_json).problems.len()).to(be_equal_to(1));
+        let pact_json = json!({ "consumer": { "name": "ui" }, "provider": { "name": "orders" }, "interactions": [] });
+        let validation = validate_pact("test", &pact_json);
+        expect!(validation.spec_version).to(be_none());
+        expect!(validation.problems.len()).to(be_equal_to(0));
+    }
+}
@@ -0,0 +1,301 @@
+use pact_matching::models::{parse_query_string, Consumer, Interaction, OptionalBody, Pact, PactSpecification, Provider, Request, Response};
+use pact_matching::models::matchingrules::{MatchingRule, MatchingRules, RuleLogic};
+use pact_matching::models::provider_states::ProviderState;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Consumer name used for the pacts created from WireMock mappings
+static WIREMOCK_CONSUMER: &str = "wiremock";
+
+/// Methods a WireMock mapping with the `ANY` method is expanded to
+static ANY_METHODS: [&str; 7] = [ "GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS" ];
+
+/// Priority WireMock gives to mappings that do not have one
+const DEFAULT_PRIORITY: i64 = 5;
+
+/// Loads the WireMock stub mappings from a directory, and converts them into interactions. The directory can either be
+/// a WireMock root directory (with `mappings` and `__files` sub-directories), or a directory of mapping files.
+pub fn load_wiremock_dir(dir: &Path) -> Result<Pact, String> {
+    let (mappings_dir, files_dir) = if dir.join("mappings").is_dir() {
+        (dir.join("mappings"), dir.join("__files"))
+    } else {
+        (dir.to_path_buf(), dir.parent().map(|parent| parent.join("__files")).unwrap_or_else(|| dir.join("__files")))
+    };
+    let mut mappings = vec![];
+    for path in mapping_files(&mappings_dir)
+        .map_err(|err| format!("Could not read the WireMock mappings from '{}' - {}", mappings_dir.display(), err))? {
+        let json: Value = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| serde_json::from_str(&contents).map_err(|err| err.to_string()))
+            .map_err(|err| format!("Could not load the WireMock mapping '{}' - {}", path.display(), err))?;
+        match json.get("mappings") {
+            Some(Value::Array(values)) => mappings.extend(values.iter().cloned()),
+            _ => mappings.push(json)
+        }
+    }
+
+    let provider = dir.canonicalize().ok()
+        .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or_else(|| s!("wiremock"));
+    pact_from_mappings(&mappings, &provider, &files_dir)
+}
+
+fn mapping_files(dir: &Path) -> ::std::io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(mapping_files(&path)?);
+        } else if path.extension().map(|ext| ext == "json").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Converts WireMock stub mappings into a pact with the given provider name. Mappings are ordered by their priority,
+/// and response bodies from files are loaded from the files directory.
+pub fn pact_from_mappings(mappings: &[Value], provider: &str, files_dir: &Path) -> Result<Pact, String> {
+    let mut mappings = mappings.iter().collect::<Vec<&Value>>();
+    mappings.sort_by_key(|mapping| mapping.get("priority").and_then(Value::as_i64).unwrap_or(DEFAULT_PRIORITY));
+
+    let mut interactions = vec![];
+    for mapping in mappings {
+        let request = mapping.get("request")
+            .ok_or_else(|| format!("WireMock mapping {} does not have a request", mapping))?;
+        let response = mapping_response(mapping.get("response").unwrap_or(&Value::Null), files_dir)?;
+        let provider_states = match (mapping.get("scenarioName").and_then(Value::as_str),
+                                     mapping.get("requiredScenarioState").and_then(Value::as_str)) {
+            (Some(scenario), Some(state)) => vec![ ProviderState::default(&format!("{}: {}", scenario, state)) ],
+            _ => vec![]
+        };
+        let method = request.get("method").and_then(Value::as_str).unwrap_or("ANY").to_uppercase();
+        let methods = if method == "ANY" {
+            ANY_METHODS.iter().map(|method| method.to_string()).collect()
+        } else {
+            vec![ method ]
+        };
+        for method in methods {
+            let request = mapping_request(&method, request);
+            let description = mapping.get("name")
+                .or_else(|| mapping.get("id"))
+                .or_else(|| mapping.get("uuid"))
+                .and_then(Value::as_str)
+                .map(|name| format!("{} ({})", name, method))
+                .unwrap_or_else(|| format!("{} {}", method, request.path));
+            interactions.push(Interaction {
+                description,
+                provider_states: provider_states.clone(),
+                request,
+                response: response.clone()
+            });
+        }
+    }
+
+    Ok(Pact {
+        consumer: Consumer { name: s!(WIREMOCK_CONSUMER) },
+        provider: Provider { name: provider.to_string() },
+        interactions,
+        specification_version: PactSpecification::V3,
+        .. Pact::default()
+    })
+}
+
+/// Converts a WireMock value pattern (like `{ "equalTo": "value" }`) into an example value and an optional matcher.
+/// Returns `None` for patterns that can not be expressed as a pact matcher.
+fn value_pattern(pattern: &Value) -> Option<(String, Option<MatchingRule>)> {
+    if let Some(value) = pattern.get("equalTo").and_then(Value::as_str) {
+        Some((value.to_string(), None))
+    } else if let Some(regex) = pattern.get("matches").and_then(Value::as_str) {
+        Some((regex.to_string(), Some(MatchingRule::Regex(regex.to_string()))))
+    } else if let Some(value) = pattern.get("contains").and_then(Value::as_str) {
+        Some((value.to_string(), Some(MatchingRule::Regex(format!(".*{}.*", regex::escape(value))))))
+    } else {
+        debug!("Ignoring unsupported WireMock pattern {}", pattern);
+        None
+    }
+}
+
+fn mapping_request(method: &str, request: &Value) -> Request {
+    let mut matching_rules = MatchingRules::default();
+    let str_value = |name: &str| request.get(name).and_then(Value::as_str);
+    let (path, mut query) = if let Some(url) = str_value("url") {
+        let mut parts = url.splitn(2, '?');
+        let path = parts.next().unwrap_or("/").to_string();
+        (path, parts.next().and_then(|query| parse_query_string(&query.to_string())))
+    } else if let Some(path) = str_value("urlPath") {
+        (path.to_string(), None)
+    } else if let Some(pattern) = str_value("urlPathPattern").or_else(|| str_value("urlPattern")) {
+        matching_rules.add_category("path").add_rule(&s!(""), MatchingRule::Regex(format!("^{}$", pattern)), &RuleLogic::And);
+        (pattern.to_string(), None)
+    } else {
+        (s!("/"), None)
+    };
+
+    if let Some(Value::Object(parameters)) = request.get("queryParameters") {
+        for (name, pattern) in parameters {
+            if let Some((value, rule)) = value_pattern(pattern) {
+                query.get_or_insert_with(HashMap::new).insert(name.clone(), vec![ value ]);
+                if let Some(rule) = rule {
+                    matching_rules.add_category("query").add_rule(name, rule, &RuleLogic::And);
+                }
+            }
+        }
+    }
+
+    let mut headers: Option<HashMap<String, Vec<String>>> = None;
+    if let Some(Value::Object(header_patterns)) = request.get("headers") {
+        for (name, pattern) in header_patterns {
+            if let Some((value, rule)) = value_pattern(pattern) {
+                headers.get_or_insert_with(HashMap::new).insert(name.clone(), vec![ value ]);
+                if let Some(rule) = rule {
+                    matching_rules.add_category("header").add_rule(name, rule, &RuleLogic::And);
+                }
+            }
+        }
+    }
+
+    let body = request.get("bodyPatterns").and_then(Value::as_array)
+        .and_then(|patterns| patterns.iter().filter_map(|pattern| {
+            match (pattern.get("equalToJson"), pattern.get("equalTo")) {
+                (Some(Value::String(json)), _) => Some(json.clone()),
+                (Some(json), _) => Some(json.to_string()),
+                (None, Some(Value::String(value))) => Some(value.clone()),
+                _ => {
+                    debug!("Ignoring unsupported WireMock body pattern {}", pattern);
+                    None
+                }
+            }
+        }).next())
+        .map(|body| OptionalBody::Present(body.into_bytes()))
+        .unwrap_or(OptionalBody::Missing);
+
+    Request {
+        method: method.to_string(),
+        path,
+        query,
+        headers,
+        body,
+        matching_rules,
+        .. Request::default_request()
+    }
+}
+
+fn mapping_response(response: &Value, files_dir: &Path) -> Result<Response, String> {
+    let mut headers = HashMap::new();
+    if let Some(Value::Object(values)) = response.get("headers") {
+        for (name, value) in values {
+            let values = match value {
+                Value::Array(values) => values.iter()
+                    .map(|value| value.as_str().map(String::from).unwrap_or_else(|| value.to_string()))
+                    .collect(),
+                Value::String(value) => vec![ value.clone() ],
+                _ => vec![ value.to_string() ]
+            };
+            headers.insert(name.clone(), values);
+        }
+    }
+
+    let body = if let Some(body) = response.get("body").and_then(Value::as_str) {
+        OptionalBody::Present(body.as_bytes().into())
+    } else if let Some(json) = response.get("jsonBody") {
+        if !headers.keys().any(|name| name.eq_ignore_ascii_case("content-type")) {
+            headers.insert(s!("Content-Type"), vec![ s!("application/json") ]);
+        }
+        OptionalBody::Present(json.to_string().into_bytes())
+    } else if let Some(encoded) = response.get("base64Body").and_then(Value::as_str) {
+        OptionalBody::Present(base64::decode(encoded)
+            .map_err(|err| format!("Invalid base64 response body - {}", err))?)
+    } else if let Some(file) = response.get("bodyFileName").and_then(Value::as_str) {
+        let path = files_dir.join(file);
+        OptionalBody::Present(fs::read(&path)
+            .map_err(|err| format!("Could not read the response body file '{}' - {}", path.display(), err))?)
+    } else {
+        OptionalBody::Missing
+    };
+
+    Ok(Response {
+        status: response.get("status").and_then(Value::as_u64).unwrap_or(200) as u16,
+        headers: if headers.is_empty() { None } else { Some(headers) },
+        body,
+        .. Response::default_response()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use crate::matching::{match_request, MatchingOptions};
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn converts_mappings_into_interactions() {
+        let mappings = vec![
+            json!({
+                "name": "Get user",
+                "request": {
+                    "method": "GET",
+                    "url": "/users/1?verbose=true",
+                    "headers": { "Accept": { "contains": "json" } }
+                },
+                "response": { "status": 200, "jsonBody": { "id": 1 } }
+            }),
+            json!({
+                "priority": 1,
+                "request": { "method": "ANY", "urlPathPattern": "/health/.*" },
+                "response": { "status": 204, "headers": { "X-Health": ["ok", "good"] } }
+            })
+        ];
+
+        let pact = pact_from_mappings(&mappings, "users", &env::temp_dir()).unwrap();
+        expect!(pact.consumer.name.as_str()).to(be_equal_to("wiremock"));
+        expect!(pact.provider.name.as_str()).to(be_equal_to("users"));
+        expect!(pact.interactions.len()).to(be_equal_to(8));
+        expect!(pact.interactions[0].description.as_str()).to(be_equal_to("GET /health/.*"));
+        expect!(pact.interactions[0].response.status).to(be_equal_to(204));
+
+        let user = &pact.interactions[7];
+        expect!(user.description.as_str()).to(be_equal_to("Get user (GET)"));
+        expect!(user.response.body.str_value()).to(be_equal_to("{\"id\":1}"));
+        expect!(user.response.headers.clone()).to(be_some().value(hashmap!{ s!("Content-Type") => vec![ s!("application/json") ] }));
+
+        let request = Request {
+            path: s!("/users/1"),
+            query: Some(hashmap!{ s!("verbose") => vec![ s!("true") ] }),
+            headers: Some(hashmap!{ s!("Accept") => vec![ s!("application/json") ] }),
+            .. Request::default_request()
+        };
+        expect!(match_request(&user.request, &request, &MatchingOptions::default()).is_empty()).to(be_true());
+        let request = Request { path: s!("/health/live"), .. Request::default_request() };
+        expect!(match_request(&pact.interactions[0].request, &request, &MatchingOptions::default()).is_empty()).to(be_true());
+    }
+
+    #[test]
+    fn maps_scenario_states_to_provider_states() {
+        let mappings = vec![ json!({
+            "scenarioName": "Orders",
+            "requiredScenarioState": "Order placed",
+            "request": { "method": "POST", "urlPath": "/orders", "bodyPatterns": [ { "equalToJson": { "item": "book" } } ] },
+            "response": { "status": 201, "body": "created" }
+        }) ];
+
+        let pact = pact_from_mappings(&mappings, "orders", &env::temp_dir()).unwrap();
+        let interaction = &pact.interactions[0];
+        expect!(interaction.provider_states[0].name.as_str()).to(be_equal_to("Orders: Order placed"));
+        expect!(interaction.request.body.str_value()).to(be_equal_to("{\"item\":\"book\"}"));
+        expect!(interaction.response.body.str_value()).to(be_equal_to("created"));
+    }
+
+    #[test]
+    fn fails_if_a_response_body_file_is_missing() {
+        let mappings = vec![ json!({
+            "request": { "method": "GET", "url": "/" },
+            "response": { "bodyFileName": "does-not-exist.json" }
+        }) ];
+        expect!(pact_from_mappings(&mappings, "files", &env::temp_dir())).to(be_err());
+    }
+}
@@ -0,0 +1,148 @@
+use pact_matching::models::{HttpPart, OptionalBody};
+use pact_matching::models::generators::Generators;
+use pact_matching::models::matchingrules::MatchingRules;
+use std::collections::HashMap;
+use std::str;
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Document, Element};
+use sxd_document::{parser, writer, Package};
+
+/// Content type that pact matching compares bodies as XML for
+const XML_CONTENT_TYPE: &str = "application/xml";
+
+/// If the content type is for an XML document, like `application/xml`, `text/xml` (as sent by SOAP 1.1 clients) or
+/// `application/soap+xml`
+pub fn is_xml_content_type(content_type: &str) -> bool {
+    let content_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    content_type == "application/xml" || content_type == "text/xml" || content_type.ends_with("+xml")
+}
+
+/// If the body of the request or response is XML, from its content type
+pub fn is_xml(part: &dyn HttpPart) -> bool {
+    part.body().is_present() && is_xml_content_type(&part.content_type())
+}
+
+/// Request or response with an XML body, given to pact matching with the content type it matches as XML. This is so
+/// `text/xml` bodies are matched element by element (and not as text), and XML bodies with different XML content types
+/// can match.
+pub struct XmlPart<'a>(pub &'a dyn HttpPart);
+
+impl HttpPart for XmlPart<'_> {
+    fn headers(&self) -> &Option<HashMap<String, Vec<String>>> {
+        self.0.headers()
+    }
+
+    fn body(&self) -> &OptionalBody {
+        self.0.body()
+    }
+
+    fn matching_rules(&self) -> &MatchingRules {
+        self.0.matching_rules()
+    }
+
+    fn generators(&self) -> &Generators {
+        self.0.generators()
+    }
+
+    fn content_type(&self) -> String {
+        s!(XML_CONTENT_TYPE)
+    }
+}
+
+/// The element children of the element
+pub fn child_elements<'d>(element: &Element<'d>) -> Vec<Element<'d>> {
+    element.children().into_iter().filter_map(|child| child.element()).collect()
+}
+
+/// Replaces the text of the element, keeping its child elements
+pub fn set_text(element: &Element, value: &str) {
+    let texts = element.children().into_iter().filter_map(|child| match child {
+        ChildOfElement::Text(text) => Some(text),
+        _ => None
+    }).collect::<Vec<_>>();
+    match texts.split_first() {
+        Some((first, rest)) => {
+            first.set_text(value);
+            for text in rest {
+                text.set_text("");
+            }
+        },
+        None => element.append_child(element.document().create_text(value))
+    }
+}
+
+/// The text of the element, without the text of its child elements
+pub fn text(element: &Element) -> String {
+    element.children().into_iter().filter_map(|child| match child {
+        ChildOfElement::Text(text) => Some(text.text()),
+        _ => None
+    }).collect()
+}
+
+fn parse(body: &[u8]) -> Result<Package, String> {
+    let text = str::from_utf8(body).map_err(|err| err.to_string())?;
+    parser::parse(text).map_err(|(position, errors)| format!("{:?} at position {}", errors, position))
+}
+
+fn root_element<'d>(document: &Document<'d>) -> Result<Element<'d>, String> {
+    document.root().children().into_iter()
+        .find_map(|child| match child {
+            ChildOfRoot::Element(element) => Some(element),
+            _ => None
+        })
+        .ok_or_else(|| s!("the document does not have a root element"))
+}
+
+/// Parses the XML document, and returns what the function reads from its root element
+pub fn read<F, T>(body: &[u8], read: F) -> Result<T, String> where F: FnOnce(Element) -> T {
+    let package = parse(body)?;
+    let document = package.as_document();
+    Ok(read(root_element(&document)?))
+}
+
+/// Parses the XML document, calls the function with its root element to change it, and returns the changed document
+pub fn update<F>(body: &[u8], change: F) -> Result<Vec<u8>, String> where F: FnOnce(Element) {
+    let package = parse(body)?;
+    let document = package.as_document();
+    change(root_element(&document)?);
+    let mut updated = vec![];
+    writer::format_document(&document, &mut updated).map_err(|err| err.to_string())?;
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod test {
+    use expectest::prelude::*;
+    use pact_matching::models::Request;
+    use super::*;
+
+    #[test]
+    fn detects_xml_content_types() {
+        expect!(is_xml_content_type("application/xml")).to(be_true());
+        expect!(is_xml_content_type("text/xml; charset=utf-8")).to(be_true());
+        expect!(is_xml_content_type("application/soap+xml")).to(be_true());
+        expect!(is_xml_content_type("application/json")).to(be_false());
+        expect!(is_xml_content_type("text/plain")).to(be_false());
+
+        let request = Request {
+            headers: Some(hashmap!{ s!("Content-Type") => vec![ s!("text/xml") ] }),
+            body: OptionalBody::Present(b"<a/>".to_vec()),
+            .. Request::default_request()
+        };
+        expect!(is_xml(&request)).to(be_true());
+        expect!(XmlPart(&request).content_type()).to(be_equal_to(s!("application/xml")));
+        expect!(is_xml(&Request { body: OptionalBody::Missing, .. request })).to(be_false());
+    }
+
+    #[test]
+    fn updates_the_text_of_the_elements() {
+        let updated = update(b"<order id=\"1\"><status>NEW</status><items><item/></items></order>", |root| {
+            root.set_attribute_value("id", "2");
+            for child in child_elements(&root) {
+                set_text(&child, "changed");
+            }
+        });
+        expect!(updated.map(|body| String::from_utf8(body).unwrap())).to(be_ok().value(
+            s!("<?xml version='1.0'?><order id='2'><status>changed</status><items><item/>changed</items></order>")));
+        expect!(update(b"not xml", |_| ())).to(be_err());
+    }
+}